@@ -1,20 +1,149 @@
-from dataclasses import dataclass
-from typing import List, Dict, Any, Optional, Callable, Union
+from concurrent.futures import Future, ThreadPoolExecutor
+from dataclasses import dataclass, field
+from collections.abc import Sequence
+from typing import List, Dict, Any, Optional, Callable, Iterable, Iterator, Tuple, Union
 from rich.progress import Progress, BarColumn, TimeRemainingColumn
 from rich.console import Console
+import atexit
+import hashlib
+import json
+import logging
 import time
-from .axicontraves import process_requests_multi, RequestMetrics
+import warnings
+import weakref
+from .axicontraves import (
+    process_requests_multi,
+    start_requests_multi,
+    plan as _plan,
+    normalize_messages,
+    detect_language,
+    count_tokens,
+    list_artifacts,
+    read_artifact,
+    RequestMetrics,
+    RunPlan,
+    ProviderPlan,
+    BatchHandle,
+    IntegrityReport,
+    ProviderStats,
+    RunSummary,
+    ProviderSummary,
+    RunProgress,
+    ProviderProgress,
+    RunStats,
+    ThroughputSample,
+    LatencyHistogram,
+    verify_results,
+    summarize_results,
+    builtin_pricing,
+)
+from .registry import RunManifest, RunRegistry, list_runs, load_summary
 
-Message = Dict[str, str]
+# "content" is a string or a list of parts: {"type": "text", "text": ...},
+# {"type": "image_url", "image_url": {"url": ...}}, {"type": "image", "data": <base64>, "media_type": ...},
+# {"type": "input_audio", "input_audio": {"data": <base64>, "format": "wav"}}
+# or a document: {"type": "file", "file": {"file_data": "data:application/pdf;base64,...",
+# "filename": ...}} (or "file_id"), Anthropic's {"type": "document", "source": ...}, or
+# {"type": "file", "path": "report.pdf"}, which the Rust core reads and encodes itself so
+# the file never has to be loaded into Python.
+# An optional "cache_control" (e.g. {"type": "ephemeral"}) marks an Anthropic prompt-cache
+# breakpoint; cache writes/reads are reported on RequestMetrics.cache_*_input_tokens.
+# Assistant messages may carry OpenAI "tool_calls" (content then optional) and "tool"
+# messages answer one by "tool_call_id".
+# An optional "name" identifies the speaker in multi-party chats (sent as OpenAI's `name`,
+# prefixed to the text as "{name}: " for Anthropic and Gemini). Any other keys are passed
+# through unchanged on OpenAI-format messages.
+Message = Dict[str, Any]
+# Either a plain message list or {"messages": [...], ...overrides}. Overrides take
+# precedence over the provider config: "model", "temperature", "max_tokens", "top_p",
+# "frequency_penalty", "presence_penalty", "n", "reasoning_effort",
+# "max_completion_tokens", "response_format", "json_schema",
+# "stop", "seed", "logit_bias", "logprobs", "constraint", "tools", "tool_choice" and "extra_body"
+# (merged verbatim).
+# "json_schema" (a bare schema or {"name", "schema", "strict"}) is sent as a json_schema
+# response_format, or to Anthropic as a tool the model is made to call whose input is the
+# result's content; validate_schema checks the output against it.
+# A trailing assistant message prefills the response (continued natively by Anthropic and
+# llama.cpp, via continue_final_message on vLLM); with "continue": True its trailing
+# whitespace is stripped and the result's content starts with it.
+# "user" and "metadata" are forwarded to the API, "request_id" is echoed back on the
+# result's request_id and "stream_to" names a file the response streams into as tokens arrive.
+# "priority" ("high", "normal" or "low") decides dispatch order: higher priorities are sent
+# first, in submission order within a priority, and results come back in that order.
+# On streamed requests "stop_regex" cancels generation once the output so far matches
+# (finish_reason "stop_regex", token counts estimated).
+# {"type": "image", "prompt": ..., "size", "quality", "style", "response_format"} is an
+# image generation request to /v1/images/generations ("model", "n", "user" and "extra_body"
+# apply too); results carry image_urls and image_bytes, and b64_json data as choices.
+# With BatchProcessor templates, a request can instead name a template:
+# (template_name, variables) or {"template": ..., "variables": {...}, ...overrides}.
+Request = Union[List[Message], Dict[str, Any], Tuple[str, Dict[str, Any]]]
 
 @dataclass
 class ProviderConfig:
+    # "openai" (and OpenAI-compatible servers) or "anthropic"
     name: str
     api_key: str
     config: Dict[str, Any]
     base_url: Optional[str] = None
+    # Limits of this provider's key, enforced independently of the other providers.
+    # Whatever is set, the remaining quota reported in x-ratelimit-remaining-* (or
+    # anthropic-ratelimit-*-remaining) response headers is also followed: once it runs out,
+    # requests wait for the reported reset instead of running into 429s.
+    # Token budget: requests wait until their estimated prompt tokens fit, and reported
+    # completion tokens are charged afterwards
     tokens_per_minute: Optional[int] = None
+    # Request rate; starts are spaced evenly
+    rpm: Optional[int] = None
+    # Requests in flight at once
+    max_concurrency: Optional[int] = None
+    # Share of the requests relative to the other providers, e.g. 7 and 3 for a 70/30
+    # split between two keys; unset means 1, an even round-robin
+    weight: Optional[float] = None
+    # Timeouts in seconds: establishing a connection, the whole request, and for streamed
+    # requests the longest silence between chunks. Unset means wait indefinitely.
+    connect_timeout: Optional[float] = None
+    timeout: Optional[float] = None
+    read_timeout: Optional[float] = None
     test_mode: bool = False
+    # Capacity model for simulated runs (implies test_mode), e.g.
+    # {"max_concurrency": 8, "service_time": {"distribution": "lognormal", "median_ms": 400},
+    #  "rate_limit_threshold": 32, "warmup_requests": 10, "warmup_factor": 3.0}
+    simulator: Optional[Dict[str, Any]] = None
+    # Serving stack behind the endpoint ("openai", "vllm", "llamacpp"); selects how
+    # per-request {"constraint": {"type": "gbnf"|"regex"|"json_schema"|"choice", "value": ...}}
+    # is expressed in the payload
+    backend: str = "openai"
+    # Extra HTTP headers for every request, e.g. {"OpenAI-Organization": "org-..."} or
+    # gateway auth; they replace built-in headers of the same name
+    headers: Optional[Dict[str, str]] = None
+    # Jinja chat template (the "chat_template" of a Hugging Face tokenizer_config.json);
+    # when set, messages are rendered into a prompt and sent to /v1/completions, or to
+    # /completion for backend="llamacpp", instead of /v1/chat/completions
+    chat_template: Optional[str] = None
+    # Template variables such as {"bos_token": "<s>", "eos_token": "</s>"}
+    special_tokens: Optional[Dict[str, str]] = None
+
+    def options(self) -> Dict[str, Any]:
+        return {
+            "test_mode": self.test_mode,
+            "simulator": self.simulator,
+            "backend": self.backend,
+            "headers": self.headers,
+            "chat_template": self.chat_template,
+            "special_tokens": self.special_tokens,
+            "tokens_per_minute": self.tokens_per_minute,
+            "rpm": self.rpm,
+            "max_concurrency": self.max_concurrency,
+            "weight": self.weight,
+            "connect_timeout": self.connect_timeout,
+            "timeout": self.timeout,
+            "read_timeout": self.read_timeout,
+        }
+
+    def as_tuple(self):
+        """(name, api_key, base_url, config, options) as expected by the Rust core."""
+        return (self.name, self.api_key, self.base_url, self.config, self.options())
 
 @dataclass
 class BatchRequestResult:
@@ -27,6 +156,40 @@ class BatchRequestResult:
     total_request_bytes: int
     total_response_bytes: int
     provider_metrics: Dict[str, 'BatchRequestResult']
+    # Set when the run was recorded in a RunRegistry
+    run_id: Optional[str] = None
+    # Per detected prompt language, with detect_language or language_routing
+    language_metrics: Dict[str, 'BatchRequestResult'] = field(default_factory=dict)
+    # Whether every request came back exactly once (a RuntimeWarning is raised when not)
+    integrity: Optional[IntegrityReport] = None
+    # Set when Ctrl-C stopped the run: `metrics` then holds the finished requests alongside
+    # the ones that never got an answer, with status "cancelled"
+    interrupted: bool = False
+    # Latency percentiles (p50/p90/p95/p99 of successful requests), throughput, duration
+    # and counts per outcome, computed in Rust, with summary.errors counting failures by
+    # category (timeout, rate_limit, server_error, connection, parse, ...) and
+    # summary.providers breaking requests, tokens, bytes, error rate and latency down by
+    # provider, plus summary.samples with a sample_interval. summary.latency_histogram and
+    # summary.ttft_histogram hold every latency in an HdrHistogram for tail percentiles
+    # (percentiles(), value_at()) or export (encode()); set on the top-level result
+    summary: Optional[RunSummary] = None
+
+    @property
+    def cancelled_requests(self) -> int:
+        return sum(1 for m in self.metrics if m.status == "cancelled")
+
+    @property
+    def hedged_requests(self) -> int:
+        return sum(1 for m in self.metrics if m.hedged_to is not None)
+
+    @property
+    def hedges_won(self) -> int:
+        return sum(1 for m in self.metrics if m.hedge_won)
+
+    @property
+    def cost_usd(self) -> Optional[float]:
+        costs = [m.cost_usd for m in self.metrics if m.cost_usd is not None]
+        return sum(costs) if costs else None
 
     @property
     def requests_per_second(self) -> float:
@@ -52,12 +215,357 @@ class BatchRequestResult:
     def downlink_mbps(self) -> float:
         return (self.total_response_bytes * 8) / (self.total_time * 1_000_000) if self.total_time > 0 else 0
 
+# Silent unless the application configures logging
+logging.getLogger("axicontraves").addHandler(logging.NullHandler())
+
+# Batches started with start_batch(); any still running when the interpreter exits are
+# cancelled and their requests in flight drained instead of being cut off mid-response
+_running: "weakref.WeakSet[BatchHandle]" = weakref.WeakSet()
+
+@atexit.register
+def _drain_running() -> None:
+    for handle in list(_running):
+        if not handle.done():
+            handle.cancel()
+            handle.wait()
+
+def _current_traceparent() -> Optional[str]:
+    """W3C traceparent of the active OpenTelemetry span, if opentelemetry is installed."""
+    try:
+        from opentelemetry import propagate
+    except ImportError:
+        return None
+    carrier: Dict[str, str] = {}
+    propagate.inject(carrier)
+    return carrier.get("traceparent")
+
+def plan(
+    requests: List[Request],
+    providers: Union[ProviderConfig, List[ProviderConfig]],
+    pricing: Optional[Dict[str, Dict[str, float]]] = None,
+    concurrency: Optional[int] = None,
+    reorder_by_prefix: bool = False,
+    templates: Optional[Dict[str, Union[str, List[Message]]]] = None,
+) -> RunPlan:
+    """What-if estimate for running requests against providers; nothing is sent.
+
+    Providers with a simulator capacity model are planned against it; the rest use
+    the default test-mode latency model.
+    """
+    providers = [providers] if isinstance(providers, ProviderConfig) else providers
+    return _plan([p.as_tuple() for p in providers], requests, pricing, concurrency, reorder_by_prefix, templates)
+
 class BatchProcessor:
-    def __init__(self, providers: Union[ProviderConfig, List[ProviderConfig]], progress_callback: Optional[Callable[[int, int], None]] = None):
-        self.providers = [providers] if isinstance(providers, ProviderConfig) else providers
+    """Runs batches of requests against one or more providers.
+
+    The options are described under "BatchProcessor options" in the README.
+    """
+
+    def __init__(
+        self,
+        providers: Union[ProviderConfig, List[ProviderConfig]],
+        progress_callback: Optional[Callable[..., None]] = None,
+        validate_schema: bool = False,
+        result_callback: Optional[Callable[[RequestMetrics], None]] = None,
+        callback_workers: Optional[int] = None,
+        compress_content: bool = False,
+        capture_raw_response: bool = False,
+        reorder_by_prefix: bool = False,
+        stream_dir: Optional[str] = None,
+        think_time: Optional[Dict[str, Any]] = None,
+        circuit_breaker: Optional[int] = None,
+        run_name: Optional[str] = None,
+        registry: Union[RunRegistry, str, None] = None,
+        dedupe_ttl: Optional[float] = None,
+        on_duplicate: str = "skip",
+        artifact_dir: Optional[str] = None,
+        artifact_min_bytes: int = 4096,
+        detect_language: bool = False,
+        language_routing: Optional[Dict[str, Union[int, List[int]]]] = None,
+        sanitize_inputs: bool = False,
+        tools: Optional[Dict[str, Callable[..., Any]]] = None,
+        max_tool_rounds: int = 8,
+        choice_policy: Union[str, Callable[[List[Optional[str]]], int], None] = None,
+        templates: Optional[Dict[str, Union[str, List[Message]]]] = None,
+        rate_limit_retries: int = 3,
+        retry_budget: Optional[float] = None,
+        pause_on_rate_limit: bool = False,
+        failover: Optional[List[int]] = None,
+        max_concurrency: Optional[int] = None,
+        max_concurrency_per_host: Optional[int] = None,
+        max_queue_depth: Optional[int] = None,
+        adaptive_concurrency: bool = False,
+        spillover: bool = False,
+        hedge_requests: bool = False,
+        race_providers: Optional[int] = None,
+        dedupe_requests: bool = False,
+        routing: str = "round_robin",
+        health_check: bool = False,
+        health_check_interval: Optional[float] = None,
+        warmup_requests: Optional[int] = None,
+        warmup_seconds: Optional[float] = None,
+        max_duration_seconds: Optional[float] = None,
+        max_cost_usd: Optional[float] = None,
+        max_total_tokens: Optional[int] = None,
+        pricing: Optional[Dict[str, Dict[str, float]]] = None,
+        preserve_order: bool = False,
+        drain_timeout: Optional[float] = None,
+        traceparent: Optional[str] = None,
+        log_level: Optional[Union[int, str]] = None,
+        metrics_log: Optional[str] = None,
+        sample_interval: Optional[float] = None,
+        statsd: Optional[str] = None,
+        statsd_prefix: str = "axicontraves",
+        statsd_tags: Optional[Dict[str, str]] = None,
+        legacy_progress: bool = False,
+    ):
+        self.providers = [providers] if isinstance(providers, ProviderConfig) else list(providers)
+        if not self.providers:
+            raise ValueError("At least one provider is required")
+        for i, provider in enumerate(self.providers):
+            if not isinstance(provider, ProviderConfig):
+                raise TypeError(f"providers[{i}] must be a ProviderConfig, got {type(provider).__name__}")
+        # Called with a RunProgress after each batch of results, or (completed, total) with legacy_progress
         self._progress_callback = progress_callback
+        self.legacy_progress = legacy_progress
+        self.validate_schema = validate_schema
+        # Per-result callback, run on a thread pool of callback_workers when set
+        self._result_callback = result_callback
+        self.callback_workers = callback_workers
+        # Keep response content zstd-compressed in Rust; decompressed on attribute access
+        self.compress_content = compress_content
+        # Keep each full provider response in RequestMetrics.raw_response
+        self.capture_raw_response = capture_raw_response
+        # Cluster requests by shared prompt prefix to exploit server-side prefix caching
+        self.reorder_by_prefix = reorder_by_prefix
+        # Stream every response into {stream_dir}/{request_index}.txt as tokens arrive
+        self.stream_dir = stream_dir
+        # Pause each concurrent slot between its requests, e.g. {"distribution": "exponential", "mean_ms": 2000}
+        self.think_time = think_time
+        # Consecutive failures after which a provider leaves the rotation
+        self.circuit_breaker = circuit_breaker
+        # Record every processed batch under run_name in the registry
+        self.run_name = run_name
+        self.registry = registry if isinstance(registry, RunRegistry) else RunRegistry(registry)
+        # Skip (or warn about) requests identical to one sent within dedupe_ttl seconds
+        if on_duplicate not in ("skip", "warn"):
+            raise ValueError(f"on_duplicate must be 'skip' or 'warn', got {on_duplicate!r}")
+        self.dedupe_ttl = dedupe_ttl
+        self.on_duplicate = on_duplicate
+        self._submitted: Dict[str, float] = {}
+        # Move outputs of at least artifact_min_bytes into a content-addressed store
+        self.artifact_dir = artifact_dir
+        self.artifact_min_bytes = artifact_min_bytes
+        # Tag requests with their prompt language; language_routing implies it
+        self.detect_language = detect_language or language_routing is not None
+        self.language_routing = None if language_routing is None else {
+            language: [providers] if isinstance(providers, int) else list(providers)
+            for language, providers in language_routing.items()
+        }
+        # Clean up message text before sending
+        self.sanitize_inputs = sanitize_inputs
+        # Agent mode: functions to call for requests with "tools", by tool name
+        self.tools = tools
+        self.max_tool_rounds = max_tool_rounds
+        # With n > 1, which choice becomes `content`
+        self.choice_policy = choice_policy
+        # Named Jinja templates for (template_name, variables) requests
+        self.templates = templates
+        # Retries of 429/503 responses, after the wait the provider asks for
+        self.rate_limit_retries = rate_limit_retries
+        self.pause_on_rate_limit = pause_on_rate_limit
+        # Cap on retries across the run, as a fraction of the requests sent
+        self.retry_budget = retry_budget
+        # Fallback order of providers by position, e.g. [0, 2, 1]
+        self.failover = None if failover is None else list(failover)
+        # Requests in flight at once across all providers (default 64)
+        self.max_concurrency = max_concurrency
+        # Requests in flight at once to any one host, however many providers share it
+        self.max_concurrency_per_host = max_concurrency_per_host
+        # Requests held back by a provider's limits before more are shed
+        self.max_queue_depth = max_queue_depth
+        # Tune concurrency from the results instead of running at max_concurrency
+        self.adaptive_concurrency = adaptive_concurrency
+        # Send a request a provider's limits would hold to one that can start it now
+        self.spillover = spillover
+        # Copy requests still unanswered past the recent p95 latency to a second provider
+        self.hedge_requests = hedge_requests
+        # Send every request to this many providers and keep the first success
+        self.race_providers = race_providers
+        # Send identical requests once per batch and copy the result to the rest
+        self.dedupe_requests = dedupe_requests
+        # "round_robin" or "least_loaded"
+        self.routing = routing
+        # Probe providers before the run, and every health_check_interval seconds during it
+        self.health_check = health_check
+        self.health_check_interval = health_check_interval
+        # Requests sent to each provider, or seconds spent, before the run proper
+        self.warmup_requests = warmup_requests
+        self.warmup_seconds = warmup_seconds
+        # Run budget; cost uses pricing on top of builtin_pricing()
+        self.max_duration_seconds = max_duration_seconds
+        self.max_cost_usd = max_cost_usd
+        self.max_total_tokens = max_total_tokens
+        self.pricing = pricing
+        # Return and yield results in input order
+        self.preserve_order = preserve_order
+        # Seconds requests in flight get to finish once a run is cancelled
+        self.drain_timeout = drain_timeout
+        # W3C traceparent the batch's OTLP span continues
+        self.traceparent = traceparent
+        # Level of the "axicontraves" logger the Rust core logs to
+        if log_level is not None:
+            logging.getLogger("axicontraves").setLevel(log_level)
+        # File to append one JSON line to per result as the run goes
+        self.metrics_log = metrics_log
+        # Seconds between throughput samples, in summary.samples
+        self.sample_interval = sample_interval
+        # StatsD/DogStatsD agent ("host" or "host:port") to send metrics to over UDP
+        self.statsd = statsd
+        self.statsd_prefix = statsd_prefix
+        self.statsd_tags = statsd_tags
+
+    def _duplicates(self, requests: Iterable[Request]) -> List[int]:
+        """Indices of requests already submitted within the TTL; records the rest."""
+        if self.dedupe_ttl is None:
+            return []
+        if not isinstance(requests, Sequence):
+            raise ValueError("dedupe_ttl needs the requests as a list, not an iterator")
+        now = time.time()
+        self._submitted = {h: t for h, t in self._submitted.items() if now - t < self.dedupe_ttl}
+        hashes = [
+            hashlib.sha256(json.dumps(r, sort_keys=True, default=str).encode()).hexdigest()
+            for r in requests
+        ]
+        duplicates = [i for i, h in enumerate(hashes) if h in self._submitted]
+        self._submitted.update((h, now) for h in hashes)
+        if duplicates:
+            action = "skipping" if self.on_duplicate == "skip" else "sending anyway"
+            warnings.warn(
+                f"{len(duplicates)} request(s) were already submitted in the last "
+                f"{self.dedupe_ttl:g}s; {action}",
+                stacklevel=3,
+            )
+        return duplicates if self.on_duplicate == "skip" else []
+
+    def _run_options(self, requests: Iterable[Request]) -> Dict[str, Any]:
+        """Keyword arguments process_batch and start_batch both pass to the Rust core."""
+        return dict(
+            validate_schema=self.validate_schema,
+            compress_content=self.compress_content,
+            reorder_by_prefix=self.reorder_by_prefix,
+            stream_dir=self.stream_dir,
+            think_time=self.think_time,
+            capture_raw_response=self.capture_raw_response,
+            circuit_breaker=self.circuit_breaker,
+            skip=self._duplicates(requests),
+            artifact_dir=self.artifact_dir,
+            artifact_min_bytes=self.artifact_min_bytes,
+            detect_language=self.detect_language,
+            language_routing=self.language_routing,
+            sanitize_inputs=self.sanitize_inputs,
+            tools=self.tools,
+            max_tool_rounds=self.max_tool_rounds,
+            choice_policy=self.choice_policy,
+            templates=self.templates,
+            rate_limit_retries=self.rate_limit_retries,
+            retry_budget=self.retry_budget,
+            pause_on_rate_limit=self.pause_on_rate_limit,
+            failover=self.failover,
+            max_concurrency=self.max_concurrency,
+            max_concurrency_per_host=self.max_concurrency_per_host,
+            max_queue_depth=self.max_queue_depth,
+            adaptive_concurrency=self.adaptive_concurrency,
+            spillover=self.spillover,
+            hedge_requests=self.hedge_requests,
+            race_providers=self.race_providers,
+            dedupe_requests=self.dedupe_requests,
+            routing=self.routing,
+            health_check=self.health_check,
+            health_check_interval=self.health_check_interval,
+            warmup_requests=self.warmup_requests,
+            warmup_seconds=self.warmup_seconds,
+            max_duration_seconds=self.max_duration_seconds,
+            max_cost_usd=self.max_cost_usd,
+            max_total_tokens=self.max_total_tokens,
+            pricing=self.pricing,
+            drain_timeout=self.drain_timeout,
+            traceparent=self.traceparent or _current_traceparent(),
+            metrics_log=self.metrics_log,
+            sample_interval=self.sample_interval,
+            statsd=self.statsd,
+            statsd_prefix=self.statsd_prefix,
+            statsd_tags=self.statsd_tags,
+        )
+
+    def plan(self, requests: List[Request], pricing: Optional[Dict[str, Dict[str, float]]] = None) -> RunPlan:
+        """Predict wall-clock time, cost and per-provider load without sending anything.
+
+        pricing maps model name to {"input": usd_per_1m_tokens, "output": usd_per_1m_tokens}
+        and defaults to the processor's pricing.
+        """
+        return plan(
+            requests,
+            self.providers,
+            pricing if pricing is not None else self.pricing,
+            concurrency=self.max_concurrency,
+            reorder_by_prefix=self.reorder_by_prefix,
+            templates=self.templates,
+        )
+
+    def start_batch(self, requests: Iterable[Request]) -> BatchHandle:
+        """Run the batch on a background thread. The returned handle exposes
+        cancel_request(index), cancel(), completed/total, done(), results() and wait(),
+        and iterating over it yields each result as soon as it finishes.
+        Ctrl-C during wait() cancels the run and returns the partial results once the
+        requests in flight have had drain_timeout to finish.
+        add_provider(config) brings another ProviderConfig into the rotation mid-run and
+        returns its index; drain_provider(index) stops sending to a provider (numbered from
+        0 in the order given), lets its requests in flight finish and hands those its
+        limits still hold back to the other providers, e.g. to rotate keys without
+        restarting a long job. provider_stats() lists each provider's state and
+        its pending (held back by its own limits), in_flight, completed and shed requests.
+        summary() returns a RunSummary of the results so far. get_stats() returns a
+        RunStats (completed, in_flight, rates and latency percentiles) without going over
+        the results, so a dashboard thread can poll it as often as it likes.
 
-    def process_batch(self, requests: List[List[Message]], show_progress: bool = True) -> BatchRequestResult:
+        requests can also be an iterator or generator: it is pulled from lazily, one request
+        each time a concurrency slot frees up, so a dataset never has to be held in memory.
+        Requests are then sent in the order they are yielded (priority has no effect), an
+        index is the position in the iterator, total counts the requests pulled so far, and
+        reorder_by_prefix and dedupe_ttl are not available. An exception raised by the
+        iterator, or a malformed request, stops the pulling; it is raised once the requests
+        already in flight have finished."""
+        handle = start_requests_multi(
+            [p.as_tuple() for p in self.providers],
+            requests,
+            **self._run_options(requests),
+        )
+        _running.add(handle)
+        return handle
+
+    def iter_batch(self, requests: Iterable[Request]) -> Iterator[RequestMetrics]:
+        """Yield each result as soon as it finishes, in completion order, so downstream
+        processing overlaps with the requests still running. With preserve_order, each
+        result is yielded once every earlier request's result has been."""
+        if not self.preserve_order:
+            yield from self.start_batch(requests)
+            return
+        finished: Dict[int, RequestMetrics] = {}
+        next_index = 0
+        for metrics in self.start_batch(requests):
+            finished[metrics.index] = metrics
+            while next_index in finished:
+                yield finished.pop(next_index)
+                next_index += 1
+        # Only left over when a run lost results; the integrity warning has the details
+        yield from (finished[index] for index in sorted(finished))
+
+    def process_batch(self, requests: Iterable[Request], show_progress: bool = True) -> BatchRequestResult:
+        """Run the batch to completion. Ctrl-C stops it early: requests in flight get
+        drain_timeout to finish, those not answered by then come back with status
+        "cancelled" alongside the finished ones, and the result is marked interrupted. requests may be an
+        iterator pulled from lazily, as described under start_batch()."""
         console = Console()
         start_time = time.time()
         total_tokens = 0
@@ -88,7 +596,7 @@ class BatchProcessor:
         ) as progress:
             task = progress.add_task(
                 "[cyan]Processing batch requests...",
-                total=len(requests),
+                total=len(requests) if isinstance(requests, Sequence) else None,
                 prompt_rate=0.0,
                 completion_rate=0.0,
                 uplink=0.0,
@@ -97,13 +605,13 @@ class BatchProcessor:
                 providers=len(self.providers)
             )
 
-            def update_progress(completed: int, total: int, new_prompt_tokens: int, new_completion_tokens: int, new_request_bytes: int, new_response_bytes: int, thread_count: int):
+            def update_progress(update: RunProgress):
                 nonlocal total_tokens, prompt_tokens, completion_tokens, total_request_bytes, total_response_bytes
-                prompt_tokens += new_prompt_tokens
-                completion_tokens += new_completion_tokens
+                prompt_tokens = update.prompt_tokens
+                completion_tokens = update.completion_tokens
                 total_tokens = prompt_tokens + completion_tokens
-                total_request_bytes += new_request_bytes
-                total_response_bytes += new_response_bytes
+                total_request_bytes = update.request_bytes
+                total_response_bytes = update.response_bytes
                 
                 elapsed = time.time() - start_time
                 if elapsed > 0:
@@ -116,57 +624,103 @@ class BatchProcessor:
                 
                 progress.update(
                     task,
-                    completed=completed,
+                    completed=update.completed,
                     prompt_rate=prompt_rate,
                     completion_rate=completion_rate,
                     uplink=uplink_mbps,
                     downlink=downlink_mbps,
-                    threads=thread_count
+                    threads=update.threads
                 )
-                if self._progress_callback:
-                    self._progress_callback(completed, total)
+                if self._progress_callback and self.legacy_progress:
+                    self._progress_callback(update.completed, update.total)
+                elif self._progress_callback:
+                    self._progress_callback(update)
+
+            run_options = self._run_options(requests)
 
             # Convert providers to format expected by Rust
-            provider_configs = [
-                (p.name, p.api_key, p.base_url, p.config)
-                for p in self.providers
-            ]
+            provider_configs = [p.as_tuple() for p in self.providers]
+
+            executor = None
+            pending: List[Future] = []
+            result_callback = self._result_callback
+            if result_callback and self.callback_workers:
+                executor = ThreadPoolExecutor(max_workers=self.callback_workers)
+
+                def result_callback(metric: RequestMetrics):
+                    pending.append(executor.submit(self._result_callback, metric))
+
+            samples: List[ThroughputSample] = []
+
+            # The run is timed from the end of the warm-up
+            def warmed_up():
+                nonlocal start_time
+                start_time = time.time()
 
             # Process all requests through all providers in round-robin fashion
-            metrics = process_requests_multi(
-                provider_configs,
-                requests,
-                update_progress,
-                self.providers[0].test_mode,  # Use first provider's test mode
-                self.providers[0].tokens_per_minute,  # Use first provider's rate limit
-            )
+            try:
+                metrics = process_requests_multi(
+                    provider_configs,
+                    requests,
+                    update_progress,
+                    False,  # test_mode is set per provider in the options dict
+                    None,  # rate limits are set per provider in the options dict
+                    result_callback=result_callback,
+                    warmup_callback=warmed_up,
+                    sample_callback=samples.append,
+                    **run_options,
+                )
+            finally:
+                if executor:
+                    executor.shutdown(wait=True)
+            # Surface the first exception raised by a pooled callback
+            for future in pending:
+                future.result()
+            if self.preserve_order:
+                metrics.sort(key=lambda m: m.index)
 
-            # Create per-provider metrics
+            def subtotal(group: List[RequestMetrics]) -> BatchRequestResult:
+                return BatchRequestResult(
+                    total_requests=len(group),
+                    total_tokens=sum(m.prompt_tokens + m.completion_tokens for m in group),
+                    prompt_tokens=sum(m.prompt_tokens for m in group),
+                    completion_tokens=sum(m.completion_tokens for m in group),
+                    total_time=time.time() - start_time,
+                    metrics=group,
+                    total_request_bytes=sum(m.request_bytes for m in group),
+                    total_response_bytes=sum(m.response_bytes for m in group),
+                    provider_metrics={},
+                )
+
+            # Create per-provider metrics, keyed by the names the results carry: a provider's
+            # base_url is None when it uses the default endpoint
             provider_results = {}
-            for provider in self.providers:
-                provider_key = f"{provider.name}:{provider.base_url}"
-                provider_metrics = [m for m in metrics if m.provider_name == provider_key]
-                if provider_metrics:  # Only create metrics if we have results for this provider
-                    provider_results[provider_key] = BatchRequestResult(
-                        total_requests=len(provider_metrics),
-                        total_tokens=sum(m.prompt_tokens + m.completion_tokens for m in provider_metrics),
-                        prompt_tokens=sum(m.prompt_tokens for m in provider_metrics),
-                        completion_tokens=sum(m.completion_tokens for m in provider_metrics),
-                        total_time=time.time() - start_time,
-                        metrics=provider_metrics,
-                        total_request_bytes=sum(m.request_bytes for m in provider_metrics),
-                        total_response_bytes=sum(m.response_bytes for m in provider_metrics),
-                        provider_metrics={},
-                    )
-
-            return BatchRequestResult(
+            for provider_name in dict.fromkeys(m.provider_name for m in metrics if m.provider_name):
+                provider_results[provider_name] = subtotal([m for m in metrics if m.provider_name == provider_name])
+
+            language_results = {}
+            for language in sorted({m.language for m in metrics if m.language}):
+                language_results[language] = subtotal([m for m in metrics if m.language == language])
+
+            total_time = time.time() - start_time
+            result = BatchRequestResult(
                 total_requests=len(metrics),
                 total_tokens=total_tokens,
                 prompt_tokens=prompt_tokens,
                 completion_tokens=completion_tokens,
-                total_time=time.time() - start_time,
+                total_time=total_time,
                 metrics=metrics,
                 total_request_bytes=total_request_bytes,
                 total_response_bytes=total_response_bytes,
                 provider_metrics=provider_results,
+                language_metrics=language_results,
+                integrity=verify_results(metrics, len(requests) if isinstance(requests, Sequence) else len(metrics)),
+                interrupted=any(m.status == "cancelled" for m in metrics),
+                summary=summarize_results(metrics, total_time, samples),
             )
+            if self.run_name:
+                result.run_id = self.registry.record(self.run_name, result, self.providers)
+            return result
+
+# Imported last: the experiment module builds on BatchProcessor
+from .experiment import Experiment, ExperimentResult, Variant, VariantSummary  # noqa: E402
@@ -0,0 +1,182 @@
+from dataclasses import dataclass, field
+from typing import Any, Callable, Dict, List, Optional, Tuple, Union
+import time
+
+from rich.console import Console
+from rich.table import Table
+
+from . import BatchProcessor, ProviderConfig, Request, RequestMetrics
+
+# Scores one response against its request; None skips the request in the mean
+QualityHook = Callable[[Request, RequestMetrics], Optional[float]]
+
+
+@dataclass
+class Variant:
+    """One arm of an experiment: the providers to send to plus request overrides
+    (e.g. {"temperature": 0.2} or {"model": "gpt-4o-mini"}) applied to every request."""
+    name: str
+    providers: Union[ProviderConfig, List[ProviderConfig]]
+    overrides: Dict[str, Any] = field(default_factory=dict)
+
+    def apply(self, request: Request) -> Request:
+        if not self.overrides:
+            return request
+        if isinstance(request, dict):
+            return {**request, **self.overrides}
+        return {"messages": request, **self.overrides}
+
+
+@dataclass
+class VariantSummary:
+    name: str
+    requests: int
+    succeeded: int
+    prompt_tokens: int
+    completion_tokens: int
+    # Wall-clock time spent on this variant's batches
+    total_time: float
+    requests_per_second: float
+    mean_latency_ms: float
+    p50_latency_ms: float
+    p95_latency_ms: float
+    cost_usd: Optional[float]
+    mean_quality: Optional[float]
+    # (request index, metrics) for every successful request
+    results: List[Tuple[int, RequestMetrics]] = field(repr=False, default_factory=list)
+
+    @property
+    def failed(self) -> int:
+        return self.requests - self.succeeded
+
+
+@dataclass
+class ExperimentResult:
+    variants: List[VariantSummary]
+
+    def table(self) -> Table:
+        table = Table(title="Experiment")
+        for column in ("variant", "ok/total", "prompt tok", "completion tok", "req/s",
+                       "mean ms", "p50 ms", "p95 ms", "cost $", "quality"):
+            table.add_column(column, justify="left" if column == "variant" else "right")
+        for v in self.variants:
+            table.add_row(
+                v.name,
+                f"{v.succeeded}/{v.requests}",
+                str(v.prompt_tokens),
+                str(v.completion_tokens),
+                f"{v.requests_per_second:.2f}",
+                f"{v.mean_latency_ms:.0f}",
+                f"{v.p50_latency_ms:.0f}",
+                f"{v.p95_latency_ms:.0f}",
+                "-" if v.cost_usd is None else f"{v.cost_usd:.4f}",
+                "-" if v.mean_quality is None else f"{v.mean_quality:.3f}",
+            )
+        return table
+
+    def print_table(self, console: Optional[Console] = None) -> None:
+        (console or Console()).print(self.table())
+
+
+def _percentile(values: List[float], q: float) -> float:
+    if not values:
+        return 0.0
+    ordered = sorted(values)
+    return ordered[min(len(ordered) - 1, int(q * len(ordered)))]
+
+
+class Experiment:
+    """Run one request set against several variants and compare cost and latency.
+
+    mode="sequential" runs each variant over the whole set in turn; "interleaved" splits
+    the set into chunks of `chunk_size` and runs every variant on each chunk (rotating the
+    order) so drifting provider load affects all variants alike. `pricing` uses the same
+    {model: {"input": usd_per_1m, "output": usd_per_1m}} shape as `plan`, plus an optional
+    "cached_input" rate applied to the prompt tokens a provider served from its cache.
+    Remaining keyword arguments are passed to each variant's BatchProcessor.
+    """
+
+    def __init__(
+        self,
+        variants: List[Variant],
+        pricing: Optional[Dict[str, Dict[str, float]]] = None,
+        quality: Optional[QualityHook] = None,
+        mode: str = "sequential",
+        chunk_size: int = 32,
+        **batch_options: Any,
+    ):
+        if not variants:
+            raise ValueError("At least one variant is required")
+        names = [v.name for v in variants]
+        if len(set(names)) != len(names):
+            raise ValueError("Variant names must be unique")
+        if mode not in ("sequential", "interleaved"):
+            raise ValueError(f"Unknown experiment mode: {mode}")
+        if chunk_size < 1:
+            raise ValueError("chunk_size must be positive")
+        self.variants = variants
+        self.pricing = pricing or {}
+        self.quality = quality
+        self.mode = mode
+        self.chunk_size = chunk_size
+        self.batch_options = batch_options
+
+    def _schedule(self, total: int) -> List[Tuple[Variant, range]]:
+        if self.mode == "sequential":
+            return [(variant, range(total)) for variant in self.variants]
+        schedule = []
+        for round_index, start in enumerate(range(0, total, self.chunk_size)):
+            chunk = range(start, min(start + self.chunk_size, total))
+            offset = round_index % len(self.variants)
+            for variant in self.variants[offset:] + self.variants[:offset]:
+                schedule.append((variant, chunk))
+        return schedule
+
+    def _cost(self, variant: Variant, metrics: RequestMetrics) -> Optional[float]:
+        providers = variant.providers if isinstance(variant.providers, list) else [variant.providers]
+        candidates = [metrics.model, variant.overrides.get("model")] + [p.config.get("model") for p in providers]
+        price = next((self.pricing[m] for m in candidates if m in self.pricing), None)
+        if price is None:
+            return None
+        cached = (metrics.cached_tokens or 0) if "cached_input" in price else 0
+        prompt = (metrics.prompt_tokens - cached) * price["input"] + cached * price.get("cached_input", 0)
+        return (prompt + metrics.completion_tokens * price["output"]) / 1_000_000
+
+    def run(self, requests: List[Request], show_progress: bool = True) -> ExperimentResult:
+        processors = {v.name: BatchProcessor(v.providers, **self.batch_options) for v in self.variants}
+        results: Dict[str, List[Tuple[int, RequestMetrics]]] = {v.name: [] for v in self.variants}
+        elapsed = {v.name: 0.0 for v in self.variants}
+
+        for variant, indices in self._schedule(len(requests)):
+            batch = [variant.apply(requests[i]) for i in indices]
+            started = time.time()
+            outcome = processors[variant.name].process_batch(batch, show_progress=show_progress)
+            elapsed[variant.name] += time.time() - started
+            # Metrics indices are relative to the submitted chunk
+            results[variant.name].extend((indices[m.index], m) for m in outcome.metrics if m.status == "ok")
+
+        summaries = []
+        for variant in self.variants:
+            pairs = results[variant.name]
+            latencies = [m.latency_ms for _, m in pairs]
+            costs = [self._cost(variant, m) for _, m in pairs]
+            scores = []
+            if self.quality:
+                scores = [s for s in (self.quality(requests[i], m) for i, m in pairs) if s is not None]
+            total_time = elapsed[variant.name]
+            summaries.append(VariantSummary(
+                name=variant.name,
+                requests=len(requests),
+                succeeded=len(pairs),
+                prompt_tokens=sum(m.prompt_tokens for _, m in pairs),
+                completion_tokens=sum(m.completion_tokens for _, m in pairs),
+                total_time=total_time,
+                requests_per_second=len(pairs) / total_time if total_time > 0 else 0.0,
+                mean_latency_ms=sum(latencies) / len(latencies) if latencies else 0.0,
+                p50_latency_ms=_percentile(latencies, 0.5),
+                p95_latency_ms=_percentile(latencies, 0.95),
+                cost_usd=None if not costs or None in costs else sum(costs),
+                mean_quality=sum(scores) / len(scores) if scores else None,
+                results=sorted(pairs, key=lambda pair: pair[0]),
+            ))
+        return ExperimentResult(variants=summaries)
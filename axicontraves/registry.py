@@ -0,0 +1,135 @@
+from collections import Counter
+from dataclasses import asdict, dataclass
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Union
+import json
+import os
+import re
+import uuid
+
+# Overridable so CI and notebooks can keep separate histories
+DEFAULT_ROOT = Path(os.environ.get("AXICONTRAVES_RUNS_DIR", Path.home() / ".axicontraves" / "runs"))
+
+
+@dataclass
+class RunManifest:
+    run_id: str
+    name: str
+    # ISO-8601, UTC
+    created_at: str
+    total_requests: int
+    # name, base_url and model of each provider; API keys are never written
+    providers: List[Dict[str, Any]]
+    tags: Dict[str, Any]
+
+    @property
+    def created(self) -> datetime:
+        return datetime.fromisoformat(self.created_at)
+
+
+def _slug(name: str) -> str:
+    return re.sub(r"[^A-Za-z0-9_.-]+", "-", name).strip("-") or "run"
+
+
+def _summarize(result) -> Dict[str, Any]:
+    latencies = sorted(m.latency_ms for m in result.metrics if m.status == "ok")
+
+    def percentile(q: float) -> float:
+        return latencies[min(len(latencies) - 1, int(q * len(latencies)))] if latencies else 0.0
+
+    return {
+        "total_requests": result.total_requests,
+        "prompt_tokens": result.prompt_tokens,
+        "completion_tokens": result.completion_tokens,
+        "total_time": result.total_time,
+        "requests_per_second": result.requests_per_second,
+        "tokens_per_second": result.tokens_per_second,
+        "total_request_bytes": result.total_request_bytes,
+        "total_response_bytes": result.total_response_bytes,
+        "latency_ms": {"p50": percentile(0.5), "p95": percentile(0.95), "max": latencies[-1] if latencies else 0.0},
+        "finish_reasons": dict(Counter(m.finish_reason or "unknown" for m in result.metrics)),
+        "statuses": dict(Counter(m.status for m in result.metrics)),
+        "providers": {
+            key: {
+                "total_requests": provider.total_requests,
+                "prompt_tokens": provider.prompt_tokens,
+                "completion_tokens": provider.completion_tokens,
+            }
+            for key, provider in result.provider_metrics.items()
+        },
+        "languages": {
+            language: {
+                "total_requests": subset.total_requests,
+                "prompt_tokens": subset.prompt_tokens,
+                "completion_tokens": subset.completion_tokens,
+            }
+            for language, subset in result.language_metrics.items()
+        },
+    }
+
+
+class RunRegistry:
+    """Directory of past runs, one `<run_id>/` folder each holding manifest.json and summary.json."""
+
+    def __init__(self, root: Union[str, Path, None] = None):
+        self.root = Path(root) if root is not None else DEFAULT_ROOT
+
+    def record(self, name: str, result, providers, tags: Optional[Dict[str, Any]] = None) -> str:
+        created = datetime.now(timezone.utc)
+        run_id = f"{created:%Y%m%dT%H%M%S}-{_slug(name)}-{uuid.uuid4().hex[:6]}"
+        manifest = RunManifest(
+            run_id=run_id,
+            name=name,
+            created_at=created.isoformat(),
+            total_requests=result.total_requests,
+            providers=[{"name": p.name, "base_url": p.base_url, "model": p.config.get("model")} for p in providers],
+            tags=tags or {},
+        )
+        directory = self.root / run_id
+        directory.mkdir(parents=True)
+        (directory / "manifest.json").write_text(json.dumps(asdict(manifest), indent=2))
+        (directory / "summary.json").write_text(json.dumps(_summarize(result), indent=2))
+        return run_id
+
+    def list_runs(
+        self,
+        name: Optional[str] = None,
+        since: Optional[datetime] = None,
+        until: Optional[datetime] = None,
+    ) -> List[RunManifest]:
+        """Runs oldest first, optionally filtered by exact name and a created_at window
+        (naive datetimes are taken as UTC)."""
+        since, until = (_aware(d) for d in (since, until))
+        runs = []
+        for path in sorted(self.root.glob("*/manifest.json")):
+            manifest = RunManifest(**json.loads(path.read_text()))
+            if name is not None and manifest.name != name:
+                continue
+            if since is not None and manifest.created < since:
+                continue
+            if until is not None and manifest.created > until:
+                continue
+            runs.append(manifest)
+        return sorted(runs, key=lambda run: run.created_at)
+
+    def load_summary(self, run_id: str) -> Dict[str, Any]:
+        path = self.root / run_id / "summary.json"
+        if not path.exists():
+            raise KeyError(f"Unknown run: {run_id}")
+        return json.loads(path.read_text())
+
+
+def _aware(value: Optional[datetime]) -> Optional[datetime]:
+    if value is not None and value.tzinfo is None:
+        return value.replace(tzinfo=timezone.utc)
+    return value
+
+
+def list_runs(name: Optional[str] = None, since: Optional[datetime] = None, until: Optional[datetime] = None,
+              root: Union[str, Path, None] = None) -> List[RunManifest]:
+    return RunRegistry(root).list_runs(name, since, until)
+
+
+def load_summary(run_id: str, root: Union[str, Path, None] = None) -> Dict[str, Any]:
+    return RunRegistry(root).load_summary(run_id)
@@ -0,0 +1,67 @@
+use crate::status::Status;
+use crate::RequestMetrics;
+
+// Median latency above this multiple of the lowest seen counts as the provider queueing
+const LATENCY_INFLATION: f64 = 2.0;
+// Failed fraction of a round tolerated before backing off
+const ERROR_RATE: f64 = 0.1;
+
+// AIMD controller for the number of requests in flight, in the spirit of TCP Vegas. A round
+// is the results of a limit's worth of requests sent at the current limit; after each the
+// limit grows while the round looked healthy and is halved on rate limiting (any 429/503 retries), a high failure rate or a
+// median latency well above the best round so far. Growth doubles until the first backoff
+// (slow start) and then adds one per round.
+pub struct AdaptiveConcurrency {
+    limit: usize,
+    max: usize,
+    slow_start: bool,
+    // Lowest median latency of any healthy round, taken as the unloaded latency
+    baseline_ms: Option<f64>,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self { limit: max.min(4), max, slow_start: true, baseline_ms: None }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // Adjust the limit from the outcome of a round
+    pub fn update(&mut self, results: &[RequestMetrics]) -> usize {
+        let sent: Vec<&RequestMetrics> = results.iter().filter(|m| m.status == Status::Ok || m.status.is_failure()).collect();
+        if sent.is_empty() {
+            return self.limit;
+        }
+        let failed = sent.iter().filter(|m| m.status.is_failure()).count();
+        let rate_limited = sent.iter().any(|m| m.retries > 0);
+        let mut latencies: Vec<f64> = sent.iter().filter(|m| m.status == Status::Ok).map(|m| m.latency_ms).collect();
+        latencies.sort_by(f64::total_cmp);
+        let median = latencies.get(latencies.len() / 2).copied();
+        let inflated = match (median, self.baseline_ms) {
+            (Some(median), Some(baseline)) => median > baseline * LATENCY_INFLATION,
+            _ => false,
+        };
+
+        if rate_limited || inflated || failed as f64 > sent.len() as f64 * ERROR_RATE {
+            // Slow even one at a time: the provider itself got slower, so start over from here
+            if inflated && self.limit == 1 {
+                self.baseline_ms = median;
+            }
+            self.limit = (self.limit / 2).max(1);
+            self.slow_start = false;
+        } else {
+            if let Some(median) = median {
+                self.baseline_ms = Some(self.baseline_ms.map_or(median, |baseline| baseline.min(median)));
+            }
+            // Only grow once the round actually used the whole limit
+            if sent.len() >= self.limit {
+                let grown = if self.slow_start { self.limit * 2 } else { self.limit + 1 };
+                self.limit = grown.min(self.max);
+            }
+        }
+        self.limit
+    }
+}
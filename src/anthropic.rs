@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use reqwest::header::HeaderMap;
+use reqwest::Client;
+use serde_json::json;
+
+use crate::message::MessageFormat;
+use crate::planner::RequestEstimate;
+use crate::ratelimit::RateLimiter;
+use crate::simulator::Simulator;
+use crate::streaming::{consume_stream, STOPPED_BY_PATTERN};
+use crate::tokenizer::{count_prompt_tokens, count_tokens};
+use crate::{
+    calculate_prompt_tokens, check_status, estimated_service_ms, expected_completion_tokens, extract_config_value,
+    extract_json_object, extract_string_list, get_required_value, min_limit, provider_request_id, read_json, simulate_usage, url_host, wire_bytes,
+    ChatRequest, LLMProvider, RequestMetrics, ResponseContent,
+};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+// Map stop_reason onto the OpenAI finish_reason vocabulary so results compare across providers
+fn finish_reason(stop_reason: &str) -> String {
+    match stop_reason {
+        "end_turn" | "stop_sequence" | "pause_turn" => "stop",
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        "refusal" => "content_filter",
+        other => other,
+    }
+    .to_string()
+}
+
+#[derive(Debug)]
+pub struct AnthropicConfig {
+    model: String,
+    // Required by the Messages API, so it always has a value
+    max_tokens: usize,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    top_k: Option<usize>,
+    stop: Option<Vec<String>>,
+    extra_body: Option<serde_json::Value>,
+}
+
+impl AnthropicConfig {
+    pub fn extract(config: &PyDict) -> PyResult<Self> {
+        Ok(Self {
+            model: get_required_value(config, "model")?,
+            max_tokens: extract_config_value(config, "max_tokens")?.unwrap_or(1024),
+            temperature: extract_config_value(config, "temperature")?,
+            top_p: extract_config_value(config, "top_p")?,
+            top_k: extract_config_value(config, "top_k")?,
+            stop: extract_string_list(config, "stop")?,
+            extra_body: extract_json_object(config, "extra_body")?,
+        })
+    }
+}
+
+pub struct AnthropicProvider {
+    pub client: Client,
+    pub api_key: String,
+    pub base_url: String,
+    pub config: AnthropicConfig,
+    pub test_mode: bool,
+    pub simulator: Option<Arc<Simulator>>,
+    pub headers: HeaderMap,
+    pub limits: RateLimiter,
+    pub weight: f64,
+    pub read_timeout: Option<Duration>,
+}
+
+impl AnthropicProvider {
+    fn requested_model(&self, request: &ChatRequest) -> String {
+        request.overrides.model.as_ref().unwrap_or(&self.config.model).clone()
+    }
+
+    fn max_tokens(&self, request: &ChatRequest) -> usize {
+        request.overrides.max_tokens
+            .or(request.overrides.max_completion_tokens)
+            .unwrap_or(self.config.max_tokens)
+    }
+
+    fn build_payload(&self, request: &ChatRequest) -> Result<serde_json::Map<String, serde_json::Value>, Box<dyn Error + Send + Sync>> {
+        let overrides = &request.overrides;
+        if overrides.tools.is_some() {
+            return Err("Tools are only supported for OpenAI-compatible providers".into());
+        }
+        if request.image.is_some() {
+            return Err("Image generation is only supported for OpenAI-compatible providers".into());
+        }
+        let mut payload = MessageFormat::Anthropic.normalize(&request.messages)?;
+        payload.insert("model".to_string(), json!(self.requested_model(request)));
+        payload.insert("max_tokens".to_string(), json!(self.max_tokens(request)));
+        if let Some(temperature) = overrides.temperature.or(self.config.temperature) {
+            payload.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(top_p) = overrides.top_p.or(self.config.top_p) {
+            payload.insert("top_p".to_string(), json!(top_p));
+        }
+        if let Some(top_k) = self.config.top_k {
+            payload.insert("top_k".to_string(), json!(top_k));
+        }
+        if let Some(stop) = overrides.stop.as_ref().or(self.config.stop.as_ref()) {
+            payload.insert("stop_sequences".to_string(), json!(stop));
+        }
+        // Anthropic's metadata only carries an end-user ID
+        if let Some(user) = &overrides.user {
+            payload.insert("metadata".to_string(), json!({"user_id": user}));
+        }
+        // There is no response_format; the schema becomes the one tool the model must call,
+        // and the call's input is the structured output
+        if let Some(spec) = request.json_schema_spec() {
+            let name = spec["name"].as_str().unwrap_or("response");
+            payload.insert("tools".to_string(), json!([{
+                "name": name,
+                "description": "Respond with a JSON object matching this schema",
+                "input_schema": spec["schema"],
+            }]));
+            payload.insert("tool_choice".to_string(), json!({"type": "tool", "name": name}));
+        }
+        if request.stream_to.is_some() {
+            payload.insert("stream".to_string(), json!(true));
+        }
+        for extra_body in [&self.config.extra_body, &overrides.extra_body].into_iter().flatten() {
+            if let serde_json::Value::Object(fields) = extra_body {
+                payload.extend(fields.clone());
+            }
+        }
+        Ok(payload)
+    }
+
+    // The Messages API request, serialized once and counted as it goes on the wire
+    fn messages_request(&self, request: &ChatRequest) -> Result<reqwest::Request, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}/v1/messages", self.base_url.trim_end_matches('/'));
+        let request_body = serde_json::to_string(&self.build_payload(request)?)?;
+        Ok(self.client
+            .post(&url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .headers(self.headers.clone())
+            .body(request_body)
+            .build()?)
+    }
+}
+
+#[async_trait]
+impl LLMProvider for AnthropicProvider {
+    async fn send_chat_request(&self, request: &ChatRequest) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>> {
+        let http_request = self.messages_request(request)?;
+        let request_bytes = wire_bytes(&http_request);
+        if self.test_mode {
+            let (prompt_tokens, completion_tokens) = simulate_usage(self.simulator.as_deref(), MessageFormat::Anthropic, &request.messages, 1).await?;
+            let max_tokens = self.max_tokens(request);
+            let mut metrics = RequestMetrics::new(
+                prompt_tokens,
+                completion_tokens.min(max_tokens),
+                request_bytes,
+                completion_tokens.min(max_tokens) * 4,
+                self.display_name(),
+            );
+            metrics.model = Some(self.requested_model(request));
+            metrics.finish_reason = Some(if completion_tokens > max_tokens { "length" } else { "stop" }.to_string());
+            return Ok(metrics);
+        }
+
+        let sent = Instant::now();
+        let response = self.client.execute(http_request).await?;
+        self.limits.observe(response.headers());
+        let provider_request_id = provider_request_id(response.headers());
+        let response = check_status(response, provider_request_id.as_deref()).await?;
+
+        // Streamed responses come back reassembled as a chat completion, so the text sits
+        // under choices instead of content blocks
+        let (response_data, response_bytes, text, stop_reason, timing) = match &request.stream_to {
+            Some(path) => {
+                let (data, bytes, timing) =
+                    consume_stream(response, path, request.overrides.stop_regex.as_ref(), self.read_timeout, sent).await?;
+                let text = data["choices"][0]["message"]["content"].as_str().map(str::to_string);
+                let stop_reason = data["choices"][0]["finish_reason"].as_str().map(|reason| match reason {
+                    // As below, the forced tool call is the answer
+                    "tool_use" if request.json_schema_spec().is_some() => "end_turn".to_string(),
+                    reason => reason.to_string(),
+                });
+                (data, bytes, text, stop_reason, Some(timing))
+            }
+            None => {
+                let (data, bytes) = read_json(response).await?;
+                if let Some(error) = data.get("error") {
+                    return Err(format!("Anthropic error: {}", error).into());
+                }
+                let blocks = data["content"].as_array();
+                let structured = blocks
+                    .and_then(|blocks| blocks.iter().find(|block| block["type"] == "tool_use"))
+                    .filter(|_| request.json_schema_spec().is_some());
+                let (text, stop_reason) = match structured {
+                    // The forced tool call is the answer, not a request to run a tool
+                    Some(call) => (Some(call["input"].to_string()), Some("end_turn".to_string())),
+                    None => (
+                        blocks.map(|blocks| blocks.iter().filter_map(|block| block["text"].as_str()).collect::<String>()),
+                        data["stop_reason"].as_str().map(str::to_string),
+                    ),
+                };
+                (data, bytes, text, stop_reason, None)
+            }
+        };
+
+        let model = response_data["model"].as_str().map(str::to_string).unwrap_or_else(|| self.requested_model(request));
+        let count = |key: &str| response_data["usage"][key].as_u64().map(|v| v as usize);
+        let cache_creation = count("cache_creation_input_tokens");
+        let cache_read = count("cache_read_input_tokens");
+        // Output counts arrive with message_delta, which a stop_regex cut never reaches
+        let stopped_early = stop_reason.as_deref() == Some(STOPPED_BY_PATTERN);
+        let input_tokens = count("input_tokens");
+        let output_tokens = count("output_tokens").filter(|_| !stopped_early);
+        // Counts missing from usage (e.g. stripped by a proxy) are tokenized locally.
+        // input_tokens excludes cached tokens; report the full prompt the model attended to.
+        let prompt_tokens = input_tokens.unwrap_or_else(|| count_prompt_tokens(&model, &request.messages))
+            + cache_creation.unwrap_or(0)
+            + cache_read.unwrap_or(0);
+        let completion_tokens = output_tokens
+            .unwrap_or_else(|| text.as_deref().map_or(0, |text| count_tokens(&model, text)));
+
+        let mut metrics = RequestMetrics::new(
+            prompt_tokens,
+            completion_tokens,
+            request_bytes,
+            response_bytes,
+            self.display_name(),
+        );
+        metrics.usage_estimated = input_tokens.is_none() || output_tokens.is_none();
+        metrics.choices = vec![text.map(ResponseContent::Plain)];
+        metrics.finish_reason = stop_reason.as_deref().map(finish_reason);
+        metrics.cache_creation_input_tokens = cache_creation;
+        metrics.cache_read_input_tokens = cache_read;
+        metrics.cached_tokens = cache_read;
+        if let Some(timing) = timing {
+            timing.apply(&mut metrics);
+        }
+        metrics.model = Some(model);
+        metrics.raw_response = Some(response_data);
+        metrics.provider_request_id = provider_request_id;
+        Ok(metrics)
+    }
+
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    fn display_name(&self) -> String {
+        format!("{}:{}", self.name(), self.base_url)
+    }
+
+    fn estimate(&self, request: &ChatRequest) -> RequestEstimate {
+        let prompt_tokens = calculate_prompt_tokens(MessageFormat::Anthropic, &request.messages);
+        let completion_tokens = expected_completion_tokens(prompt_tokens).min(self.max_tokens(request));
+        let service_ms = estimated_service_ms(self.simulator.as_deref(), prompt_tokens, completion_tokens);
+        RequestEstimate { prompt_tokens, completion_tokens, service_ms }
+    }
+
+    fn max_concurrency(&self) -> Option<usize> {
+        let capacity = self.simulator.as_ref().and_then(|simulator| simulator.max_concurrency());
+        min_limit(capacity, self.limits.max_concurrency())
+    }
+
+    fn limits(&self) -> Option<&RateLimiter> {
+        Some(&self.limits)
+    }
+
+    fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn host(&self) -> String {
+        url_host(&self.base_url)
+    }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.test_mode {
+            return Ok(());
+        }
+        let response = self.client
+            .get(format!("{}/v1/models", self.base_url.trim_end_matches('/')))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+        check_status(response, None).await?;
+        Ok(())
+    }
+}
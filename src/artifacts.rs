@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use sha2::{Digest, Sha256};
+
+use crate::storage::{open_storage, Storage};
+
+// Content-addressed store for large outputs: each text is written once under the key
+// {hash[..2]}/{hash}.txt and results keep only the key, so identical generations share
+// one entry and result sets stay small.
+pub struct ArtifactStore {
+    storage: Box<dyn Storage>,
+    // Outputs shorter than this stay inline
+    min_bytes: usize,
+}
+
+impl ArtifactStore {
+    pub fn open(location: &str, min_bytes: usize) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self { storage: open_storage(location)?, min_bytes })
+    }
+
+    pub fn accepts(&self, text: &str) -> bool {
+        text.len() >= self.min_bytes
+    }
+
+    // Store `text` unless an artifact with the same hash already exists; returns its key
+    pub async fn put(self: &Arc<Self>, text: String) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let store = Arc::clone(self);
+        tokio::task::spawn_blocking(move || {
+            let hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+            let key = format!("{}/{}.txt", &hash[..2], hash);
+            if !store.storage.contains(&key)? {
+                store.storage.put(&key, text.as_bytes())?;
+            }
+            Ok(key)
+        })
+        .await?
+    }
+
+    pub fn read(&self, key: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let bytes = self.storage.get(key)?.ok_or_else(|| format!("Artifact {} is missing", key))?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    pub fn keys(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        self.storage.list("")
+    }
+
+    pub fn local_path(&self, key: &str) -> Option<PathBuf> {
+        self.storage.local_path(key)
+    }
+}
+
+// Hash part of an artifact key
+pub fn artifact_hash(key: &str) -> Option<String> {
+    Path::new(key).file_stem().map(|stem| stem.to_string_lossy().into_owned())
+}
@@ -0,0 +1,123 @@
+// Per-provider circuit breakers for the dispatch loop. A provider that fails `threshold`
+// times in a row is taken out of the rotation; requests it still had in flight are left to
+// finish, and the ones that fail on it are handed back so they can be requeued elsewhere.
+pub struct ProviderHealth {
+    threshold: Option<usize>,
+    consecutive_failures: Vec<usize>,
+    open: Vec<bool>,
+    next: usize,
+    // Relative share of each provider, and the running credit of smooth weighted
+    // round-robin; only used when the weights differ
+    weights: Vec<f64>,
+    credit: Vec<f64>,
+    // Drained for good: out of the rotation and never readmitted
+    retired: Vec<bool>,
+}
+
+impl ProviderHealth {
+    pub fn new(weights: Vec<f64>, threshold: Option<usize>) -> Self {
+        let providers = weights.len();
+        Self {
+            threshold,
+            consecutive_failures: vec![0; providers],
+            open: vec![false; providers],
+            next: 0,
+            weights,
+            credit: vec![0.0; providers],
+            retired: vec![false; providers],
+        }
+    }
+
+    // A provider added mid-run, in the rotation from the start
+    pub fn add(&mut self, weight: f64) {
+        self.consecutive_failures.push(0);
+        self.open.push(false);
+        self.weights.push(weight);
+        self.credit.push(0.0);
+        self.retired.push(false);
+    }
+
+    // Take a provider out of the rotation for the rest of the run
+    pub fn retire(&mut self, slot: usize) {
+        self.open[slot] = true;
+        self.retired[slot] = true;
+    }
+
+    // Next provider in round-robin order that is still in the rotation. With every breaker
+    // closed this is plain position % providers, so prefix-clustered orders stay intact.
+    pub fn next_provider(&mut self) -> Option<usize> {
+        self.next_provider_where(|_| true)
+    }
+
+    // Same rotation restricted to providers `allowed` returns true for, e.g. the ones a
+    // language is routed to
+    pub fn next_provider_where(&mut self, allowed: impl Fn(usize) -> bool) -> Option<usize> {
+        if self.weights.windows(2).any(|pair| pair[0] != pair[1]) {
+            return self.next_weighted_where(allowed);
+        }
+        let providers = self.open.len();
+        let slot = (0..providers)
+            .map(|offset| (self.next + offset) % providers)
+            .find(|&slot| !self.open[slot] && allowed(slot))?;
+        self.next = (slot + 1) % providers;
+        Some(slot)
+    }
+
+    // Smooth weighted round-robin (as in nginx): every eligible provider earns its weight,
+    // the richest is picked and pays back the total, which interleaves a 7:3 split as
+    // AABAABAABA rather than in bursts
+    fn next_weighted_where(&mut self, allowed: impl Fn(usize) -> bool) -> Option<usize> {
+        let eligible: Vec<usize> = (0..self.open.len()).filter(|&slot| !self.open[slot] && allowed(slot)).collect();
+        let mut total = 0.0;
+        for &slot in &eligible {
+            self.credit[slot] += self.weights[slot];
+            total += self.weights[slot];
+        }
+        let slot = eligible.into_iter().max_by(|&a, &b| self.credit[a].total_cmp(&self.credit[b]).then(b.cmp(&a)))?;
+        self.credit[slot] -= total;
+        Some(slot)
+    }
+
+    // Take a provider out of the rotation whatever its failure count, e.g. after a failed
+    // health check
+    pub fn evict(&mut self, slot: usize) {
+        self.open[slot] = true;
+    }
+
+    // Put a provider back into the rotation with a clean record, unless it was retired
+    pub fn readmit(&mut self, slot: usize) {
+        if self.retired[slot] {
+            return;
+        }
+        self.open[slot] = false;
+        self.consecutive_failures[slot] = 0;
+    }
+
+    pub fn is_available(&self, slot: usize) -> bool {
+        !self.open[slot]
+    }
+
+    pub fn state(&self, slot: usize) -> &'static str {
+        match (self.retired[slot], self.open[slot]) {
+            (true, _) => "drained",
+            (false, true) => "unavailable",
+            (false, false) => "active",
+        }
+    }
+
+    pub fn record(&mut self, slot: usize, success: bool) {
+        if success {
+            self.consecutive_failures[slot] = 0;
+            return;
+        }
+        self.consecutive_failures[slot] += 1;
+        if self.threshold.is_some_and(|threshold| self.consecutive_failures[slot] >= threshold) {
+            self.open[slot] = true;
+        }
+    }
+
+    // Whether a request that failed on `slot` should be retried on another provider
+    pub fn should_requeue(&self, slot: usize) -> bool {
+        self.open[slot] && self.open.iter().any(|open| !open)
+    }
+}
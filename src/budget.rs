@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use crate::pricing::Pricing;
+use crate::RequestMetrics;
+
+// Limits on a whole run. Once one is reached no further requests are sent; those already
+// in flight finish and count towards the totals, so a run can overshoot by what was in
+// flight.
+pub struct RunBudget {
+    max_duration: Option<Duration>,
+    max_cost_usd: Option<f64>,
+    max_total_tokens: Option<usize>,
+    pricing: Pricing,
+    // Set when the first request is dispatched
+    started: Option<Instant>,
+    cost_usd: f64,
+    total_tokens: usize,
+}
+
+impl RunBudget {
+    pub fn new(
+        max_duration_seconds: Option<f64>,
+        max_cost_usd: Option<f64>,
+        max_total_tokens: Option<usize>,
+        pricing: Pricing,
+    ) -> Result<Self, String> {
+        let max_duration = max_duration_seconds
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|_| "max_duration_seconds must be a non-negative number of seconds".to_string())?;
+        Ok(Self {
+            max_duration,
+            max_cost_usd,
+            max_total_tokens,
+            pricing,
+            started: None,
+            cost_usd: 0.0,
+            total_tokens: 0,
+        })
+    }
+
+    pub fn start(&mut self) {
+        self.started.get_or_insert_with(Instant::now);
+    }
+
+    // With max_cost_usd, a model that can't be priced, whose requests would never count
+    // towards the limit
+    pub fn unpriced<'a>(&self, mut models: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+        self.max_cost_usd?;
+        models.find(|model| self.pricing.get(model).is_none())
+    }
+
+    // Count a finished request and set its cost_usd, priced by the first of `models` (most
+    // specific first) that has a price
+    pub fn record(&mut self, models: &[&str], metrics: &mut RequestMetrics) {
+        self.total_tokens += metrics.prompt_tokens + metrics.completion_tokens;
+        let price = models.iter().find_map(|model| self.pricing.get(model));
+        if let Some(price) = price {
+            let cost = price.cost_of(metrics);
+            metrics.cost_usd = Some(cost);
+            self.cost_usd += cost;
+        }
+    }
+
+    // The limit that has been reached, if any
+    pub fn exhausted(&self) -> Option<&'static str> {
+        if let (Some(max), Some(started)) = (self.max_duration, self.started) {
+            if started.elapsed() >= max {
+                return Some("max_duration_seconds");
+            }
+        }
+        if self.max_cost_usd.is_some_and(|max| self.cost_usd >= max) {
+            return Some("max_cost_usd");
+        }
+        if self.max_total_tokens.is_some_and(|max| self.total_tokens >= max) {
+            return Some("max_total_tokens");
+        }
+        None
+    }
+}
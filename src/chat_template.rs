@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::error::Error;
+use minijinja::{Environment, ErrorKind, Value};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::json;
+
+use crate::constraints::Backend;
+use crate::message::Message;
+use crate::tokenizer::count_tokens;
+use crate::{
+    check_status, extract_config_value, provider_request_id, read_json, wire_bytes, ChatRequest, LLMProvider, OpenAIProvider,
+    RequestMetrics, ResponseContent,
+};
+
+// A Jinja chat template in the format of Hugging Face's tokenizer_config.json, turning a
+// message list into a single prompt for raw completion endpoints. Templates see
+// `messages` (role, content and, if set, name), `add_generation_prompt` and the provider's
+// special tokens (e.g. bos_token); Python string methods such as .strip() work as they do
+// in transformers.
+pub struct ChatTemplate {
+    env: Environment<'static>,
+    special_tokens: HashMap<String, String>,
+}
+
+impl ChatTemplate {
+    // From the provider options "chat_template" and "special_tokens"
+    pub fn extract(options: &PyDict) -> PyResult<Option<Self>> {
+        let Some(source) = extract_config_value::<Option<String>>(options, "chat_template")?.flatten() else {
+            return Ok(None);
+        };
+        let mut env = Environment::new();
+        env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+        env.add_function("raise_exception", |message: String| -> Result<Value, minijinja::Error> {
+            Err(minijinja::Error::new(ErrorKind::InvalidOperation, message))
+        });
+        env.add_template_owned("chat", source)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid chat_template: {}", e)))?;
+        let special_tokens = match options.get_item("special_tokens")? {
+            Some(tokens) if !tokens.is_none() => tokens.extract()?,
+            _ => HashMap::new(),
+        };
+        Ok(Some(Self { env, special_tokens }))
+    }
+
+    // A trailing assistant message is a prefill: the prompt opens a new assistant turn and
+    // ends with its text, so the model continues it
+    pub fn render(&self, messages: &[Message], prefill: Option<&str>) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let turns = if prefill.is_some() { &messages[..messages.len() - 1] } else { messages };
+        let mut context: HashMap<&str, Value> = self
+            .special_tokens
+            .iter()
+            .map(|(name, token)| (name.as_str(), Value::from(token.as_str())))
+            .collect();
+        context.insert(
+            "messages",
+            Value::from_serialize(
+                turns
+                    .iter()
+                    .map(|m| match &m.name {
+                        Some(name) => json!({"role": m.role, "content": m.text(), "name": name}),
+                        None => json!({"role": m.role, "content": m.text()}),
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        );
+        context.insert("add_generation_prompt", Value::from(true));
+        let prompt = self.env.get_template("chat")?.render(context)?;
+        Ok(prompt + prefill.unwrap_or_default())
+    }
+}
+
+impl OpenAIProvider {
+    // Send a chat request as a rendered prompt: llama.cpp's /completion for the llamacpp
+    // backend, /v1/completions otherwise
+    pub(crate) async fn send_completion_request(
+        &self,
+        request: &ChatRequest,
+        template: &ChatTemplate,
+    ) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>> {
+        let overrides = &request.overrides;
+        if request.stream_to.is_some() {
+            return Err("Streaming is not supported with a chat_template".into());
+        }
+        if overrides.tools.is_some() {
+            return Err("Tools need the chat endpoint and can't be used with a chat_template".into());
+        }
+        let prompt = template.render(&request.messages, request.prefill().as_deref())?;
+        let model = self.requested_model(request);
+        let llama = self.backend == Backend::LlamaCpp;
+
+        let mut payload = serde_json::Map::new();
+        payload.insert("prompt".to_string(), json!(prompt));
+        if let Some(model) = &model {
+            payload.insert("model".to_string(), json!(model));
+        }
+        let max_tokens = overrides.max_tokens
+            .or(overrides.max_completion_tokens)
+            .or(self.config.max_tokens)
+            .or(self.config.max_completion_tokens);
+        if let Some(max_tokens) = max_tokens {
+            payload.insert((if llama { "n_predict" } else { "max_tokens" }).to_string(), json!(max_tokens));
+        }
+        for (key, value) in [
+            ("temperature", overrides.temperature.or(self.config.temperature)),
+            ("top_p", overrides.top_p.or(self.config.top_p)),
+            ("frequency_penalty", overrides.frequency_penalty.or(self.config.frequency_penalty)),
+            ("presence_penalty", overrides.presence_penalty.or(self.config.presence_penalty)),
+        ] {
+            if let Some(value) = value {
+                payload.insert(key.to_string(), json!(value));
+            }
+        }
+        if let Some(n) = self.choices(request).filter(|_| !llama) {
+            payload.insert("n".to_string(), json!(n));
+        }
+        if let Some(stop) = overrides.stop.as_ref().or(self.config.stop.as_ref()) {
+            payload.insert("stop".to_string(), json!(stop));
+        }
+        if let Some(seed) = overrides.seed.or(self.config.seed) {
+            payload.insert("seed".to_string(), json!(seed));
+        }
+        if let Some(constraint) = &overrides.constraint {
+            payload.extend(self.backend.constraint_fields(constraint)?);
+        }
+        for extra_body in [&self.config.extra_body, &overrides.extra_body].into_iter().flatten() {
+            if let serde_json::Value::Object(fields) = extra_body {
+                payload.extend(fields.clone());
+            }
+        }
+
+        let path = if llama { "completion" } else { "v1/completions" };
+        let url = format!("{}/{}", self.base_url.trim_end_matches('/'), path);
+        let request_body = serde_json::Value::Object(payload).to_string();
+        let http_request = self.client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .headers(self.headers.clone())
+            .body(request_body)
+            .build()?;
+        let request_bytes = wire_bytes(&http_request);
+        let response = self.client.execute(http_request).await?;
+        self.limits.observe(response.headers());
+        let provider_request_id = provider_request_id(response.headers());
+        let response = check_status(response, provider_request_id.as_deref()).await?;
+        let (data, response_bytes) = read_json(response).await?;
+
+        // llama.cpp answers with a single completion and its own field names
+        let (texts, finish_reasons, prompt_tokens, completion_tokens) = if llama {
+            let finish_reason = match data["stop_type"].as_str() {
+                Some("limit") => Some("length"),
+                Some(_) => Some("stop"),
+                None if data["stopped_limit"].as_bool() == Some(true) => Some("length"),
+                None => data["stop"].as_bool().filter(|&stopped| stopped).map(|_| "stop"),
+            };
+            (
+                vec![data["content"].as_str().map(str::to_string)],
+                vec![finish_reason.map(str::to_string)],
+                data["tokens_evaluated"].as_u64(),
+                data["tokens_predicted"].as_u64(),
+            )
+        } else {
+            let choices = data["choices"].as_array().cloned().unwrap_or_default();
+            (
+                choices.iter().map(|choice| choice["text"].as_str().map(str::to_string)).collect(),
+                choices.iter().map(|choice| choice["finish_reason"].as_str().map(str::to_string)).collect(),
+                data["usage"]["prompt_tokens"].as_u64(),
+                data["usage"]["completion_tokens"].as_u64(),
+            )
+        };
+        let tokenizer_model = model.as_deref().unwrap_or_default();
+        let mut metrics = RequestMetrics::new(
+            prompt_tokens.map_or_else(|| count_tokens(tokenizer_model, &prompt), |count| count as usize),
+            completion_tokens.map_or_else(|| texts.iter().flatten().map(|text| count_tokens(tokenizer_model, text)).sum(), |count| count as usize),
+            request_bytes,
+            response_bytes,
+            self.display_name(),
+        );
+        metrics.usage_estimated = prompt_tokens.is_none() || completion_tokens.is_none();
+        metrics.choices = texts.into_iter().map(|text| text.map(ResponseContent::Plain)).collect();
+        metrics.finish_reason = finish_reasons.first().cloned().flatten();
+        metrics.choice_finish_reasons = finish_reasons;
+        metrics.model = data["model"].as_str().map(str::to_string).or(model);
+        metrics.raw_response = Some(data);
+        metrics.provider_request_id = provider_request_id;
+        Ok(metrics)
+    }
+}
@@ -0,0 +1,116 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::json;
+
+use crate::{get_required_value, py_to_json};
+
+// Output constraint for backends that support constrained decoding
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    Gbnf(String),
+    Regex(String),
+    JsonSchema(serde_json::Value),
+    Choice(Vec<String>),
+}
+
+impl Constraint {
+    // {"type": "gbnf" | "regex" | "json_schema" | "choice", "value": ...}
+    pub fn extract(dict: &PyDict) -> PyResult<Self> {
+        let kind: String = get_required_value(dict, "type")?;
+        match kind.as_str() {
+            "gbnf" | "grammar" => Ok(Constraint::Gbnf(get_required_value(dict, "value")?)),
+            "regex" => Ok(Constraint::Regex(get_required_value(dict, "value")?)),
+            "json_schema" => Ok(Constraint::JsonSchema(py_to_json(get_required_value(dict, "value")?)?)),
+            "choice" => Ok(Constraint::Choice(get_required_value(dict, "value")?)),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                format!("Unsupported constraint type: {}", other),
+            )),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Constraint::Gbnf(_) => "gbnf",
+            Constraint::Regex(_) => "regex",
+            Constraint::JsonSchema(_) => "json_schema",
+            Constraint::Choice(_) => "choice",
+        }
+    }
+}
+
+// Serving stack behind an OpenAI-compatible endpoint; decides which constrained
+// decoding parameters the server understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    OpenAI,
+    Vllm,
+    LlamaCpp,
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "openai" => Ok(Backend::OpenAI),
+            "vllm" => Ok(Backend::Vllm),
+            "llamacpp" | "llama.cpp" => Ok(Backend::LlamaCpp),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                format!("Unknown backend: {}", other),
+            )),
+        }
+    }
+
+    pub fn supports(&self, constraint: &Constraint) -> bool {
+        match self {
+            Backend::OpenAI => matches!(constraint, Constraint::JsonSchema(_)),
+            Backend::Vllm => true,
+            Backend::LlamaCpp => matches!(constraint, Constraint::Gbnf(_) | Constraint::JsonSchema(_)),
+        }
+    }
+
+    // Payload fields that express the constraint for this backend
+    pub fn constraint_fields(&self, constraint: &Constraint) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        if !self.supports(constraint) {
+            return Err(format!("{:?} backend does not support {} constraints", self, constraint.kind()));
+        }
+        let mut fields = serde_json::Map::new();
+        match (self, constraint) {
+            (Backend::OpenAI, Constraint::JsonSchema(schema)) => {
+                fields.insert("response_format".to_string(), json!({
+                    "type": "json_schema",
+                    "json_schema": {"name": "response", "schema": schema, "strict": true},
+                }));
+            }
+            (Backend::Vllm, Constraint::Gbnf(grammar)) => {
+                fields.insert("guided_grammar".to_string(), json!(grammar));
+            }
+            (Backend::Vllm, Constraint::Regex(regex)) => {
+                fields.insert("guided_regex".to_string(), json!(regex));
+            }
+            (Backend::Vllm, Constraint::JsonSchema(schema)) => {
+                fields.insert("guided_json".to_string(), schema.clone());
+            }
+            (Backend::Vllm, Constraint::Choice(choices)) => {
+                fields.insert("guided_choice".to_string(), json!(choices));
+            }
+            (Backend::LlamaCpp, Constraint::Gbnf(grammar)) => {
+                fields.insert("grammar".to_string(), json!(grammar));
+            }
+            (Backend::LlamaCpp, Constraint::JsonSchema(schema)) => {
+                fields.insert("json_schema".to_string(), schema.clone());
+            }
+            _ => unreachable!("unsupported constraints are rejected above"),
+        }
+        Ok(fields)
+    }
+
+    // Payload fields that make the server continue a trailing assistant message instead of
+    // starting a new turn. llama.cpp does this on its own; OpenAI has no equivalent.
+    pub fn prefill_fields(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut fields = serde_json::Map::new();
+        if *self == Backend::Vllm {
+            fields.insert("continue_final_message".to_string(), json!(true));
+            fields.insert("add_generation_prompt".to_string(), json!(false));
+        }
+        fields
+    }
+}
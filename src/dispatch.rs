@@ -0,0 +1,1145 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use pyo3::{PyErr, Python};
+use sha2::{Digest, Sha256};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, sleep_until};
+use tracing::{debug, info, warn};
+
+use crate::adaptive::AdaptiveConcurrency;
+use crate::breaker::ProviderHealth;
+use crate::budget::RunBudget;
+use crate::failure;
+use crate::hedge::{self, Hedging};
+use crate::language::LanguageRoutes;
+use crate::metrics_log::MetricsLog;
+use crate::statsd::StatsdSink;
+use crate::otel::Tracer;
+use crate::probe::{HealthChecks, ProbeResult};
+use crate::ratelimit::RateLimiter;
+use crate::routing::{ProviderLoad, Routing};
+use crate::simulator::ServiceTime;
+use crate::source::RequestSource;
+use crate::stats::{ProviderStatus, SharedStatus};
+use crate::status::Status;
+use crate::throughput::{Sampler, SharedSamples};
+use crate::warmup::Warmup;
+use crate::{BatchProcessor, ChatRequest, LLMProvider, RequestMetrics, ResultOptions};
+
+// Request indices the caller has cancelled, or the whole run. Queued requests are skipped
+// at dispatch and in-flight ones are aborted as soon as they are cancelled; when the whole
+// run is, those in flight first get the drain timeout to finish.
+pub struct Cancellation {
+    cancelled: Mutex<HashSet<usize>>,
+    all: AtomicBool,
+    drain: Duration,
+    // When requests still in flight after cancelling the whole run are aborted
+    deadline: Mutex<Option<Instant>>,
+    generation: watch::Sender<u64>,
+}
+
+impl Cancellation {
+    pub fn new(drain_seconds: Option<f64>) -> Result<Self, String> {
+        let drain = match drain_seconds.map(Duration::try_from_secs_f64) {
+            None => Duration::ZERO,
+            Some(Ok(drain)) => drain,
+            Some(Err(_)) => return Err("drain_timeout must be a non-negative number of seconds".to_string()),
+        };
+        Ok(Self {
+            cancelled: Mutex::new(HashSet::new()),
+            all: AtomicBool::new(false),
+            drain,
+            deadline: Mutex::new(None),
+            generation: watch::channel(0).0,
+        })
+    }
+
+    pub fn cancel(&self, index: usize) {
+        self.cancelled.lock().unwrap().insert(index);
+        self.generation.send_modify(|generation| *generation += 1);
+    }
+
+    // Stop dispatching and let the requests in flight drain; cancelling again aborts them
+    // right away
+    pub fn cancel_all(&self) {
+        let now = Instant::now();
+        let mut deadline = self.deadline.lock().unwrap();
+        *deadline = Some(if self.all.swap(true, Ordering::SeqCst) { now } else { now + self.drain });
+        drop(deadline);
+        self.generation.send_modify(|generation| *generation += 1);
+        info!(drain_seconds = self.drain.as_secs_f64(), "run cancelled; nothing more is sent");
+    }
+
+    pub fn is_cancelled(&self, index: usize) -> bool {
+        self.all_cancelled() || self.cancelled.lock().unwrap().contains(&index)
+    }
+
+    pub fn all_cancelled(&self) -> bool {
+        self.all.load(Ordering::SeqCst)
+    }
+
+    // Run `work` while checking for Python signals, so Ctrl-C reaches a run driven from
+    // the main thread without the GIL: the KeyboardInterrupt is swallowed and everything
+    // still queued, or in flight past the drain timeout, comes back "cancelled". A second
+    // Ctrl-C cuts the drain short.
+    pub async fn interruptible<F: Future>(&self, work: F) -> F::Output {
+        tokio::pin!(work);
+        loop {
+            tokio::select! {
+                output = &mut work => return output,
+                _ = sleep(SIGNAL_POLL_INTERVAL) => {
+                    if Python::with_gil(|py| py.check_signals()).is_err() {
+                        self.cancel_all();
+                    }
+                }
+            }
+        }
+    }
+
+    // Resolves once `index` is cancelled on its own, or the whole run is and its drain
+    // timeout has passed
+    async fn cancelled(&self, index: usize) {
+        tokio::select! {
+            _ = self.until(|| self.cancelled.lock().unwrap().contains(&index)) => {}
+            _ = self.drained() => {}
+        }
+    }
+
+    // Resolves once the whole run is cancelled
+    async fn all_cancelled_signal(&self) {
+        self.until(|| self.all_cancelled()).await
+    }
+
+    async fn drained(&self) {
+        loop {
+            // Subscribe before reading the deadline so cutting the drain short isn't missed
+            let mut generation = self.generation.subscribe();
+            let deadline = *self.deadline.lock().unwrap();
+            match deadline {
+                Some(deadline) => tokio::select! {
+                    _ = sleep_until(deadline.into()) => return,
+                    _ = generation.changed() => {}
+                },
+                None => {
+                    if generation.changed().await.is_err() {
+                        futures::future::pending::<()>().await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn until(&self, done: impl Fn() -> bool) {
+        // Subscribe before checking so a cancel between the two can't be missed
+        let mut generation = self.generation.subscribe();
+        while !done() {
+            if generation.changed().await.is_err() {
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+pub const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+enum ProviderChange {
+    Add(Arc<dyn LLMProvider>),
+    Drain(usize),
+}
+
+// Providers added or drained through the control handle while a run is going, picked up by
+// the dispatcher the next time it sends or a request finishes
+pub struct ProviderChanges {
+    pending: Mutex<Vec<ProviderChange>>,
+    // Providers in the run once the pending changes apply; an added one takes the next slot
+    providers: Mutex<usize>,
+    changed: Notify,
+}
+
+impl ProviderChanges {
+    fn new(providers: usize) -> Self {
+        Self { pending: Mutex::new(Vec::new()), providers: Mutex::new(providers), changed: Notify::new() }
+    }
+
+    // The slot the provider will take
+    pub fn add(&self, provider: Arc<dyn LLMProvider>) -> usize {
+        let mut providers = self.providers.lock().unwrap();
+        self.pending.lock().unwrap().push(ProviderChange::Add(provider));
+        self.changed.notify_one();
+        *providers += 1;
+        *providers - 1
+    }
+
+    // Returns false for a slot that doesn't exist
+    pub fn drain(&self, slot: usize) -> bool {
+        let providers = self.providers.lock().unwrap();
+        if slot >= *providers {
+            return false;
+        }
+        self.pending.lock().unwrap().push(ProviderChange::Drain(slot));
+        self.changed.notify_one();
+        true
+    }
+
+    pub fn count(&self) -> usize {
+        *self.providers.lock().unwrap()
+    }
+
+    fn take(&self) -> Vec<ProviderChange> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+// Dispatch order of a request: higher priorities are sent first, and requests of the same
+// priority keep their submission order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "high" => Some(Self::High),
+            "normal" => Some(Self::Normal),
+            "low" => Some(Self::Low),
+            _ => None,
+        }
+    }
+}
+
+type ResultCallback = Box<dyn Fn(&RequestMetrics) + Send>;
+
+// The providers a request was tried on, in order, and the last attempt's outcome; None
+// when it was cancelled in flight
+type Attempts = Option<(Vec<usize>, Result<RequestMetrics, Box<dyn Error + Send + Sync>>)>;
+
+// Identity of a request for deduplication: everything that goes into its payload. Streamed
+// requests each write their own file, so they are never merged.
+fn dedupe_key(request: &ChatRequest) -> Option<String> {
+    if request.stream_to.is_some() {
+        return None;
+    }
+    let identity = format!("{:?}\n{:?}\n{:?}", request.messages, request.overrides, request.image);
+    Some(format!("{:x}", Sha256::digest(identity.as_bytes())))
+}
+
+// A request sent on behalf of identical ones: they wait for its result, which is then kept,
+// with the provider that answered it, to answer any that come later if it succeeded
+enum Shared {
+    Pending(Vec<ChatRequest>),
+    Done(Box<RequestMetrics>, usize),
+}
+
+// The result of `original` handed to an identical request
+fn copy_result(original: &RequestMetrics, request: &ChatRequest) -> RequestMetrics {
+    let mut metrics = original.clone();
+    metrics.duplicate_of = Some(original.index);
+    metrics.index = request.index;
+    metrics.request_id = request.request_id.clone();
+    metrics.language = request.language.map(str::to_string);
+    metrics.sanitization = request.sanitization;
+    metrics.prompt_tokens = 0;
+    metrics.completion_tokens = 0;
+    metrics.total_tokens = 0;
+    metrics.request_bytes = 0;
+    metrics.response_bytes = 0;
+    metrics.hedged_to = None;
+    metrics.hedge_won = false;
+    metrics.raced.clear();
+    metrics.cost_usd = metrics.cost_usd.map(|_| 0.0);
+    // Answered the moment the copy is made, without ever being sent
+    metrics.queue_ms = 0.0;
+    metrics.total_ms = request.queued_at.map_or(0.0, |queued_at| millis(queued_at.elapsed()));
+    metrics
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// How long a request sent at `sent_at` waited in the queue, and how long it took from
+// entering it to its result
+fn record_timing(metrics: &mut RequestMetrics, request: &ChatRequest, sent_at: Instant) {
+    if let Some(queued_at) = request.queued_at {
+        metrics.queue_ms = millis(sent_at.saturating_duration_since(queued_at));
+        metrics.total_ms = millis(queued_at.elapsed());
+    }
+}
+
+// A request whose provider call has ended
+struct Finished {
+    slot: usize,
+    request: ChatRequest,
+    // Requests in flight when it was sent, itself included
+    in_flight: usize,
+    // Position in send order
+    sequence: usize,
+    // Providers the request was raced on besides `slot`
+    rivals: Vec<usize>,
+    // Provider its hedged copy may go to, counted as in flight there until it is done
+    hedged: Option<usize>,
+    sent_at: Instant,
+    attempts: Attempts,
+    // Handed back unsent: its provider was drained while its limits still held it
+    reclaimed: bool,
+}
+
+// Keeps up to the concurrency limit of requests in flight, sending the next queued request
+// the moment one finishes, with round-robin or least-loaded provider selection. Each slot acts as one
+// simulated user who pauses for a think time after every response before sending the next
+// request.
+pub struct Dispatcher {
+    providers: Vec<Arc<dyn LLMProvider>>,
+    queue: VecDeque<ChatRequest>,
+    // Pulled from once the queue runs dry
+    source: Option<RequestSource>,
+    // Indices of every request taken in, in dispatch order
+    order: Vec<usize>,
+    health: ProviderHealth,
+    max_concurrency: usize,
+    // Adjusts the limit as results come in, up to max_concurrency
+    adaptive: Option<AdaptiveConcurrency>,
+    // Requests a provider may hold back before more are shed rather than queued behind them
+    max_queue_depth: Option<usize>,
+    // Per provider, for the stats the batch handle reports
+    status: SharedStatus,
+    // Send a request past a provider whose own rate limits would hold it back to one that
+    // can take it now
+    spillover: bool,
+    // Recent latencies, with hedged requests on
+    hedging: Option<Hedging>,
+    // Providers each request is raced on
+    race: Option<usize>,
+    // With deduplication, requests sent so far by identity
+    shared: Option<HashMap<String, Shared>>,
+    // Results of requests sent since the adaptive limit was last adjusted; earlier ones
+    // reflect the old limit and would count the same congestion twice
+    window: Vec<RequestMetrics>,
+    adjusted_at: usize,
+    think_time: Option<ServiceTime>,
+    options: ResultOptions,
+    rate_limiter: Arc<RateLimiter>,
+    // Indices reported as "skipped" without being sent
+    skip: HashSet<usize>,
+    routes: Option<LanguageRoutes>,
+    // Provider indices in fallback order: a request failing on one moves on to the next
+    failover: Vec<usize>,
+    routing: Routing,
+    load: ProviderLoad,
+    health_checks: Option<HealthChecks>,
+    // Whether the probe round before the first request has run
+    probed: bool,
+    // Whether the requests queued up front have been stamped with when the run started
+    stamped: bool,
+    // Taken once it has run
+    warmup: Option<Warmup>,
+    // Providers taken out of the rotation by a failed health check, with the failure
+    evictions: Vec<(String, String)>,
+    changes: Arc<ProviderChanges>,
+    // Per provider, raised once it is drained
+    drained: Vec<watch::Sender<bool>>,
+    cancellation: Arc<Cancellation>,
+    budget: RunBudget,
+    // The budget limit that stopped dispatch early
+    stopped_by: Option<&'static str>,
+    in_flight: FuturesUnordered<JoinHandle<Finished>>,
+    // Requests sent so far
+    dispatched: usize,
+    // Called with each result the moment it is final
+    on_result: Option<ResultCallback>,
+    // Exports a span per result; taken to end the batch span once the run is over
+    tracer: Option<Tracer>,
+    // Why exporting the spans failed
+    trace_error: Option<String>,
+    // Appended to with each result the moment it is final
+    metrics_log: Option<MetricsLog>,
+    // Sent each result's metrics the moment it is final
+    statsd: Option<StatsdSink>,
+    // Throughput every so often through the run
+    sampler: Option<Sampler>,
+    // Whether the end of the run has been logged
+    finished: bool,
+}
+
+impl Dispatcher {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        providers: Vec<Arc<dyn LLMProvider>>,
+        requests: Vec<ChatRequest>,
+        source: Option<RequestSource>,
+        max_concurrency: usize,
+        max_concurrency_per_host: Option<usize>,
+        max_queue_depth: Option<usize>,
+        adaptive: bool,
+        spillover: bool,
+        hedge: bool,
+        race: Option<usize>,
+        dedupe: bool,
+        circuit_breaker: Option<usize>,
+        think_time: Option<ServiceTime>,
+        options: ResultOptions,
+        rate_limiter: Arc<RateLimiter>,
+        skip: HashSet<usize>,
+        routes: Option<LanguageRoutes>,
+        failover: Vec<usize>,
+        routing: Routing,
+        health_checks: Option<HealthChecks>,
+        warmup: Option<Warmup>,
+        cancellation: Arc<Cancellation>,
+        budget: RunBudget,
+        tracer: Option<Tracer>,
+        metrics_log: Option<MetricsLog>,
+        sample_interval: Option<Duration>,
+        statsd: Option<StatsdSink>,
+    ) -> Self {
+        let mut requests = requests;
+        requests.sort_by_key(|request| request.priority);
+        let weights: Vec<f64> = providers.iter().map(|p| p.weight()).collect();
+        let hosts: Vec<String> = providers.iter().map(|p| p.host()).collect();
+        let providers_count = providers.len();
+        let status = providers.iter().map(|p| ProviderStatus::new(Arc::clone(p))).collect();
+        let limits = providers.iter().map(|p| p.limits().and_then(RateLimiter::max_concurrency)).collect();
+        Self {
+            health: ProviderHealth::new(weights.clone(), circuit_breaker),
+            load: ProviderLoad::new(weights, hosts, max_concurrency_per_host, limits),
+            providers,
+            order: requests.iter().map(|request| request.index).collect(),
+            queue: requests.into(),
+            source,
+            max_concurrency: max_concurrency.max(1),
+            adaptive: adaptive.then(|| AdaptiveConcurrency::new(max_concurrency)),
+            max_queue_depth,
+            status: Arc::new(Mutex::new(status)),
+            spillover,
+            hedging: hedge.then(Hedging::new),
+            race,
+            shared: dedupe.then(HashMap::new),
+            window: Vec::new(),
+            adjusted_at: 0,
+            think_time,
+            options,
+            rate_limiter,
+            skip,
+            routes,
+            failover,
+            routing,
+            health_checks,
+            probed: false,
+            stamped: false,
+            warmup,
+            evictions: Vec::new(),
+            changes: Arc::new(ProviderChanges::new(providers_count)),
+            drained: (0..providers_count).map(|_| watch::Sender::new(false)).collect(),
+            cancellation,
+            budget,
+            stopped_by: None,
+            in_flight: FuturesUnordered::new(),
+            dispatched: 0,
+            on_result: None,
+            tracer,
+            trace_error: None,
+            metrics_log,
+            statsd,
+            sampler: sample_interval.map(Sampler::new),
+            finished: false,
+        }
+    }
+
+    pub fn on_result(&mut self, callback: impl Fn(&RequestMetrics) + Send + 'static) {
+        self.on_result = Some(Box::new(callback));
+    }
+
+    // Hand a result to the callback and the exporters. `slot` is the provider it is
+    // attributed to, if it got as far as one.
+    fn publish(&self, metrics: RequestMetrics, request: &ChatRequest, slot: Option<usize>) -> RequestMetrics {
+        if let Some(callback) = &self.on_result {
+            callback(&metrics);
+        }
+        // The model asked for: the request's own, or else its provider's
+        let provider = slot.map(|slot| self.providers[slot].model());
+        let model = request.overrides.model.as_deref().or(provider).filter(|model| !model.is_empty());
+        if let Some(tracer) = &self.tracer {
+            tracer.record(&metrics, model);
+        }
+        if let Some(log) = &self.metrics_log {
+            log.record(&metrics, model);
+        }
+        if let Some(statsd) = &self.statsd {
+            statsd.record(&metrics, model);
+        }
+        metrics
+    }
+
+    // Request indices in dispatch order; with an iterator as the source, only the requests
+    // pulled so far
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    // Requests taken in so far
+    pub fn submitted(&self) -> usize {
+        self.order.len()
+    }
+
+    // Whether requests are pulled from an iterator, so the total isn't known up front
+    pub fn open_ended(&self) -> bool {
+        self.source.is_some()
+    }
+
+    // What stopped the request iterator early, if anything
+    pub fn take_source_error(&mut self) -> Option<PyErr> {
+        self.source.as_mut().and_then(RequestSource::take_error)
+    }
+
+    // The next queued request, or else the next one from the iterator, which is left alone
+    // once the run has been cancelled
+    fn next_request(&mut self) -> Option<ChatRequest> {
+        if let Some(request) = self.queue.pop_front() {
+            return Some(request);
+        }
+        if self.cancellation.all_cancelled() {
+            return None;
+        }
+        let mut request = self.source.as_mut()?.next()?;
+        request.queued_at = Some(Instant::now());
+        self.order.push(request.index);
+        Some(request)
+    }
+
+    pub fn stopped_by(&self) -> Option<&'static str> {
+        self.stopped_by
+    }
+
+    pub fn evictions(&self) -> &[(String, String)] {
+        &self.evictions
+    }
+
+    pub fn trace_error(&self) -> Option<&str> {
+        self.trace_error.as_deref()
+    }
+
+    // Why writing the metrics log stopped, if it did
+    pub fn metrics_log_error(&self) -> Option<String> {
+        self.metrics_log.as_ref().and_then(MetricsLog::error)
+    }
+
+    pub fn provider_changes(&self) -> Arc<ProviderChanges> {
+        Arc::clone(&self.changes)
+    }
+
+    pub fn provider_status(&self) -> SharedStatus {
+        Arc::clone(&self.status)
+    }
+
+    // Throughput samples taken so far; none without a sample interval
+    pub fn samples(&self) -> SharedSamples {
+        self.sampler.as_ref().map(Sampler::samples).unwrap_or_default()
+    }
+
+    fn sample_if_due(&mut self) {
+        let in_flight = self.in_flight.len();
+        if let Some(sampler) = self.sampler.as_mut().filter(|sampler| sampler.due().is_some_and(|due| due <= Instant::now())) {
+            sampler.sample(in_flight);
+        }
+    }
+
+    // A batch of results for the caller, counted towards the throughput samples
+    fn hand_back(&mut self, results: Vec<RequestMetrics>) -> Option<Vec<RequestMetrics>> {
+        if let Some(sampler) = self.sampler.as_mut() {
+            sampler.count(&results);
+        }
+        Some(results)
+    }
+
+    // Bring the reported provider states in line with the rotation
+    fn update_states(&self) {
+        for (slot, status) in self.status.lock().unwrap().iter_mut().enumerate() {
+            status.state = self.health.state(slot);
+        }
+    }
+
+    // A drained provider keeps its slot, so indices stay stable. Its requests in flight
+    // finish normally, and those its limits still hold back go to the other providers.
+    fn apply_changes(&mut self) {
+        for change in self.changes.take() {
+            match change {
+                ProviderChange::Add(provider) => {
+                    self.health.add(provider.weight());
+                    self.load.add(provider.weight(), provider.host(), provider.limits().and_then(RateLimiter::max_concurrency));
+                    self.status.lock().unwrap().push(ProviderStatus::new(Arc::clone(&provider)));
+                    self.drained.push(watch::Sender::new(false));
+                    self.providers.push(provider);
+                }
+                ProviderChange::Drain(slot) => {
+                    self.health.retire(slot);
+                    self.drained[slot].send_replace(true);
+                }
+            }
+        }
+        self.update_states();
+    }
+
+    fn limit(&self) -> usize {
+        self.adaptive.as_ref().map_or(self.max_concurrency, AdaptiveConcurrency::limit)
+    }
+
+    // The assigned provider followed by its fallbacks: the providers after it in the
+    // failover order whose breakers haven't tripped
+    fn chain(&self, slot: usize) -> Vec<usize> {
+        let mut chain = vec![slot];
+        if let Some(position) = self.failover.iter().position(|&provider| provider == slot) {
+            chain.extend(
+                self.failover[position + 1..]
+                    .iter()
+                    .copied()
+                    .filter(|&next| next != slot && self.health.is_available(next)),
+            );
+        }
+        chain
+    }
+
+    // Next provider among `allowed` whose breaker hasn't tripped and that, along with its
+    // host, is below its cap
+    fn pick(&mut self, allowed: &[usize]) -> Option<usize> {
+        let load = &self.load;
+        let eligible = |slot: usize| load.has_room(slot) && allowed.contains(&slot);
+        match self.routing {
+            Routing::RoundRobin => self.health.next_provider_where(eligible),
+            Routing::LeastLoaded => {
+                let candidates: Vec<usize> =
+                    (0..self.providers.len()).filter(|&slot| self.health.is_available(slot) && eligible(slot)).collect();
+                self.load.least_loaded(candidates.into_iter())
+            }
+        }
+    }
+
+    // The providers a request may go to: those of its language route whose breakers
+    // haven't tripped, or once every one of them has, any that hasn't
+    fn candidates(&self, routed: Option<&[usize]>) -> Vec<usize> {
+        let available = |slot: &usize| self.health.is_available(*slot);
+        let candidates: Vec<usize> = routed.into_iter().flatten().copied().filter(available).collect();
+        if candidates.is_empty() {
+            return (0..self.providers.len()).filter(available).collect();
+        }
+        candidates
+    }
+
+    // Next provider for `request` among `candidates`. With spillover, one whose own limits
+    // let it start right away comes first; when there is none it waits on its provider.
+    fn pick_for(&mut self, request: &ChatRequest, candidates: &[usize]) -> Option<usize> {
+        if self.spillover {
+            let ready: Vec<usize> = candidates
+                .iter()
+                .copied()
+                .filter(|&slot| {
+                    let provider = self.providers[slot].as_ref();
+                    self.load.has_room(slot) && provider.limits().is_none_or(|limits| limits.has_headroom(provider, request))
+                })
+                .collect();
+            if let Some(slot) = self.pick(&ready) {
+                return Some(slot);
+            }
+        }
+        self.pick(candidates)
+    }
+
+    // Probe the providers and send the warm-up requests, copies of the first request, to
+    // those that passed. Runs once, by the first next_batch() unless called before it.
+    pub async fn warm_up(&mut self) {
+        if !self.probed {
+            self.probed = true;
+            if let Some(checks) = self.health_checks.as_mut() {
+                for outcome in checks.round(&self.providers).await {
+                    self.apply_probe(outcome);
+                }
+            }
+        }
+        let Some(warmup) = self.warmup.take() else { return };
+        let request = match self.queue.front() {
+            Some(request) => request.clone(),
+            None => {
+                let Some(request) = self.next_request() else { return };
+                self.queue.push_front(request.clone());
+                request
+            }
+        };
+        let providers: Vec<Arc<dyn LLMProvider>> = (0..self.providers.len())
+            .filter(|&slot| self.health.is_available(slot))
+            .map(|slot| Arc::clone(&self.providers[slot]))
+            .collect();
+        tokio::select! {
+            _ = warmup.run(&request, &providers, self.limit(), &self.rate_limiter, &self.options) => {}
+            _ = self.cancellation.all_cancelled_signal() => {}
+        }
+    }
+
+    // Wait for the next results. Returns None once every request has one; after every
+    // provider has tripped the remaining requests come back as "error".
+    pub async fn next_batch(&mut self) -> Option<Vec<RequestMetrics>> {
+        self.warm_up().await;
+        let mut results = Vec::new();
+        loop {
+            self.sample_if_due();
+            self.fill(&mut results);
+            if !results.is_empty() {
+                return self.hand_back(results);
+            }
+            if self.in_flight.is_empty() {
+                if !self.finished {
+                    self.finished = true;
+                    info!(sent = self.dispatched, "run finished");
+                    // The tail of the run, shorter than an interval
+                    if let Some(sampler) = self.sampler.as_mut().filter(|sampler| sampler.has_unsampled()) {
+                        sampler.sample(0);
+                    }
+                }
+                if let Some(tracer) = self.tracer.take() {
+                    self.trace_error = tracer.finish().await;
+                    if let Some(error) = &self.trace_error {
+                        warn!(error = %error, "exporting traces failed");
+                    }
+                }
+                return None;
+            }
+            let (in_flight, checks, providers, changes) =
+                (&mut self.in_flight, &mut self.health_checks, &self.providers, Arc::clone(&self.changes));
+            let probed = async {
+                match checks {
+                    Some(checks) => checks.next(providers).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            let sample_due = self.sampler.as_ref().and_then(Sampler::due);
+            let sampled = async {
+                match sample_due {
+                    Some(due) => sleep_until(due.into()).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            tokio::select! {
+                finished = in_flight.next() => {
+                    self.settle(finished.expect("in flight").expect("request tasks don't panic"), &mut results);
+                    // Along with anything else that is already done
+                    while let Some(Some(finished)) = self.in_flight.next().now_or_never() {
+                        self.settle(finished.expect("request tasks don't panic"), &mut results);
+                    }
+                    return self.hand_back(results);
+                }
+                outcome = probed => self.apply_probe(outcome),
+                _ = sampled => {}
+                _ = changes.changed.notified() => {}
+            }
+        }
+    }
+
+    // Evict a provider that failed its probe, and readmit one that passed, including one
+    // its circuit breaker had tripped
+    fn apply_probe(&mut self, (slot, outcome): ProbeResult) {
+        match outcome {
+            Ok(()) => {
+                if !self.health.is_available(slot) {
+                    info!(provider = %self.providers[slot].display_name(), "provider passed its health check and is back in the rotation");
+                }
+                self.health.readmit(slot)
+            }
+            Err(e) if self.health.is_available(slot) => {
+                warn!(provider = %self.providers[slot].display_name(), error = %e, "provider failed its health check and was taken out of the rotation");
+                self.health.evict(slot);
+                self.evictions.push((self.providers[slot].display_name(), e));
+            }
+            Err(_) => {}
+        }
+        self.update_states();
+    }
+
+    // Send queued requests until the concurrency limit is reached. Requests that are
+    // settled without being sent go straight to `results`.
+    fn fill(&mut self, results: &mut Vec<RequestMetrics>) {
+        self.budget.start();
+        if !self.stamped {
+            self.stamped = true;
+            info!(
+                queued = self.queue.len(),
+                providers = self.providers.len(),
+                max_concurrency = self.max_concurrency,
+                "run started"
+            );
+            let now = Instant::now();
+            for request in self.queue.iter_mut() {
+                request.queued_at = Some(now);
+            }
+            if let Some(sampler) = self.sampler.as_mut() {
+                sampler.start();
+            }
+        }
+        self.apply_changes();
+        while self.in_flight.len() < self.limit() {
+            if let Some(limit) = self.budget.exhausted() {
+                // Nothing more is sent; what is in flight drains normally
+                self.stopped_by = Some(limit);
+                warn!(limit, not_sent = self.queue.len(), "run budget reached; requests in flight finish and nothing more is sent");
+                while let Some(request) = self.queue.pop_front() {
+                    let mut metrics = RequestMetrics::unsent(&request, String::new(), Status::Skipped);
+                    metrics.error = Some(format!("Not sent: {} reached", limit));
+                    results.push(self.publish(metrics, &request, None));
+                }
+                break;
+            }
+            let Some(request) = self.next_request() else { break };
+            if self.skip.contains(&request.index) {
+                results.push(self.publish(RequestMetrics::unsent(&request, String::new(), Status::Skipped), &request, None));
+                continue;
+            }
+            if self.cancellation.is_cancelled(request.index) {
+                results.push(self.publish(RequestMetrics::unsent(&request, String::new(), Status::Cancelled), &request, None));
+                continue;
+            }
+            let routed = self.routes.as_ref().zip(request.language).and_then(|(routes, language)| routes.providers_for(language));
+            // Once every provider of a route has tripped its requests fall back to the rest
+            let candidates = self.candidates(routed);
+            if !candidates.is_empty() && candidates.iter().all(|&slot| !self.load.has_room(slot)) {
+                // Every one is at its cap or on a host at its cap; sent once one of their
+                // requests finishes
+                self.queue.push_front(request);
+                break;
+            }
+            let Some(request) = self.hold_duplicate(request, results) else { continue };
+            let Some(mut slot) = self.pick_for(&request, &candidates) else {
+                let error = "every provider has tripped its circuit breaker, failed its health check or been drained".to_string();
+                results.push(self.publish(RequestMetrics::failed(&request, String::new(), error), &request, None));
+                continue;
+            };
+            if let Some(max) = self.max_queue_depth.filter(|&max| self.pending(slot) >= max) {
+                let shallow: Vec<usize> = candidates.iter().copied().filter(|&other| self.pending(other) < max).collect();
+                match self.pick(&shallow) {
+                    Some(other) => slot = other,
+                    None => {
+                        self.shed(slot, request, max, results);
+                        continue;
+                    }
+                }
+            }
+            let hedge = self.hedge_target(slot, &candidates);
+            let rivals = self.rivals(slot, &candidates);
+            self.send(slot, request, hedge, rivals);
+        }
+    }
+
+    // With hedging, once enough latencies are known: how long a request sent to `slot` waits
+    // before it is hedged, and the least loaded other candidate with room to take the copy.
+    // The copy holds its place there from now on, so it can't take the target past its cap
+    // when it goes out; without a candidate with room the request isn't hedged.
+    fn hedge_target(&mut self, slot: usize, candidates: &[usize]) -> Option<(Duration, usize)> {
+        let delay = self.hedging.as_ref()?.delay()?;
+        let others: Vec<usize> = candidates.iter().copied().filter(|&other| other != slot && self.load.has_room(other)).collect();
+        let target = self.load.least_loaded(others.into_iter())?;
+        self.load.started(target);
+        Some((delay, target))
+    }
+
+    // With racing, the least loaded other candidates with room, up to the race size
+    fn rivals(&mut self, slot: usize, candidates: &[usize]) -> Vec<usize> {
+        let Some(race) = self.race else { return Vec::new() };
+        let mut rivals = Vec::new();
+        while rivals.len() + 1 < race {
+            let others: Vec<usize> = candidates
+                .iter()
+                .copied()
+                .filter(|&other| other != slot && !rivals.contains(&other) && self.load.has_room(other))
+                .collect();
+            let Some(rival) = self.load.least_loaded(others.into_iter()) else { break };
+            self.load.started(rival);
+            rivals.push(rival);
+        }
+        rivals
+    }
+
+    fn pending(&self, slot: usize) -> usize {
+        self.status.lock().unwrap()[slot].pending()
+    }
+
+    // Fail a request, and any identical ones waiting on it, instead of queueing it behind
+    // `max` others
+    fn shed(&mut self, slot: usize, request: ChatRequest, max: usize, results: &mut Vec<RequestMetrics>) {
+        let provider = self.providers[slot].display_name();
+        let error = format!("Shed: {} already has {} requests held back by its limits", provider, max);
+        warn!(index = request.index, provider = %provider, max_queue_depth = max, "request shed");
+        let mut shed = vec![request];
+        shed.extend(self.release_duplicates(&shed[0]));
+        self.status.lock().unwrap()[slot].shed += shed.len();
+        for request in shed {
+            results.push(self.publish(RequestMetrics::failed(&request, provider.clone(), error.clone()), &request, Some(slot)));
+        }
+    }
+
+    // With deduplication, a request identical to one already sent waits for that one's
+    // result, or takes it right away when it is in. Returns the request if it is to be sent.
+    fn hold_duplicate(&mut self, request: ChatRequest, results: &mut Vec<RequestMetrics>) -> Option<ChatRequest> {
+        let Some(shared) = self.shared.as_mut() else { return Some(request) };
+        let Some(key) = dedupe_key(&request) else { return Some(request) };
+        match shared.get_mut(&key) {
+            Some(Shared::Pending(waiting)) => {
+                waiting.push(request);
+                None
+            }
+            Some(Shared::Done(original, slot)) => {
+                let slot = *slot;
+                let metrics = copy_result(original, &request);
+                results.push(self.publish(metrics, &request, Some(slot)));
+                None
+            }
+            None => {
+                shared.insert(key, Shared::Pending(Vec::new()));
+                Some(request)
+            }
+        }
+    }
+
+    // Requests that were waiting on `request`, no longer tied to it
+    fn release_duplicates(&mut self, request: &ChatRequest) -> Vec<ChatRequest> {
+        let Some(shared) = self.shared.as_mut() else { return Vec::new() };
+        let Some(key) = dedupe_key(request) else { return Vec::new() };
+        match shared.remove(&key) {
+            Some(Shared::Pending(waiting)) => waiting,
+            _ => Vec::new(),
+        }
+    }
+
+    // Put a request back at the head of the queue, with its duplicates lined up behind it
+    fn requeue(&mut self, request: ChatRequest) {
+        for duplicate in self.release_duplicates(&request).into_iter().rev() {
+            self.queue.push_front(duplicate);
+        }
+        self.queue.push_front(request);
+    }
+
+    fn send(&mut self, slot: usize, request: ChatRequest, hedge: Option<(Duration, usize)>, rivals: Vec<usize>) {
+        let chain: Vec<(usize, Arc<dyn LLMProvider>)> =
+            self.chain(slot).into_iter().map(|slot| (slot, Arc::clone(&self.providers[slot]))).collect();
+        let hedged = hedge.map(|(_, target)| target);
+        let hedge = hedge.map(|(delay, slot)| (delay, (slot, Arc::clone(&self.providers[slot]))));
+        let racers: Vec<(usize, Arc<dyn LLMProvider>)> =
+            rivals.iter().map(|&slot| (slot, Arc::clone(&self.providers[slot]))).collect();
+        let raced: Vec<String> = if racers.is_empty() {
+            Vec::new()
+        } else {
+            std::iter::once(slot).chain(rivals.iter().copied()).map(|slot| self.providers[slot].display_name()).collect()
+        };
+        let rate_limiter = Arc::clone(&self.rate_limiter);
+        let cancellation = Arc::clone(&self.cancellation);
+        // The first request of each slot goes out right away; later ones follow a response
+        let think_ms = self.think_time.as_ref().filter(|_| self.dispatched >= self.limit()).map(ServiceTime::sample_ms);
+        let sent = Arc::new(AtomicBool::new(false));
+        let options = ResultOptions { sent: Some(Arc::clone(&sent)), ..self.options.clone() };
+        let mut drained = self.drained[slot].subscribe();
+        let in_flight = self.in_flight.len() + 1;
+        let sequence = self.dispatched;
+        let sent_at = Instant::now();
+        self.dispatched += 1;
+        self.load.started(slot);
+        self.status.lock().unwrap()[slot].assigned += 1;
+        debug!(index = request.index, provider = %self.providers[slot].display_name(), in_flight, "sending request");
+        self.in_flight.push(tokio::spawn(async move {
+            let index = request.index;
+            let work = async {
+                if let Some(think_ms) = think_ms {
+                    sleep(Duration::from_secs_f64(think_ms / 1000.0)).await;
+                }
+                let primary = async {
+                    let mut tried = Vec::new();
+                    let mut result = None;
+                    for (slot, provider) in chain {
+                        tried.push(slot);
+                        let attempt =
+                            BatchProcessor::process_request(provider, request.clone(), Arc::clone(&rate_limiter), options.clone()).await;
+                        let failed = attempt.is_err();
+                        result = Some(attempt);
+                        if !failed {
+                            break;
+                        }
+                    }
+                    (tried, result.expect("a chain starts with the assigned provider"))
+                };
+                if !racers.is_empty() {
+                    let mut attempts = vec![primary.boxed()];
+                    for (slot, provider) in racers {
+                        let attempt =
+                            BatchProcessor::process_request(provider, request.clone(), Arc::clone(&rate_limiter), options.clone());
+                        attempts.push(attempt.map(move |result| (vec![slot], result)).boxed());
+                    }
+                    let (tried, result) = hedge::first_success(attempts).await;
+                    return (tried, result.map(|mut metrics| {
+                        metrics.raced = raced;
+                        metrics
+                    }));
+                }
+                match hedge {
+                    Some((delay, target)) => {
+                        hedge::race(primary, delay, target, request.clone(), Arc::clone(&rate_limiter), options.clone()).await
+                    }
+                    None => primary.await,
+                }
+            };
+            // Only a request that no copy has been sent for yet can be handed back
+            let reclaimed = async {
+                if drained.wait_for(|&drained| drained).await.is_err() || sent.load(Ordering::SeqCst) {
+                    futures::future::pending::<()>().await;
+                }
+            };
+            let (attempts, reclaimed) = tokio::select! {
+                result = work => (Some(result), false),
+                _ = cancellation.cancelled(index) => (None, false),
+                _ = reclaimed => (None, true),
+            };
+            Finished { slot, request, in_flight, sequence, rivals, hedged, sent_at, attempts, reclaimed }
+        }));
+    }
+
+    fn settle(&mut self, finished: Finished, results: &mut Vec<RequestMetrics>) {
+        let Finished { slot, request, in_flight, sequence, rivals, hedged, sent_at, attempts, reclaimed } = finished;
+        // Only the provider that answered on its own has a usable latency
+        let latency_ms = |provider: usize| match &attempts {
+            Some((tried, Ok(metrics))) if tried == &[provider] => Some(metrics.latency_ms),
+            _ => None,
+        };
+        self.load.finished(slot, latency_ms(slot));
+        for rival in rivals {
+            self.load.finished(rival, latency_ms(rival));
+        }
+        // A winning copy's latency runs from when the primary was sent, so it says nothing
+        // about the target
+        if let Some(target) = hedged {
+            self.load.finished(target, None);
+        }
+        {
+            let mut status = self.status.lock().unwrap();
+            status[slot].assigned -= 1;
+            status[slot].completed += attempts.is_some() as usize;
+        }
+        if reclaimed {
+            info!(
+                index = request.index,
+                provider = %self.providers[slot].display_name(),
+                "provider drained before the request was sent; request requeued"
+            );
+            self.requeue(request);
+            return;
+        }
+        let Some((tried, result)) = attempts else {
+            // Identical requests that weren't cancelled themselves go out on their own
+            for duplicate in self.release_duplicates(&request).into_iter().rev() {
+                self.queue.push_front(duplicate);
+            }
+            debug!(index = request.index, provider = %self.providers[slot].display_name(), "request cancelled in flight");
+            let mut metrics = RequestMetrics::unsent(&request, self.providers[slot].display_name(), Status::Cancelled);
+            record_timing(&mut metrics, &request, sent_at);
+            results.push(self.publish(metrics, &request, Some(slot)));
+            return;
+        };
+        let (&last, failed_over) = tried.split_last().expect("at least one provider was tried");
+        for &attempted in failed_over {
+            self.health.record(attempted, false);
+        }
+        let mut metrics = match result {
+            Ok(mut metrics) => {
+                self.health.record(last, true);
+                if let Some(hedging) = self.hedging.as_mut() {
+                    hedging.record(metrics.latency_ms);
+                }
+                metrics.failovers = failed_over.iter().map(|&slot| self.providers[slot].display_name()).collect();
+                debug!(index = request.index, provider = %metrics.provider_name, latency_ms = metrics.latency_ms, "request finished");
+                metrics
+            }
+            Err(e) => {
+                self.health.record(last, false);
+                self.update_states();
+                // A tripped provider's failures go to the healthy ones instead of being lost
+                if self.health.should_requeue(last) {
+                    info!(
+                        index = request.index,
+                        provider = %self.providers[last].display_name(),
+                        error = %e,
+                        "provider tripped its circuit breaker; request requeued"
+                    );
+                    self.requeue(request);
+                    return;
+                }
+                let mut metrics = RequestMetrics::failed(&request, self.providers[last].display_name(), e.to_string());
+                let category = failure::categorize(&*e);
+                metrics.status = Status::of_failure(category);
+                metrics.error_category = Some(category.to_string());
+                warn!(
+                    index = request.index,
+                    provider = %metrics.provider_name,
+                    category = %category,
+                    error = %e,
+                    "request failed"
+                );
+                metrics
+            }
+        };
+        self.update_states();
+        metrics.concurrency = in_flight;
+        record_timing(&mut metrics, &request, sent_at);
+        // Priced by the model the request asked for, then the one that answered, then the
+        // provider's own
+        let response_model = metrics.model.clone();
+        let models: Vec<&str> = [request.overrides.model.as_deref(), response_model.as_deref(), Some(self.providers[last].model())]
+            .into_iter()
+            .flatten()
+            .collect();
+        self.budget.record(&models, &mut metrics);
+        let metrics = self.publish(metrics, &request, Some(last));
+        if self.shared.is_some() && metrics.status != Status::Ok {
+            // A failure isn't worth sharing: the identical requests go out on their own, the
+            // first of them sent for the rest
+            for duplicate in self.release_duplicates(&request).into_iter().rev() {
+                self.queue.push_front(duplicate);
+            }
+        } else if self.shared.is_some() {
+            for duplicate in self.release_duplicates(&request) {
+                let copy = if self.cancellation.is_cancelled(duplicate.index) {
+                    RequestMetrics::unsent(&duplicate, String::new(), Status::Cancelled)
+                } else {
+                    copy_result(&metrics, &duplicate)
+                };
+                results.push(self.publish(copy, &duplicate, Some(last)));
+            }
+            if let (Some(shared), Some(key)) = (self.shared.as_mut(), dedupe_key(&request)) {
+                shared.insert(key, Shared::Done(Box::new(metrics.clone()), last));
+            }
+        }
+        if let Some(adaptive) = self.adaptive.as_mut().filter(|_| sequence >= self.adjusted_at) {
+            self.window.push(metrics.clone());
+            // Adjust once a full limit's worth of requests has come back, or right away when
+            // the provider is pushing back
+            if self.window.len() >= adaptive.limit() || metrics.retries > 0 {
+                adaptive.update(&self.window);
+                self.window.clear();
+                self.adjusted_at = self.dispatched;
+            }
+        }
+        results.push(metrics);
+    }
+}
+
+// The runtime outlives the run, so requests still in flight when it is abandoned (a callback
+// raised, say) would otherwise carry on in the background
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        for request in self.in_flight.iter() {
+            request.abort();
+        }
+    }
+}
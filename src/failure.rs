@@ -0,0 +1,91 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use reqwest::StatusCode;
+
+use crate::retry::RateLimited;
+use crate::simulator::SimulatedRateLimit;
+
+// A non-2xx response other than a rate limit
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for HttpError {}
+
+// What ended a failed request: "timeout", "rate_limit" (429), "server_error" (5xx, 503
+// included), "client_error" (any other 4xx), "connection", "parse" (a response that
+// couldn't be decoded) or "other". The first cause in the error's source chain that can
+// be told apart decides.
+pub fn categorize(error: &(dyn Error + 'static)) -> &'static str {
+    let mut cause = Some(error);
+    while let Some(error) = cause {
+        if let Some(limited) = error.downcast_ref::<RateLimited>() {
+            return by_status(limited.status);
+        }
+        if let Some(http) = error.downcast_ref::<HttpError>() {
+            return by_status(http.status);
+        }
+        if error.is::<SimulatedRateLimit>() {
+            return "rate_limit";
+        }
+        if error.is::<serde_json::Error>() {
+            return "parse";
+        }
+        if error.is::<tokio::time::error::Elapsed>() {
+            return "timeout";
+        }
+        if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+            if e.is_timeout() {
+                return "timeout";
+            }
+            if e.is_decode() {
+                return "parse";
+            }
+            if let Some(status) = e.status() {
+                return by_status(status);
+            }
+            if e.is_connect() {
+                return "connection";
+            }
+        }
+        if let Some(e) = error.downcast_ref::<io::Error>() {
+            match e.kind() {
+                io::ErrorKind::TimedOut => return "timeout",
+                io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof => return "connection",
+                _ => {}
+            }
+        }
+        cause = error.source();
+    }
+    // Sending or reading the body failed without a more specific cause
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(e) if e.is_request() || e.is_body() => "connection",
+        _ => "other",
+    }
+}
+
+fn by_status(status: StatusCode) -> &'static str {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        "rate_limit"
+    } else if status.is_server_error() {
+        "server_error"
+    } else if status.is_client_error() {
+        "client_error"
+    } else {
+        "other"
+    }
+}
@@ -0,0 +1,258 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use pyo3::prelude::*;
+
+use crate::dispatch::{Cancellation, Dispatcher, ProviderChanges, SIGNAL_POLL_INTERVAL};
+use crate::integrity::{self, IntegrityReport};
+use crate::progress::{LiveStats, RunStats};
+use crate::stats::{self, ProviderStats, SharedStatus};
+use crate::summary::{self, RunSummary};
+use crate::throughput::SharedSamples;
+use crate::{extract_provider, runtime, BatchProcessor, RequestMetrics};
+
+struct RunState {
+    results: Mutex<Vec<RequestMetrics>>,
+    // Signalled as each result arrives and when the run finishes
+    arrived: Condvar,
+    finished: AtomicBool,
+    // Requests taken in so far, all of them up front for a list
+    submitted: AtomicUsize,
+    // What stopped a request iterator early, raised once by wait() or iteration
+    error: Mutex<Option<PyErr>>,
+    started: Instant,
+    // How long the run took, once it has finished
+    duration: Mutex<Option<Duration>>,
+}
+
+// Control handle for a batch running on a background thread. Iterating over it yields each
+// result as soon as it finishes.
+#[pyclass(weakref)]
+pub struct BatchHandle {
+    state: Arc<RunState>,
+    // Results already yielded by iteration
+    yielded: usize,
+    cancellation: Arc<Cancellation>,
+    providers: Arc<ProviderChanges>,
+    status: SharedStatus,
+    samples: SharedSamples,
+    live: Arc<Mutex<LiveStats>>,
+    // A request iterator's total isn't known up front
+    open_ended: bool,
+    // Applies to providers added mid-run as it did to the initial ones
+    test_mode: bool,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BatchHandle {
+    pub(crate) fn spawn(
+        processor: BatchProcessor,
+        mut dispatcher: Dispatcher,
+        cancellation: Arc<Cancellation>,
+        test_mode: bool,
+    ) -> Self {
+        let state = Arc::new(RunState {
+            results: Mutex::new(Vec::new()),
+            arrived: Condvar::new(),
+            finished: AtomicBool::new(false),
+            submitted: AtomicUsize::new(dispatcher.submitted()),
+            error: Mutex::new(None),
+            started: Instant::now(),
+            duration: Mutex::new(None),
+        });
+        let live = Arc::new(Mutex::new(LiveStats::start()));
+        let run_state = Arc::clone(&state);
+        let run_live = Arc::clone(&live);
+        dispatcher.on_result(move |metrics| {
+            run_live.lock().unwrap().record(metrics);
+            run_state.results.lock().unwrap().push(metrics.clone());
+            run_state.arrived.notify_all();
+        });
+        let providers = dispatcher.provider_changes();
+        let status = dispatcher.provider_status();
+        let samples = dispatcher.samples();
+        let open_ended = dispatcher.open_ended();
+        let run_state = Arc::clone(&state);
+        let thread = std::thread::spawn(move || {
+            while processor.runtime.block_on(dispatcher.next_batch()).is_some() {
+                run_state.submitted.store(dispatcher.submitted(), Ordering::SeqCst);
+            }
+            run_state.submitted.store(dispatcher.submitted(), Ordering::SeqCst);
+            *run_state.error.lock().unwrap() = dispatcher.take_source_error();
+            *run_state.duration.lock().unwrap() = Some(run_state.started.elapsed());
+            let _results = run_state.results.lock().unwrap();
+            run_state.finished.store(true, Ordering::SeqCst);
+            run_state.arrived.notify_all();
+        });
+        Self {
+            state,
+            yielded: 0,
+            cancellation,
+            providers,
+            status,
+            samples,
+            live,
+            open_ended,
+            test_mode,
+            thread: Mutex::new(Some(thread)),
+        }
+    }
+}
+
+#[pymethods]
+impl BatchHandle {
+    // Abort a queued or in-flight request; it comes back with status "cancelled".
+    // Returns False when the request already has a result.
+    fn cancel_request(&self, index: usize) -> PyResult<bool> {
+        let total = self.total();
+        if index >= total {
+            return Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>(
+                format!("request index {} out of range for {} requests", index, total),
+            ));
+        }
+        if self.state.results.lock().unwrap().iter().any(|metrics| metrics.index == index) {
+            return Ok(false);
+        }
+        self.cancellation.cancel(index);
+        Ok(true)
+    }
+
+    // Stop sending requests and give those in flight the run's drain_timeout to finish
+    // (calling it again aborts them right away); results() keeps what finished and the
+    // rest come back with status "cancelled"
+    fn cancel(&self) {
+        self.cancellation.cancel_all();
+    }
+
+    // Bring another provider (a ProviderConfig or its as_tuple()) into the rotation of the
+    // running batch, e.g. a fresh key or extra capacity. Returns its index for
+    // drain_provider(); the providers the batch started with are numbered from 0.
+    fn add_provider(&self, provider: &PyAny) -> PyResult<usize> {
+        if self.done() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("the batch has already finished"));
+        }
+        let provider = if provider.hasattr("as_tuple")? { provider.call_method0("as_tuple")? } else { provider };
+        let index = self.providers.count();
+        let provider = extract_provider(provider, index, &runtime::client(), self.test_mode)?;
+        Ok(self.providers.add(provider))
+    }
+
+    // Stop sending requests to a provider, by index; those it has in flight finish, and
+    // those its limits still hold back go to the other providers. Health checks don't
+    // bring a drained provider back.
+    fn drain_provider(&self, index: usize) -> PyResult<()> {
+        if !self.providers.drain(index) {
+            return Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>(format!(
+                "provider index {} out of range for {} providers",
+                index,
+                self.providers.count()
+            )));
+        }
+        Ok(())
+    }
+
+    // Each provider's queue right now, in provider index order
+    fn provider_stats(&self) -> Vec<ProviderStats> {
+        stats::snapshot(&self.status)
+    }
+
+    // Completed count, rates and latency percentiles right now, kept up to date as results
+    // arrive; cheap enough to poll from a dashboard thread while the batch runs
+    fn get_stats(&self) -> RunStats {
+        self.live.lock().unwrap().snapshot(self.total(), self.open_ended, &self.status)
+    }
+
+    // Requests in the batch; for a request iterator, those pulled from it so far
+    #[getter]
+    fn total(&self) -> usize {
+        self.state.submitted.load(Ordering::SeqCst)
+    }
+
+    #[getter]
+    fn completed(&self) -> usize {
+        self.state.results.lock().unwrap().len()
+    }
+
+    fn done(&self) -> bool {
+        self.state.finished.load(Ordering::SeqCst)
+    }
+
+    // Results so far, in completion order
+    fn results(&self) -> Vec<RequestMetrics> {
+        self.state.results.lock().unwrap().clone()
+    }
+
+    // Block until the run finishes (without holding the GIL) and return every result.
+    // Ctrl-C cancels the run and returns the partial results once in-flight requests have
+    // drained; a second Ctrl-C stops waiting for them.
+    fn wait(&self, py: Python<'_>) -> PyResult<Vec<RequestMetrics>> {
+        while !self.done() {
+            py.allow_threads(|| std::thread::sleep(SIGNAL_POLL_INTERVAL));
+            if py.check_signals().is_err() {
+                self.cancellation.cancel_all();
+            }
+        }
+        let thread = self.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            py.allow_threads(|| thread.join())
+                .map_err(|_| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("batch thread panicked"))?;
+        }
+        if let Some(error) = self.state.error.lock().unwrap().take() {
+            return Err(error);
+        }
+        let results = self.results();
+        integrity::verify(&results, self.total()).warn_if_incomplete(py)?;
+        Ok(results)
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    // The next result to finish, waiting for it without holding the GIL; Ctrl-C while
+    // waiting cancels the run
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<RequestMetrics>> {
+        let state = Arc::clone(&self.state);
+        loop {
+            let yielded = self.yielded;
+            let next = py.allow_threads(|| {
+                let results = state.results.lock().unwrap();
+                let (results, _) = state
+                    .arrived
+                    .wait_timeout_while(results, SIGNAL_POLL_INTERVAL, |results| {
+                        results.len() <= yielded && !state.finished.load(Ordering::SeqCst)
+                    })
+                    .unwrap();
+                results.get(yielded).cloned()
+            });
+            if let Some(metrics) = next {
+                self.yielded += 1;
+                return Ok(Some(metrics));
+            }
+            if self.done() {
+                return match self.state.error.lock().unwrap().take() {
+                    Some(error) => Err(error),
+                    None => Ok(None),
+                };
+            }
+            if py.check_signals().is_err() {
+                self.cancellation.cancel_all();
+            }
+        }
+    }
+
+    // Latency percentiles, throughput and error counts over the results so far, with the
+    // time since the batch started (or its whole duration once done) and the throughput
+    // samples taken so far
+    fn summary(&self) -> RunSummary {
+        let duration = self.state.duration.lock().unwrap().unwrap_or_else(|| self.state.started.elapsed());
+        let samples = self.samples.lock().unwrap().clone();
+        summary::summarize(&self.state.results.lock().unwrap(), duration.as_secs_f64(), samples)
+    }
+
+    // Check the results so far against the submitted requests; only meaningful once done
+    fn integrity(&self) -> IntegrityReport {
+        integrity::verify(&self.state.results.lock().unwrap(), self.total())
+    }
+}
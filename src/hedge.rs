@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use tokio::time::{sleep, Instant};
+
+use crate::ratelimit::RateLimiter;
+use crate::{BatchProcessor, ChatRequest, LLMProvider, RequestMetrics, ResultOptions};
+
+type Outcome = (Vec<usize>, Result<RequestMetrics, Box<dyn Error + Send + Sync>>);
+
+// Latencies kept for the percentile, and how many it takes before any request is hedged
+const WINDOW: usize = 200;
+const MIN_SAMPLES: usize = 20;
+const PERCENTILE: f64 = 0.95;
+
+// Hedged requests: a request still unanswered after the p95 latency of recent ones is also
+// sent to a second provider, and whichever copy answers first is kept
+pub struct Hedging {
+    latencies: VecDeque<f64>,
+}
+
+impl Hedging {
+    pub fn new() -> Self {
+        Self { latencies: VecDeque::with_capacity(WINDOW) }
+    }
+
+    pub fn record(&mut self, latency_ms: f64) {
+        if self.latencies.len() == WINDOW {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency_ms);
+    }
+
+    // How long a request waits before it is hedged; None until enough have come back
+    pub fn delay(&self) -> Option<Duration> {
+        if self.latencies.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mut sorted: Vec<f64> = self.latencies.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let rank = ((sorted.len() as f64 * PERCENTILE).ceil() as usize).clamp(1, sorted.len());
+        Duration::try_from_secs_f64(sorted[rank - 1] / 1000.0).ok()
+    }
+}
+
+// Run `primary`, and once `delay` has passed without an answer send `request` to `hedge` as
+// well. The first success wins and the other copy is dropped, which cancels it; when one
+// copy fails the other is waited for, and when both do the primary's failure is kept. A
+// winning hedge's latency is counted from when the primary was sent.
+pub async fn race(
+    primary: impl Future<Output = Outcome>,
+    delay: Duration,
+    (slot, provider): (usize, Arc<dyn LLMProvider>),
+    request: ChatRequest,
+    rate_limiter: Arc<RateLimiter>,
+    options: ResultOptions,
+) -> Outcome {
+    let started = Instant::now();
+    let hedged_to = provider.display_name();
+    tokio::pin!(primary);
+    tokio::select! {
+        outcome = &mut primary => return outcome,
+        _ = sleep(delay) => {}
+    }
+    let hedge = BatchProcessor::process_request(provider, request, rate_limiter, options);
+    tokio::pin!(hedge);
+    let (tried, result) = tokio::select! {
+        (tried, result) = &mut primary => match result {
+            Ok(metrics) => (tried, Ok(metrics)),
+            Err(e) => match hedge.await {
+                Ok(metrics) => (vec![slot], Ok(won(metrics, started))),
+                Err(_) => (tried, Err(e)),
+            },
+        },
+        result = &mut hedge => match result {
+            Ok(metrics) => (vec![slot], Ok(won(metrics, started))),
+            Err(_) => primary.await,
+        },
+    };
+    let result = result.map(|mut metrics| {
+        metrics.hedged_to = Some(hedged_to);
+        metrics
+    });
+    (tried, result)
+}
+
+fn won(mut metrics: RequestMetrics, started: Instant) -> RequestMetrics {
+    metrics.hedge_won = true;
+    metrics.latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+    metrics
+}
+
+// Provider racing: run every attempt at once and keep the first success, dropping (and so
+// cancelling) the rest. When all of them fail, the failure of the earliest one is kept.
+pub async fn first_success(attempts: Vec<BoxFuture<'_, Outcome>>) -> Outcome {
+    let mut attempts: FuturesUnordered<_> =
+        attempts.into_iter().enumerate().map(|(position, attempt)| attempt.map(move |outcome| (position, outcome))).collect();
+    let mut failure: Option<(usize, Outcome)> = None;
+    while let Some((position, outcome)) = attempts.next().await {
+        if outcome.1.is_ok() {
+            return outcome;
+        }
+        if failure.as_ref().is_none_or(|(first, _)| position < *first) {
+            failure = Some((position, outcome));
+        }
+    }
+    failure.expect("a race has at least one attempt").1
+}
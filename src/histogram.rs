@@ -0,0 +1,131 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hdrhistogram::serialization::{Deserializer, Serializer, V2DeflateSerializer};
+use hdrhistogram::Histogram;
+use pyo3::prelude::*;
+
+// Significant digits kept for every recorded value
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+// Quantiles reported by percentiles() when none are asked for
+const DEFAULT_QUANTILES: [f64; 7] = [0.5, 0.9, 0.95, 0.99, 0.999, 0.9999, 1.0];
+
+// Latencies in an HdrHistogram at microsecond resolution, so tail percentiles stay precise
+// over millions of requests without keeping every value. encode() gives the standard
+// compressed base64 form HdrHistogram tooling reads, in microseconds.
+#[pyclass]
+#[derive(Clone)]
+pub struct LatencyHistogram {
+    histogram: Histogram<u64>,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self { histogram: Histogram::new(SIGNIFICANT_DIGITS).expect("valid significant digits") }
+    }
+
+    pub fn of(latencies_ms: impl Iterator<Item = f64>) -> Self {
+        let mut histogram = Self::new();
+        for latency_ms in latencies_ms {
+            histogram.record(latency_ms);
+        }
+        histogram
+    }
+
+    pub fn record(&mut self, latency_ms: f64) {
+        // The cast saturates, so a negative or NaN latency counts as 0, and the histogram
+        // grows to fit anything up to u64::MAX
+        let micros = (latency_ms * 1000.0).round() as u64;
+        self.histogram.record(micros).expect("an auto-resizing histogram takes any u64");
+    }
+
+    // Latency at `quantile`, which must be between 0 and 1
+    pub fn quantile_ms(&self, quantile: f64) -> f64 {
+        self.millis(self.histogram.value_at_quantile(quantile))
+    }
+
+    fn millis(&self, micros: u64) -> f64 {
+        micros as f64 / 1000.0
+    }
+}
+
+#[pymethods]
+impl LatencyHistogram {
+    #[getter]
+    fn count(&self) -> u64 {
+        self.histogram.len()
+    }
+
+    #[getter]
+    fn min_ms(&self) -> f64 {
+        self.millis(self.histogram.min())
+    }
+
+    #[getter]
+    fn max_ms(&self) -> f64 {
+        self.millis(self.histogram.max())
+    }
+
+    #[getter]
+    pub fn mean_ms(&self) -> f64 {
+        self.histogram.mean() / 1000.0
+    }
+
+    // Latency at `quantile`, between 0 and 1; 0 for an empty histogram
+    fn value_at(&self, quantile: f64) -> PyResult<f64> {
+        if !(0.0..=1.0).contains(&quantile) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "quantile must be between 0 and 1, got {}",
+                quantile
+            )));
+        }
+        Ok(self.quantile_ms(quantile))
+    }
+
+    // Latencies at each of `quantiles`, in the same order; by default p50, p90, p95, p99,
+    // p99.9, p99.99 and the maximum
+    #[pyo3(signature = (quantiles=None))]
+    fn percentiles(&self, quantiles: Option<Vec<f64>>) -> PyResult<Vec<f64>> {
+        quantiles.unwrap_or_else(|| DEFAULT_QUANTILES.to_vec()).into_iter().map(|quantile| self.value_at(quantile)).collect()
+    }
+
+    // Compressed (V2 deflate) HdrHistogram, base64 encoded
+    fn encode(&self) -> PyResult<String> {
+        let mut bytes = Vec::new();
+        V2DeflateSerializer::new()
+            .serialize(&self.histogram, &mut bytes)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:?}", e)))?;
+        Ok(STANDARD.encode(bytes))
+    }
+
+    #[staticmethod]
+    fn decode(encoded: &str) -> PyResult<Self> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Not a base64 histogram: {}", e)))?;
+        let mut histogram: Histogram<u64> = Deserializer::new()
+            .deserialize(&mut bytes.as_slice())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Not an HdrHistogram: {:?}", e)))?;
+        histogram.auto(true);
+        Ok(Self { histogram })
+    }
+
+    // A histogram with the values of both, e.g. to combine runs
+    fn merge(&self, other: &Self) -> PyResult<Self> {
+        let mut histogram = self.histogram.clone();
+        histogram
+            .add(&other.histogram)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:?}", e)))?;
+        Ok(Self { histogram })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "LatencyHistogram(count={}, p50_ms={:.1}, p99_ms={:.1}, max_ms={:.1})",
+            self.count(),
+            self.quantile_ms(0.5),
+            self.quantile_ms(0.99),
+            self.max_ms()
+        )
+    }
+}
@@ -0,0 +1,117 @@
+use std::error::Error;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::json;
+
+use crate::tokenizer::count_tokens;
+use crate::{
+    check_status, extract_config_value, provider_request_id, read_json, wire_bytes, ChatRequest, LLMProvider, OpenAIProvider,
+    RequestMetrics, ResponseContent,
+};
+
+// Parameters of an image generation request, {"type": "image", "prompt": ...}, sent to
+// /v1/images/generations. The prompt itself travels as the request's single user message
+// so language detection, planning and sanitization treat it like any other prompt; "model",
+// "n", "user" and "extra_body" come from the usual request overrides.
+#[derive(Debug, Clone, Default)]
+pub struct ImageRequest {
+    pub size: Option<String>,
+    pub quality: Option<String>,
+    pub style: Option<String>,
+    // "url" or "b64_json"
+    pub response_format: Option<String>,
+}
+
+impl ImageRequest {
+    // None for chat requests (no "type" or "type": "chat")
+    pub fn extract(dict: &PyDict) -> PyResult<Option<Self>> {
+        match extract_config_value::<String>(dict, "type")?.as_deref() {
+            None | Some("chat") => Ok(None),
+            Some("image") => Ok(Some(Self {
+                size: extract_config_value(dict, "size")?,
+                quality: extract_config_value(dict, "quality")?,
+                style: extract_config_value(dict, "style")?,
+                response_format: extract_config_value(dict, "response_format")?,
+            })),
+            Some(other) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                format!("Unknown request type '{}'; expected 'chat' or 'image'", other),
+            )),
+        }
+    }
+}
+
+// Size of the data a base64 string decodes to
+fn decoded_len(encoded: &str) -> usize {
+    let padding = encoded.bytes().rev().take_while(|&b| b == b'=').count();
+    (encoded.len() / 4 * 3).saturating_sub(padding)
+}
+
+impl OpenAIProvider {
+    pub(crate) async fn send_image_request(
+        &self,
+        request: &ChatRequest,
+        image: &ImageRequest,
+    ) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}/v1/images/generations", self.base_url.trim_end_matches('/'));
+        let prompt = request.messages.iter().map(|message| message.text()).collect::<Vec<_>>().join("\n");
+        let model = self.requested_model(request);
+
+        let mut payload = serde_json::Map::new();
+        payload.insert("prompt".to_string(), json!(prompt));
+        if let Some(model) = &model {
+            payload.insert("model".to_string(), json!(model));
+        }
+        if let Some(n) = self.choices(request) {
+            payload.insert("n".to_string(), json!(n));
+        }
+        for (key, value) in [
+            ("size", &image.size),
+            ("quality", &image.quality),
+            ("style", &image.style),
+            ("response_format", &image.response_format),
+            ("user", &request.overrides.user),
+        ] {
+            if let Some(value) = value {
+                payload.insert(key.to_string(), json!(value));
+            }
+        }
+        if let Some(serde_json::Value::Object(fields)) = &request.overrides.extra_body {
+            payload.extend(fields.clone());
+        }
+        let request_body = serde_json::Value::Object(payload).to_string();
+        let http_request = self.client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .headers(self.headers.clone())
+            .body(request_body)
+            .build()?;
+        let request_bytes = wire_bytes(&http_request);
+        let response = self.client.execute(http_request).await?;
+        self.limits.observe(response.headers());
+        let provider_request_id = provider_request_id(response.headers());
+        let response = check_status(response, provider_request_id.as_deref()).await?;
+        let (response_data, response_bytes) = read_json(response).await?;
+
+        let images = response_data["data"].as_array().cloned().unwrap_or_default();
+        // Token usage is only reported by token-billed models such as gpt-image-1
+        let reported = |key: &str| response_data["usage"][key].as_u64().map(|count| count as usize);
+        let (input_tokens, output_tokens) = (reported("input_tokens"), reported("output_tokens"));
+        let mut metrics = RequestMetrics::new(
+            input_tokens.unwrap_or_else(|| count_tokens(model.as_deref().unwrap_or_default(), &prompt)),
+            output_tokens.unwrap_or(0),
+            request_bytes,
+            response_bytes,
+            self.display_name(),
+        );
+        metrics.usage_estimated = input_tokens.is_none() || output_tokens.is_none();
+        metrics.image_urls = images.iter().filter_map(|image| image["url"].as_str().map(str::to_string)).collect();
+        let encoded: Vec<&str> = images.iter().filter_map(|image| image["b64_json"].as_str()).collect();
+        metrics.image_bytes = encoded.iter().map(|data| decoded_len(data)).sum();
+        metrics.choices = encoded.iter().map(|data| Some(ResponseContent::Plain(data.to_string()))).collect();
+        metrics.model = model;
+        metrics.raw_response = Some(response_data);
+        metrics.provider_request_id = provider_request_id;
+        Ok(metrics)
+    }
+}
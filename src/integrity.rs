@@ -0,0 +1,82 @@
+use std::collections::{BTreeMap, HashMap};
+use pyo3::exceptions::PyRuntimeWarning;
+use pyo3::prelude::*;
+
+use crate::RequestMetrics;
+
+// End-of-run check that every submitted request came back exactly once, so a result lost or duplicated along the way is reported instead of silently
+// shrinking the totals
+#[pyclass]
+#[derive(Clone)]
+pub struct IntegrityReport {
+    #[pyo3(get)]
+    pub total: usize,
+    // Result count per status
+    #[pyo3(get)]
+    pub statuses: HashMap<String, usize>,
+    // Indices without a result
+    #[pyo3(get)]
+    pub missing: Vec<usize>,
+    // Indices with more than one result
+    #[pyo3(get)]
+    pub duplicated: Vec<usize>,
+    // Indices out of range
+    #[pyo3(get)]
+    pub unexpected: Vec<usize>,
+}
+
+pub fn verify(results: &[RequestMetrics], total: usize) -> IntegrityReport {
+    let mut seen = vec![0usize; total];
+    let mut statuses = HashMap::new();
+    let mut unexpected = Vec::new();
+    for metrics in results {
+        *statuses.entry(metrics.status.to_string()).or_insert(0) += 1;
+        match seen.get_mut(metrics.index) {
+            Some(count) => *count += 1,
+            None => unexpected.push(metrics.index),
+        }
+    }
+    unexpected.sort_unstable();
+    unexpected.dedup();
+    IntegrityReport {
+        total,
+        statuses,
+        missing: (0..total).filter(|&index| seen[index] == 0).collect(),
+        duplicated: (0..total).filter(|&index| seen[index] > 1).collect(),
+        unexpected,
+    }
+}
+
+impl IntegrityReport {
+    // Emit a RuntimeWarning describing the gaps, if any
+    pub fn warn_if_incomplete(&self, py: Python<'_>) -> PyResult<()> {
+        if self.complete() {
+            return Ok(());
+        }
+        let message = format!("Run finished with gaps: {}", self.__repr__());
+        PyErr::warn(py, py.get_type::<PyRuntimeWarning>(), &message, 1)
+    }
+}
+
+#[pymethods]
+impl IntegrityReport {
+    // Every index has exactly one terminal result
+    #[getter]
+    pub fn complete(&self) -> bool {
+        self.missing.is_empty() && self.duplicated.is_empty() && self.unexpected.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        let statuses: BTreeMap<_, _> = self.statuses.iter().collect();
+        format!(
+            "IntegrityReport(total={}, statuses={:?}, missing={:?}, duplicated={:?}, unexpected={:?})",
+            self.total, statuses, self.missing, self.duplicated, self.unexpected
+        )
+    }
+}
+
+// Check a result list against the number of submitted requests
+#[pyfunction]
+pub fn verify_results(results: Vec<RequestMetrics>, total: usize) -> IntegrityReport {
+    verify(&results, total)
+}
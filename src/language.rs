@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use crate::ChatRequest;
+
+// Function words that tell the common Latin-script languages apart
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "is", "of", "to", "what", "this", "you", "with", "are"]),
+    ("es", &["el", "la", "los", "las", "que", "es", "y", "por", "una", "del"]),
+    ("fr", &["le", "la", "les", "et", "est", "des", "une", "que", "pour", "du"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "zu"]),
+    ("pt", &["o", "os", "as", "que", "é", "e", "um", "uma", "não", "do"]),
+    ("it", &["il", "la", "che", "è", "e", "di", "un", "una", "per", "non"]),
+];
+
+// Script-first language guess, cheap enough to run on every prompt: the dominant non-Latin
+// script decides the language outright, Latin text is scored on common function words.
+// Returns an ISO 639-1 code or "unknown".
+pub fn detect(text: &str) -> &'static str {
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    let mut kana = 0;
+    let mut latin = 0;
+    for c in text.chars() {
+        let script = match c as u32 {
+            0x3040..=0x30FF => {
+                kana += 1;
+                "ja"
+            }
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => "zh",
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => "ko",
+            0x0400..=0x04FF => "ru",
+            0x0600..=0x06FF => "ar",
+            0x0900..=0x097F => "hi",
+            0x0370..=0x03FF => "el",
+            0x0590..=0x05FF => "he",
+            0x0E00..=0x0E7F => "th",
+            _ => {
+                if c.is_alphabetic() {
+                    latin += 1;
+                }
+                continue;
+            }
+        };
+        *counts.entry(script).or_default() += 1;
+    }
+    // Japanese mixes kanji with kana; any kana means the Han characters are Japanese
+    if kana > 0 {
+        let han = counts.remove("zh").unwrap_or(0);
+        *counts.entry("ja").or_default() += han;
+    }
+    match counts.into_iter().max_by_key(|&(_, count)| count) {
+        Some((script, count)) if count >= latin => script,
+        _ if latin > 0 => detect_latin(text),
+        _ => "unknown",
+    }
+}
+
+fn detect_latin(text: &str) -> &'static str {
+    let lowered = text.to_lowercase();
+    let words: Vec<&str> = lowered.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).collect();
+    STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| (*language, words.iter().filter(|w| stopwords.contains(w)).count()))
+        .filter(|&(_, hits)| hits > 0)
+        .max_by_key(|&(_, hits)| hits)
+        .map_or("unknown", |(language, _)| language)
+}
+
+// The conversation text to classify; system prompts are skipped since they are often
+// written in English whatever language the user writes in
+pub fn request_language(request: &ChatRequest) -> &'static str {
+    let text: Vec<String> = request.messages.iter().filter(|m| !m.is_system()).map(|m| m.text()).collect();
+    detect(&text.join("\n"))
+}
+
+// Language → provider slots. Keys are language codes as returned by `detect` or "cjk"
+// for Chinese, Japanese and Korean together; an exact code wins over the group.
+pub struct LanguageRoutes {
+    rules: HashMap<String, Vec<usize>>,
+}
+
+impl LanguageRoutes {
+    pub fn new(rules: HashMap<String, Vec<usize>>, providers: usize) -> Result<Self, String> {
+        for (language, slots) in &rules {
+            if slots.is_empty() {
+                return Err(format!("Language route '{}' lists no providers", language));
+            }
+            if let Some(slot) = slots.iter().find(|&&slot| slot >= providers) {
+                return Err(format!(
+                    "Language route '{}' refers to provider {} but only {} are configured",
+                    language, slot, providers
+                ));
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn providers_for(&self, language: &str) -> Option<&[usize]> {
+        let group = matches!(language, "zh" | "ja" | "ko").then_some("cjk");
+        self.rules
+            .get(language)
+            .or_else(|| group.and_then(|group| self.rules.get(group)))
+            .map(Vec::as_slice)
+    }
+}
@@ -1,40 +1,359 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyTuple};
+use pyo3::types::{PyBool, PyDict, PyFloat, PyIterator, PyList, PyLong, PyString, PyTuple};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use reqwest::Client;
-use reqwest::ClientBuilder;
 use tokio::runtime::Runtime;
-use futures::future::join_all;
-use serde::{Serialize, Deserialize};
 use async_trait::async_trait;
 use rand::Rng;
-use num_cpus;
-use tokio::sync::{Mutex, Semaphore, RwLock};
-use tokio::time::{sleep, Instant};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+mod adaptive;
+mod anthropic;
+mod artifacts;
+mod breaker;
+mod budget;
+mod chat_template;
+mod constraints;
+mod dispatch;
+mod failure;
+mod handle;
+mod histogram;
+mod hedge;
+mod images;
+mod integrity;
+mod language;
+mod logging;
+mod message;
+mod metrics_log;
+mod otel;
+mod planner;
+mod prefix;
+mod pricing;
+mod probe;
+mod progress;
+mod ratelimit;
+mod retry;
+mod routing;
+mod runtime;
+mod sanitize;
+mod selection;
+mod simulator;
+mod source;
+mod stats;
+mod statsd;
+mod status;
+mod templates;
+mod throughput;
+mod storage;
+mod streaming;
+mod summary;
+mod tokenizer;
+mod tools;
+mod vision;
+mod warmup;
+
+pub use message::{ContentPart, ImageSource, Message, MessageContent};
+use anthropic::{AnthropicConfig, AnthropicProvider};
+use artifacts::{artifact_hash, ArtifactStore};
+use budget::RunBudget;
+use chat_template::ChatTemplate;
+use constraints::{Backend, Constraint};
+use dispatch::{Cancellation, Dispatcher, Priority};
+use failure::HttpError;
+use handle::BatchHandle;
+use histogram::LatencyHistogram;
+use images::ImageRequest;
+use integrity::IntegrityReport;
+use language::{request_language, LanguageRoutes};
+use message::{openai_messages, MessageFormat};
+use metrics_log::MetricsLog;
+use otel::Tracer;
+use planner::{plan_run, ProviderPlan, RequestEstimate, RunPlan};
+use prefix::prefix_order;
+use pricing::extract_pricing;
+use probe::HealthChecks;
+use progress::{ProgressTracker, ProviderProgress, RunProgress, RunStats};
+use ratelimit::RateLimiter;
+use retry::{backoff, is_rate_limited, retry_delay, RateLimited, RetryBudget};
+use routing::Routing;
+use sanitize::{read_text, SanitizeReport};
+use selection::{select_choice, ChoicePolicy};
+use simulator::{ServiceTime, Simulator, SimulatorConfig};
+use source::RequestSource;
+use stats::ProviderStats;
+use streaming::consume_stream;
+use statsd::StatsdSink;
+use status::Status;
+use summary::{ProviderSummary, RunSummary};
+use throughput::{SharedSamples, ThroughputSample};
+use templates::PromptTemplates;
+use tokenizer::{count_prompt_tokens, count_tokens};
+use vision::image_tokens;
+use tools::{run_tool_loop, ToolRunner};
+use warmup::Warmup;
 
 // Helper functions for config extraction
 fn extract_config_value<'a, T: FromPyObject<'a>>(dict: &'a PyDict, key: &str) -> PyResult<Option<T>> {
     match dict.get_item(key)? {
-        Some(value) => Ok(Some(value.extract()?)),
+        Some(value) => Ok(Some(value.extract().map_err(|e| invalid_value(dict.py(), key, e))?)),
         None => Ok(None),
     }
 }
 
 fn get_required_value<'a, T: FromPyObject<'a>>(dict: &'a PyDict, key: &str) -> PyResult<T> {
     match dict.get_item(key)? {
-        Some(value) => value.extract(),
+        Some(value) => value.extract().map_err(|e| invalid_value(dict.py(), key, e)),
         None => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
             format!("Missing required key: {}", key),
         )),
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Message {
-    pub role: String,
-    pub content: String,
+fn invalid_value(py: Python<'_>, key: &str, err: PyErr) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!("Invalid value for '{}': {}", key, err.value(py)))
+}
+
+// Accept either a single string or a list of strings (e.g. stop sequences)
+fn extract_string_list(dict: &PyDict, key: &str) -> PyResult<Option<Vec<String>>> {
+    match dict.get_item(key)? {
+        Some(value) if value.is_none() => Ok(None),
+        Some(value) => match value.extract::<String>() {
+            Ok(single) => Ok(Some(vec![single])),
+            Err(_) => Ok(Some(value.extract().map_err(|e| invalid_value(dict.py(), key, e))?)),
+        },
+        None => Ok(None),
+    }
+}
+
+// Token-id keyed bias map; ids may be given as ints or strings from Python
+fn extract_logit_bias(dict: &PyDict, key: &str) -> PyResult<Option<BTreeMap<String, f32>>> {
+    let Some(value) = dict.get_item(key)? else {
+        return Ok(None);
+    };
+    if value.is_none() {
+        return Ok(None);
+    }
+    let bias: &PyDict = value.downcast().map_err(|e| invalid_value(dict.py(), key, e.into()))?;
+    bias.iter()
+        .map(|(token, weight)| {
+            let token = match token.extract::<i64>() {
+                Ok(id) => id.to_string(),
+                Err(_) => token.extract::<String>().map_err(|e| invalid_value(dict.py(), key, e))?,
+            };
+            Ok((token, weight.extract::<f32>().map_err(|e| invalid_value(dict.py(), key, e))?))
+        })
+        .collect::<PyResult<BTreeMap<_, _>>>()
+        .map(Some)
+}
+
+fn extract_json_object(dict: &PyDict, key: &str) -> PyResult<Option<serde_json::Value>> {
+    let value = extract_json_value(dict, key)?;
+    if matches!(value, Some(ref value) if !value.is_object()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!("{} must be a dict", key)));
+    }
+    Ok(value)
+}
+
+fn extract_json_value(dict: &PyDict, key: &str) -> PyResult<Option<serde_json::Value>> {
+    match dict.get_item(key)? {
+        Some(value) if !value.is_none() => Ok(Some(py_to_json(value)?)),
+        _ => Ok(None),
+    }
+}
+
+// Convert an arbitrary Python object (dicts, lists, scalars) into a JSON value
+fn py_to_json(value: &PyAny) -> PyResult<serde_json::Value> {
+    if value.is_none() {
+        return Ok(serde_json::Value::Null);
+    }
+    if let Ok(b) = value.downcast::<PyBool>() {
+        return Ok(serde_json::Value::Bool(b.is_true()));
+    }
+    if let Ok(i) = value.downcast::<PyLong>() {
+        if let Ok(i) = i.extract::<i64>() {
+            return Ok(serde_json::Value::Number(i.into()));
+        }
+        return Ok(serde_json::Value::Number(i.extract::<u64>()?.into()));
+    }
+    if let Ok(f) = value.downcast::<PyFloat>() {
+        return serde_json::Number::from_f64(f.value())
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "Cannot convert non-finite float to JSON",
+            ));
+    }
+    if let Ok(s) = value.downcast::<PyString>() {
+        return Ok(serde_json::Value::String(s.to_str()?.to_string()));
+    }
+    if let Ok(dict) = value.downcast::<PyDict>() {
+        let mut map = serde_json::Map::new();
+        for (k, v) in dict.iter() {
+            map.insert(k.extract::<String>()?, py_to_json(v)?);
+        }
+        return Ok(serde_json::Value::Object(map));
+    }
+    if let Ok(list) = value.downcast::<PyList>() {
+        return list.iter().map(py_to_json).collect::<PyResult<Vec<_>>>().map(serde_json::Value::Array);
+    }
+    if let Ok(tuple) = value.downcast::<PyTuple>() {
+        return tuple.iter().map(py_to_json).collect::<PyResult<Vec<_>>>().map(serde_json::Value::Array);
+    }
+    Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+        format!("Cannot convert {} to JSON", value.get_type().name()?),
+    ))
+}
+
+// Per-request settings that take precedence over the provider config
+#[derive(Debug, Clone, Default)]
+pub struct RequestOverrides {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<usize>,
+    pub top_p: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub n: Option<usize>,
+    pub reasoning_effort: Option<String>,
+    pub max_completion_tokens: Option<usize>,
+    pub response_format: Option<serde_json::Value>,
+    pub json_schema: Option<serde_json::Value>,
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+    pub logit_bias: Option<BTreeMap<String, f32>>,
+    pub constraint: Option<Constraint>,
+    pub extra_body: Option<serde_json::Value>,
+    // End-user identifier and free-form tags forwarded as OpenAI's `user` / `metadata`
+    pub user: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    // OpenAI function tools and tool_choice, forwarded verbatim
+    pub tools: Option<serde_json::Value>,
+    pub tool_choice: Option<serde_json::Value>,
+    // Continue a trailing assistant message: its trailing whitespace is stripped before
+    // sending and the result's content includes it
+    pub continue_final: bool,
+    // Streaming only: stop reading once the first choice's text matches
+    pub stop_regex: Option<regex::Regex>,
+    // Return per-token logprobs (RequestMetrics.choice_logprobs)
+    pub logprobs: Option<bool>,
+}
+
+// Convert a JSON value into the equivalent Python object
+fn json_to_py(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+    Ok(match value {
+        serde_json::Value::Null => py.None(),
+        serde_json::Value::Bool(b) => b.into_py(py),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_py(py),
+            (None, Some(u)) => u.into_py(py),
+            _ => n.as_f64().unwrap_or_default().into_py(py),
+        },
+        serde_json::Value::String(s) => s.into_py(py),
+        serde_json::Value::Array(items) => {
+            let items = items.iter().map(|item| json_to_py(py, item)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items).into_py(py)
+        }
+        serde_json::Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map {
+                dict.set_item(key, json_to_py(py, item)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    // Position in the caller's request list
+    pub index: usize,
+    pub messages: Vec<Message>,
+    pub overrides: RequestOverrides,
+    // Stream the response and append tokens to this file as they arrive
+    pub stream_to: Option<PathBuf>,
+    // Caller-supplied correlation ID, echoed back on the result
+    pub request_id: Option<String>,
+    // Detected prompt language, set when the run detects or routes by language
+    pub language: Option<&'static str>,
+    // What sanitize_inputs changed in the message text
+    pub sanitization: Option<SanitizeReport>,
+    // Set for image generation requests
+    pub image: Option<ImageRequest>,
+    pub priority: Priority,
+    // When the dispatcher took it in: the start of the run for a list, the moment it was
+    // pulled for an iterator
+    pub queued_at: Option<Instant>,
+}
+
+impl ChatRequest {
+    // Text of a trailing assistant message, which the model continues instead of replying to
+    fn prefill(&self) -> Option<String> {
+        self.messages.last().filter(|m| m.role == "assistant" && m.tool_calls.is_none()).map(Message::text)
+    }
+
+    // Accepts either a bare JSON schema or OpenAI's {"name", "schema", "strict"} wrapper
+    fn json_schema_spec(&self) -> Option<serde_json::Value> {
+        let schema = self.overrides.json_schema.as_ref()?;
+        if schema.get("schema").is_some() {
+            return Some(schema.clone());
+        }
+        Some(serde_json::json!({
+            "name": "response",
+            "schema": schema,
+            "strict": true,
+        }))
+    }
+
+    fn json_schema(&self) -> Option<&serde_json::Value> {
+        let schema = self.overrides.json_schema.as_ref()?;
+        Some(schema.get("schema").unwrap_or(schema))
+    }
+}
+
+// Check a completion against the request's JSON schema, returning the validation errors
+fn validate_json_output(schema: &serde_json::Value, content: &str) -> Result<(), String> {
+    let compiled = jsonschema::JSONSchema::compile(schema)
+        .map_err(|e| format!("Invalid schema: {}", e))?;
+    let instance: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| format!("Output is not valid JSON: {}", e))?;
+    compiled.validate(&instance).map_err(|errors| {
+        errors.map(|e| format!("{}: {}", e.instance_path, e)).collect::<Vec<_>>().join("; ")
+    })
+}
+
+// Response text, optionally held zstd-compressed to cut resident memory on large batches
+// or moved out to the artifact store
+#[derive(Clone)]
+pub enum ResponseContent {
+    Plain(String),
+    Compressed(Vec<u8>),
+    Artifact { store: Arc<ArtifactStore>, key: String },
+}
+
+impl ResponseContent {
+    fn compress(self) -> Self {
+        match self {
+            ResponseContent::Plain(text) => match zstd::encode_all(text.as_bytes(), 3) {
+                Ok(bytes) => ResponseContent::Compressed(bytes),
+                Err(_) => ResponseContent::Plain(text),
+            },
+            compressed => compressed,
+        }
+    }
+
+    fn text(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        match self {
+            ResponseContent::Plain(text) => Ok(text.clone()),
+            ResponseContent::Compressed(bytes) => Ok(String::from_utf8(zstd::decode_all(bytes.as_slice())?)?),
+            ResponseContent::Artifact { store, key } => store.read(key),
+        }
+    }
 }
 
 #[pyclass]
@@ -52,6 +371,141 @@ pub struct RequestMetrics {
     pub response_bytes: usize,
     #[pyo3(get)]
     pub provider_name: String,
+    // One entry per returned choice, None where the choice carried no text (e.g. only tool
+    // calls); the first is exposed as `content`
+    pub choices: Vec<Option<ResponseContent>>,
+    // Per choice, in the same order: finish reason, mean token logprob (when logprobs
+    // were returned) and requested tool calls
+    pub choice_finish_reasons: Vec<Option<String>>,
+    #[pyo3(get)]
+    pub choice_logprobs: Vec<Option<f64>>,
+    pub choice_tool_calls: Vec<Option<serde_json::Value>>,
+    // Provider-side index of the choice a choice_policy moved to the front
+    #[pyo3(get)]
+    pub selected_choice: Option<usize>,
+    #[pyo3(get)]
+    pub schema_valid: Option<bool>,
+    #[pyo3(get)]
+    pub schema_error: Option<String>,
+    #[pyo3(get)]
+    pub system_fingerprint: Option<String>,
+    #[pyo3(get)]
+    pub model: Option<String>,
+    #[pyo3(get)]
+    pub request_id: Option<String>,
+    // Provider's own ID for the response (x-request-id / request-id / cf-ray header)
+    #[pyo3(get)]
+    pub provider_request_id: Option<String>,
+    // "ok"; "error", "timeout" or "rate_limited" when the provider call failed;
+    // "cancelled" for requests aborted through a BatchHandle or an interrupted run; or
+    // "skipped" for requests the caller excluded (e.g. duplicate submissions)
+    pub status: Status,
+    // What went wrong for a failed request, and what kind of failure it was: "timeout",
+    // "rate_limit", "server_error", "client_error", "connection", "parse" or "other"
+    #[pyo3(get)]
+    pub error: Option<String>,
+    #[pyo3(get)]
+    pub error_category: Option<String>,
+    // Why generation ended for the selected (first) choice, in OpenAI terms: "stop", "length",
+    // "tool_calls" or "content_filter", or "stop_regex" when the request's stop_regex
+    // cut the stream short
+    #[pyo3(get)]
+    pub finish_reason: Option<String>,
+    // Full provider response body, kept only with capture_raw_response
+    pub raw_response: Option<serde_json::Value>,
+    // Position of the originating request in the submitted list (or iterator), for joining
+    // results back to input rows whatever order they come back in
+    #[pyo3(get)]
+    pub index: usize,
+    // Wall-clock time of the provider call, including simulated latency in test mode
+    #[pyo3(get)]
+    pub latency_ms: f64,
+    // Time from entering the dispatcher's queue until the request was sent, and until its
+    // result was in. total_ms also covers think time, rate-limit waits and failed-over
+    // attempts, which latency_ms leaves out; both are 0 for requests that were never sent.
+    #[pyo3(get)]
+    pub queue_ms: f64,
+    #[pyo3(get)]
+    pub total_ms: f64,
+    // Streamed requests: time from sending to the first text, the mean, median and p95
+    // gaps between text chunks, and output tokens per second after the first. None for
+    // requests that weren't streamed or produced no text.
+    #[pyo3(get)]
+    pub ttft_ms: Option<f64>,
+    #[pyo3(get)]
+    pub itl_mean_ms: Option<f64>,
+    #[pyo3(get)]
+    pub itl_p50_ms: Option<f64>,
+    #[pyo3(get)]
+    pub itl_p95_ms: Option<f64>,
+    #[pyo3(get)]
+    pub output_tokens_per_second: Option<f64>,
+    // Anthropic prompt caching: tokens written to / served from the cache. Both are
+    // already included in prompt_tokens.
+    #[pyo3(get)]
+    pub cache_creation_input_tokens: Option<usize>,
+    #[pyo3(get)]
+    pub cache_read_input_tokens: Option<usize>,
+    // Usage breakdown from prompt_tokens_details / completion_tokens_details: prompt
+    // tokens served from the provider's cache (also set from Anthropic cache reads),
+    // hidden reasoning tokens and audio tokens in and out. All are already included in
+    // prompt_tokens / completion_tokens; None when the provider doesn't report them.
+    #[pyo3(get)]
+    pub cached_tokens: Option<usize>,
+    #[pyo3(get)]
+    pub reasoning_tokens: Option<usize>,
+    #[pyo3(get)]
+    pub audio_tokens: Option<usize>,
+    // Detected prompt language (ISO 639-1 or "unknown") when language detection is on
+    #[pyo3(get)]
+    pub language: Option<String>,
+    // Token counts were tokenized locally (see count_tokens) because the provider reported
+    // no usage, or cut short by stop_regex
+    #[pyo3(get)]
+    pub usage_estimated: bool,
+    pub sanitization: Option<SanitizeReport>,
+    // Tool calls requested by the selected (first) choice, in OpenAI's shape
+    pub tool_calls: Option<serde_json::Value>,
+    // With registered tools: each executed call as {"id", "name", "arguments", "result"},
+    // and how many model round trips were spent on tool use. Token and byte counts then
+    // cover the whole exchange.
+    pub tool_trace: Vec<serde_json::Value>,
+    #[pyo3(get)]
+    pub tool_rounds: usize,
+    // Image generation: returned image URLs, and the decoded size of images returned as
+    // b64_json (whose base64 data are the choices)
+    #[pyo3(get)]
+    pub image_urls: Vec<String>,
+    #[pyo3(get)]
+    pub image_bytes: usize,
+    // Times the request was retried after a 429/503 response
+    #[pyo3(get)]
+    pub retries: usize,
+    // Providers that failed the request before provider_name answered it
+    #[pyo3(get)]
+    pub failovers: Vec<String>,
+    // Requests in flight when this one was sent, itself included
+    #[pyo3(get)]
+    pub concurrency: usize,
+    // With request deduplication, the index of the identical request whose response this
+    // is a copy of; nothing was sent for this one, so its tokens and bytes are zero
+    #[pyo3(get)]
+    pub duplicate_of: Option<usize>,
+    // With hedged requests, the provider a copy was also sent to once this request had
+    // waited past the p95 latency, and whether that copy's response is the one kept
+    // (provider_name is then that provider)
+    #[pyo3(get)]
+    pub hedged_to: Option<String>,
+    #[pyo3(get)]
+    pub hedge_won: bool,
+    // With provider racing, every provider the request was sent to at once; provider_name
+    // is the one whose response came back first
+    #[pyo3(get)]
+    pub raced: Vec<String>,
+    // Priced by the run's pricing (the built-in table plus the caller's entries), cached
+    // and reasoning tokens at their own rates; None for models without a price
+    #[pyo3(get)]
+    pub cost_usd: Option<f64>,
 }
 
 impl RequestMetrics {
@@ -69,24 +523,244 @@ impl RequestMetrics {
             request_bytes,
             response_bytes,
             provider_name,
+            choices: Vec::new(),
+            choice_finish_reasons: Vec::new(),
+            choice_logprobs: Vec::new(),
+            choice_tool_calls: Vec::new(),
+            selected_choice: None,
+            schema_valid: None,
+            schema_error: None,
+            system_fingerprint: None,
+            model: None,
+            request_id: None,
+            provider_request_id: None,
+            status: Status::Ok,
+            error: None,
+            error_category: None,
+            finish_reason: None,
+            raw_response: None,
+            index: 0,
+            latency_ms: 0.0,
+            queue_ms: 0.0,
+            total_ms: 0.0,
+            ttft_ms: None,
+            itl_mean_ms: None,
+            itl_p50_ms: None,
+            itl_p95_ms: None,
+            output_tokens_per_second: None,
+            cache_creation_input_tokens: None,
+            cache_read_input_tokens: None,
+            cached_tokens: None,
+            reasoning_tokens: None,
+            audio_tokens: None,
+            language: None,
+            usage_estimated: false,
+            sanitization: None,
+            tool_calls: None,
+            tool_trace: Vec::new(),
+            tool_rounds: 0,
+            image_urls: Vec::new(),
+            image_bytes: 0,
+            retries: 0,
+            failovers: Vec::new(),
+            concurrency: 0,
+            duplicate_of: None,
+            hedged_to: None,
+            hedge_won: false,
+            raced: Vec::new(),
+            cost_usd: None,
+        }
+    }
+
+    // Placeholder result for a request that never completed, e.g. "cancelled" or "skipped"
+    pub fn unsent(request: &ChatRequest, provider_name: String, status: Status) -> Self {
+        let mut metrics = Self::new(0, 0, 0, 0, provider_name);
+        metrics.status = status;
+        metrics.index = request.index;
+        metrics.request_id = request.request_id.clone();
+        metrics.language = request.language.map(str::to_string);
+        metrics.sanitization = request.sanitization;
+        metrics
+    }
+
+    // Make choice `index` the canonical (first) one, keeping the others in order behind it
+    pub fn select_choice(&mut self, index: usize) {
+        fn promote<T>(items: &mut [T], index: usize) {
+            if index < items.len() {
+                items[..=index].rotate_right(1);
+            }
+        }
+        promote(&mut self.choices, index);
+        promote(&mut self.choice_finish_reasons, index);
+        promote(&mut self.choice_logprobs, index);
+        promote(&mut self.choice_tool_calls, index);
+        if let Some(reason) = self.choice_finish_reasons.first() {
+            self.finish_reason = reason.clone();
+        }
+        if let Some(calls) = self.choice_tool_calls.first() {
+            self.tool_calls = calls.clone();
+        }
+        self.selected_choice = Some(index);
+    }
+
+    // The canonical choice's content, when it has any
+    pub fn first_content(&self) -> Option<&ResponseContent> {
+        self.choices.first().and_then(Option::as_ref)
+    }
+
+    pub fn failed(request: &ChatRequest, provider_name: String, error: String) -> Self {
+        let mut metrics = Self::unsent(request, provider_name, Status::Error);
+        metrics.error = Some(error);
+        metrics.error_category = Some("other".to_string());
+        metrics
+    }
+}
+
+#[pymethods]
+impl RequestMetrics {
+    #[getter]
+    fn status(&self) -> &'static str {
+        self.status.as_str()
+    }
+
+    // Decompressed lazily so compressed results only pay for the content that is read
+    #[getter]
+    fn content(&self) -> PyResult<Option<String>> {
+        self.first_content()
+            .map(|content| content.text())
+            .transpose()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    #[getter]
+    fn choices(&self) -> PyResult<Vec<Option<String>>> {
+        self.choices
+            .iter()
+            .map(|content| content.as_ref().map(ResponseContent::text).transpose())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+
+    // Provider JSON as Python objects; None unless the run set capture_raw_response
+    #[getter]
+    fn raw_response(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.raw_response.as_ref().map(|raw| json_to_py(py, raw)).transpose()
+    }
+
+    #[getter]
+    fn tool_calls(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.tool_calls.as_ref().map(|calls| json_to_py(py, calls)).transpose()
+    }
+
+    #[getter]
+    fn tool_trace(&self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        self.tool_trace.iter().map(|call| json_to_py(py, call)).collect()
+    }
+
+    #[getter]
+    fn raw_response_json(&self) -> Option<String> {
+        self.raw_response.as_ref().map(|raw| raw.to_string())
+    }
+
+    // Counts of what sanitize_inputs changed ("null_bytes", "lone_surrogates",
+    // "normalized"); None when sanitization was off
+    #[getter]
+    fn sanitization(&self) -> Option<HashMap<&'static str, usize>> {
+        self.sanitization.map(SanitizeReport::to_map)
+    }
+
+    #[getter]
+    fn is_compressed(&self) -> bool {
+        matches!(self.first_content(), Some(ResponseContent::Compressed(_)))
+    }
+
+    // Where the first choice was stored when it went to the artifact store; the path is
+    // only set for filesystem stores
+    #[getter]
+    fn artifact_key(&self) -> Option<String> {
+        match self.first_content() {
+            Some(ResponseContent::Artifact { key, .. }) => Some(key.clone()),
+            _ => None,
+        }
+    }
+
+    #[getter]
+    fn artifact_path(&self) -> Option<String> {
+        match self.first_content() {
+            Some(ResponseContent::Artifact { store, key }) => store.local_path(key).map(|path| path.to_string_lossy().into_owned()),
+            _ => None,
+        }
+    }
+
+    #[getter]
+    fn artifact_hash(&self) -> Option<String> {
+        match self.first_content() {
+            Some(ResponseContent::Artifact { key, .. }) => artifact_hash(key),
+            _ => None,
         }
     }
 }
 
 #[async_trait]
 pub trait LLMProvider: Send + Sync {
-    async fn send_chat_request(&self, messages: Vec<Message>) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>>;
+    async fn send_chat_request(&self, request: &ChatRequest) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>>;
     fn name(&self) -> &str;
+    fn model(&self) -> &str;
+    // Provider key used in metrics, e.g. "openai:https://api.openai.com"
+    fn display_name(&self) -> String {
+        self.name().to_string()
+    }
+    // Expected token usage and service time, used by the what-if planner
+    fn estimate(&self, request: &ChatRequest) -> RequestEstimate;
+    fn max_concurrency(&self) -> Option<usize> {
+        None
+    }
+    // The provider's own rate and concurrency limits, on top of the run-wide ones
+    fn limits(&self) -> Option<&RateLimiter> {
+        None
+    }
+    // Share of the round-robin rotation relative to the other providers
+    fn weight(&self) -> f64 {
+        1.0
+    }
+    // Server the requests go to, for the per-host concurrency cap
+    fn host(&self) -> String {
+        self.display_name()
+    }
+    // A cheap request showing whether the provider is reachable and accepts the key
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 struct OpenAIConfig {
     model: String,
-    temperature: f32,
+    temperature: Option<f32>,
     max_tokens: Option<usize>,
     top_p: Option<f32>,
     frequency_penalty: Option<f32>,
     presence_penalty: Option<f32>,
+    response_format: Option<serde_json::Value>,
+    n: Option<usize>,
+    stop: Option<Vec<String>>,
+    seed: Option<i64>,
+    logit_bias: Option<BTreeMap<String, f32>>,
+    extra_body: Option<serde_json::Value>,
+    reasoning_effort: Option<String>,
+    max_completion_tokens: Option<usize>,
+    // Force reasoning-model payload rules on or off; autodetected from the model name otherwise
+    reasoning: Option<bool>,
+    // Send stream_options.include_usage when streaming (default on); servers that reject
+    // the field can turn it off and fall back to estimated usage
+    stream_usage: Option<bool>,
+}
+
+// o-series models reject sampling parameters and use max_completion_tokens instead of max_tokens
+fn is_reasoning_model(model: &str) -> bool {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    let mut chars = model.chars();
+    matches!((chars.next(), chars.next()), (Some('o'), Some(digit)) if digit.is_ascii_digit())
 }
 
 struct OpenAIProvider {
@@ -95,89 +769,347 @@ struct OpenAIProvider {
     base_url: String,
     config: OpenAIConfig,
     test_mode: bool,
+    simulator: Option<Arc<Simulator>>,
+    backend: Backend,
+    headers: HeaderMap,
+    // Render messages into a prompt for a raw completion endpoint instead of /v1/chat/completions
+    chat_template: Option<Arc<ChatTemplate>>,
+    limits: RateLimiter,
+    weight: f64,
+    read_timeout: Option<Duration>,
+}
+
+impl OpenAIProvider {
+    fn requested_model(&self, request: &ChatRequest) -> Option<String> {
+        Some(request.overrides.model.as_ref().unwrap_or(&self.config.model).clone()).filter(|m| !m.is_empty())
+    }
+
+    fn choices(&self, request: &ChatRequest) -> Option<usize> {
+        request.overrides.n.or(self.config.n)
+    }
+
+    fn build_payload(&self, request: &ChatRequest) -> Result<serde_json::Map<String, serde_json::Value>, Box<dyn Error + Send + Sync>> {
+        let overrides = &request.overrides;
+        let mut payload = serde_json::Map::new();
+        let model = overrides.model.as_ref().unwrap_or(&self.config.model);
+        if !model.is_empty() {
+            payload.insert("model".to_string(), serde_json::Value::String(model.clone()));
+        }
+        payload.insert("messages".to_string(), openai_messages(&request.messages)?);
+        let reasoning = self.config.reasoning.unwrap_or_else(|| is_reasoning_model(model));
+        let max_tokens = overrides.max_tokens.or(self.config.max_tokens);
+        let max_completion_tokens = overrides.max_completion_tokens.or(self.config.max_completion_tokens);
+
+        if reasoning {
+            // max_tokens is rejected by reasoning models; carry it over as the completion cap
+            if let Some(max_completion_tokens) = max_completion_tokens.or(max_tokens) {
+                payload.insert("max_completion_tokens".to_string(), serde_json::Value::Number(serde_json::Number::from(max_completion_tokens)));
+            }
+            if let Some(reasoning_effort) = overrides.reasoning_effort.as_ref().or(self.config.reasoning_effort.as_ref()) {
+                payload.insert("reasoning_effort".to_string(), serde_json::Value::String(reasoning_effort.clone()));
+            }
+        } else {
+            if let Some(temperature) = overrides.temperature.or(self.config.temperature) {
+                payload.insert("temperature".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(temperature as f64).unwrap()));
+            }
+            if let Some(max_tokens) = max_tokens {
+                payload.insert("max_tokens".to_string(), serde_json::Value::Number(serde_json::Number::from(max_tokens)));
+            }
+            if let Some(max_completion_tokens) = max_completion_tokens {
+                payload.insert("max_completion_tokens".to_string(), serde_json::Value::Number(serde_json::Number::from(max_completion_tokens)));
+            }
+            if let Some(top_p) = overrides.top_p.or(self.config.top_p) {
+                payload.insert("top_p".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(top_p as f64).unwrap()));
+            }
+            if let Some(frequency_penalty) = overrides.frequency_penalty.or(self.config.frequency_penalty) {
+                payload.insert("frequency_penalty".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(frequency_penalty as f64).unwrap()));
+            }
+            if let Some(presence_penalty) = overrides.presence_penalty.or(self.config.presence_penalty) {
+                payload.insert("presence_penalty".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(presence_penalty as f64).unwrap()));
+            }
+        }
+        if let Some(n) = self.choices(request) {
+            payload.insert("n".to_string(), serde_json::Value::Number(serde_json::Number::from(n)));
+        }
+        if let Some(stop) = request.overrides.stop.as_ref().or(self.config.stop.as_ref()) {
+            payload.insert("stop".to_string(), serde_json::json!(stop));
+        }
+        if let Some(seed) = request.overrides.seed.or(self.config.seed) {
+            payload.insert("seed".to_string(), serde_json::Value::Number(serde_json::Number::from(seed)));
+        }
+        if let Some(logit_bias) = request.overrides.logit_bias.as_ref().or(self.config.logit_bias.as_ref()) {
+            payload.insert("logit_bias".to_string(), serde_json::json!(logit_bias));
+        }
+        if let Some(logprobs) = request.overrides.logprobs {
+            payload.insert("logprobs".to_string(), serde_json::json!(logprobs));
+        }
+        if let Some(user) = &request.overrides.user {
+            payload.insert("user".to_string(), serde_json::json!(user));
+        }
+        if let Some(metadata) = &request.overrides.metadata {
+            payload.insert("metadata".to_string(), metadata.clone());
+        }
+        if request.prefill().is_some() {
+            payload.extend(self.backend.prefill_fields());
+        }
+        if let Some(tools) = &request.overrides.tools {
+            payload.insert("tools".to_string(), tools.clone());
+        }
+        if let Some(tool_choice) = &request.overrides.tool_choice {
+            payload.insert("tool_choice".to_string(), tool_choice.clone());
+        }
+        if let Some(json_schema) = request.json_schema_spec() {
+            payload.insert("response_format".to_string(), serde_json::json!({
+                "type": "json_schema",
+                "json_schema": json_schema,
+            }));
+        } else if let Some(response_format) = request.overrides.response_format.as_ref().or(self.config.response_format.as_ref()) {
+            payload.insert("response_format".to_string(), response_format.clone());
+        }
+        if let Some(constraint) = &request.overrides.constraint {
+            payload.extend(self.backend.constraint_fields(constraint)?);
+        }
+        if request.stream_to.is_some() {
+            payload.insert("stream".to_string(), serde_json::json!(true));
+            // Usage only arrives in streams when explicitly requested
+            if self.config.stream_usage.unwrap_or(true) {
+                payload.insert("stream_options".to_string(), serde_json::json!({"include_usage": true}));
+            }
+        }
+        // Provider-specific knobs are merged last and verbatim; request-level keys win
+        for extra_body in [&self.config.extra_body, &request.overrides.extra_body].into_iter().flatten() {
+            if let serde_json::Value::Object(fields) = extra_body {
+                payload.extend(fields.clone());
+            }
+        }
+        Ok(payload)
+    }
+
+    // The chat completion request with its body serialized once, so it can be counted as it
+    // goes on the wire; audio/image payloads can be megabytes and shouldn't be serialized twice
+    fn chat_request(&self, request: &ChatRequest) -> Result<reqwest::Request, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'));
+        let request_body = serde_json::to_string(&self.build_payload(request)?)?;
+        Ok(self.client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .headers(self.headers.clone())
+            .body(request_body)
+            .build()?)
+    }
 }
 
 #[async_trait]
 impl LLMProvider for OpenAIProvider {
-    async fn send_chat_request(&self, messages: Vec<Message>) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>> {
+    async fn send_chat_request(&self, request: &ChatRequest) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>> {
+        let messages = &request.messages;
         if self.test_mode {
-            let prompt_tokens = calculate_prompt_tokens(&messages);
-            let completion_tokens = simulate_completion_tokens(prompt_tokens);
-            let total_tokens = prompt_tokens + completion_tokens;
-            
-            // Simulate API latency
-            let base_latency = Duration::from_millis(50);
-            let token_processing_time = Duration::from_micros((total_tokens * 100) as u64);
-            sleep(base_latency + token_processing_time).await;
-            
-            // Simulate request/response sizes
-            let request_bytes = serde_json::to_string(&messages).unwrap_or_default().len();
+            let (prompt_tokens, completion_tokens) =
+                simulate_usage(self.simulator.as_deref(), MessageFormat::OpenAI, messages, self.choices(request).unwrap_or(1)).await?;
+
+            // The request is built as it would be sent, so its bytes count on the same basis as
+            // a real run's; only the response size is simulated
+            let request_bytes = wire_bytes(&self.chat_request(request)?);
             let response_bytes = completion_tokens * 4;
-            
-            return Ok(RequestMetrics::new(
+
+            let mut metrics = RequestMetrics::new(
                 prompt_tokens,
                 completion_tokens,
                 request_bytes,
                 response_bytes,
-                format!("{}:{}", self.name(), self.base_url),
-            ));
+                self.display_name(),
+            );
+            metrics.model = self.requested_model(request);
+            metrics.finish_reason = Some("stop".to_string());
+            return Ok(metrics);
         }
-
-        let url = format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'));
-        
-        let mut payload = serde_json::Map::new();
-        if !self.config.model.is_empty() {
-            payload.insert("model".to_string(), serde_json::Value::String(self.config.model.clone()));
+        if let Some(image) = &request.image {
+            return self.send_image_request(request, image).await;
         }
-        payload.insert("messages".to_string(), serde_json::to_value(messages).unwrap());
-        payload.insert("temperature".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(self.config.temperature as f64).unwrap()));
-        
-        if let Some(max_tokens) = self.config.max_tokens {
-            payload.insert("max_tokens".to_string(), serde_json::Value::Number(serde_json::Number::from(max_tokens)));
+        if let Some(template) = &self.chat_template {
+            return self.send_completion_request(request, template).await;
         }
-        if let Some(top_p) = self.config.top_p {
-            payload.insert("top_p".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(top_p as f64).unwrap()));
-        }
-        if let Some(frequency_penalty) = self.config.frequency_penalty {
-            payload.insert("frequency_penalty".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(frequency_penalty as f64).unwrap()));
-        }
-        if let Some(presence_penalty) = self.config.presence_penalty {
-            payload.insert("presence_penalty".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(presence_penalty as f64).unwrap()));
+
+        let http_request = self.chat_request(request)?;
+        let request_bytes = wire_bytes(&http_request);
+        let sent = Instant::now();
+        let response = self.client.execute(http_request).await?;
+        self.limits.observe(response.headers());
+        let provider_request_id = provider_request_id(response.headers());
+        let response = check_status(response, provider_request_id.as_deref()).await?;
+
+        let (response_data, response_bytes, timing) = match &request.stream_to {
+            Some(path) => {
+                let (data, bytes, timing) =
+                    consume_stream(response, path, request.overrides.stop_regex.as_ref(), self.read_timeout, sent).await?;
+                (data, bytes, Some(timing))
+            }
+            None => {
+                let (data, bytes) = read_json(response).await?;
+                (data, bytes, None)
+            }
+        };
+
+        // Every choice keeps its place, including tool-call-only ones whose content is null
+        let returned: &[serde_json::Value] = response_data["choices"].as_array().map_or(&[], Vec::as_slice);
+        let choices: Vec<Option<&str>> = returned.iter().map(|choice| choice["message"]["content"].as_str()).collect();
+        let model = response_data["model"].as_str().map(str::to_string).or_else(|| self.requested_model(request));
+        // Proxies often strip usage and streams from servers that ignore include_usage end
+        // without a usage chunk; missing counts are tokenized locally instead
+        let reported = |key: &str| response_data["usage"][key].as_u64().map(|count| count as usize);
+        let (prompt_tokens, completion_tokens) = (reported("prompt_tokens"), reported("completion_tokens"));
+        let usage_estimated = prompt_tokens.is_none() || completion_tokens.is_none();
+        let tokenizer_model = model.as_deref().unwrap_or_default();
+
+        let mut metrics = RequestMetrics::new(
+            prompt_tokens.unwrap_or_else(|| count_prompt_tokens(tokenizer_model, messages)),
+            completion_tokens.unwrap_or_else(|| choices.iter().flatten().map(|content| count_tokens(tokenizer_model, content)).sum()),
+            request_bytes,
+            response_bytes,
+            self.display_name(),
+        );
+        metrics.usage_estimated = usage_estimated;
+        let details = |group: &str, key: &str| response_data["usage"][group][key].as_u64().map(|count| count as usize);
+        metrics.cached_tokens = details("prompt_tokens_details", "cached_tokens");
+        metrics.reasoning_tokens = details("completion_tokens_details", "reasoning_tokens");
+        metrics.audio_tokens = add_counts(
+            details("prompt_tokens_details", "audio_tokens"),
+            details("completion_tokens_details", "audio_tokens"),
+        );
+        metrics.choices = choices.iter().map(|content| content.map(|c| ResponseContent::Plain(c.to_string()))).collect();
+        metrics.choice_finish_reasons = returned.iter().map(|choice| choice["finish_reason"].as_str().map(str::to_string)).collect();
+        metrics.choice_logprobs = returned.iter().map(mean_logprob).collect();
+        metrics.choice_tool_calls = returned
+            .iter()
+            .map(|choice| Some(choice["message"]["tool_calls"].clone()).filter(|calls| !calls.is_null()))
+            .collect();
+        metrics.finish_reason = metrics.choice_finish_reasons.first().cloned().flatten();
+        metrics.tool_calls = metrics.choice_tool_calls.first().cloned().flatten();
+        metrics.system_fingerprint = response_data["system_fingerprint"].as_str().map(str::to_string);
+        if let Some(timing) = timing {
+            timing.apply(&mut metrics);
         }
+        metrics.model = model;
+        metrics.raw_response = Some(response_data);
+        metrics.provider_request_id = provider_request_id;
+        Ok(metrics)
+    }
+
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
 
-        let request_body = serde_json::to_string(&payload)?;
-        let request_bytes = request_body.len() + format!("Authorization: Bearer {}\n", self.api_key).len();
-        
+    fn display_name(&self) -> String {
+        format!("{}:{}", self.name(), self.base_url)
+    }
+
+    fn estimate(&self, request: &ChatRequest) -> RequestEstimate {
+        let prompt_tokens = calculate_prompt_tokens(MessageFormat::OpenAI, &request.messages);
+        let per_choice = expected_completion_tokens(prompt_tokens);
+        let cap = request.overrides.max_completion_tokens
+            .or(self.config.max_completion_tokens)
+            .or(request.overrides.max_tokens)
+            .or(self.config.max_tokens);
+        let per_choice = cap.map_or(per_choice, |max| per_choice.min(max));
+        let completion_tokens = per_choice * self.choices(request).unwrap_or(1).max(1);
+        let service_ms = estimated_service_ms(self.simulator.as_deref(), prompt_tokens, completion_tokens);
+        RequestEstimate { prompt_tokens, completion_tokens, service_ms }
+    }
+
+    fn max_concurrency(&self) -> Option<usize> {
+        let capacity = self.simulator.as_ref().and_then(|simulator| simulator.max_concurrency());
+        min_limit(capacity, self.limits.max_concurrency())
+    }
+
+    fn limits(&self) -> Option<&RateLimiter> {
+        Some(&self.limits)
+    }
+
+    fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn host(&self) -> String {
+        url_host(&self.base_url)
+    }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.test_mode {
+            return Ok(());
+        }
         let response = self.client
-            .post(&url)
+            .get(format!("{}/v1/models", self.base_url.trim_end_matches('/')))
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&payload)
+            .headers(self.headers.clone())
             .send()
             .await?;
-            
-        let response_bytes = response.content_length().unwrap_or(0) as usize;
-            
-        let response_data: serde_json::Value = response.json().await?;
-            
-        let usage = response_data["usage"].as_object()
-            .ok_or("Missing usage data")?;
-            
-        Ok(RequestMetrics::new(
-            usage["prompt_tokens"].as_u64().unwrap_or(0) as usize,
-            usage["completion_tokens"].as_u64().unwrap_or(0) as usize,
-            request_bytes,
-            response_bytes,
-            format!("{}:{}", self.name(), self.base_url),
-        ))
+        check_status(response, None).await?;
+        Ok(())
     }
+}
 
-    fn name(&self) -> &str {
-        "openai"
+// The tighter of two optional limits
+fn min_limit(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        _ => a.or(b),
     }
 }
 
-fn calculate_prompt_tokens(messages: &[Message]) -> usize {
-    messages.iter().map(|m| m.content.len() / 4).sum()
+// Provider-assigned ID of a response, quoted when escalating failed or slow requests
+fn provider_request_id(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    ["x-request-id", "request-id", "cf-ray"]
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok().map(str::to_string))
+}
+
+// Turn a non-2xx response into an error carrying the status, body and provider request ID
+async fn check_status(
+    response: reqwest::Response,
+    request_id: Option<&str>,
+) -> Result<reqwest::Response, Box<dyn Error + Send + Sync>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = retry_delay(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    let mut message = format!("HTTP {}: {}", status, body.trim());
+    if let Some(request_id) = request_id {
+        message.push_str(&format!(" (request id: {})", request_id));
+    }
+    if is_rate_limited(status) {
+        return Err(Box::new(RateLimited { status, retry_after, message }));
+    }
+    Err(Box::new(HttpError { status, message }))
+}
+
+// Mean of a choice's token logprobs, when they were requested and returned
+fn mean_logprob(choice: &serde_json::Value) -> Option<f64> {
+    let logprobs: Vec<f64> = choice["logprobs"]["content"].as_array()?.iter().filter_map(|token| token["logprob"].as_f64()).collect();
+    (!logprobs.is_empty()).then(|| logprobs.iter().sum::<f64>() / logprobs.len() as f64)
+}
+
+// Sum of two optional token counts, None only when both are missing
+fn add_counts(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+    }
+}
+
+// Rough prompt size: 4 characters per text token plus the provider's cost for images
+fn calculate_prompt_tokens(format: MessageFormat, messages: &[Message]) -> usize {
+    messages.iter().map(|m| m.text().len() / 4).sum::<usize>() + image_tokens(format, messages)
+}
+
+fn expected_completion_tokens(prompt_tokens: usize) -> usize {
+    ((prompt_tokens as f64 * 1.5) as usize).max(50)
 }
 
 fn simulate_completion_tokens(prompt_tokens: usize) -> usize {
@@ -187,154 +1119,1216 @@ fn simulate_completion_tokens(prompt_tokens: usize) -> usize {
     ((base * (1.0 + variation)) as usize).max(50)
 }
 
+// Test-mode stand-in for a provider call: waits out the simulated latency and returns
+// (prompt_tokens, completion_tokens), with each of the n choices generated separately
+async fn simulate_usage(
+    simulator: Option<&Simulator>,
+    format: MessageFormat,
+    messages: &[Message],
+    choices: usize,
+) -> Result<(usize, usize), Box<dyn Error + Send + Sync>> {
+    let prompt_tokens = calculate_prompt_tokens(format, messages);
+    let completion_tokens = (0..choices.max(1))
+        .map(|_| simulate_completion_tokens(prompt_tokens))
+        .sum::<usize>();
+
+    // Simulate API latency, either through the capacity model or a fixed formula
+    if let Some(simulator) = simulator {
+        simulator.serve(completion_tokens).await?;
+    } else {
+        let base_latency = Duration::from_millis(50);
+        let token_processing_time = Duration::from_micros(((prompt_tokens + completion_tokens) * 100) as u64);
+        sleep(base_latency + token_processing_time).await;
+    }
+    Ok((prompt_tokens, completion_tokens))
+}
+
+fn estimated_service_ms(simulator: Option<&Simulator>, prompt_tokens: usize, completion_tokens: usize) -> f64 {
+    match simulator {
+        Some(simulator) => simulator.mean_service_ms(completion_tokens),
+        None => 50.0 + (prompt_tokens + completion_tokens) as f64 * 0.1,
+    }
+}
+
+// Running totals reported to the progress callback; u64 so multi-billion token runs don't wrap
+#[derive(Default)]
+struct RunTotals {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    request_bytes: u64,
+    response_bytes: u64,
+}
+
+impl RunTotals {
+    fn add(&mut self, metrics: &RequestMetrics) {
+        self.prompt_tokens += metrics.prompt_tokens as u64;
+        self.completion_tokens += metrics.completion_tokens as u64;
+        self.request_bytes += metrics.request_bytes as u64;
+        self.response_bytes += metrics.response_bytes as u64;
+    }
+}
+
+// Per-request handling shared by a run: the tool loop and the post-processing applied to
+// each result before it is handed back
+#[derive(Clone)]
+struct ResultOptions {
+    validate_schema: bool,
+    compress_content: bool,
+    capture_raw_response: bool,
+    artifacts: Option<Arc<ArtifactStore>>,
+    tools: Option<Arc<ToolRunner>>,
+    choice_policy: Option<Arc<ChoicePolicy>>,
+    // Retries of a 429/503 response before the request fails, and whether the provider
+    // stops starting requests while it waits
+    rate_limit_retries: usize,
+    pause_on_rate_limit: bool,
+    // Shared by every request of the run
+    retry_budget: Option<Arc<RetryBudget>>,
+    // Set by the dispatcher for each request it sends, and raised once the request is past
+    // its limits; until then a provider drained mid-run can hand it back
+    sent: Option<Arc<AtomicBool>>,
+}
+
 struct BatchProcessor {
-    runtime: Runtime,
+    // Shared with every other run in the process
+    runtime: Arc<Runtime>,
     thread_count: usize,
-    rate_limiter: Arc<RwLock<()>>,
+    rate_limiter: Arc<RateLimiter>,
 }
 
 impl BatchProcessor {
-    fn new(tokens_per_minute: Option<usize>) -> Self {
-        let thread_count = num_cpus::get();
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .worker_threads(thread_count)
-            .enable_all()
-            .build()
-            .unwrap();
-        
+    fn new(tokens_per_minute: Option<usize>, rpm: Option<usize>) -> Self {
         Self {
-            runtime,
-            thread_count,
-            rate_limiter: Arc::new(RwLock::new(())),
+            runtime: runtime::runtime(),
+            thread_count: runtime::thread_count(),
+            rate_limiter: Arc::new(RateLimiter::new(tokens_per_minute, rpm, None)),
         }
     }
 
     async fn process_request(
         provider: Arc<dyn LLMProvider>,
-        messages: Vec<Message>,
-        rate_limiter: Arc<RwLock<()>>,
+        mut request: ChatRequest,
+        rate_limiter: Arc<RateLimiter>,
+        options: ResultOptions,
     ) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>> {
-        let _lock = rate_limiter.read().await;
-        provider.send_chat_request(messages).await
+        if options.choice_policy.as_ref().is_some_and(|policy| policy.needs_logprobs()) {
+            request.overrides.logprobs.get_or_insert(true);
+        }
+        let limits = provider.limits();
+        let mut own = match limits {
+            Some(limits) => Some(limits.acquire(provider.as_ref(), &request).await),
+            None => None,
+        };
+        let mut shared = rate_limiter.acquire(provider.as_ref(), &request).await;
+        if let Some(sent) = &options.sent {
+            sent.store(true, Ordering::SeqCst);
+        }
+        if let Some(budget) = &options.retry_budget {
+            budget.request();
+        }
+        let started = Instant::now();
+        let mut retries = 0;
+        let mut metrics = loop {
+            let result = match &options.tools {
+                Some(runner) if request.overrides.tools.is_some() => run_tool_loop(&provider, &request, runner).await,
+                _ => provider.send_chat_request(&request).await,
+            };
+            let limited = result.as_ref().err().and_then(|e| e.downcast_ref::<RateLimited>());
+            match limited {
+                Some(limited)
+                    if retries < options.rate_limit_retries
+                        && options.retry_budget.as_ref().is_none_or(|budget| budget.try_retry()) =>
+                {
+                    let delay = limited.retry_after.unwrap_or_else(|| backoff(retries));
+                    if let Some(limits) = limits.filter(|_| options.pause_on_rate_limit) {
+                        limits.pause(delay);
+                    }
+                    retries += 1;
+                    info!(
+                        index = request.index,
+                        provider = %provider.display_name(),
+                        status = limited.status.as_u16(),
+                        retry = retries,
+                        delay_ms = delay.as_millis() as u64,
+                        "rate limited; retrying"
+                    );
+                    sleep(delay).await;
+                    // A retry is another request as far as rpm and tpm go
+                    if let (Some(limits), Some(own)) = (limits, own.as_mut()) {
+                        limits.readmit(own, provider.as_ref(), &request).await;
+                    }
+                    rate_limiter.readmit(&mut shared, provider.as_ref(), &request).await;
+                }
+                Some(_) => {
+                    warn!(index = request.index, provider = %provider.display_name(), retries, "rate limited; out of retries");
+                    break result?;
+                }
+                _ => break result?,
+            }
+        };
+        metrics.retries = retries;
+        rate_limiter.record(&shared, &metrics);
+        if let (Some(limits), Some(own)) = (limits, &own) {
+            limits.record(own, &metrics);
+        }
+        // Free the concurrency slots before post-processing
+        drop((shared, own));
+        metrics.latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        metrics.index = request.index;
+        metrics.request_id = request.request_id.clone();
+        metrics.language = request.language.map(str::to_string);
+        metrics.sanitization = request.sanitization;
+        if let Some(policy) = &options.choice_policy {
+            select_choice(policy, &mut metrics).await?;
+        }
+        if let (true, Some(prefill), Some(ResponseContent::Plain(text))) =
+            (request.overrides.continue_final, request.prefill(), metrics.choices.first_mut().and_then(Option::as_mut))
+        {
+            text.insert_str(0, &prefill);
+        }
+        if !options.capture_raw_response {
+            metrics.raw_response = None;
+        }
+        if options.validate_schema {
+            if let (Some(schema), Some(content)) = (request.json_schema(), metrics.first_content()) {
+                let validation = validate_json_output(schema, &content.text()?);
+                metrics.schema_valid = Some(validation.is_ok());
+                metrics.schema_error = validation.err();
+            }
+        }
+        if let Some(store) = &options.artifacts {
+            for choice in metrics.choices.iter_mut().flatten() {
+                if let ResponseContent::Plain(text) = choice {
+                    if store.accepts(text) {
+                        let key = store.put(std::mem::take(text)).await?;
+                        *choice = ResponseContent::Artifact { store: Arc::clone(store), key };
+                    }
+                }
+            }
+        }
+        if options.compress_content {
+            metrics.choices = metrics.choices.into_iter().map(|choice| choice.map(ResponseContent::compress)).collect();
+        }
+        Ok(metrics)
     }
 }
 
-// Build an optimized HTTP client
-fn build_client() -> Client {
-    ClientBuilder::new()
-        .pool_max_idle_per_host(100)
-        .pool_idle_timeout(Duration::from_secs(30))
-        .tcp_nodelay(true)
-        .tcp_keepalive(Duration::from_secs(30))
-        .http2_keep_alive_interval(Duration::from_secs(20))
-        .http2_keep_alive_timeout(Duration::from_secs(10))
-        .http2_adaptive_window(true)
-        .build()
-        .unwrap()
+// "host:port" of a base URL, or the URL itself when it doesn't parse
+fn url_host(base_url: &str) -> String {
+    reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|url| Some(format!("{}:{}", url.host_str()?, url.port_or_known_default()?)))
+        .unwrap_or_else(|| base_url.to_string())
 }
 
+// Per-provider timeouts, in seconds in the options dict. Without any, requests can wait on
+// a hung connection indefinitely.
+#[derive(Debug, Clone, Copy, Default)]
+struct Timeouts {
+    // "connect_timeout": establishing the connection
+    connect: Option<Duration>,
+    // "timeout": the whole request, from sending to the last byte of the response
+    total: Option<Duration>,
+    // "read_timeout": streaming only, the longest gap between chunks
+    read: Option<Duration>,
+}
+
+impl Timeouts {
+    fn extract(options: &PyDict) -> PyResult<Self> {
+        let seconds = |key: &str| -> PyResult<Option<Duration>> {
+            let Some(seconds) = extract_config_value::<Option<f64>>(options, key)?.flatten() else {
+                return Ok(None);
+            };
+            Duration::try_from_secs_f64(seconds)
+                .map(Some)
+                .map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{} must be a non-negative number of seconds", key)))
+        };
+        Ok(Self { connect: seconds("connect_timeout")?, total: seconds("timeout")?, read: seconds("read_timeout")? })
+    }
+
+    // The shared client, or the one shared by providers with the same connection and
+    // request timeouts
+    fn client(&self, shared: &Client) -> PyResult<Client> {
+        if self.connect.is_none() && self.total.is_none() {
+            return Ok(shared.clone());
+        }
+        runtime::client_with_timeouts(self.connect, self.total)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+}
+
+// Provider-level settings that aren't sampling parameters, from the optional fifth tuple element
+struct ProviderOptions {
+    test_mode: bool,
+    simulator: Option<SimulatorConfig>,
+    backend: Backend,
+    // Sent with every request to this provider, replacing defaults of the same name
+    headers: HeaderMap,
+    chat_template: Option<ChatTemplate>,
+    // From "tokens_per_minute", "rpm" and "max_concurrency"
+    limits: RateLimiter,
+    timeouts: Timeouts,
+    // Relative share of requests, from "weight"
+    weight: f64,
+}
+
+impl ProviderOptions {
+    fn extract(options: Option<&PyDict>, default_test_mode: bool) -> PyResult<Self> {
+        let Some(options) = options else {
+            return Ok(Self {
+                test_mode: default_test_mode,
+                simulator: None,
+                backend: Backend::OpenAI,
+                headers: HeaderMap::new(),
+                chat_template: None,
+                limits: RateLimiter::new(None, None, None),
+                timeouts: Timeouts::default(),
+                weight: 1.0,
+            });
+        };
+        let simulator = match options.get_item("simulator")? {
+            Some(value) if !value.is_none() => Some(SimulatorConfig::extract(value.downcast()?)?),
+            _ => None,
+        };
+        Ok(Self {
+            // A capacity model only makes sense for simulated requests
+            test_mode: simulator.is_some() || extract_config_value(options, "test_mode")?.unwrap_or(default_test_mode),
+            simulator,
+            backend: match extract_config_value::<String>(options, "backend")? {
+                Some(backend) => Backend::from_name(&backend)?,
+                None => Backend::OpenAI,
+            },
+            headers: extract_headers(options)?,
+            chat_template: ChatTemplate::extract(options)?,
+            limits: RateLimiter::new(
+                extract_config_value::<Option<usize>>(options, "tokens_per_minute")?.flatten(),
+                extract_config_value::<Option<usize>>(options, "rpm")?.flatten(),
+                extract_config_value::<Option<usize>>(options, "max_concurrency")?.flatten(),
+            ),
+            timeouts: Timeouts::extract(options)?,
+            weight: match extract_config_value::<Option<f64>>(options, "weight")?.flatten() {
+                Some(weight) if !(weight.is_finite() && weight > 0.0) => {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "weight must be a positive number, got {}",
+                        weight
+                    )))
+                }
+                weight => weight.unwrap_or(1.0),
+            },
+        })
+    }
+}
+
+fn extract_headers(options: &PyDict) -> PyResult<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    let invalid = |e: String| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid header: {}", e));
+    let headers_dict = match options.get_item("headers")? {
+        Some(value) if !value.is_none() => extract_config_value::<HashMap<String, String>>(options, "headers")?,
+        _ => None,
+    };
+    for (name, value) in headers_dict.unwrap_or_default() {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(format!("{}: {}", name, e)))?,
+            HeaderValue::from_str(&value).map_err(|e| invalid(format!("{}: {}", name, e)))?,
+        );
+    }
+    Ok(headers)
+}
+
+// Bytes `request` takes on the wire as HTTP/1.1 writes it: the request line, each header as
+// "name: value\r\n" (with the Accept, Host and Content-Length headers the client and hyper
+// add on the way out), the blank line and the body. HTTP/2 compresses headers, so there it
+// is an upper bound.
+fn wire_bytes(request: &reqwest::Request) -> usize {
+    let url = request.url();
+    let target = url.path().len() + url.query().map_or(0, |query| 1 + query.len());
+    let request_line = request.method().as_str().len() + 1 + target + " HTTP/1.1\r\n".len();
+    let header = |name: &str, value_len: usize| name.len() + value_len + 4;
+    let headers = request.headers();
+    let mut bytes = request_line + headers.iter().map(|(name, value)| header(name.as_str(), value.len())).sum::<usize>();
+    let body = request.body().and_then(reqwest::Body::as_bytes).map_or(0, <[u8]>::len);
+    if !headers.contains_key(reqwest::header::ACCEPT) {
+        bytes += header("accept", "*/*".len());
+    }
+    if !headers.contains_key(reqwest::header::HOST) {
+        let host = url.host_str().unwrap_or_default();
+        let port = url.port().map_or(0, |port| 1 + port.to_string().len());
+        bytes += header("host", host.len() + port);
+    }
+    if !headers.contains_key(reqwest::header::CONTENT_LENGTH) && request.body().is_some() {
+        bytes += header("content-length", body.to_string().len());
+    }
+    bytes + 2 + body
+}
+
+// A JSON response body with the number of bytes actually received for it; chunked
+// responses come without a Content-Length
+async fn read_json(response: reqwest::Response) -> Result<(serde_json::Value, usize), Box<dyn Error + Send + Sync>> {
+    let body = response.bytes().await?;
+    Ok((serde_json::from_slice(&body)?, body.len()))
+}
+
+// Parse a (name, api_key, base_url, config[, options]) tuple, reporting which entry and field is malformed
+fn extract_provider(obj: &PyAny, index: usize, client: &Client, test_mode: bool) -> PyResult<Arc<dyn LLMProvider>> {
+    let invalid = |msg: String| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("providers[{}]: {}", index, msg));
+    let tuple = obj.downcast::<PyTuple>().map_err(|_| {
+        invalid(format!(
+            "expected a (name, api_key, base_url, config[, options]) tuple, got {}",
+            obj.get_type().name().unwrap_or("?")
+        ))
+    })?;
+    if !(4..=5).contains(&tuple.len()) {
+        return Err(invalid(format!(
+            "expected a (name, api_key, base_url, config[, options]) tuple, got {} elements",
+            tuple.len()
+        )));
+    }
+    let name: &str = tuple.get_item(0)?.extract().map_err(|_| invalid("name must be a string".to_string()))?;
+    let api_key: &str = tuple.get_item(1)?.extract().map_err(|_| invalid("api_key must be a string".to_string()))?;
+    let base_url: Option<&str> = tuple.get_item(2)?.extract().map_err(|_| invalid("base_url must be a string or None".to_string()))?;
+    let config: &PyDict = tuple.get_item(3)?.downcast().map_err(|_| invalid("config must be a dict".to_string()))?;
+    let options: Option<&PyDict> = match tuple.get_item(4) {
+        Ok(options) if !options.is_none() => Some(options.downcast().map_err(|_| invalid("options must be a dict or None".to_string()))?),
+        _ => None,
+    };
+
+    let with_context = |e: PyErr| invalid(e.value(obj.py()).to_string());
+    let options = ProviderOptions::extract(options, test_mode).map_err(with_context)?;
+    let client = options.timeouts.client(client).map_err(with_context)?;
+    match name {
+        "openai" => Ok(Arc::new(OpenAIProvider {
+            client: client.clone(),
+            api_key: api_key.to_string(),
+            base_url: base_url.unwrap_or("https://api.openai.com").to_string(),
+            config: OpenAIConfig {
+                model: get_required_value(config, "model").map_err(with_context)?,
+                temperature: extract_config_value(config, "temperature").map_err(with_context)?,
+                max_tokens: extract_config_value(config, "max_tokens").map_err(with_context)?,
+                top_p: extract_config_value(config, "top_p").map_err(with_context)?,
+                frequency_penalty: extract_config_value(config, "frequency_penalty").map_err(with_context)?,
+                presence_penalty: extract_config_value(config, "presence_penalty").map_err(with_context)?,
+                response_format: extract_json_value(config, "response_format").map_err(with_context)?,
+                n: extract_config_value(config, "n").map_err(with_context)?,
+                stop: extract_string_list(config, "stop").map_err(with_context)?,
+                seed: extract_config_value(config, "seed").map_err(with_context)?,
+                logit_bias: extract_logit_bias(config, "logit_bias").map_err(with_context)?,
+                extra_body: extract_json_object(config, "extra_body").map_err(with_context)?,
+                reasoning_effort: extract_config_value(config, "reasoning_effort").map_err(with_context)?,
+                max_completion_tokens: extract_config_value(config, "max_completion_tokens").map_err(with_context)?,
+                reasoning: extract_config_value(config, "reasoning").map_err(with_context)?,
+                stream_usage: extract_config_value(config, "stream_usage").map_err(with_context)?,
+            },
+            test_mode: options.test_mode,
+            simulator: options.simulator.map(|config| Arc::new(Simulator::new(config))),
+            backend: options.backend,
+            headers: options.headers,
+            chat_template: options.chat_template.map(Arc::new),
+            limits: options.limits,
+            weight: options.weight,
+            read_timeout: options.timeouts.read,
+        })),
+        "anthropic" if options.chat_template.is_some() => {
+            Err(invalid("chat_template is only supported for OpenAI-compatible providers".to_string()))
+        }
+        "anthropic" => Ok(Arc::new(AnthropicProvider {
+            client: client.clone(),
+            api_key: api_key.to_string(),
+            base_url: base_url.unwrap_or("https://api.anthropic.com").to_string(),
+            config: AnthropicConfig::extract(config).map_err(with_context)?,
+            test_mode: options.test_mode,
+            simulator: options.simulator.map(|config| Arc::new(Simulator::new(config))),
+            headers: options.headers,
+            limits: options.limits,
+            weight: options.weight,
+            read_timeout: options.timeouts.read,
+        })),
+        _ => Err(invalid(format!("unsupported provider '{}'", name))),
+    }
+}
+
+// A request is either a plain list of messages or a dict with "messages" plus overrides.
+// With a run-level `stream_dir`, every request streams into `{stream_dir}/{index}.txt`
+// unless it names its own "stream_to" file.
+fn extract_request(
+    obj: &PyAny,
+    index: usize,
+    stream_dir: Option<&Path>,
+    sanitize: bool,
+    templates: Option<&PromptTemplates>,
+) -> PyResult<ChatRequest> {
+    let default_stream_to = stream_dir.map(|dir| dir.join(format!("{}.txt", index)));
+    // (template_name, variables) is shorthand for {"template": ..., "variables": ...}
+    let obj = match obj.downcast::<PyTuple>() {
+        Ok(pair) if pair.len() == 2 => {
+            let dict = PyDict::new(obj.py());
+            dict.set_item("template", pair.get_item(0)?)?;
+            dict.set_item("variables", pair.get_item(1)?)?;
+            dict.as_ref()
+        }
+        _ => obj,
+    };
+    let (image, template) = match obj.downcast::<PyDict>() {
+        Ok(dict) => (ImageRequest::extract(dict)?, extract_config_value::<String>(dict, "template")?),
+        Err(_) => (None, None),
+    };
+    let priority = match obj.downcast::<PyDict>() {
+        Ok(dict) => match extract_config_value::<String>(dict, "priority")? {
+            Some(name) => Priority::parse(&name).ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown priority '{}'; expected 'high', 'normal' or 'low'",
+                    name
+                ))
+            })?,
+            None => Priority::default(),
+        },
+        Err(_) => Priority::default(),
+    };
+    let (messages, overrides, stream_to, request_id) = match obj.downcast::<PyDict>() {
+        Ok(dict) => (
+            match (&image, &template) {
+                (Some(_), _) => get_required_value::<&PyAny>(dict, "prompt")?,
+                (None, Some(_)) => match dict.get_item("variables")? {
+                    Some(variables) => variables,
+                    None => PyDict::new(obj.py()).as_ref(),
+                },
+                (None, None) => get_required_value::<&PyAny>(dict, "messages")?,
+            },
+            RequestOverrides {
+                model: extract_config_value(dict, "model")?,
+                temperature: extract_config_value(dict, "temperature")?,
+                max_tokens: extract_config_value(dict, "max_tokens")?,
+                top_p: extract_config_value(dict, "top_p")?,
+                frequency_penalty: extract_config_value(dict, "frequency_penalty")?,
+                presence_penalty: extract_config_value(dict, "presence_penalty")?,
+                n: extract_config_value(dict, "n")?,
+                reasoning_effort: extract_config_value(dict, "reasoning_effort")?,
+                max_completion_tokens: extract_config_value(dict, "max_completion_tokens")?,
+                response_format: extract_json_value(dict, "response_format")?,
+                json_schema: extract_json_value(dict, "json_schema")?,
+                stop: extract_string_list(dict, "stop")?,
+                seed: extract_config_value(dict, "seed")?,
+                logit_bias: extract_logit_bias(dict, "logit_bias")?,
+                constraint: match dict.get_item("constraint")? {
+                    Some(constraint) if !constraint.is_none() => Some(Constraint::extract(constraint.downcast()?)?),
+                    _ => None,
+                },
+                extra_body: extract_json_object(dict, "extra_body")?,
+                user: extract_config_value(dict, "user")?,
+                metadata: extract_json_object(dict, "metadata")?,
+                tools: extract_json_value(dict, "tools")?,
+                tool_choice: extract_json_value(dict, "tool_choice")?,
+                continue_final: extract_config_value(dict, "continue")?.unwrap_or(false),
+                stop_regex: extract_config_value::<String>(dict, "stop_regex")?
+                    .map(|pattern| regex::Regex::new(&pattern))
+                    .transpose()
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+                logprobs: extract_config_value(dict, "logprobs")?,
+            },
+            extract_config_value::<PathBuf>(dict, "stream_to")?.or(default_stream_to),
+            extract_config_value(dict, "request_id")?,
+        ),
+        Err(_) => (obj, RequestOverrides::default(), default_stream_to, None),
+    };
+    // Image responses aren't streamed
+    let stream_to = stream_to.filter(|_| image.is_none());
+    if overrides.stop_regex.is_some() && stream_to.is_none() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "stop_regex requires a streamed request (stream_to or stream_dir)",
+        ));
+    }
+    let mut sanitization = sanitize.then(SanitizeReport::default);
+    let mut messages = match (&image, template) {
+        (Some(_), _) => vec![Message::user(read_text(messages, sanitization.as_mut())?)],
+        (None, Some(name)) => templates
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("Template requests need templates"))?
+            .render(&name, &py_to_json(messages)?, sanitization.as_mut())
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?,
+        (None, None) => messages
+            .extract::<Vec<&PyDict>>()?
+            .into_iter()
+            .map(|message| Message::extract(message, sanitization.as_mut()))
+            .collect::<PyResult<Vec<Message>>>()?,
+    };
+    if overrides.continue_final {
+        if let Some(last) = messages.last_mut().filter(|m| m.role == "assistant") {
+            last.trim_end();
+        }
+    }
+    Ok(ChatRequest {
+        index,
+        messages,
+        overrides,
+        stream_to,
+        request_id,
+        language: None,
+        sanitization,
+        image,
+        priority,
+        queued_at: None,
+    })
+}
+
+fn extract_providers(py: Python<'_>, providers: &[PyObject], client: &Client, test_mode: bool) -> PyResult<Vec<Arc<dyn LLMProvider>>> {
+    if providers.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "At least one provider is required",
+        ));
+    }
+    providers
+        .iter()
+        .enumerate()
+        .map(|(index, provider)| extract_provider(provider.as_ref(py), index, client, test_mode))
+        .collect()
+}
+
+// Convert Python messages to Rust messages
+fn extract_requests(
+    py: Python<'_>,
+    requests: Vec<PyObject>,
+    stream_dir: Option<&Path>,
+    sanitize: bool,
+    templates: Option<&PromptTemplates>,
+) -> PyResult<Vec<ChatRequest>> {
+    requests
+        .into_iter()
+        .enumerate()
+        .map(|(index, req)| {
+            extract_request(req.as_ref(py), index, stream_dir, sanitize, templates).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("requests[{}]: {}", index, e.value(py)))
+            })
+        })
+        .collect()
+}
+
+fn reorder(requests: Vec<ChatRequest>, order: &[usize]) -> Vec<ChatRequest> {
+    let mut slots: Vec<Option<ChatRequest>> = requests.into_iter().map(Some).collect();
+    order.iter().map(|&index| slots[index].take().expect("order is a permutation")).collect()
+}
+
+// Requests in flight when max_concurrency isn't given. Requests are I/O-bound, so this is
+// independent of the core count; provider limits and simulator capacity still cap it.
+const DEFAULT_MAX_CONCURRENCY: usize = 64;
+
+// Show how a message list is translated for a provider (system prompt placement, content parts)
 #[pyfunction]
-fn process_requests_multi(
+fn normalize_messages(py: Python<'_>, provider: &str, messages: Vec<&PyDict>) -> PyResult<PyObject> {
+    let format = MessageFormat::from_provider(provider).ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unsupported provider '{}'", provider))
+    })?;
+    let messages = messages.into_iter().map(|m| Message::extract(m, None)).collect::<PyResult<Vec<_>>>()?;
+    let fields = format
+        .normalize(&messages)
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    json_to_py(py, &serde_json::Value::Object(fields))
+}
+
+// Keys of everything in an artifact store, e.g. one written with artifact_dir="sqlite:///runs.db"
+#[pyfunction]
+fn list_artifacts(location: &str) -> PyResult<Vec<String>> {
+    ArtifactStore::open(location, 0)
+        .and_then(|store| store.keys())
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+#[pyfunction]
+fn read_artifact(location: &str, key: &str) -> PyResult<String> {
+    ArtifactStore::open(location, 0)
+        .and_then(|store| store.read(key))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyKeyError, _>(e.to_string()))
+}
+
+// Language code (ISO 639-1 or "unknown") that language routing would assign to `text`
+#[pyfunction]
+fn detect_language(text: &str) -> &'static str {
+    language::detect(text)
+}
+
+// Tokens in `text` under the encoding used to estimate missing usage for `model`
+#[pyfunction]
+#[pyo3(name = "count_tokens", signature = (text, model=""))]
+fn count_text_tokens(text: &str, model: &str) -> usize {
+    count_tokens(model, text)
+}
+
+// Predict time, cost and per-provider load for a run without sending any requests
+#[pyfunction]
+#[pyo3(signature = (providers, requests, pricing=None, concurrency=None, reorder_by_prefix=false, templates=None))]
+fn plan(
     py: Python<'_>,
-    providers: Vec<(&str, &str, Option<&str>, PyObject)>, // (name, api_key, base_url, config)
+    providers: Vec<PyObject>,
     requests: Vec<PyObject>,
+    pricing: Option<&PyDict>,
+    concurrency: Option<usize>,
+    reorder_by_prefix: bool,
+    templates: Option<&PyDict>,
+) -> PyResult<RunPlan> {
+    let client = runtime::client();
+    let providers = extract_providers(py, &providers, &client, true)?;
+    let templates = templates.map(PromptTemplates::extract).transpose()?;
+    let mut requests = extract_requests(py, requests, None, false, templates.as_ref())?;
+    if reorder_by_prefix {
+        let order = prefix_order(&requests, providers.len());
+        requests = reorder(requests, &order);
+    }
+    let pricing = extract_pricing(pricing)?;
+    let concurrency = concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY);
+    Ok(plan_run(&providers, &requests, concurrency, &pricing))
+}
+
+// Keyword arguments read by name; any the caller passed that were never read are unexpected
+struct Kwargs<'py> {
+    dict: Option<&'py PyDict>,
+    read: HashSet<&'static str>,
+}
+
+impl<'py> Kwargs<'py> {
+    fn new(dict: Option<&'py PyDict>) -> Self {
+        Self { dict, read: HashSet::new() }
+    }
+
+    // None when the argument is missing or None
+    fn get<T: FromPyObject<'py>>(&mut self, key: &'static str) -> PyResult<Option<T>> {
+        self.read.insert(key);
+        match self.dict {
+            Some(dict) => Ok(extract_config_value::<Option<T>>(dict, key)?.flatten()),
+            None => Ok(None),
+        }
+    }
+
+    fn finish(self) -> PyResult<()> {
+        for key in self.dict.into_iter().flat_map(PyDict::keys) {
+            let key = key.extract::<&str>()?;
+            if !self.read.contains(key) {
+                return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                    "unexpected keyword argument '{}'",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// The keyword arguments process_requests_multi and start_requests_multi share, read in one
+// place so both take the same options with the same defaults
+struct RunOptions<'py> {
+    validate_schema: bool,
+    compress_content: bool,
+    reorder_by_prefix: bool,
+    stream_dir: Option<PathBuf>,
+    think_time: Option<&'py PyDict>,
+    capture_raw_response: bool,
+    circuit_breaker: Option<usize>,
+    skip: HashSet<usize>,
+    // Language routing rules; Some (even empty) turns detection on
+    languages: Option<HashMap<String, Vec<usize>>>,
+    sanitize_inputs: bool,
+    artifact_dir: Option<String>,
+    artifact_min_bytes: usize,
+    tools: Option<HashMap<String, PyObject>>,
+    max_tool_rounds: usize,
+    choice_policy: Option<&'py PyAny>,
+    templates: Option<&'py PyDict>,
+    rpm: Option<usize>,
+    rate_limit_retries: usize,
+    retry_budget: Option<f64>,
+    pause_on_rate_limit: bool,
+    // Provider indices in fallback order
+    failover: Vec<usize>,
+    // Requests in flight at once; DEFAULT_MAX_CONCURRENCY when unset
+    max_concurrency: Option<usize>,
+    // Requests in flight at once to any one host, counted by the provider each was sent to
+    max_concurrency_per_host: Option<usize>,
+    // Requests a provider may hold back under its own limits before the next is failed fast
+    max_queue_depth: Option<usize>,
+    // Let the dispatcher tune concurrency as results come in, with max_concurrency as ceiling
+    adaptive_concurrency: bool,
+    // Pass over providers whose own rate limits would hold a request back
+    spillover: bool,
+    // Send a copy of a request still unanswered past the p95 latency to a second provider
+    hedge_requests: bool,
+    // Send each request to this many providers at once and keep the first response
+    race_providers: Option<usize>,
+    // Send identical requests once and copy the result to the others
+    dedupe_requests: bool,
+    // "round_robin" or "least_loaded"
+    routing: String,
+    // Probe providers before the run and, with an interval in seconds, again during it
+    health_check: bool,
+    health_check_interval: Option<f64>,
+    // Throwaway requests per provider, or seconds of them, before the run proper
+    warmup_requests: Option<usize>,
+    warmup_seconds: Option<f64>,
+    max_duration_seconds: Option<f64>,
+    max_cost_usd: Option<f64>,
+    max_total_tokens: Option<usize>,
+    pricing: Option<&'py PyDict>,
+    // Seconds requests in flight get to finish once the run is cancelled
+    drain_timeout: Option<f64>,
+    // W3C traceparent of the span the batch's trace continues; with OTLP export configured
+    // through OTEL_* environment variables
+    traceparent: Option<String>,
+    // File to append a JSON line to for each result as it comes in
+    metrics_log: Option<PathBuf>,
+    // Seconds between throughput samples
+    sample_interval: Option<f64>,
+    // StatsD agent ("host" or "host:port") to send counters and timings to for each result,
+    // in DogStatsD's format; metric names start with statsd_prefix and every metric carries
+    // statsd_tags along with the result's provider, model and status
+    statsd: Option<String>,
+    statsd_prefix: String,
+    statsd_tags: BTreeMap<String, String>,
+}
+
+impl<'py> RunOptions<'py> {
+    fn extract(kwargs: Option<&'py PyDict>) -> PyResult<Self> {
+        let mut kwargs = Kwargs::new(kwargs);
+        let detect_language = kwargs.get("detect_language")?.unwrap_or(false);
+        let options = Self {
+            validate_schema: kwargs.get("validate_schema")?.unwrap_or(false),
+            compress_content: kwargs.get("compress_content")?.unwrap_or(false),
+            reorder_by_prefix: kwargs.get("reorder_by_prefix")?.unwrap_or(false),
+            stream_dir: kwargs.get("stream_dir")?,
+            think_time: kwargs.get("think_time")?,
+            capture_raw_response: kwargs.get("capture_raw_response")?.unwrap_or(false),
+            circuit_breaker: kwargs.get("circuit_breaker")?,
+            skip: kwargs.get::<Vec<usize>>("skip")?.into_iter().flatten().collect(),
+            languages: kwargs.get("language_routing")?.or_else(|| detect_language.then(HashMap::new)),
+            sanitize_inputs: kwargs.get("sanitize_inputs")?.unwrap_or(false),
+            artifact_dir: kwargs.get("artifact_dir")?,
+            artifact_min_bytes: kwargs.get("artifact_min_bytes")?.unwrap_or(4096),
+            tools: kwargs.get("tools")?,
+            max_tool_rounds: kwargs.get("max_tool_rounds")?.unwrap_or(8),
+            choice_policy: kwargs.get("choice_policy")?,
+            templates: kwargs.get("templates")?,
+            rpm: kwargs.get("rpm")?,
+            rate_limit_retries: kwargs.get("rate_limit_retries")?.unwrap_or(3),
+            retry_budget: kwargs.get("retry_budget")?,
+            pause_on_rate_limit: kwargs.get("pause_on_rate_limit")?.unwrap_or(false),
+            failover: kwargs.get("failover")?.unwrap_or_default(),
+            max_concurrency: kwargs.get("max_concurrency")?,
+            max_concurrency_per_host: kwargs.get("max_concurrency_per_host")?,
+            max_queue_depth: kwargs.get("max_queue_depth")?,
+            adaptive_concurrency: kwargs.get("adaptive_concurrency")?.unwrap_or(false),
+            spillover: kwargs.get("spillover")?.unwrap_or(false),
+            hedge_requests: kwargs.get("hedge_requests")?.unwrap_or(false),
+            race_providers: kwargs.get("race_providers")?,
+            dedupe_requests: kwargs.get("dedupe_requests")?.unwrap_or(false),
+            routing: kwargs.get("routing")?.unwrap_or_else(|| "round_robin".to_string()),
+            health_check: kwargs.get("health_check")?.unwrap_or(false),
+            health_check_interval: kwargs.get("health_check_interval")?,
+            warmup_requests: kwargs.get("warmup_requests")?,
+            warmup_seconds: kwargs.get("warmup_seconds")?,
+            max_duration_seconds: kwargs.get("max_duration_seconds")?,
+            max_cost_usd: kwargs.get("max_cost_usd")?,
+            max_total_tokens: kwargs.get("max_total_tokens")?,
+            pricing: kwargs.get("pricing")?,
+            drain_timeout: kwargs.get("drain_timeout")?,
+            traceparent: kwargs.get("traceparent")?,
+            metrics_log: kwargs.get("metrics_log")?,
+            sample_interval: kwargs.get("sample_interval")?,
+            statsd: kwargs.get("statsd")?,
+            statsd_prefix: kwargs.get("statsd_prefix")?.unwrap_or_else(|| "axicontraves".to_string()),
+            statsd_tags: kwargs.get("statsd_tags")?.unwrap_or_default(),
+        };
+        kwargs.finish()?;
+        Ok(options)
+    }
+
+    fn result_options(&self) -> PyResult<ResultOptions> {
+        Ok(ResultOptions {
+            validate_schema: self.validate_schema,
+            compress_content: self.compress_content,
+            capture_raw_response: self.capture_raw_response,
+            artifacts: self
+                .artifact_dir
+                .as_ref()
+                .map(|location| ArtifactStore::open(location, self.artifact_min_bytes).map(Arc::new))
+                .transpose()
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+            tools: self.tools.clone().map(|callbacks| Arc::new(ToolRunner::new(callbacks, self.max_tool_rounds))),
+            choice_policy: self.choice_policy.map(ChoicePolicy::extract).transpose()?.map(Arc::new),
+            rate_limit_retries: self.rate_limit_retries,
+            pause_on_rate_limit: self.pause_on_rate_limit,
+            retry_budget: self
+                .retry_budget
+                .map(RetryBudget::new)
+                .transpose()
+                .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?
+                .map(Arc::new),
+            sent: None,
+        })
+    }
+
+    fn cancellation(&self) -> PyResult<Arc<Cancellation>> {
+        Cancellation::new(self.drain_timeout)
+            .map(Arc::new)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+}
+
+// Convert the Python inputs into a ready-to-run dispatcher and the runtime that drives it
+#[allow(clippy::too_many_arguments)]
+fn prepare_run(
+    py: Python<'_>,
+    providers: &[PyObject],
+    // A list, or any other iterable to pull from lazily
+    requests: &PyAny,
+    test_mode: bool,
+    tokens_per_minute: Option<usize>,
+    run: RunOptions<'_>,
+    options: ResultOptions,
+    cancellation: Arc<Cancellation>,
+) -> PyResult<(BatchProcessor, Dispatcher)> {
+    logging::sync_level(py)?;
+    let think_time = run.think_time.map(ServiceTime::extract).transpose()?;
+    let client = runtime::client();
+    let processor = BatchProcessor::new(tokens_per_minute, run.rpm);
+    let stream_dir = run.stream_dir.as_deref();
+
+    let providers = extract_providers(py, providers, &client, test_mode)?;
+    let templates = run.templates.map(PromptTemplates::extract).transpose()?;
+    let routes = run
+        .languages
+        .map(|rules| LanguageRoutes::new(rules, providers.len()))
+        .transpose()
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let (mut requests, source) = match requests.extract::<Vec<PyObject>>() {
+        Ok(requests) => (extract_requests(py, requests, stream_dir, run.sanitize_inputs, templates.as_ref())?, None),
+        Err(_) if run.reorder_by_prefix => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("reorder_by_prefix needs the requests as a list"));
+        }
+        Err(_) => {
+            let iterator = PyIterator::from_object(requests)?;
+            let source = RequestSource::new(iterator, stream_dir.map(Path::to_path_buf), run.sanitize_inputs, templates, routes.is_some());
+            (Vec::new(), Some(source))
+        }
+    };
+    if run.reorder_by_prefix {
+        let order = prefix_order(&requests, providers.len());
+        requests = reorder(requests, &order);
+    }
+    let routing = Routing::parse(&run.routing).ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Unknown routing '{}', expected 'round_robin' or 'least_loaded'",
+            run.routing
+        ))
+    })?;
+    let health_checks = match run.health_check_interval {
+        Some(seconds) => match Duration::try_from_secs_f64(seconds) {
+            Ok(interval) if !interval.is_zero() => Some(HealthChecks::new(Some(interval))),
+            _ => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                    "health_check_interval must be a positive number of seconds",
+                ))
+            }
+        },
+        None => run.health_check.then(|| HealthChecks::new(None)),
+    };
+    let sample_interval = match run.sample_interval.map(Duration::try_from_secs_f64) {
+        None => None,
+        Some(Ok(interval)) if !interval.is_zero() => Some(interval),
+        Some(_) => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "sample_interval must be a positive number of seconds",
+            ))
+        }
+    };
+    let warmup = Warmup::new(run.warmup_requests, run.warmup_seconds).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let budget = RunBudget::new(run.max_duration_seconds, run.max_cost_usd, run.max_total_tokens, extract_pricing(run.pricing)?)
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    if run.max_concurrency == Some(0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("max_concurrency must be at least 1"));
+    }
+    if run.race_providers.is_some_and(|race| race < 2) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("race_providers must be at least 2"));
+    }
+    if run.hedge_requests && run.race_providers.is_some() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("hedge_requests and race_providers can't be combined"));
+    }
+    if run.max_concurrency_per_host == Some(0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("max_concurrency_per_host must be at least 1"));
+    }
+    if let Some(&index) = run.failover.iter().find(|&&index| index >= providers.len()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "failover refers to provider {} but only {} are configured",
+            index,
+            providers.len()
+        )));
+    }
+    if let Some(model) = budget.unpriced(providers.iter().map(|provider| provider.model())) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "max_cost_usd needs pricing for model '{}'",
+            model
+        )));
+    }
+    if routes.is_some() {
+        for request in requests.iter_mut() {
+            request.language = Some(request_language(request));
+        }
+    }
+    let parent = run
+        .traceparent
+        .as_deref()
+        .map(otel::parse_traceparent)
+        .transpose()
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let tracer = match Tracer::from_env(parent) {
+        Ok(tracer) => tracer,
+        Err(e) => {
+            let message = format!("Traces won't be exported: {}", e);
+            PyErr::warn(py, py.get_type::<pyo3::exceptions::PyRuntimeWarning>(), &message, 1)?;
+            None
+        }
+    };
+    let metrics_log = run
+        .metrics_log
+        .as_deref()
+        .map(MetricsLog::open)
+        .transpose()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Can't open metrics_log: {}", e)))?;
+    let statsd = run
+        .statsd
+        .as_deref()
+        .map(|address| StatsdSink::connect(address, &run.statsd_prefix, &run.statsd_tags))
+        .transpose()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Can't send to statsd: {}", e)))?;
+
+    let dispatcher = Dispatcher::new(
+        providers,
+        requests,
+        source,
+        run.max_concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY),
+        run.max_concurrency_per_host,
+        run.max_queue_depth,
+        run.adaptive_concurrency,
+        run.spillover,
+        run.hedge_requests,
+        run.race_providers,
+        run.dedupe_requests,
+        run.circuit_breaker,
+        think_time,
+        options,
+        processor.rate_limiter.clone(),
+        run.skip,
+        routes,
+        run.failover,
+        routing,
+        health_checks,
+        warmup,
+        cancellation,
+        budget,
+        tracer,
+        metrics_log,
+        sample_interval,
+        statsd,
+    );
+    Ok((processor, dispatcher))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (providers, requests, callback, test_mode, tokens_per_minute, result_callback=None, warmup_callback=None, sample_callback=None, legacy_progress=false, **options))]
+fn process_requests_multi(
+    py: Python<'_>,
+    providers: Vec<PyObject>, // (name, api_key, base_url, config[, options])
+    requests: &PyAny,
     callback: PyObject,
     test_mode: bool,
     tokens_per_minute: Option<usize>,
+    result_callback: Option<PyObject>,
+    // Called once the warm-up is over, right before the first request of the run proper
+    warmup_callback: Option<PyObject>,
+    // Called with each throughput sample shortly after it is taken
+    sample_callback: Option<PyObject>,
+    // Call `callback` with the old positional tuple (completed, total, then the batch's
+    // prompt tokens, completion tokens, request bytes and response bytes, the thread
+    // count and the same four as run totals) instead of a RunProgress
+    legacy_progress: bool,
+    // The rest, as RunOptions
+    options: Option<&PyDict>,
 ) -> PyResult<Vec<RequestMetrics>> {
-    let client = build_client();
-    let processor = BatchProcessor::new(tokens_per_minute);
-    let total_requests = requests.len();
-    let mut completed = 0;
+    let run = RunOptions::extract(options)?;
+    let options = run.result_options()?;
+    let retry_budget = options.retry_budget.clone();
+    let cancellation = run.cancellation()?;
     let mut results = Vec::new();
 
-    // Create provider instances
-    let providers: Vec<Arc<dyn LLMProvider>> = providers
-        .into_iter()
-        .map(|(name, api_key, base_url, config)| {
-            let config = config.extract::<&PyDict>(py)?;
-            match name {
-                "openai" => Ok(Arc::new(OpenAIProvider {
-                    client: client.clone(),
-                    api_key: api_key.to_string(),
-                    base_url: base_url.unwrap_or("https://api.openai.com").to_string(),
-                    config: OpenAIConfig {
-                        model: get_required_value(config, "model")?,
-                        temperature: get_required_value(config, "temperature")?,
-                        max_tokens: extract_config_value(config, "max_tokens")?,
-                        top_p: extract_config_value(config, "top_p")?,
-                        frequency_penalty: extract_config_value(config, "frequency_penalty")?,
-                        presence_penalty: extract_config_value(config, "presence_penalty")?,
-                    },
-                    test_mode,
-                }) as Arc<dyn LLMProvider>),
-                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("Unsupported provider")),
+    let (processor, mut dispatcher) = prepare_run(
+        py,
+        &providers,
+        requests,
+        test_mode,
+        tokens_per_minute,
+        run,
+        options,
+        Arc::clone(&cancellation),
+    )?;
+
+    let samples = dispatcher.samples();
+    let mut forwarded = 0;
+    py.allow_threads(|| processor.runtime.block_on(cancellation.interruptible(dispatcher.warm_up())));
+    if let Some(warmup_callback) = &warmup_callback {
+        warmup_callback.call0(py)?;
+    }
+    let status = dispatcher.provider_status();
+    let mut progress = ProgressTracker::start();
+
+    // Release the GIL while each batch runs so callback worker threads can make progress
+    while let Some(valid_results) =
+        py.allow_threads(|| processor.runtime.block_on(cancellation.interruptible(dispatcher.next_batch())))
+    {
+        progress.record(&valid_results);
+        if legacy_progress {
+            let mut batch = RunTotals::default();
+            for metrics in &valid_results {
+                batch.add(metrics);
             }
-        })
-        .collect::<PyResult<Vec<_>>>()?;
+            let totals = &progress.totals;
+            let args = PyTuple::new(
+                py,
+                [
+                    (results.len() + valid_results.len()) as u64,
+                    dispatcher.submitted() as u64,
+                    batch.prompt_tokens,
+                    batch.completion_tokens,
+                    batch.request_bytes,
+                    batch.response_bytes,
+                    processor.thread_count as u64,
+                    totals.prompt_tokens,
+                    totals.completion_tokens,
+                    totals.request_bytes,
+                    totals.response_bytes,
+                ],
+            );
+            callback.call1(py, args)?;
+        } else {
+            let update = progress.update(dispatcher.submitted(), dispatcher.open_ended(), processor.thread_count, &status);
+            callback.call1(py, (update,))?;
+        }
 
-    // Convert Python messages to Rust messages
-    let requests: Vec<Vec<Message>> = requests
-        .into_iter()
-        .map(|req| {
-            let messages = req.extract::<Vec<&PyDict>>(py)?;
-            messages
-                .into_iter()
-                .map(|msg| {
-                    Ok(Message {
-                        role: get_required_value(msg, "role")?,
-                        content: get_required_value(msg, "content")?,
-                    })
-                })
-                .collect::<PyResult<Vec<Message>>>()
-        })
-        .collect::<PyResult<Vec<Vec<Message>>>>()?;
-
-    let batch_size = std::cmp::min(processor.thread_count, 4);
-    let mut provider_index = 0;
-
-    // Process requests in parallel batches with round-robin provider selection
-    for chunk in requests.chunks(batch_size) {
-        let chunk_futures = chunk.iter().enumerate().map(|(i, messages)| {
-            let provider = Arc::clone(&providers[provider_index]);
-            provider_index = (provider_index + 1) % providers.len();
-            let rate_limiter = processor.rate_limiter.clone();
-            BatchProcessor::process_request(provider, messages.clone(), rate_limiter)
-        });
-        
-        let batch_results = processor.runtime.block_on(join_all(chunk_futures));
-        let valid_results: Vec<_> = batch_results.into_iter().filter_map(Result::ok).collect();
-        
-        completed += valid_results.len();
-        
-        let batch_prompt_tokens: usize = valid_results.iter().map(|m| m.prompt_tokens).sum();
-        let batch_completion_tokens: usize = valid_results.iter().map(|m| m.completion_tokens).sum();
-        let batch_request_bytes: usize = valid_results.iter().map(|m| m.request_bytes).sum();
-        let batch_response_bytes: usize = valid_results.iter().map(|m| m.response_bytes).sum();
-        
-        let args = PyTuple::new(
-            py,
-            &[
-                completed as i32,
-                total_requests as i32,
-                batch_prompt_tokens as i32,
-                batch_completion_tokens as i32,
-                batch_request_bytes as i32,
-                batch_response_bytes as i32,
-                processor.thread_count as i32
-            ],
-        );
-        callback.call1(py, args)?;
+        if let Some(result_callback) = &result_callback {
+            for metrics in &valid_results {
+                result_callback.call1(py, (metrics.clone(),))?;
+            }
+        }
+        forward_samples(py, &samples, &mut forwarded, sample_callback.as_ref())?;
 
         results.extend(valid_results);
     }
+    forward_samples(py, &samples, &mut forwarded, sample_callback.as_ref())?;
+    if let Some(error) = dispatcher.take_source_error() {
+        return Err(error);
+    }
 
+    // Results arrive as requests finish; hand them back in dispatch order
+    let total_requests = dispatcher.submitted();
+    let mut rank = vec![0; total_requests];
+    for (position, &index) in dispatcher.order().iter().enumerate() {
+        rank[index] = position;
+    }
+    results.sort_by_key(|metrics| rank[metrics.index]);
+    if let Some(limit) = dispatcher.stopped_by() {
+        let skipped = results.iter().filter(|metrics| metrics.status == Status::Skipped && metrics.error.is_some()).count();
+        let message = format!("Run stopped at {}; {} of {} requests were not sent", limit, skipped, total_requests);
+        PyErr::warn(py, py.get_type::<pyo3::exceptions::PyRuntimeWarning>(), &message, 1)?;
+    }
+    if let Some(denied) = retry_budget.map(|budget| budget.denied()).filter(|&denied| denied > 0) {
+        let message = format!("Retry budget spent; {} rate-limited requests failed without retrying", denied);
+        PyErr::warn(py, py.get_type::<pyo3::exceptions::PyRuntimeWarning>(), &message, 1)?;
+    }
+    if let Some(error) = dispatcher.trace_error() {
+        let message = format!("Exporting traces failed: {}", error);
+        PyErr::warn(py, py.get_type::<pyo3::exceptions::PyRuntimeWarning>(), &message, 1)?;
+    }
+    if let Some(error) = dispatcher.metrics_log_error() {
+        let message = format!("Writing the metrics log failed partway: {}", error);
+        PyErr::warn(py, py.get_type::<pyo3::exceptions::PyRuntimeWarning>(), &message, 1)?;
+    }
+    for (provider, error) in dispatcher.evictions() {
+        let message = format!("Provider {} failed its health check and was taken out of the rotation: {}", provider, error);
+        PyErr::warn(py, py.get_type::<pyo3::exceptions::PyRuntimeWarning>(), &message, 1)?;
+    }
+    if cancellation.all_cancelled() {
+        let cancelled = results.iter().filter(|metrics| metrics.status == Status::Cancelled).count();
+        let message = format!("Run interrupted; {} of {} requests were cancelled", cancelled, total_requests);
+        PyErr::warn(py, py.get_type::<pyo3::exceptions::PyRuntimeWarning>(), &message, 1)?;
+    }
+    integrity::verify(&results, total_requests).warn_if_incomplete(py)?;
     Ok(results)
 }
 
+// Pass the samples taken since the last call on to `callback`
+fn forward_samples(
+    py: Python<'_>,
+    samples: &SharedSamples,
+    forwarded: &mut usize,
+    callback: Option<&PyObject>,
+) -> PyResult<()> {
+    let new: Vec<ThroughputSample> = samples.lock().unwrap()[*forwarded..].to_vec();
+    *forwarded += new.len();
+    if let Some(callback) = callback {
+        for sample in new {
+            callback.call1(py, (sample,))?;
+        }
+    }
+    Ok(())
+}
+
+// Start a batch on a background thread and return a handle to poll, cancel or wait on it
+#[pyfunction]
+#[pyo3(signature = (providers, requests, test_mode=false, tokens_per_minute=None, **options))]
+fn start_requests_multi(
+    py: Python<'_>,
+    providers: Vec<PyObject>,
+    requests: &PyAny,
+    test_mode: bool,
+    tokens_per_minute: Option<usize>,
+    // The rest, as RunOptions
+    options: Option<&PyDict>,
+) -> PyResult<BatchHandle> {
+    let run = RunOptions::extract(options)?;
+    let options = run.result_options()?;
+    let cancellation = run.cancellation()?;
+    let (processor, dispatcher) = prepare_run(
+        py,
+        &providers,
+        requests,
+        test_mode,
+        tokens_per_minute,
+        run,
+        options,
+        Arc::clone(&cancellation),
+    )?;
+    Ok(BatchHandle::spawn(processor, dispatcher, cancellation, test_mode))
+}
+
 #[pymodule]
-fn axicontraves(_py: Python, m: &PyModule) -> PyResult<()> {
+fn axicontraves(py: Python, m: &PyModule) -> PyResult<()> {
+    logging::install(py)?;
     m.add_class::<RequestMetrics>()?;
+    m.add_class::<RunPlan>()?;
+    m.add_class::<ProviderPlan>()?;
+    m.add_class::<BatchHandle>()?;
+    m.add_class::<IntegrityReport>()?;
+    m.add_class::<ProviderStats>()?;
+    m.add_class::<RunSummary>()?;
+    m.add_class::<ProviderSummary>()?;
+    m.add_class::<ThroughputSample>()?;
+    m.add_class::<LatencyHistogram>()?;
+    m.add_class::<RunProgress>()?;
+    m.add_class::<ProviderProgress>()?;
+    m.add_class::<RunStats>()?;
     m.add_function(wrap_pyfunction!(process_requests_multi, m)?)?;
+    m.add_function(wrap_pyfunction!(start_requests_multi, m)?)?;
+    m.add_function(wrap_pyfunction!(plan, m)?)?;
+    m.add_function(wrap_pyfunction!(normalize_messages, m)?)?;
+    m.add_function(wrap_pyfunction!(detect_language, m)?)?;
+    m.add_function(wrap_pyfunction!(count_text_tokens, m)?)?;
+    m.add_function(wrap_pyfunction!(list_artifacts, m)?)?;
+    m.add_function(wrap_pyfunction!(read_artifact, m)?)?;
+    m.add_function(wrap_pyfunction!(integrity::verify_results, m)?)?;
+    m.add_function(wrap_pyfunction!(summary::summarize_results, m)?)?;
+    m.add_function(wrap_pyfunction!(pricing::builtin_pricing, m)?)?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pyo3::prelude::*;
+
+// Forwards the crate's log records (the `tracing` events in dispatch, retries, rate-limit
+// waits and failures) to Python's `logging`, under loggers named after the module, e.g.
+// "axicontraves.dispatch". Records from other crates are dropped.
+struct PythonLogger;
+
+static LOGGER: PythonLogger = PythonLogger;
+
+impl Log for PythonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && metadata.target().starts_with("axicontraves")
+    }
+
+    fn log(&self, record: &Record) {
+        // Nothing to forward to once the interpreter is gone
+        if !self.enabled(record.metadata()) || unsafe { pyo3::ffi::Py_IsInitialized() } == 0 {
+            return;
+        }
+        Python::with_gil(|py| {
+            let name = record.target().replace("::", ".");
+            let forwarded = py.import("logging").and_then(|logging| {
+                let logger = logging.call_method1("getLogger", (name,))?;
+                logger.call_method1("log", (python_level(record.level()), record.args().to_string()))
+            });
+            // A broken handler shouldn't take the run down with it
+            if let Err(e) = forwarded {
+                e.print(py);
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+fn python_level(level: Level) -> u32 {
+    match level {
+        Level::Error => 40,
+        Level::Warn => 30,
+        Level::Info => 20,
+        Level::Debug => 10,
+        Level::Trace => 5,
+    }
+}
+
+pub fn install(py: Python<'_>) -> PyResult<()> {
+    // Another extension may have claimed the global logger first; ours then stays quiet
+    if log::set_logger(&LOGGER).is_ok() {
+        sync_level(py)?;
+    }
+    Ok(())
+}
+
+// Emit only what the "axicontraves" Python logger would let through, so records it would
+// drop are never formatted or passed across the GIL. Picked up at the start of each run.
+pub fn sync_level(py: Python<'_>) -> PyResult<()> {
+    let level: u32 = py
+        .import("logging")?
+        .call_method1("getLogger", ("axicontraves",))?
+        .call_method0("getEffectiveLevel")?
+        .extract()?;
+    log::set_max_level(match level {
+        0..=5 => LevelFilter::Trace,
+        6..=10 => LevelFilter::Debug,
+        11..=20 => LevelFilter::Info,
+        21..=30 => LevelFilter::Warn,
+        31..=40 => LevelFilter::Error,
+        _ => LevelFilter::Off,
+    });
+    Ok(())
+}
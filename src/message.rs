@@ -0,0 +1,593 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use base64::engine::general_purpose::STANDARD;
+use base64::write::EncoderStringWriter;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+use serde_json::json;
+
+use crate::sanitize::{read_text, SanitizeReport};
+use crate::{extract_config_value, extract_json_object, extract_json_value, get_required_value, invalid_value, py_to_json};
+
+// Message keys with a meaning of their own; any others are passed through verbatim
+const KNOWN_KEYS: [&str; 6] = ["role", "content", "cache_control", "tool_calls", "tool_call_id", "name"];
+
+#[derive(Debug, Clone)]
+pub enum ImageSource {
+    Url(String),
+    Base64 { media_type: String, data: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum FileSource {
+    Base64 { media_type: String, data: String },
+    // Read from disk and encoded while the payload is built, so the document never has
+    // to be loaded into the Python process
+    Path { media_type: String, path: PathBuf },
+    Url(String),
+    // Uploaded beforehand through the provider's Files API
+    FileId(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum ContentPart {
+    Text(String),
+    Image { source: ImageSource, detail: Option<String> },
+    // Base64-encoded audio clip with its container format (e.g. "wav", "mp3")
+    InputAudio { data: String, format: String },
+    // Document input such as a PDF
+    File { source: FileSource, filename: Option<String> },
+}
+
+#[derive(Debug, Clone)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub role: String,
+    pub content: MessageContent,
+    // Anthropic prompt-cache breakpoint, e.g. {"type": "ephemeral"}; placed on the
+    // message's last content block and ignored by providers that cache implicitly
+    pub cache_control: Option<serde_json::Value>,
+    // OpenAI tool use: the calls an assistant turn made, and on "tool" messages the ID of
+    // the call being answered
+    pub tool_calls: Option<serde_json::Value>,
+    pub tool_call_id: Option<String>,
+    // Speaker in multi-party chats. OpenAI takes it as the message's `name`; Anthropic and
+    // Gemini have no such field, so their text is prefixed with "{name}: " instead
+    pub name: Option<String>,
+    // Any other keys, forwarded as-is on OpenAI-format messages
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ContentPart {
+    // Accepts OpenAI-style image_url/input_audio parts, Anthropic-style base64 sources and
+    // flat {"type": "image", "data"/"url": ...} / {"type": "audio", "data", "format"} shorthands
+    fn extract(dict: &PyDict, sanitize: Option<&mut SanitizeReport>) -> PyResult<Self> {
+        let kind: String = get_required_value(dict, "type")?;
+        match kind.as_str() {
+            "text" => Ok(ContentPart::Text(
+                read_text(get_required_value(dict, "text")?, sanitize).map_err(|e| invalid_value(dict.py(), "text", e))?,
+            )),
+            "image_url" => {
+                let image_url = get_required_value::<&PyAny>(dict, "image_url")?;
+                if let Ok(url) = image_url.downcast::<PyString>() {
+                    return Ok(ContentPart::Image { source: ImageSource::Url(url.to_str()?.to_string()), detail: None });
+                }
+                let image_url: &PyDict = image_url.downcast()?;
+                Ok(ContentPart::Image {
+                    source: ImageSource::Url(get_required_value(image_url, "url")?),
+                    detail: extract_config_value(image_url, "detail")?,
+                })
+            }
+            "image" => {
+                let detail = extract_config_value(dict, "detail")?;
+                let source = match dict.get_item("source")? {
+                    Some(source) => {
+                        let source: &PyDict = source.downcast()?;
+                        match extract_config_value::<String>(source, "type")?.as_deref() {
+                            Some("url") => ImageSource::Url(get_required_value(source, "url")?),
+                            _ => ImageSource::Base64 {
+                                media_type: get_required_value(source, "media_type")?,
+                                data: get_required_value(source, "data")?,
+                            },
+                        }
+                    }
+                    None => match extract_config_value::<String>(dict, "url")? {
+                        Some(url) => ImageSource::Url(url),
+                        None => ImageSource::Base64 {
+                            media_type: extract_config_value(dict, "media_type")?.unwrap_or_else(|| "image/png".to_string()),
+                            data: get_required_value(dict, "data")?,
+                        },
+                    },
+                };
+                Ok(ContentPart::Image { source, detail })
+            }
+            "input_audio" => {
+                let audio: &PyDict = get_required_value::<&PyAny>(dict, "input_audio")?.downcast()?;
+                Ok(ContentPart::InputAudio {
+                    data: get_required_value(audio, "data")?,
+                    format: get_required_value(audio, "format")?,
+                })
+            }
+            "audio" => Ok(ContentPart::InputAudio {
+                data: get_required_value(dict, "data")?,
+                format: get_required_value(dict, "format")?,
+            }),
+            // {"type": "file", "file": {...}} as in OpenAI chat, or the flat fields directly
+            "file" | "input_file" => {
+                let fields = match dict.get_item("file")? {
+                    Some(file) if kind == "file" => file.downcast::<PyDict>()?,
+                    _ => dict,
+                };
+                let filename = extract_config_value(fields, "filename")?;
+                let source = if let Some(path) = extract_config_value::<PathBuf>(fields, "path")? {
+                    FileSource::Path {
+                        media_type: extract_config_value(fields, "media_type")?.unwrap_or_else(|| guess_media_type(&path).to_string()),
+                        path,
+                    }
+                } else if let Some(file_id) = extract_config_value(fields, "file_id")? {
+                    FileSource::FileId(file_id)
+                } else if let Some(url) = extract_config_value(fields, "file_url")? {
+                    FileSource::Url(url)
+                } else {
+                    let data: String = get_required_value(fields, "file_data")?;
+                    match parse_data_url(&data) {
+                        Some((media_type, data)) => FileSource::Base64 { media_type, data },
+                        None => FileSource::Base64 {
+                            media_type: extract_config_value(fields, "media_type")?.unwrap_or_else(|| "application/pdf".to_string()),
+                            data,
+                        },
+                    }
+                };
+                Ok(ContentPart::File { source, filename })
+            }
+            // Anthropic's document block
+            "document" => {
+                let source: &PyDict = get_required_value::<&PyAny>(dict, "source")?.downcast()?;
+                let source = match extract_config_value::<String>(source, "type")?.as_deref() {
+                    Some("url") => FileSource::Url(get_required_value(source, "url")?),
+                    Some("file") => FileSource::FileId(get_required_value(source, "file_id")?),
+                    _ => FileSource::Base64 {
+                        media_type: get_required_value(source, "media_type")?,
+                        data: get_required_value(source, "data")?,
+                    },
+                };
+                Ok(ContentPart::File { source, filename: extract_config_value(dict, "title")? })
+            }
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                format!("Unsupported content part type: {}", other),
+            )),
+        }
+    }
+
+    fn to_openai(&self) -> Result<serde_json::Value, String> {
+        Ok(match self {
+            ContentPart::Text(text) => json!({"type": "text", "text": text}),
+            ContentPart::Image { source, detail } => {
+                let url = match source {
+                    ImageSource::Url(url) => url.clone(),
+                    ImageSource::Base64 { media_type, data } => format!("data:{};base64,{}", media_type, data),
+                };
+                let mut image_url = json!({"url": url});
+                if let Some(detail) = detail {
+                    image_url["detail"] = json!(detail);
+                }
+                json!({"type": "image_url", "image_url": image_url})
+            }
+            ContentPart::InputAudio { data, format } => {
+                json!({"type": "input_audio", "input_audio": {"data": data, "format": format}})
+            }
+            ContentPart::File { source: FileSource::FileId(file_id), .. } => {
+                json!({"type": "file", "file": {"file_id": file_id}})
+            }
+            ContentPart::File { source: FileSource::Url(_), .. } => {
+                return Err("OpenAI does not accept file URLs; use file_data, file_id or path".to_string());
+            }
+            ContentPart::File { source, filename } => {
+                let (media_type, data) = source.base64()?;
+                json!({"type": "file", "file": {
+                    "filename": source.filename(filename),
+                    "file_data": format!("data:{};base64,{}", media_type, data),
+                }})
+            }
+        })
+    }
+}
+
+impl FileSource {
+    // Media type and base64 data of an inline or on-disk file
+    fn base64(&self) -> Result<(&str, String), String> {
+        match self {
+            FileSource::Base64 { media_type, data } => Ok((media_type, data.clone())),
+            FileSource::Path { media_type, path } => Ok((media_type, encode_file(path)?)),
+            FileSource::Url(_) | FileSource::FileId(_) => unreachable!("not an inline file"),
+        }
+    }
+
+    // OpenAI requires a filename with inline file data
+    fn filename(&self, filename: &Option<String>) -> String {
+        let from_path = match self {
+            FileSource::Path { path, .. } => path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            _ => None,
+        };
+        filename.clone().or(from_path).unwrap_or_else(|| "document".to_string())
+    }
+}
+
+// Base64-encode a file through a fixed-size buffer rather than reading it whole first
+fn encode_file(path: &Path) -> Result<String, String> {
+    let unreadable = |e: std::io::Error| format!("Cannot read {}: {}", path.display(), e);
+    let mut reader = BufReader::new(File::open(path).map_err(unreadable)?);
+    let mut encoder = EncoderStringWriter::new(&STANDARD);
+    std::io::copy(&mut reader, &mut encoder).map_err(unreadable)?;
+    Ok(encoder.into_inner())
+}
+
+// Split "data:application/pdf;base64,...." into media type and data
+fn parse_data_url(url: &str) -> Option<(String, String)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let media_type = header.strip_suffix(";base64")?;
+    Some((media_type.to_string(), data.to_string()))
+}
+
+fn guess_media_type(path: &Path) -> &'static str {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("txt") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("json") => "application/json",
+        Some("docx") => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        _ => "application/octet-stream",
+    }
+}
+
+impl Message {
+    // With a report, text is sanitized (see sanitize.rs) and the changes are tallied in it
+    pub fn extract(dict: &PyDict, sanitize: Option<&mut SanitizeReport>) -> PyResult<Self> {
+        let tool_calls = extract_json_value(dict, "tool_calls")?;
+        let content = match dict.get_item("content")? {
+            // Assistant turns that only call tools carry no content
+            Some(content) if content.is_none() && tool_calls.is_some() => MessageContent::Text(String::new()),
+            None if tool_calls.is_some() => MessageContent::Text(String::new()),
+            _ => Self::extract_content(get_required_value(dict, "content")?, sanitize)?,
+        };
+        let mut extra = serde_json::Map::new();
+        for (key, value) in dict.iter() {
+            let key: String = key.extract()?;
+            if !KNOWN_KEYS.contains(&key.as_str()) {
+                extra.insert(key, py_to_json(value)?);
+            }
+        }
+        Ok(Message {
+            role: get_required_value(dict, "role")?,
+            content,
+            cache_control: extract_json_object(dict, "cache_control")?,
+            tool_calls,
+            tool_call_id: extract_config_value(dict, "tool_call_id")?,
+            name: extract_config_value::<Option<String>>(dict, "name")?.flatten(),
+            extra,
+        })
+    }
+
+    fn extract_content(content: &PyAny, mut sanitize: Option<&mut SanitizeReport>) -> PyResult<MessageContent> {
+        Ok(match content.downcast::<PyString>() {
+            Ok(text) => MessageContent::Text(read_text(text, sanitize)?),
+            Err(_) => MessageContent::Parts(
+                content
+                    .extract::<Vec<&PyDict>>()?
+                    .into_iter()
+                    .map(|part| ContentPart::extract(part, sanitize.as_deref_mut()))
+                    .collect::<PyResult<Vec<_>>>()?,
+            ),
+        })
+    }
+
+    pub fn user(text: String) -> Self {
+        Self::new("user", text)
+    }
+
+    pub fn new(role: &str, text: String) -> Self {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Text(text),
+            cache_control: None,
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    // The assistant turn that requested `tool_calls`, as replayed to the model
+    pub fn tool_request(content: Option<String>, tool_calls: serde_json::Value) -> Self {
+        Message {
+            role: "assistant".to_string(),
+            content: MessageContent::Text(content.unwrap_or_default()),
+            cache_control: None,
+            tool_calls: Some(tool_calls),
+            tool_call_id: None,
+            name: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    pub fn tool_result(tool_call_id: String, result: String) -> Self {
+        Message {
+            role: "tool".to_string(),
+            content: MessageContent::Text(result),
+            cache_control: None,
+            tool_calls: None,
+            tool_call_id: Some(tool_call_id),
+            name: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    // Drop trailing whitespace from the final text, as prefilled assistant turns require
+    pub fn trim_end(&mut self) {
+        let last = match &mut self.content {
+            MessageContent::Text(text) => Some(text),
+            MessageContent::Parts(parts) => parts.iter_mut().rev().find_map(|part| match part {
+                ContentPart::Text(text) => Some(text),
+                _ => None,
+            }),
+        };
+        if let Some(text) = last {
+            text.truncate(text.trim_end().len());
+        }
+    }
+
+    fn is_tool_message(&self) -> bool {
+        self.tool_calls.is_some() || self.role == "tool"
+    }
+
+    // OpenAI's "developer" role is the system prompt under its newer name
+    pub fn is_system(&self) -> bool {
+        matches!(self.role.as_str(), "system" | "developer")
+    }
+
+    // Concatenated text of the message, ignoring non-text parts
+    pub fn text(&self) -> String {
+        match &self.content {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    pub fn to_openai(&self) -> Result<serde_json::Value, String> {
+        let content = match &self.content {
+            MessageContent::Text(text) if text.is_empty() && self.tool_calls.is_some() => serde_json::Value::Null,
+            MessageContent::Text(text) => json!(text),
+            MessageContent::Parts(parts) => parts.iter().map(ContentPart::to_openai).collect::<Result<_, _>>()?,
+        };
+        let mut message = json!({"role": self.role, "content": content});
+        if let Some(tool_calls) = &self.tool_calls {
+            message["tool_calls"] = tool_calls.clone();
+        }
+        if let Some(tool_call_id) = &self.tool_call_id {
+            message["tool_call_id"] = json!(tool_call_id);
+        }
+        if let Some(name) = &self.name {
+            message["name"] = json!(name);
+        }
+        let fields = message.as_object_mut().expect("message object");
+        for (key, value) in &self.extra {
+            fields.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        Ok(message)
+    }
+
+    // Content with the speaker's name prefixed to its first text, for formats without a
+    // `name` field
+    fn attributed_content(&self) -> MessageContent {
+        let Some(name) = &self.name else {
+            return self.content.clone();
+        };
+        match &self.content {
+            MessageContent::Text(text) => MessageContent::Text(format!("{}: {}", name, text)),
+            MessageContent::Parts(parts) => {
+                let mut parts = parts.clone();
+                match parts.iter_mut().find_map(|part| match part {
+                    ContentPart::Text(text) => Some(text),
+                    _ => None,
+                }) {
+                    Some(text) => *text = format!("{}: {}", name, text),
+                    None => parts.insert(0, ContentPart::Text(format!("{}:", name))),
+                }
+                MessageContent::Parts(parts)
+            }
+        }
+    }
+}
+
+pub fn openai_messages(messages: &[Message]) -> Result<serde_json::Value, String> {
+    messages.iter().map(Message::to_openai).collect()
+}
+
+// Wire format a provider expects for the conversation part of the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    OpenAI,
+    Anthropic,
+    Gemini,
+}
+
+impl MessageFormat {
+    pub fn from_provider(name: &str) -> Option<Self> {
+        match name {
+            "openai" => Some(MessageFormat::OpenAI),
+            "anthropic" => Some(MessageFormat::Anthropic),
+            "gemini" => Some(MessageFormat::Gemini),
+            _ => None,
+        }
+    }
+
+    // Translate an OpenAI-style message list into the provider's payload fields. System
+    // (and "developer") messages stay inline for OpenAI, become the top-level `system`
+    // field for Anthropic and `systemInstruction` for Gemini, so one request list works
+    // for all of them.
+    pub fn normalize(&self, messages: &[Message]) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        let mut fields = serde_json::Map::new();
+        if *self != MessageFormat::OpenAI && messages.iter().any(Message::is_tool_message) {
+            return Err("Tool calls are only supported for OpenAI-compatible providers".to_string());
+        }
+        match self {
+            MessageFormat::OpenAI => {
+                fields.insert("messages".to_string(), openai_messages(messages)?);
+            }
+            MessageFormat::Anthropic => {
+                let (system, rest) = split_system(messages);
+                if messages.iter().any(|m| m.is_system() && m.cache_control.is_some()) {
+                    // Cache breakpoints need the block form of `system`
+                    let blocks: Vec<serde_json::Value> = messages
+                        .iter()
+                        .filter(|m| m.is_system())
+                        .flat_map(|m| with_cache_control(vec![json!({"type": "text", "text": m.text()})], m))
+                        .collect();
+                    fields.insert("system".to_string(), json!(blocks));
+                } else if let Some(system) = system {
+                    fields.insert("system".to_string(), json!(system));
+                }
+                let mut turns: Vec<serde_json::Value> = Vec::new();
+                // A trailing assistant turn is a prefill, which Anthropic rejects if it ends
+                // in whitespace
+                let prefill = rest.last().filter(|m| m.role == "assistant").map(|m| {
+                    let mut message = (*m).clone();
+                    message.trim_end();
+                    message
+                });
+                let rest = match &prefill {
+                    Some(prefill) => rest[..rest.len() - 1].iter().copied().chain([prefill]).collect(),
+                    None => rest,
+                };
+                for message in rest {
+                    let role = match message.role.as_str() {
+                        "assistant" => "assistant",
+                        _ => "user",
+                    };
+                    let blocks = with_cache_control(anthropic_blocks(&message.attributed_content())?, message);
+                    // Anthropic requires alternating roles, so merge consecutive turns
+                    match turns.last_mut() {
+                        Some(last) if last["role"] == role => {
+                            last["content"].as_array_mut().expect("content blocks").extend(blocks);
+                        }
+                        _ => turns.push(json!({"role": role, "content": blocks})),
+                    }
+                }
+                fields.insert("messages".to_string(), json!(turns));
+            }
+            MessageFormat::Gemini => {
+                let (system, rest) = split_system(messages);
+                if let Some(system) = system {
+                    fields.insert("systemInstruction".to_string(), json!({"parts": [{"text": system}]}));
+                }
+                let contents = rest
+                    .iter()
+                    .map(|message| {
+                        let role = match message.role.as_str() {
+                            "assistant" | "model" => "model",
+                            _ => "user",
+                        };
+                        Ok(json!({"role": role, "parts": gemini_parts(&message.attributed_content())?}))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                fields.insert("contents".to_string(), json!(contents));
+            }
+        }
+        Ok(fields)
+    }
+}
+
+// Pull every system message out of the conversation, joining them in order
+fn split_system(messages: &[Message]) -> (Option<String>, Vec<&Message>) {
+    let (system, rest): (Vec<&Message>, Vec<&Message>) = messages.iter().partition(|m| m.is_system());
+    let system = if system.is_empty() {
+        None
+    } else {
+        Some(system.iter().map(|m| m.text()).collect::<Vec<_>>().join("\n\n"))
+    };
+    (system, rest)
+}
+
+fn with_cache_control(mut blocks: Vec<serde_json::Value>, message: &Message) -> Vec<serde_json::Value> {
+    if let (Some(cache_control), Some(last)) = (&message.cache_control, blocks.last_mut()) {
+        last["cache_control"] = cache_control.clone();
+    }
+    blocks
+}
+
+fn anthropic_blocks(content: &MessageContent) -> Result<Vec<serde_json::Value>, String> {
+    match content {
+        MessageContent::Text(text) => Ok(vec![json!({"type": "text", "text": text})]),
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text(text) => Ok(json!({"type": "text", "text": text})),
+                ContentPart::Image { source: ImageSource::Url(url), .. } => {
+                    Ok(json!({"type": "image", "source": {"type": "url", "url": url}}))
+                }
+                ContentPart::Image { source: ImageSource::Base64 { media_type, data }, .. } => Ok(json!({
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": media_type, "data": data},
+                })),
+                ContentPart::InputAudio { .. } => Err("Anthropic does not accept audio content".to_string()),
+                ContentPart::File { source, filename } => {
+                    let source = match source {
+                        FileSource::Url(url) => json!({"type": "url", "url": url}),
+                        FileSource::FileId(file_id) => json!({"type": "file", "file_id": file_id}),
+                        _ => {
+                            let (media_type, data) = source.base64()?;
+                            json!({"type": "base64", "media_type": media_type, "data": data})
+                        }
+                    };
+                    let mut block = json!({"type": "document", "source": source});
+                    if let Some(filename) = filename {
+                        block["title"] = json!(filename);
+                    }
+                    Ok(block)
+                }
+            })
+            .collect(),
+    }
+}
+
+fn gemini_parts(content: &MessageContent) -> Result<Vec<serde_json::Value>, String> {
+    match content {
+        MessageContent::Text(text) => Ok(vec![json!({"text": text})]),
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text(text) => Ok(json!({"text": text})),
+                ContentPart::Image { source: ImageSource::Url(url), .. } => Ok(json!({"file_data": {"file_uri": url}})),
+                ContentPart::Image { source: ImageSource::Base64 { media_type, data }, .. } => {
+                    Ok(json!({"inline_data": {"mime_type": media_type, "data": data}}))
+                }
+                ContentPart::InputAudio { data, format } => {
+                    Ok(json!({"inline_data": {"mime_type": format!("audio/{}", format), "data": data}}))
+                }
+                ContentPart::File { source: FileSource::Url(uri) | FileSource::FileId(uri), .. } => {
+                    Ok(json!({"file_data": {"file_uri": uri}}))
+                }
+                ContentPart::File { source, .. } => {
+                    let (media_type, data) = source.base64()?;
+                    Ok(json!({"inline_data": {"mime_type": media_type, "data": data}}))
+                }
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::json;
+
+use crate::RequestMetrics;
+
+// Appends one JSON line per result to a file as the run goes. Each line is written with a
+// single unbuffered write, so what is on disk survives the Python process dying mid-run and
+// lines from concurrent runs appending to the same file don't interleave.
+pub struct MetricsLog {
+    file: Mutex<File>,
+    // The first write that failed; the run carries on without the rest of the log
+    error: Mutex<Option<String>>,
+}
+
+impl MetricsLog {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Mutex::new(file), error: Mutex::new(None) })
+    }
+
+    // A final result; `model` is the one it was sent to
+    pub fn record(&self, metrics: &RequestMetrics, model: Option<&str>) {
+        let mut error = self.error.lock().unwrap();
+        if error.is_some() {
+            return;
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+        let line = json!({
+            "timestamp": timestamp,
+            "index": metrics.index,
+            "request_id": metrics.request_id,
+            "provider": metrics.provider_name,
+            "model": metrics.model.as_deref().or(model),
+            "status": metrics.status.as_str(),
+            "error_category": metrics.error_category,
+            "prompt_tokens": metrics.prompt_tokens,
+            "completion_tokens": metrics.completion_tokens,
+            "total_tokens": metrics.total_tokens,
+            "latency_ms": metrics.latency_ms,
+            "ttft_ms": metrics.ttft_ms,
+            "queue_ms": metrics.queue_ms,
+            "total_ms": metrics.total_ms,
+            "retries": metrics.retries,
+            "cost_usd": metrics.cost_usd,
+        });
+        let mut line = line.to_string();
+        line.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            *error = Some(e.to_string());
+        }
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+}
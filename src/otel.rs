@@ -0,0 +1,354 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+
+use crate::status::Status;
+use crate::{runtime, RequestMetrics};
+
+// Spans are sent in batches of this many while the run goes on; the rest, and the batch
+// span, when it ends
+const EXPORT_BATCH: usize = 512;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// OTLP span kinds and status codes
+const KIND_INTERNAL: u8 = 1;
+const KIND_CLIENT: u8 = 3;
+const STATUS_ERROR: u8 = 2;
+
+// Where and how spans are exported, from the standard OTEL_* environment variables
+struct Exporter {
+    endpoint: String,
+    headers: HeaderMap,
+    timeout: Duration,
+    resource: Vec<(String, String)>,
+}
+
+// Run-wide counts for the batch span
+#[derive(Default)]
+struct Totals {
+    requests: usize,
+    succeeded: usize,
+    failed: usize,
+    cancelled: usize,
+    skipped: usize,
+    prompt_tokens: usize,
+    completion_tokens: usize,
+    cost_usd: Option<f64>,
+}
+
+#[derive(Default)]
+struct Pending {
+    spans: Vec<Value>,
+    totals: Totals,
+    exports: Vec<JoinHandle<Result<(), String>>>,
+}
+
+// Exports one OTLP span per request, as children of a span covering the whole batch, over
+// OTLP/HTTP with JSON encoding. On when OTEL_EXPORTER_OTLP_TRACES_ENDPOINT or
+// OTEL_EXPORTER_OTLP_ENDPOINT is set, unless OTEL_SDK_DISABLED is true or
+// OTEL_TRACES_EXPORTER leaves out "otlp". OTEL_EXPORTER_OTLP_(TRACES_)HEADERS,
+// OTEL_EXPORTER_OTLP_(TRACES_)TIMEOUT, OTEL_SERVICE_NAME and OTEL_RESOURCE_ATTRIBUTES are
+// honoured as well.
+pub struct Tracer {
+    exporter: Exporter,
+    trace_id: String,
+    batch_span_id: String,
+    // The caller's span the batch span hangs off, from its W3C traceparent
+    parent_span_id: Option<String>,
+    started: SystemTime,
+    pending: Mutex<Pending>,
+}
+
+impl Tracer {
+    // None when tracing is off, or the caller's trace isn't sampled. Fails when the
+    // environment asks for something that can't be done.
+    pub fn from_env(parent: Option<Parent>) -> Result<Option<Self>, String> {
+        if parent.as_ref().is_some_and(|parent| !parent.sampled) {
+            return Ok(None);
+        }
+        let Some(exporter) = Exporter::from_env()? else { return Ok(None) };
+        let (trace_id, parent_span_id) = match parent {
+            Some(parent) => (parent.trace_id, Some(parent.span_id)),
+            None => (format!("{:032x}", rand::random::<u128>().max(1)), None),
+        };
+        Ok(Some(Self {
+            exporter,
+            trace_id,
+            batch_span_id: span_id(),
+            parent_span_id,
+            started: SystemTime::now(),
+            pending: Mutex::new(Pending::default()),
+        }))
+    }
+
+    // A finished request, sent to `model`; its span ends now and starts when the request
+    // entered the queue
+    pub fn record(&self, metrics: &RequestMetrics, model: Option<&str>) {
+        let ended = SystemTime::now();
+        let took = Duration::from_secs_f64(metrics.total_ms.max(metrics.latency_ms).max(0.0) / 1000.0);
+        let started = ended.checked_sub(took).unwrap_or(ended);
+        let model = model.or(metrics.model.as_deref());
+        let mut attributes = vec![
+            attribute("gen_ai.operation.name", "chat"),
+            attribute("axicontraves.request.index", metrics.index),
+            attribute("axicontraves.status", metrics.status.as_str()),
+            attribute("gen_ai.usage.input_tokens", metrics.prompt_tokens),
+            attribute("gen_ai.usage.output_tokens", metrics.completion_tokens),
+            attribute("axicontraves.latency_ms", metrics.latency_ms),
+            attribute("axicontraves.queue_ms", metrics.queue_ms),
+            attribute("axicontraves.retries", metrics.retries),
+        ];
+        if !metrics.provider_name.is_empty() {
+            let system = metrics.provider_name.split(':').next().unwrap_or_default();
+            attributes.push(attribute("gen_ai.system", system));
+            attributes.push(attribute("axicontraves.provider", metrics.provider_name.as_str()));
+        }
+        if let Some(model) = model {
+            attributes.push(attribute("gen_ai.request.model", model));
+        }
+        if let Some(model) = &metrics.model {
+            attributes.push(attribute("gen_ai.response.model", model.as_str()));
+        }
+        if let Some(request_id) = &metrics.request_id {
+            attributes.push(attribute("axicontraves.request.id", request_id.as_str()));
+        }
+        if let Some(cost) = metrics.cost_usd {
+            attributes.push(attribute("axicontraves.cost_usd", cost));
+        }
+        let mut span = json!({
+            "traceId": self.trace_id,
+            "spanId": span_id(),
+            "parentSpanId": self.batch_span_id,
+            "name": model.map_or_else(|| "chat".to_string(), |model| format!("chat {}", model)),
+            "kind": KIND_CLIENT,
+            "startTimeUnixNano": unix_nanos(started),
+            "endTimeUnixNano": unix_nanos(ended),
+        });
+        if metrics.status.is_failure() {
+            if let Some(category) = &metrics.error_category {
+                attributes.push(attribute("error.type", category.as_str()));
+            }
+            span["status"] = json!({"code": STATUS_ERROR, "message": metrics.error.clone().unwrap_or_default()});
+        }
+        span["attributes"] = Value::Array(attributes);
+
+        let mut pending = self.pending.lock().unwrap();
+        let totals = &mut pending.totals;
+        totals.requests += 1;
+        match metrics.status {
+            Status::Ok => totals.succeeded += 1,
+            Status::Error | Status::Timeout | Status::RateLimited => totals.failed += 1,
+            Status::Cancelled => totals.cancelled += 1,
+            Status::Skipped => totals.skipped += 1,
+        }
+        totals.prompt_tokens += metrics.prompt_tokens;
+        totals.completion_tokens += metrics.completion_tokens;
+        if let Some(cost) = metrics.cost_usd {
+            totals.cost_usd = Some(totals.cost_usd.unwrap_or(0.0) + cost);
+        }
+        pending.spans.push(span);
+        if pending.spans.len() >= EXPORT_BATCH {
+            let body = self.payload(std::mem::take(&mut pending.spans));
+            pending.exports.push(tokio::spawn(self.exporter.send(body)));
+        }
+    }
+
+    // End the batch span and send whatever hasn't been sent. Returns the first export
+    // failure, if any.
+    pub async fn finish(&self) -> Option<String> {
+        let (body, exports) = {
+            let mut pending = self.pending.lock().unwrap();
+            let totals = &pending.totals;
+            let mut attributes = vec![
+                attribute("axicontraves.requests", totals.requests),
+                attribute("axicontraves.succeeded", totals.succeeded),
+                attribute("axicontraves.failed", totals.failed),
+                attribute("axicontraves.cancelled", totals.cancelled),
+                attribute("axicontraves.skipped", totals.skipped),
+                attribute("gen_ai.usage.input_tokens", totals.prompt_tokens),
+                attribute("gen_ai.usage.output_tokens", totals.completion_tokens),
+            ];
+            if let Some(cost) = totals.cost_usd {
+                attributes.push(attribute("axicontraves.cost_usd", cost));
+            }
+            let mut batch = json!({
+                "traceId": self.trace_id,
+                "spanId": self.batch_span_id,
+                "name": "axicontraves batch",
+                "kind": KIND_INTERNAL,
+                "startTimeUnixNano": unix_nanos(self.started),
+                "endTimeUnixNano": unix_nanos(SystemTime::now()),
+                "attributes": attributes,
+            });
+            if let Some(parent) = &self.parent_span_id {
+                batch["parentSpanId"] = json!(parent);
+            }
+            let mut spans = std::mem::take(&mut pending.spans);
+            spans.push(batch);
+            (self.payload(spans), std::mem::take(&mut pending.exports))
+        };
+        let mut error = self.exporter.send(body).await.err();
+        for export in exports {
+            if let Ok(Err(e)) = export.await {
+                error.get_or_insert(e);
+            }
+        }
+        error
+    }
+
+    // An ExportTraceServiceRequest in OTLP's JSON encoding
+    fn payload(&self, spans: Vec<Value>) -> Value {
+        let resource: Vec<Value> = self.exporter.resource.iter().map(|(key, value)| attribute(key, value.as_str())).collect();
+        json!({
+            "resourceSpans": [{
+                "resource": {"attributes": resource},
+                "scopeSpans": [{
+                    "scope": {"name": "axicontraves", "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+}
+
+impl Exporter {
+    fn from_env() -> Result<Option<Self>, String> {
+        let var = |name: &str| std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        if var("OTEL_SDK_DISABLED").is_some_and(|disabled| disabled.eq_ignore_ascii_case("true")) {
+            return Ok(None);
+        }
+        if var("OTEL_TRACES_EXPORTER").is_some_and(|exporters| !exporters.split(',').any(|exporter| exporter.trim() == "otlp")) {
+            return Ok(None);
+        }
+        let endpoint = match (var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"), var("OTEL_EXPORTER_OTLP_ENDPOINT")) {
+            (Some(endpoint), _) => endpoint,
+            (None, Some(base)) => format!("{}/v1/traces", base.trim_end_matches('/')),
+            (None, None) => return Ok(None),
+        };
+        let protocol = var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL").or_else(|| var("OTEL_EXPORTER_OTLP_PROTOCOL"));
+        if protocol.as_deref() == Some("grpc") {
+            return Err("traces are exported over OTLP/HTTP; OTEL_EXPORTER_OTLP_PROTOCOL=grpc isn't supported".to_string());
+        }
+        let mut headers = HeaderMap::new();
+        for (key, value) in pairs(var("OTEL_EXPORTER_OTLP_HEADERS")).into_iter().chain(pairs(var("OTEL_EXPORTER_OTLP_TRACES_HEADERS"))) {
+            let name = HeaderName::from_bytes(key.as_bytes()).map_err(|_| format!("Invalid OTLP header name '{}'", key))?;
+            let value = HeaderValue::from_str(&value).map_err(|_| format!("Invalid value for OTLP header '{}'", key))?;
+            headers.insert(name, value);
+        }
+        let timeout = match var("OTEL_EXPORTER_OTLP_TRACES_TIMEOUT").or_else(|| var("OTEL_EXPORTER_OTLP_TIMEOUT")) {
+            Some(ms) => Duration::from_millis(ms.parse().map_err(|_| format!("OTLP timeout must be in milliseconds, got '{}'", ms))?),
+            None => DEFAULT_TIMEOUT,
+        };
+        let mut resource: Vec<(String, String)> =
+            pairs(var("OTEL_RESOURCE_ATTRIBUTES")).into_iter().filter(|(key, _)| key != "service.name").collect();
+        let service = var("OTEL_SERVICE_NAME")
+            .or_else(|| pairs(var("OTEL_RESOURCE_ATTRIBUTES")).into_iter().find(|(key, _)| key == "service.name").map(|(_, value)| value))
+            .unwrap_or_else(|| "axicontraves".to_string());
+        resource.insert(0, ("service.name".to_string(), service));
+        Ok(Some(Self { endpoint, headers, timeout, resource }))
+    }
+
+    fn send(&self, body: Value) -> impl std::future::Future<Output = Result<(), String>> + Send + 'static {
+        let request = runtime::client().post(&self.endpoint).headers(self.headers.clone()).timeout(self.timeout).json(&body);
+        let endpoint = self.endpoint.clone();
+        async move {
+            let response = request.send().await.map_err(|e| format!("{}: {}", endpoint, e))?;
+            if !response.status().is_success() {
+                return Err(format!("{}: HTTP {}", endpoint, response.status()));
+            }
+            Ok(())
+        }
+    }
+}
+
+// The caller's span, for the batch span to join its trace
+pub struct Parent {
+    trace_id: String,
+    span_id: String,
+    sampled: bool,
+}
+
+// "00-<32 hex trace id>-<16 hex span id>-<2 hex flags>"
+pub fn parse_traceparent(traceparent: &str) -> Result<Parent, String> {
+    let invalid = || format!("traceparent must look like '00-<trace id>-<span id>-01', got '{}'", traceparent);
+    let parts: Vec<&str> = traceparent.trim().split('-').collect();
+    let [version, trace_id, span_id, flags] = parts[..] else { return Err(invalid()) };
+    let hex = |part: &str, len: usize| {
+        part.len() == len && part.bytes().all(|byte| byte.is_ascii_hexdigit()) && part.bytes().any(|byte| byte != b'0')
+    };
+    if version.len() != 2 || version == "ff" || !hex(trace_id, 32) || !hex(span_id, 16) || flags.len() != 2 {
+        return Err(invalid());
+    }
+    let flags = u8::from_str_radix(flags, 16).map_err(|_| invalid())?;
+    Ok(Parent { trace_id: trace_id.to_lowercase(), span_id: span_id.to_lowercase(), sampled: flags & 1 == 1 })
+}
+
+fn span_id() -> String {
+    format!("{:016x}", rand::random::<u64>().max(1))
+}
+
+// Nanoseconds since the epoch, as a string the way OTLP JSON encodes 64-bit integers
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
+
+// "key1=value1,key2=value2" with percent-encoded values, as in OTEL_RESOURCE_ATTRIBUTES
+fn pairs(list: Option<String>) -> Vec<(String, String)> {
+    list.iter()
+        .flat_map(|list| list.split(','))
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), percent_decode(value.trim())))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+    while position < bytes.len() {
+        let escaped = (bytes[position] == b'%')
+            .then(|| value.get(position + 1..position + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                position += 3;
+            }
+            None => {
+                decoded.push(bytes[position]);
+                position += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+trait AttributeValue {
+    fn otlp(self) -> Value;
+}
+
+impl AttributeValue for &str {
+    fn otlp(self) -> Value {
+        json!({"stringValue": self})
+    }
+}
+
+impl AttributeValue for usize {
+    fn otlp(self) -> Value {
+        json!({"intValue": self.to_string()})
+    }
+}
+
+impl AttributeValue for f64 {
+    fn otlp(self) -> Value {
+        json!({"doubleValue": self})
+    }
+}
+
+fn attribute(key: &str, value: impl AttributeValue) -> Value {
+    json!({"key": key, "value": value.otlp()})
+}
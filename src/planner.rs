@@ -0,0 +1,140 @@
+use std::sync::Arc;
+use pyo3::prelude::*;
+
+use crate::breaker::ProviderHealth;
+use crate::prefix::estimate_prefix_reuse;
+use crate::pricing::Pricing;
+use crate::{ChatRequest, LLMProvider};
+
+// Expected resource use of a single request, derived without sending anything
+#[derive(Debug, Clone, Copy)]
+pub struct RequestEstimate {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub service_ms: f64,
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct ProviderPlan {
+    #[pyo3(get)]
+    pub provider_name: String,
+    #[pyo3(get)]
+    pub requests: usize,
+    #[pyo3(get)]
+    pub prompt_tokens: usize,
+    #[pyo3(get)]
+    pub completion_tokens: usize,
+    #[pyo3(get)]
+    pub concurrency: usize,
+    #[pyo3(get)]
+    pub estimated_seconds: f64,
+    #[pyo3(get)]
+    pub estimated_cost_usd: Option<f64>,
+    // Share of prompt text that repeats the previous request's prefix on this provider
+    #[pyo3(get)]
+    pub prefix_reuse: f64,
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct RunPlan {
+    #[pyo3(get)]
+    pub total_requests: usize,
+    #[pyo3(get)]
+    pub prompt_tokens: usize,
+    #[pyo3(get)]
+    pub completion_tokens: usize,
+    #[pyo3(get)]
+    pub estimated_seconds: f64,
+    #[pyo3(get)]
+    pub estimated_cost_usd: Option<f64>,
+    #[pyo3(get)]
+    pub prefix_reuse: f64,
+    #[pyo3(get)]
+    pub providers: Vec<ProviderPlan>,
+}
+
+#[pymethods]
+impl RunPlan {
+    fn __repr__(&self) -> String {
+        let cost = self.estimated_cost_usd.map_or("None".to_string(), |cost| format!("{:.4}", cost));
+        format!(
+            "RunPlan(total_requests={}, estimated_seconds={:.2}, estimated_cost_usd={})",
+            self.total_requests, self.estimated_seconds, cost
+        )
+    }
+}
+
+// Predict wall-clock time, cost and per-provider load for the same round-robin
+// assignment the dispatcher uses, weights included. Each provider gets its weighted share
+// of the global concurrency, capped by its simulated server capacity, and the run finishes
+// when the slowest provider drains its queue.
+pub fn plan_run(
+    providers: &[Arc<dyn LLMProvider>],
+    requests: &[ChatRequest],
+    concurrency: usize,
+    pricing: &Pricing,
+) -> RunPlan {
+    let mut plans: Vec<ProviderPlan> = providers
+        .iter()
+        .map(|provider| ProviderPlan {
+            provider_name: provider.display_name(),
+            requests: 0,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            concurrency: 0,
+            estimated_seconds: 0.0,
+            estimated_cost_usd: pricing.get(provider.model()).map(|_| 0.0),
+            prefix_reuse: 0.0,
+        })
+        .collect();
+    let mut service_ms = vec![0.0; providers.len()];
+    let weights: Vec<f64> = providers.iter().map(|provider| provider.weight()).collect();
+    let mut rotation = ProviderHealth::new(weights.clone(), None);
+    let slots: Vec<usize> =
+        requests.iter().map(|_| rotation.next_provider().expect("no breakers, so every provider is available")).collect();
+
+    for (request, &slot) in requests.iter().zip(&slots) {
+        let provider = &providers[slot];
+        let estimate = provider.estimate(request);
+        let plan = &mut plans[slot];
+        plan.requests += 1;
+        plan.prompt_tokens += estimate.prompt_tokens;
+        plan.completion_tokens += estimate.completion_tokens;
+        if let (Some(cost), Some(price)) = (plan.estimated_cost_usd.as_mut(), pricing.get(provider.model())) {
+            *cost += price.cost(estimate.prompt_tokens, estimate.completion_tokens);
+        }
+        service_ms[slot] += estimate.service_ms;
+    }
+
+    let reuse = estimate_prefix_reuse(requests, &slots, providers.len());
+    for (plan, reuse) in plans.iter_mut().zip(reuse) {
+        plan.prefix_reuse = reuse;
+    }
+
+    let total_weight: f64 = weights.iter().sum();
+    for (((plan, provider), total_ms), weight) in plans.iter_mut().zip(providers).zip(&service_ms).zip(&weights) {
+        let share = (concurrency as f64 * weight / total_weight).ceil().max(1.0) as usize;
+        plan.concurrency = provider.max_concurrency().map_or(share, |capacity| capacity.min(share)).max(1);
+        plan.estimated_seconds = total_ms / plan.concurrency as f64 / 1000.0;
+    }
+
+    let prompt_tokens: usize = plans.iter().map(|p| p.prompt_tokens).sum();
+    RunPlan {
+        total_requests: requests.len(),
+        prefix_reuse: if prompt_tokens > 0 {
+            plans.iter().map(|p| p.prefix_reuse * p.prompt_tokens as f64).sum::<f64>() / prompt_tokens as f64
+        } else {
+            0.0
+        },
+        prompt_tokens,
+        completion_tokens: plans.iter().map(|p| p.completion_tokens).sum(),
+        estimated_seconds: plans.iter().map(|p| p.estimated_seconds).fold(0.0, f64::max),
+        estimated_cost_usd: plans
+            .iter()
+            .map(|p| p.estimated_cost_usd)
+            .try_fold(0.0, |total, cost| cost.map(|cost| total + cost)),
+        providers: plans,
+    }
+}
@@ -0,0 +1,59 @@
+use crate::ChatRequest;
+
+// Text the server sees as the prompt prefix, in conversation order
+fn prefix_key(request: &ChatRequest) -> String {
+    request
+        .messages
+        .iter()
+        .map(|m| format!("{}\u{1f}{}\u{1e}", m.role, m.text()))
+        .collect()
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).map(|(x, _)| x.len_utf8()).sum()
+}
+
+// Dispatch order that keeps round-robin provider assignment (position % providers)
+// but hands each provider a contiguous run of prefix-sorted requests, so the
+// server's prefix cache sees related prompts back to back
+pub fn prefix_order(requests: &[ChatRequest], providers: usize) -> Vec<usize> {
+    let keys: Vec<String> = requests.iter().map(prefix_key).collect();
+    let mut sorted: Vec<usize> = (0..requests.len()).collect();
+    sorted.sort_by(|&a, &b| keys[a].cmp(&keys[b]));
+
+    // Provider p receives exactly the positions p, p + P, p + 2P, ...
+    let providers = providers.max(1);
+    let mut segments = Vec::with_capacity(providers);
+    let mut start = 0;
+    for p in 0..providers {
+        let count = (requests.len() + providers - 1 - p) / providers;
+        segments.push(sorted[start..start + count].iter());
+        start += count;
+    }
+    (0..requests.len())
+        .map(|position| *segments[position % providers].next().expect("segment sizes match positions"))
+        .collect()
+}
+
+// Fraction of prompt bytes each provider could serve from its prefix cache, assuming it
+// only remembers the previous request it handled. Requests are in dispatch order with
+// `slots` giving the provider each one goes to, and the result is indexed by provider slot.
+pub fn estimate_prefix_reuse(requests: &[ChatRequest], slots: &[usize], providers: usize) -> Vec<f64> {
+    let providers = providers.max(1);
+    let mut previous: Vec<Option<String>> = vec![None; providers];
+    let mut reused = vec![0usize; providers];
+    let mut total = vec![0usize; providers];
+    for (request, &slot) in requests.iter().zip(slots) {
+        let key = prefix_key(request);
+        if let Some(prev) = &previous[slot] {
+            reused[slot] += common_prefix_len(prev, &key);
+        }
+        total[slot] += key.len();
+        previous[slot] = Some(key);
+    }
+    reused
+        .iter()
+        .zip(&total)
+        .map(|(&r, &t)| if t > 0 { r as f64 / t as f64 } else { 0.0 })
+        .collect()
+}
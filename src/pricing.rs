@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::RequestMetrics;
+
+// USD per million tokens for one model. Cached prompt tokens and reasoning tokens are
+// already part of the prompt and completion counts; without their own prices they are
+// billed at the input and output rates.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    pub cached_input_per_million: Option<f64>,
+    pub reasoning_per_million: Option<f64>,
+}
+
+impl ModelPrice {
+    const fn new(input_per_million: f64, output_per_million: f64, cached_input_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+            cached_input_per_million: Some(cached_input_per_million),
+            reasoning_per_million: None,
+        }
+    }
+
+    pub fn cost(&self, prompt_tokens: usize, completion_tokens: usize) -> f64 {
+        (prompt_tokens as f64 * self.input_per_million + completion_tokens as f64 * self.output_per_million) / 1_000_000.0
+    }
+
+    // Cost of a finished request, with its cached and reasoning tokens at their own rates
+    pub fn cost_of(&self, metrics: &RequestMetrics) -> f64 {
+        let cached = metrics.cached_tokens.unwrap_or(0).min(metrics.prompt_tokens);
+        let reasoning = metrics.reasoning_tokens.unwrap_or(0).min(metrics.completion_tokens);
+        let input = (metrics.prompt_tokens - cached) as f64 * self.input_per_million
+            + cached as f64 * self.cached_input_per_million.unwrap_or(self.input_per_million);
+        let output = (metrics.completion_tokens - reasoning) as f64 * self.output_per_million
+            + reasoning as f64 * self.reasoning_per_million.unwrap_or(self.output_per_million);
+        (input + output) / 1_000_000.0
+    }
+}
+
+// List prices (USD per million input, output and cached input tokens) of common models.
+// A model name matches its own entry or, failing that, the longest entry it starts with
+// followed by "-", so dated snapshots like "gpt-4o-2024-08-06" are priced too.
+const BUILTIN: &[(&str, ModelPrice)] = &[
+    ("gpt-4o", ModelPrice::new(2.5, 10.0, 1.25)),
+    ("gpt-4o-mini", ModelPrice::new(0.15, 0.6, 0.075)),
+    ("gpt-4.1", ModelPrice::new(2.0, 8.0, 0.5)),
+    ("gpt-4.1-mini", ModelPrice::new(0.4, 1.6, 0.1)),
+    ("gpt-4.1-nano", ModelPrice::new(0.1, 0.4, 0.025)),
+    ("o1", ModelPrice::new(15.0, 60.0, 7.5)),
+    ("o1-mini", ModelPrice::new(1.1, 4.4, 0.55)),
+    ("o3", ModelPrice::new(2.0, 8.0, 0.5)),
+    ("o3-mini", ModelPrice::new(1.1, 4.4, 0.55)),
+    ("o4-mini", ModelPrice::new(1.1, 4.4, 0.275)),
+    ("claude-3-5-haiku", ModelPrice::new(0.8, 4.0, 0.08)),
+    ("claude-3-5-sonnet", ModelPrice::new(3.0, 15.0, 0.3)),
+    ("claude-3-7-sonnet", ModelPrice::new(3.0, 15.0, 0.3)),
+    ("claude-sonnet-4", ModelPrice::new(3.0, 15.0, 0.3)),
+    ("claude-3-opus", ModelPrice::new(15.0, 75.0, 1.5)),
+    ("claude-opus-4", ModelPrice::new(15.0, 75.0, 1.5)),
+];
+
+// The built-in prices with the caller's entries on top
+#[derive(Clone)]
+pub struct Pricing {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl Pricing {
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        if let Some(price) = self.prices.get(model) {
+            return Some(price);
+        }
+        self.prices
+            .iter()
+            .filter(|(name, _)| model.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with('-')))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| price)
+    }
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Self { prices: BUILTIN.iter().map(|&(model, price)| (model.to_string(), price)).collect() }
+    }
+}
+
+// {model: {"input": ..., "output": ..., "cached_input": ..., "reasoning": ...}} in USD per
+// million tokens; an entry replaces the built-in price of that model
+pub fn extract_pricing(pricing: Option<&PyDict>) -> PyResult<Pricing> {
+    let mut prices = Pricing::default();
+    if let Some(pricing) = pricing {
+        for (model, price) in pricing.iter() {
+            let price: &PyDict = price.downcast()?;
+            prices.prices.insert(model.extract()?, ModelPrice {
+                input_per_million: crate::get_required_value(price, "input")?,
+                output_per_million: crate::get_required_value(price, "output")?,
+                cached_input_per_million: crate::extract_config_value(price, "cached_input")?,
+                reasoning_per_million: crate::extract_config_value(price, "reasoning")?,
+            });
+        }
+    }
+    Ok(prices)
+}
+
+// The built-in price table, as accepted by `pricing`
+#[pyfunction]
+pub fn builtin_pricing() -> HashMap<String, HashMap<String, f64>> {
+    BUILTIN
+        .iter()
+        .map(|(model, price)| {
+            let mut entry = HashMap::from([
+                ("input".to_string(), price.input_per_million),
+                ("output".to_string(), price.output_per_million),
+            ]);
+            if let Some(cached) = price.cached_input_per_million {
+                entry.insert("cached_input".to_string(), cached);
+            }
+            (model.to_string(), entry)
+        })
+        .collect()
+}
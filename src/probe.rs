@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Duration;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+
+use crate::LLMProvider;
+
+// Longest a probe may take before the provider counts as unhealthy
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Outcome of probing one provider slot
+pub type ProbeResult = (usize, Result<(), String>);
+
+// Health probes of every provider: one round before the first request and, with an
+// interval, another each time it elapses. A round starts only after the previous one has
+// finished.
+pub struct HealthChecks {
+    interval: Option<Duration>,
+    due: Option<Instant>,
+    probes: FuturesUnordered<JoinHandle<ProbeResult>>,
+}
+
+impl HealthChecks {
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self { interval, due: None, probes: FuturesUnordered::new() }
+    }
+
+    fn start(&mut self, providers: &[Arc<dyn LLMProvider>]) {
+        for (slot, provider) in providers.iter().enumerate() {
+            let provider = Arc::clone(provider);
+            self.probes.push(tokio::spawn(async move {
+                let outcome = match tokio::time::timeout(PROBE_TIMEOUT, provider.health_check()).await {
+                    Ok(outcome) => outcome.map_err(|e| e.to_string()),
+                    Err(_) => Err(format!("no answer within {}s", PROBE_TIMEOUT.as_secs())),
+                };
+                (slot, outcome)
+            }));
+        }
+        self.due = None;
+    }
+
+    // Probe every provider and wait for all of them
+    pub async fn round(&mut self, providers: &[Arc<dyn LLMProvider>]) -> Vec<ProbeResult> {
+        self.start(providers);
+        let mut outcomes = Vec::new();
+        while let Some(outcome) = self.probes.next().await {
+            outcomes.push(outcome.expect("probe tasks don't panic"));
+        }
+        self.schedule();
+        outcomes
+    }
+
+    // The next probe to finish, starting the next round when it is due. Never resolves
+    // without an interval.
+    pub async fn next(&mut self, providers: &[Arc<dyn LLMProvider>]) -> ProbeResult {
+        loop {
+            if let Some(outcome) = self.probes.next().await {
+                if self.probes.is_empty() {
+                    self.schedule();
+                }
+                return outcome.expect("probe tasks don't panic");
+            }
+            match self.due {
+                Some(due) => {
+                    sleep_until(due).await;
+                    self.start(providers);
+                }
+                None => futures::future::pending::<()>().await,
+            }
+        }
+    }
+
+    fn schedule(&mut self) {
+        self.due = self.interval.map(|interval| Instant::now() + interval);
+    }
+}
+
+impl Drop for HealthChecks {
+    fn drop(&mut self) {
+        for probe in self.probes.iter() {
+            probe.abort();
+        }
+    }
+}
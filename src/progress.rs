@@ -0,0 +1,278 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use pyo3::prelude::*;
+
+use crate::histogram::LatencyHistogram;
+use crate::stats::{self, SharedStatus};
+use crate::status::Status;
+use crate::{RequestMetrics, RunTotals};
+
+// Rates in a progress update cover roughly this much of the most recent run time
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+// Where a run stands, handed to the progress callback after each batch of results
+#[pyclass]
+#[derive(Clone)]
+pub struct RunProgress {
+    #[pyo3(get)]
+    pub completed: usize,
+    // For a request iterator, the requests pulled from it so far
+    #[pyo3(get)]
+    pub total: usize,
+    // Failed requests so far
+    #[pyo3(get)]
+    pub errors: usize,
+    // Since the end of the warm-up
+    #[pyo3(get)]
+    pub elapsed_seconds: f64,
+    // At the current rate; None for a request iterator or before anything has finished
+    #[pyo3(get)]
+    pub eta_seconds: Option<f64>,
+    // Over the last few seconds
+    #[pyo3(get)]
+    pub requests_per_second: f64,
+    #[pyo3(get)]
+    pub tokens_per_second: f64,
+    #[pyo3(get)]
+    pub prompt_tokens: u64,
+    #[pyo3(get)]
+    pub completion_tokens: u64,
+    #[pyo3(get)]
+    pub request_bytes: u64,
+    #[pyo3(get)]
+    pub response_bytes: u64,
+    // Runtime worker threads
+    #[pyo3(get)]
+    pub threads: usize,
+    // By provider_name ("name:base_url")
+    #[pyo3(get)]
+    pub providers: HashMap<String, ProviderProgress>,
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct ProviderProgress {
+    #[pyo3(get)]
+    pub provider_name: String,
+    // "active", "unavailable" or "drained", as in ProviderStats
+    #[pyo3(get)]
+    pub state: String,
+    // Sent and not back yet, held back by its limits or not
+    #[pyo3(get)]
+    pub in_flight: usize,
+    #[pyo3(get)]
+    pub completed: usize,
+    #[pyo3(get)]
+    pub errors: usize,
+    #[pyo3(get)]
+    pub prompt_tokens: u64,
+    #[pyo3(get)]
+    pub completion_tokens: u64,
+}
+
+// Where a running batch stands, from BatchHandle.get_stats(). Latency percentiles cover the
+// successful requests so far and are 0 before there are any.
+#[pyclass]
+#[derive(Clone)]
+pub struct RunStats {
+    #[pyo3(get)]
+    pub completed: usize,
+    // For a request iterator, the requests pulled from it so far
+    #[pyo3(get)]
+    pub total: usize,
+    #[pyo3(get)]
+    pub errors: usize,
+    // Sent and not back yet, across the providers
+    #[pyo3(get)]
+    pub in_flight: usize,
+    #[pyo3(get)]
+    pub elapsed_seconds: f64,
+    // At the current rate; None for a request iterator or before anything has finished
+    #[pyo3(get)]
+    pub eta_seconds: Option<f64>,
+    // Since the previous get_stats(), over the last few seconds at most
+    #[pyo3(get)]
+    pub requests_per_second: f64,
+    #[pyo3(get)]
+    pub tokens_per_second: f64,
+    #[pyo3(get)]
+    pub mean_latency_ms: f64,
+    #[pyo3(get)]
+    pub p50_latency_ms: f64,
+    #[pyo3(get)]
+    pub p90_latency_ms: f64,
+    #[pyo3(get)]
+    pub p95_latency_ms: f64,
+    #[pyo3(get)]
+    pub p99_latency_ms: f64,
+    // By provider_name ("name:base_url"), as in RunProgress
+    #[pyo3(get)]
+    pub providers: HashMap<String, ProviderProgress>,
+}
+
+#[pymethods]
+impl RunProgress {
+    fn __repr__(&self) -> String {
+        format!(
+            "RunProgress(completed={}, total={}, errors={}, elapsed_seconds={:.1}, eta_seconds={}, \
+             requests_per_second={:.2}, tokens_per_second={:.1})",
+            self.completed,
+            self.total,
+            self.errors,
+            self.elapsed_seconds,
+            self.eta_seconds.map_or_else(|| "None".to_string(), |eta| format!("{:.1}", eta)),
+            self.requests_per_second,
+            self.tokens_per_second
+        )
+    }
+}
+
+#[pymethods]
+impl ProviderProgress {
+    fn __repr__(&self) -> String {
+        format!(
+            "ProviderProgress(provider_name={:?}, state={:?}, in_flight={}, completed={}, errors={})",
+            self.provider_name, self.state, self.in_flight, self.completed, self.errors
+        )
+    }
+}
+
+#[pymethods]
+impl RunStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "RunStats(completed={}, total={}, errors={}, in_flight={}, requests_per_second={:.2}, \
+             p50_latency_ms={:.1}, p99_latency_ms={:.1})",
+            self.completed,
+            self.total,
+            self.errors,
+            self.in_flight,
+            self.requests_per_second,
+            self.p50_latency_ms,
+            self.p99_latency_ms
+        )
+    }
+}
+
+// Running counts behind the progress updates of one run
+pub struct ProgressTracker {
+    started: Instant,
+    pub totals: RunTotals,
+    completed: usize,
+    errors: usize,
+    // Completed requests and tokens at recent updates, oldest first
+    recent: VecDeque<(Instant, usize, u64)>,
+    providers: HashMap<String, ProviderProgress>,
+}
+
+impl ProgressTracker {
+    pub fn start() -> Self {
+        let started = Instant::now();
+        Self {
+            started,
+            totals: RunTotals::default(),
+            completed: 0,
+            errors: 0,
+            recent: VecDeque::from([(started, 0, 0)]),
+            providers: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, results: &[RequestMetrics]) {
+        for metrics in results {
+            self.totals.add(metrics);
+            self.completed += 1;
+            let failed = metrics.status.is_failure();
+            self.errors += failed as usize;
+            if metrics.provider_name.is_empty() {
+                continue;
+            }
+            let provider = self.providers.entry(metrics.provider_name.clone()).or_default();
+            provider.completed += 1;
+            provider.errors += failed as usize;
+            provider.prompt_tokens += metrics.prompt_tokens as u64;
+            provider.completion_tokens += metrics.completion_tokens as u64;
+        }
+    }
+
+    // `open_ended` when the total isn't known up front
+    pub fn update(&mut self, total: usize, open_ended: bool, threads: usize, status: &SharedStatus) -> RunProgress {
+        let now = Instant::now();
+        let tokens = self.totals.prompt_tokens + self.totals.completion_tokens;
+        self.recent.push_back((now, self.completed, tokens));
+        // Keep one update from before the window so the rate spans all of it
+        while self.recent.len() > 2 && now.duration_since(self.recent[1].0) >= RATE_WINDOW {
+            self.recent.pop_front();
+        }
+        let (since, completed, earlier_tokens) = self.recent[0];
+        let seconds = now.duration_since(since).as_secs_f64();
+        let rate = |amount: f64| if seconds > 0.0 { amount / seconds } else { 0.0 };
+        let requests_per_second = rate((self.completed - completed) as f64);
+        let mut providers = self.providers.clone();
+        for stats in stats::snapshot(status) {
+            let provider = providers.entry(stats.provider_name.clone()).or_default();
+            provider.in_flight += stats.pending + stats.in_flight;
+            provider.state = stats.state;
+        }
+        for (name, provider) in providers.iter_mut() {
+            provider.provider_name = name.clone();
+        }
+        RunProgress {
+            completed: self.completed,
+            total,
+            errors: self.errors,
+            elapsed_seconds: now.duration_since(self.started).as_secs_f64(),
+            eta_seconds: (!open_ended && requests_per_second > 0.0)
+                .then(|| total.saturating_sub(self.completed) as f64 / requests_per_second),
+            requests_per_second,
+            tokens_per_second: rate((tokens - earlier_tokens) as f64),
+            prompt_tokens: self.totals.prompt_tokens,
+            completion_tokens: self.totals.completion_tokens,
+            request_bytes: self.totals.request_bytes,
+            response_bytes: self.totals.response_bytes,
+            threads,
+            providers,
+        }
+    }
+}
+
+// A running batch's counts and latencies, updated as each result arrives so polling them
+// from another thread doesn't go over the results
+pub struct LiveStats {
+    progress: ProgressTracker,
+    latency: LatencyHistogram,
+}
+
+impl LiveStats {
+    pub fn start() -> Self {
+        Self { progress: ProgressTracker::start(), latency: LatencyHistogram::new() }
+    }
+
+    pub fn record(&mut self, metrics: &RequestMetrics) {
+        self.progress.record(std::slice::from_ref(metrics));
+        if metrics.status == Status::Ok {
+            self.latency.record(metrics.latency_ms);
+        }
+    }
+
+    pub fn snapshot(&mut self, total: usize, open_ended: bool, status: &SharedStatus) -> RunStats {
+        let progress = self.progress.update(total, open_ended, 0, status);
+        let latency = &self.latency;
+        RunStats {
+            completed: progress.completed,
+            total: progress.total,
+            errors: progress.errors,
+            in_flight: progress.providers.values().map(|provider| provider.in_flight).sum(),
+            elapsed_seconds: progress.elapsed_seconds,
+            eta_seconds: progress.eta_seconds,
+            requests_per_second: progress.requests_per_second,
+            tokens_per_second: progress.tokens_per_second,
+            mean_latency_ms: latency.mean_ms(),
+            p50_latency_ms: latency.quantile_ms(0.5),
+            p90_latency_ms: latency.quantile_ms(0.9),
+            p95_latency_ms: latency.quantile_ms(0.95),
+            p99_latency_ms: latency.quantile_ms(0.99),
+            providers: progress.providers,
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use reqwest::header::HeaderMap;
+use tokio::time::{sleep, sleep_until};
+use tracing::debug;
+
+use crate::retry::reported_quota;
+use crate::{ChatRequest, LLMProvider, RequestMetrics};
+
+// Waits shorter than this aren't worth a log line
+const LOGGED_WAIT: Duration = Duration::from_millis(1);
+
+// A budget of `per_minute` units that refills continuously; up to a full minute's worth
+// can be spent at once, the way provider rate limits behave
+struct Bucket {
+    per_second: f64,
+    capacity: f64,
+    // Available units (negative while in debt) as of the instant
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    fn new(per_minute: usize) -> Self {
+        let capacity = per_minute as f64;
+        Self { per_second: capacity / 60.0, capacity, state: Mutex::new((capacity, Instant::now())) }
+    }
+
+    fn refilled(&self) -> MutexGuard<'_, (f64, Instant)> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.0 = (state.0 + now.duration_since(state.1).as_secs_f64() * self.per_second).min(self.capacity);
+        state.1 = now;
+        state
+    }
+
+    // Wait until `amount` is available and take it. Anything larger than the whole budget
+    // only waits for a full bucket, so it can't block forever.
+    async fn take(&self, amount: f64) {
+        let amount = amount.min(self.capacity);
+        loop {
+            let wait = {
+                let mut state = self.refilled();
+                if state.0 >= amount {
+                    state.0 -= amount;
+                    return;
+                }
+                (amount - state.0) / self.per_second
+            };
+            sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+
+    // Settle usage after the fact; the balance may go negative, delaying later takes
+    fn charge(&self, amount: f64) {
+        self.refilled().0 -= amount;
+    }
+}
+
+// Leaky bucket with no burst allowance: request starts leave one every `interval`, since
+// providers that count requests per minute often also reject short bursts
+struct Spacing {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Spacing {
+    fn new(per_minute: usize) -> Self {
+        Self { interval: Duration::from_secs_f64(60.0 / per_minute as f64), next: Mutex::new(Instant::now()) }
+    }
+
+    // Claim the next free start time and wait for it
+    async fn wait(&self) {
+        let start = {
+            let mut next = self.next.lock().unwrap();
+            let start = (*next).max(Instant::now());
+            *next = start + self.interval;
+            start
+        };
+        sleep_until(start.into()).await;
+    }
+}
+
+// What the provider said is left of one of its limits, counted down locally by the requests
+// started since; forgotten once the limit has reset
+#[derive(Debug, Clone, Copy)]
+struct Reported {
+    remaining: f64,
+    resets_at: Instant,
+}
+
+// The provider's reported limits along with the requests this limiter has in flight, which
+// a report may not count yet
+#[derive(Default)]
+struct ReportedLimits {
+    requests: Option<Reported>,
+    tokens: Option<Reported>,
+    in_flight: usize,
+    in_flight_tokens: f64,
+}
+
+impl ReportedLimits {
+    fn forget_expired(&mut self) {
+        let now = Instant::now();
+        for reported in [&mut self.requests, &mut self.tokens] {
+            if reported.is_some_and(|reported| reported.resets_at <= now) {
+                *reported = None;
+            }
+        }
+    }
+}
+
+// Throttles request starts to requests- and tokens-per-minute limits and caps requests in
+// flight; one applies to the whole run and each provider can carry its own. Starts are
+// spaced evenly to stay under the request rate. A request also reserves its estimated
+// prompt and completion tokens before it is sent, so many requests in flight at once can't
+// overrun the budget; when it returns, the reservation is corrected to the reported usage.
+// A provider's limiter also follows the remaining quota its responses report, holding
+// requests back until the reset once it runs out instead of running into 429s.
+pub struct RateLimiter {
+    requests: Option<Spacing>,
+    tokens: Option<Bucket>,
+    concurrency: Option<(usize, Semaphore)>,
+    // Set after a rate-limit response when the whole provider should back off
+    paused_until: Mutex<Option<Instant>>,
+    // Requests and tokens left according to the latest response headers
+    reported: Mutex<ReportedLimits>,
+    // Requests inside acquire() or readmit(), held back by one of the limits
+    waiting: AtomicUsize,
+}
+
+fn log_wait(provider: &dyn LLMProvider, request: &ChatRequest, started: Instant) {
+    let waited = started.elapsed();
+    if waited >= LOGGED_WAIT {
+        debug!(
+            index = request.index,
+            provider = %provider.display_name(),
+            waited_ms = waited.as_millis() as u64,
+            "held back by rate limits"
+        );
+    }
+}
+
+// Counts a request as waiting until it is admitted or given up on
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// A request's claim on a limiter, held until it has finished
+pub struct Admission<'a> {
+    limiter: &'a RateLimiter,
+    reserved: usize,
+    // Tokens taken from the reported quota
+    reported_tokens: f64,
+    _permit: Option<SemaphorePermit<'a>>,
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        let mut reported = self.limiter.reported.lock().unwrap();
+        reported.in_flight -= 1;
+        reported.in_flight_tokens -= self.reported_tokens;
+    }
+}
+
+impl RateLimiter {
+    pub fn new(tokens_per_minute: Option<usize>, requests_per_minute: Option<usize>, max_concurrency: Option<usize>) -> Self {
+        Self {
+            requests: requests_per_minute.filter(|&limit| limit > 0).map(Spacing::new),
+            tokens: tokens_per_minute.filter(|&limit| limit > 0).map(Bucket::new),
+            concurrency: max_concurrency.filter(|&limit| limit > 0).map(|limit| (limit, Semaphore::new(limit))),
+            paused_until: Mutex::new(None),
+            reported: Mutex::new(ReportedLimits::default()),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    // Hold back every request start for `delay`
+    pub fn pause(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut paused_until = self.paused_until.lock().unwrap();
+        *paused_until = Some(paused_until.map_or(until, |paused| paused.max(until)));
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.concurrency.as_ref().map(|(limit, _)| *limit)
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    // Whether the request could start right away: not paused, a free slot, its turn under
+    // the request rate, and its estimated tokens within the budget and the reported quota
+    pub fn has_headroom(&self, provider: &dyn LLMProvider, request: &ChatRequest) -> bool {
+        let now = Instant::now();
+        if self.paused_until.lock().unwrap().is_some_and(|until| until > now) {
+            return false;
+        }
+        if self.concurrency.as_ref().is_some_and(|(_, slots)| slots.available_permits() == 0) {
+            return false;
+        }
+        if self.requests.as_ref().is_some_and(|requests| *requests.next.lock().unwrap() > now) {
+            return false;
+        }
+        let mut estimated = None;
+        let mut estimate = || {
+            *estimated.get_or_insert_with(|| {
+                let estimate = provider.estimate(request);
+                (estimate.prompt_tokens + estimate.completion_tokens) as f64
+            })
+        };
+        if self.tokens.as_ref().is_some_and(|tokens| tokens.refilled().0 < estimate().min(tokens.capacity)) {
+            return false;
+        }
+        let mut reported = self.reported.lock().unwrap();
+        reported.forget_expired();
+        let requests_short = reported.requests.is_some_and(|requests| requests.remaining < 1.0);
+        let tokens_short = reported.tokens.is_some_and(|tokens| tokens.remaining < estimate());
+        !requests_short && !tokens_short
+    }
+
+    // Wait for a free slot, the next start time and the request's token reservation
+    pub async fn acquire(&self, provider: &dyn LLMProvider, request: &ChatRequest) -> Admission<'_> {
+        let started = Instant::now();
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&self.waiting);
+        let paused_until = *self.paused_until.lock().unwrap();
+        if let Some(until) = paused_until {
+            sleep_until(until.into()).await;
+        }
+        let permit = match &self.concurrency {
+            Some((_, slots)) => Some(slots.acquire().await.expect("limiter semaphore is never closed")),
+            None => None,
+        };
+        let (reserved, reported_tokens) = self.take_turn(provider, request).await;
+        log_wait(provider, request, started);
+        Admission { limiter: self, reserved, reported_tokens, _permit: permit }
+    }
+
+    // Charge a retry of an admitted request like a new start: it waits for a pause to end,
+    // its turn under the request rate and another token reservation, keeping its slot. The
+    // rejected attempt's reservation stays spent, since providers count rejected requests
+    // against their limits too.
+    pub async fn readmit(&self, admission: &mut Admission<'_>, provider: &dyn LLMProvider, request: &ChatRequest) {
+        let started = Instant::now();
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&self.waiting);
+        let paused_until = *self.paused_until.lock().unwrap();
+        if let Some(until) = paused_until {
+            sleep_until(until.into()).await;
+        }
+        let (reserved, reported_tokens) = self.take_turn(provider, request).await;
+        {
+            // Still the one request in flight, now holding the new attempt's tokens
+            let mut reported = self.reported.lock().unwrap();
+            reported.in_flight -= 1;
+            reported.in_flight_tokens -= admission.reported_tokens;
+        }
+        admission.reserved = reserved;
+        admission.reported_tokens = reported_tokens;
+        log_wait(provider, request, started);
+    }
+
+    // Wait for the next start time, then reserve the request's estimated tokens from the
+    // budget and the reported quota. Returns the tokens reserved and those taken from the
+    // quota.
+    async fn take_turn(&self, provider: &dyn LLMProvider, request: &ChatRequest) -> (usize, f64) {
+        if let Some(requests) = &self.requests {
+            requests.wait().await;
+        }
+        let mut estimated = None;
+        let mut estimate = || {
+            *estimated.get_or_insert_with(|| {
+                let estimate = provider.estimate(request);
+                estimate.prompt_tokens + estimate.completion_tokens
+            })
+        };
+        let reserved = match &self.tokens {
+            Some(tokens) => {
+                let estimated = estimate();
+                tokens.take(estimated as f64).await;
+                estimated
+            }
+            None => 0,
+        };
+        (reserved, self.within_reported(estimate).await)
+    }
+
+    // Take the request and its estimated tokens from the reported quota, waiting for the
+    // reset when not enough is left. Returns the tokens taken.
+    async fn within_reported(&self, mut estimate: impl FnMut() -> usize) -> f64 {
+        loop {
+            let wait = {
+                let mut reported = self.reported.lock().unwrap();
+                reported.forget_expired();
+                let needed = reported.tokens.map_or(0.0, |_| estimate() as f64);
+                let requests_short = reported.requests.filter(|requests| requests.remaining < 1.0);
+                let tokens_short = reported.tokens.filter(|tokens| tokens.remaining < needed);
+                match requests_short.map(|r| r.resets_at).max(tokens_short.map(|t| t.resets_at)) {
+                    Some(until) => until,
+                    None => {
+                        if let Some(requests) = reported.requests.as_mut() {
+                            requests.remaining -= 1.0;
+                        }
+                        if let Some(tokens) = reported.tokens.as_mut() {
+                            tokens.remaining -= needed;
+                        }
+                        reported.in_flight += 1;
+                        reported.in_flight_tokens += needed;
+                        return needed;
+                    }
+                }
+            };
+            sleep_until(wait.into()).await;
+        }
+    }
+
+    // Update the reported quota from a response's rate-limit headers. The response's own
+    // request is already counted by the provider; the other requests in flight may not be,
+    // so they are taken off what it reports (for tokens, their average share). Responses
+    // can arrive out of order, so until the limit resets only a lower figure is taken.
+    pub fn observe(&self, headers: &HeaderMap) {
+        let now = Instant::now();
+        let mut reported = self.reported.lock().unwrap();
+        reported.forget_expired();
+        let others = reported.in_flight.saturating_sub(1) as f64;
+        let other_tokens = if reported.in_flight > 0 { reported.in_flight_tokens * others / reported.in_flight as f64 } else { 0.0 };
+        let update = |current: &mut Option<Reported>, remaining: f64, reset: Duration| {
+            if current.is_none_or(|current| remaining < current.remaining) {
+                *current = Some(Reported { remaining, resets_at: now + reset });
+            }
+        };
+        if let Some(quota) = reported_quota(headers, "requests") {
+            update(&mut reported.requests, quota.remaining - others, quota.reset);
+        }
+        if let Some(quota) = reported_quota(headers, "tokens") {
+            update(&mut reported.tokens, quota.remaining - other_tokens, quota.reset);
+        }
+    }
+
+    pub fn record(&self, admission: &Admission<'_>, metrics: &RequestMetrics) {
+        if let Some(tokens) = &self.tokens {
+            tokens.charge((metrics.prompt_tokens + metrics.completion_tokens) as f64 - admission.reserved as f64);
+        }
+    }
+}
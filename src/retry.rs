@@ -0,0 +1,179 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+
+// A 429 or 503 response. Unlike other failures it is retried after the wait the provider
+// asked for, or an exponential backoff when it gave no hint.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub status: StatusCode,
+    pub retry_after: Option<Duration>,
+    pub message: String,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+// Retries every run may make whatever its budget, so its first rate limits don't fail
+// requests outright
+const MIN_RETRIES: usize = 10;
+
+// Run-wide cap on retries as a share of the requests sent, so a systemic outage turns into
+// fast failures instead of every request waiting out its retries in turn
+pub struct RetryBudget {
+    ratio: f64,
+    requests: AtomicUsize,
+    retries: AtomicUsize,
+    // Retries refused because the budget was spent
+    denied: AtomicUsize,
+}
+
+impl RetryBudget {
+    pub fn new(ratio: f64) -> Result<Self, String> {
+        if !(ratio.is_finite() && ratio >= 0.0) {
+            return Err(format!("retry_budget must be a non-negative fraction of the requests, got {}", ratio));
+        }
+        Ok(Self { ratio, requests: AtomicUsize::new(0), retries: AtomicUsize::new(0), denied: AtomicUsize::new(0) })
+    }
+
+    pub fn request(&self) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // Take one retry from the budget, if any is left
+    pub fn try_retry(&self) -> bool {
+        let allowed = MIN_RETRIES + (self.ratio * self.requests.load(Ordering::SeqCst) as f64) as usize;
+        let granted = self.retries.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |retries| (retries < allowed).then_some(retries + 1)).is_ok();
+        if !granted {
+            self.denied.fetch_add(1, Ordering::SeqCst);
+        }
+        granted
+    }
+
+    pub fn denied(&self) -> usize {
+        self.denied.load(Ordering::SeqCst)
+    }
+}
+
+pub fn is_rate_limited(status: StatusCode) -> bool {
+    matches!(status, StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE)
+}
+
+// 1s, 2s, 4s, ... capped at a minute
+pub fn backoff(attempt: usize) -> Duration {
+    Duration::from_secs(1 << attempt.min(6)).min(Duration::from_secs(60))
+}
+
+// Wait requested through retry-after-ms, Retry-After (seconds or an HTTP date) or, failing
+// those, OpenAI's x-ratelimit-reset-requests / -tokens for whichever limit ran out
+pub fn retry_delay(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+    if let Some(wait) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()).and_then(|ms| seconds(ms / 1000.0)) {
+        return Some(wait);
+    }
+    if let Some(value) = header("retry-after") {
+        if let Ok(number) = value.parse::<f64>() {
+            if let Some(wait) = seconds(number) {
+                return Some(wait);
+            }
+        } else if let Ok(date) = httpdate::parse_http_date(value) {
+            return Some(date.duration_since(SystemTime::now()).unwrap_or_default());
+        }
+    }
+    let resets: Vec<(bool, Duration)> = ["requests", "tokens"]
+        .iter()
+        .filter_map(|limit| {
+            let reset = parse_reset(header(&format!("x-ratelimit-reset-{}", limit))?)?;
+            let exhausted = header(&format!("x-ratelimit-remaining-{}", limit)) == Some("0");
+            Some((exhausted, reset))
+        })
+        .collect();
+    let exhausted = resets.iter().filter(|(exhausted, _)| *exhausted).map(|(_, reset)| *reset).max();
+    exhausted.or_else(|| resets.iter().map(|(_, reset)| *reset).max())
+}
+
+// Quota left on one of the provider's limits, as reported with a response
+#[derive(Debug, Clone, Copy)]
+pub struct ReportedQuota {
+    pub remaining: f64,
+    // Until the limit is fully replenished
+    pub reset: Duration,
+}
+
+// The "requests" or "tokens" quota from OpenAI's x-ratelimit-remaining-* / -reset-* headers
+// or Anthropic's anthropic-ratelimit-*-remaining / -reset, whose reset is a timestamp
+pub fn reported_quota(headers: &HeaderMap, limit: &str) -> Option<ReportedQuota> {
+    let header = |name: String| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+    let openai = || {
+        let remaining = header(format!("x-ratelimit-remaining-{}", limit))?.parse().ok()?;
+        let reset = parse_reset(header(format!("x-ratelimit-reset-{}", limit))?)?;
+        Some(ReportedQuota { remaining, reset })
+    };
+    let anthropic = || {
+        let remaining = header(format!("anthropic-ratelimit-{}-remaining", limit))?.parse().ok()?;
+        let reset = parse_timestamp(header(format!("anthropic-ratelimit-{}-reset", limit))?)?;
+        Some(ReportedQuota { remaining, reset: reset.duration_since(SystemTime::now()).unwrap_or_default() })
+    };
+    openai().or_else(anthropic)
+}
+
+// RFC 3339 timestamps such as "2024-06-01T12:00:30Z" or "2024-06-01T14:00:30.5+02:00"
+fn parse_timestamp(value: &str) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| value.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute) = (number(11..13)?, number(14..16)?);
+    let rest = value.get(17..)?;
+    let zone = rest.find(['Z', 'z', '+', '-'])?;
+    let second: f64 = rest[..zone].parse().ok()?;
+    let offset = match &rest[zone..] {
+        "Z" | "z" => 0,
+        zone => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            sign * (zone.get(1..3)?.parse::<i64>().ok()? * 3600 + zone.get(4..6)?.parse::<i64>().ok()? * 60)
+        }
+    };
+    // Days since the epoch of a proleptic Gregorian date (Howard Hinnant's days_from_civil)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    let since_epoch = seconds((days * 86400 + hour * 3600 + minute * 60 - offset) as f64 + second)?;
+    SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+}
+
+// Durations like "6m0s", "1.5s", "20ms" or a bare number of seconds
+fn parse_reset(value: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let split = tail.find(|c: char| c.is_ascii_digit()).unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(split);
+        seconds += number * match unit {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" | "" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        rest = tail;
+    }
+    self::seconds(seconds)
+}
+
+// A wait given in seconds by a header; None for a negative, NaN or infinite one, or one too
+// long for a Duration, which a misbehaving server or proxy could otherwise send to panic on
+fn seconds(value: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(value).ok()
+}
@@ -0,0 +1,100 @@
+// Weight of the newest latency in a provider's moving average
+const EWMA_ALPHA: f64 = 0.3;
+
+// How the dispatcher picks a provider for each request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Routing {
+    // Rotate through the providers, in proportion to their weights
+    #[default]
+    RoundRobin,
+    // Send to the provider expected to answer soonest
+    LeastLoaded,
+}
+
+impl Routing {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "round_robin" => Some(Self::RoundRobin),
+            "least_loaded" => Some(Self::LeastLoaded),
+            _ => None,
+        }
+    }
+}
+
+// Requests in flight and a moving average of successful latencies per provider. The
+// expected wait on a provider is its average latency times the requests it would then have
+// in flight, divided by its weight; a provider without results yet is assumed to be as
+// fast as the average of the others, so every provider gets tried early on.
+pub struct ProviderLoad {
+    in_flight: Vec<usize>,
+    latency_ms: Vec<Option<f64>>,
+    weights: Vec<f64>,
+    // Rotates the starting point so ties don't all go to the first provider
+    next: usize,
+    // Per provider, the server it sends to; with a cap, each host's requests in flight
+    // are held to it
+    hosts: Vec<String>,
+    host_limit: Option<usize>,
+    // Each provider's own max_concurrency
+    limits: Vec<Option<usize>>,
+}
+
+impl ProviderLoad {
+    pub fn new(weights: Vec<f64>, hosts: Vec<String>, host_limit: Option<usize>, limits: Vec<Option<usize>>) -> Self {
+        let providers = weights.len();
+        Self { in_flight: vec![0; providers], latency_ms: vec![None; providers], weights, next: 0, hosts, host_limit, limits }
+    }
+
+    // A provider added mid-run
+    pub fn add(&mut self, weight: f64, host: String, limit: Option<usize>) {
+        self.in_flight.push(0);
+        self.latency_ms.push(None);
+        self.weights.push(weight);
+        self.hosts.push(host);
+        self.limits.push(limit);
+    }
+
+    // Whether another request may go to `slot` under its own cap and its host's. A request
+    // held back here waits in the queue rather than taking up a run-wide slot, so the slots
+    // stay with providers that can use them.
+    pub fn has_room(&self, slot: usize) -> bool {
+        if self.limits[slot].is_some_and(|limit| self.in_flight[slot] >= limit) {
+            return false;
+        }
+        self.host_limit.is_none_or(|limit| {
+            let host = &self.hosts[slot];
+            let in_flight: usize = (0..self.in_flight.len()).filter(|&other| &self.hosts[other] == host).map(|other| self.in_flight[other]).sum();
+            in_flight < limit
+        })
+    }
+
+    pub fn started(&mut self, slot: usize) {
+        self.in_flight[slot] += 1;
+    }
+
+    // `latency_ms` is None when the request produced no usable latency, e.g. it failed
+    pub fn finished(&mut self, slot: usize, latency_ms: Option<f64>) {
+        self.in_flight[slot] -= 1;
+        if let Some(latency_ms) = latency_ms {
+            let average = &mut self.latency_ms[slot];
+            *average = Some(average.map_or(latency_ms, |average| average + EWMA_ALPHA * (latency_ms - average)));
+        }
+    }
+
+    // The candidate with the lowest expected wait, then the fewest requests in flight
+    pub fn least_loaded(&mut self, candidates: impl Iterator<Item = usize>) -> Option<usize> {
+        let known: Vec<f64> = self.latency_ms.iter().flatten().copied().collect();
+        let fallback_ms = if known.is_empty() { 0.0 } else { known.iter().sum::<f64>() / known.len() as f64 };
+        let mut candidates: Vec<usize> = candidates.collect();
+        let providers = self.in_flight.len();
+        candidates.sort_by_key(|&slot| (slot + providers - self.next % providers) % providers);
+        let slot = candidates.into_iter().min_by(|&a, &b| {
+            let wait = |slot: usize| {
+                self.latency_ms[slot].unwrap_or(fallback_ms) * (self.in_flight[slot] + 1) as f64 / self.weights[slot]
+            };
+            wait(a).total_cmp(&wait(b)).then(self.in_flight[a].cmp(&self.in_flight[b]))
+        })?;
+        self.next = slot + 1;
+        Some(slot)
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use reqwest::{Client, ClientBuilder};
+use tokio::runtime::Runtime;
+
+// The runtime and HTTP clients every run shares, built on first use and kept for the life of
+// the process, so later runs reuse warm connections and worker threads. A forked child
+// (multiprocessing) builds its own: the parent's worker threads don't exist there and its
+// pooled connections belong to the parent.
+struct Shared {
+    pid: u32,
+    runtime: Arc<Runtime>,
+    client: Client,
+    // Clients for providers with connection or request timeouts, by (connect, total)
+    with_timeouts: HashMap<(Option<Duration>, Option<Duration>), Client>,
+}
+
+static SHARED: Mutex<Option<Shared>> = Mutex::new(None);
+
+fn with_shared<T>(f: impl FnOnce(&mut Shared) -> T) -> T {
+    let mut shared = SHARED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let pid = std::process::id();
+    if shared.as_ref().is_none_or(|shared| shared.pid != pid) {
+        // Dropping the parent's runtime would wait on threads this process doesn't have
+        std::mem::forget(shared.take());
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(thread_count())
+            .enable_all()
+            .build()
+            .unwrap();
+        *shared = Some(Shared {
+            pid,
+            runtime: Arc::new(runtime),
+            client: client_builder().build().unwrap(),
+            with_timeouts: HashMap::new(),
+        });
+    }
+    f(shared.as_mut().expect("initialized above"))
+}
+
+pub fn thread_count() -> usize {
+    num_cpus::get()
+}
+
+pub fn runtime() -> Arc<Runtime> {
+    with_shared(|shared| Arc::clone(&shared.runtime))
+}
+
+pub fn client() -> Client {
+    with_shared(|shared| shared.client.clone())
+}
+
+// The shared client, or the shared one for these timeouts when any is set
+pub fn client_with_timeouts(connect: Option<Duration>, total: Option<Duration>) -> reqwest::Result<Client> {
+    if connect.is_none() && total.is_none() {
+        return Ok(client());
+    }
+    with_shared(|shared| {
+        if let Some(client) = shared.with_timeouts.get(&(connect, total)) {
+            return Ok(client.clone());
+        }
+        let mut builder = client_builder();
+        if let Some(connect) = connect {
+            builder = builder.connect_timeout(connect);
+        }
+        if let Some(total) = total {
+            builder = builder.timeout(total);
+        }
+        let client = builder.build()?;
+        shared.with_timeouts.insert((connect, total), client.clone());
+        Ok(client)
+    })
+}
+
+fn client_builder() -> ClientBuilder {
+    ClientBuilder::new()
+        .pool_max_idle_per_host(100)
+        .pool_idle_timeout(Duration::from_secs(30))
+        .tcp_nodelay(true)
+        .tcp_keepalive(Duration::from_secs(30))
+        .http2_keep_alive_interval(Duration::from_secs(20))
+        .http2_keep_alive_timeout(Duration::from_secs(10))
+        .http2_adaptive_window(true)
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+// What input sanitization changed in one request. Scraped text regularly carries null
+// bytes, decomposed accents or lone surrogates, which providers answer with a 400.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SanitizeReport {
+    pub null_bytes: usize,
+    // Unpaired UTF-16 surrogates, replaced with U+FFFD
+    pub lone_surrogates: usize,
+    // Text fields rewritten to NFC
+    pub normalized: usize,
+}
+
+impl SanitizeReport {
+    pub fn to_map(self) -> HashMap<&'static str, usize> {
+        HashMap::from([
+            ("null_bytes", self.null_bytes),
+            ("lone_surrogates", self.lone_surrogates),
+            ("normalized", self.normalized),
+        ])
+    }
+
+    // Strip null bytes and NFC-normalize text that didn't come from a Python string
+    pub fn clean(&mut self, mut text: String) -> String {
+        let before = text.len();
+        text.retain(|c| c != '\0');
+        self.null_bytes += before - text.len();
+        if is_nfc(&text) {
+            return text;
+        }
+        self.normalized += 1;
+        text.nfc().collect()
+    }
+}
+
+// Read a Python string, sanitizing it into `report` when one is given. Without a report
+// this is a plain extraction, so strings with lone surrogates are rejected as before.
+pub fn read_text(value: &PyAny, report: Option<&mut SanitizeReport>) -> PyResult<String> {
+    let Some(report) = report else {
+        return value.extract();
+    };
+    let value: &PyString = value.downcast()?;
+    let text = match value.to_str() {
+        Ok(text) => text.to_string(),
+        // Lone surrogates can't be UTF-8 encoded; go through UTF-16 to find them
+        Err(_) => {
+            let encoded = value.call_method1("encode", ("utf-16-le", "surrogatepass"))?;
+            let units = encoded.extract::<&[u8]>()?.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+            char::decode_utf16(units)
+                .map(|unit| {
+                    unit.unwrap_or_else(|_| {
+                        report.lone_surrogates += 1;
+                        char::REPLACEMENT_CHARACTER
+                    })
+                })
+                .collect()
+        }
+    };
+    Ok(report.clean(text))
+}
@@ -0,0 +1,91 @@
+use std::error::Error;
+use std::sync::Arc;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+use crate::{RequestMetrics, ResponseContent};
+
+// Which of several returned choices (n > 1) becomes the canonical result. The selected
+// choice is moved to the front, so `content`, schema validation and the rest of the
+// result handling see it; the others keep their order behind it and all of them stay
+// counted in the token totals.
+pub enum ChoicePolicy {
+    First,
+    // Most characters
+    Longest,
+    // Highest mean token logprob; requests are sent with logprobs enabled
+    MeanLogprob,
+    // Python callable taking the list of choice texts (None for choices without text, e.g.
+    // only tool calls) and returning the index to keep
+    Judge(PyObject),
+}
+
+impl ChoicePolicy {
+    pub fn extract(policy: &PyAny) -> PyResult<Self> {
+        if let Ok(name) = policy.downcast::<PyString>() {
+            return match name.to_str()? {
+                "first" => Ok(Self::First),
+                "longest" => Ok(Self::Longest),
+                "logprob" => Ok(Self::MeanLogprob),
+                other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown choice policy '{}'; expected 'first', 'longest', 'logprob' or a callable",
+                    other
+                ))),
+            };
+        }
+        if policy.is_callable() {
+            return Ok(Self::Judge(policy.into()));
+        }
+        Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("choice_policy must be a policy name or a callable"))
+    }
+
+    pub fn needs_logprobs(&self) -> bool {
+        matches!(self, Self::MeanLogprob)
+    }
+}
+
+fn choice_text(choice: &Option<ResponseContent>) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    choice.as_ref().map(ResponseContent::text).transpose()
+}
+
+pub async fn select_choice(
+    policy: &Arc<ChoicePolicy>,
+    metrics: &mut RequestMetrics,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if metrics.choices.len() < 2 {
+        return Ok(());
+    }
+    let selected = match policy.as_ref() {
+        ChoicePolicy::First => 0,
+        ChoicePolicy::Longest => {
+            let texts = metrics.choices.iter().map(choice_text).collect::<Result<Vec<_>, _>>()?;
+            // max_by_key keeps the last maximum, so iterate backwards to favour earlier choices
+            (0..texts.len()).rev().max_by_key(|&index| texts[index].as_deref().map_or(0, |text| text.chars().count())).unwrap_or(0)
+        }
+        ChoicePolicy::MeanLogprob => metrics
+            .choice_logprobs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, logprob)| logprob.map(|logprob| (index, logprob)))
+            .rev()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(index, _)| index),
+        ChoicePolicy::Judge(_) => {
+            let texts = metrics.choices.iter().map(choice_text).collect::<Result<Vec<_>, _>>()?;
+            let count = texts.len();
+            let policy = Arc::clone(policy);
+            let index = tokio::task::spawn_blocking(move || {
+                let ChoicePolicy::Judge(judge) = policy.as_ref() else { unreachable!() };
+                Python::with_gil(|py| judge.call1(py, (texts,))?.extract::<usize>(py))
+            })
+            .await?
+            .map_err(|e| format!("Choice judge failed: {}", e))?;
+            if index >= count {
+                return Err(format!("Choice judge returned {} for {} choices", index, count).into());
+            }
+            index
+        }
+    };
+    metrics.select_choice(selected);
+    Ok(())
+}
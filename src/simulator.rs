@@ -0,0 +1,162 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::Rng;
+use tokio::sync::Semaphore;
+use tokio::time::sleep;
+
+use crate::{extract_config_value, get_required_value};
+
+// Distribution that per-request base service times (and client think times) are drawn from
+#[derive(Debug, Clone)]
+pub enum ServiceTime {
+    Constant { ms: f64 },
+    Uniform { min_ms: f64, max_ms: f64 },
+    Exponential { mean_ms: f64 },
+    LogNormal { median_ms: f64, sigma: f64 },
+}
+
+impl ServiceTime {
+    pub fn extract(dict: &PyDict) -> PyResult<Self> {
+        let distribution: String = get_required_value(dict, "distribution")?;
+        match distribution.as_str() {
+            "constant" => Ok(ServiceTime::Constant { ms: get_required_value(dict, "ms")? }),
+            "uniform" => Ok(ServiceTime::Uniform {
+                min_ms: get_required_value(dict, "min_ms")?,
+                max_ms: get_required_value(dict, "max_ms")?,
+            }),
+            "exponential" => Ok(ServiceTime::Exponential { mean_ms: get_required_value(dict, "mean_ms")? }),
+            "lognormal" => Ok(ServiceTime::LogNormal {
+                median_ms: get_required_value(dict, "median_ms")?,
+                sigma: extract_config_value(dict, "sigma")?.unwrap_or(0.5),
+            }),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                format!("Unknown time distribution: {}", other),
+            )),
+        }
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        match *self {
+            ServiceTime::Constant { ms } => ms,
+            ServiceTime::Uniform { min_ms, max_ms } => (min_ms + max_ms) / 2.0,
+            ServiceTime::Exponential { mean_ms } => mean_ms,
+            ServiceTime::LogNormal { median_ms, sigma } => median_ms * (sigma * sigma / 2.0).exp(),
+        }
+    }
+
+    pub fn sample_ms(&self) -> f64 {
+        let mut rng = rand::thread_rng();
+        let ms = match *self {
+            ServiceTime::Constant { ms } => ms,
+            ServiceTime::Uniform { min_ms, max_ms } if max_ms > min_ms => rng.gen_range(min_ms..max_ms),
+            ServiceTime::Uniform { min_ms, .. } => min_ms,
+            ServiceTime::Exponential { mean_ms } => -mean_ms * (1.0 - rng.gen::<f64>()).ln(),
+            ServiceTime::LogNormal { median_ms, sigma } => {
+                // Box-Muller transform for a standard normal sample
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                median_ms * (sigma * z).exp()
+            }
+        };
+        ms.max(0.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    // Requests the simulated server processes at once; the rest queue
+    pub max_concurrency: usize,
+    pub service_time: ServiceTime,
+    // Additional decode time per generated token
+    pub per_token_ms: f64,
+    // Queue depth beyond which the server answers 429 instead of queueing
+    pub rate_limit_threshold: Option<usize>,
+    // Cold-start: the first N requests are slowed by up to `warmup_factor`, decaying linearly
+    pub warmup_requests: usize,
+    pub warmup_factor: f64,
+}
+
+impl SimulatorConfig {
+    pub fn extract(dict: &PyDict) -> PyResult<Self> {
+        let service_time = match dict.get_item("service_time")? {
+            Some(value) => ServiceTime::extract(value.downcast()?)?,
+            None => ServiceTime::Constant { ms: 50.0 },
+        };
+        Ok(Self {
+            max_concurrency: extract_config_value(dict, "max_concurrency")?.unwrap_or(usize::MAX),
+            service_time,
+            per_token_ms: extract_config_value(dict, "per_token_ms")?.unwrap_or(0.1),
+            rate_limit_threshold: extract_config_value(dict, "rate_limit_threshold")?,
+            warmup_requests: extract_config_value(dict, "warmup_requests")?.unwrap_or(0),
+            warmup_factor: extract_config_value(dict, "warmup_factor")?.unwrap_or(1.0),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SimulatedRateLimit {
+    pub queue_depth: usize,
+}
+
+impl std::fmt::Display for SimulatedRateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Simulated 429: {} requests already queued", self.queue_depth)
+    }
+}
+
+impl Error for SimulatedRateLimit {}
+
+// Capacity model shared by every request routed to one simulated provider
+pub struct Simulator {
+    config: SimulatorConfig,
+    slots: Semaphore,
+    queued: AtomicUsize,
+    served: AtomicUsize,
+}
+
+impl Simulator {
+    pub fn new(config: SimulatorConfig) -> Self {
+        let permits = config.max_concurrency.min(Semaphore::MAX_PERMITS);
+        Self {
+            slots: Semaphore::new(permits),
+            queued: AtomicUsize::new(0),
+            served: AtomicUsize::new(0),
+            config,
+        }
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        Some(self.config.max_concurrency).filter(|&c| c != usize::MAX)
+    }
+
+    // Expected service time ignoring queueing and warm-up
+    pub fn mean_service_ms(&self, completion_tokens: usize) -> f64 {
+        self.config.service_time.mean_ms() + completion_tokens as f64 * self.config.per_token_ms
+    }
+
+    // Wait for a server slot, then hold it for the sampled service time
+    pub async fn serve(&self, completion_tokens: usize) -> Result<(), SimulatedRateLimit> {
+        let queue_depth = self.queued.fetch_add(1, Ordering::SeqCst);
+        if let Some(threshold) = self.config.rate_limit_threshold {
+            if queue_depth >= threshold && self.slots.available_permits() == 0 {
+                self.queued.fetch_sub(1, Ordering::SeqCst);
+                return Err(SimulatedRateLimit { queue_depth });
+            }
+        }
+        let _slot = self.slots.acquire().await.expect("simulator semaphore closed");
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+
+        let served = self.served.fetch_add(1, Ordering::SeqCst);
+        let mut ms = self.config.service_time.sample_ms() + completion_tokens as f64 * self.config.per_token_ms;
+        if served < self.config.warmup_requests {
+            let remaining = 1.0 - served as f64 / self.config.warmup_requests as f64;
+            ms *= 1.0 + (self.config.warmup_factor - 1.0).max(0.0) * remaining;
+        }
+        sleep(Duration::from_secs_f64(ms / 1000.0)).await;
+        Ok(())
+    }
+}
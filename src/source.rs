@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+use pyo3::prelude::*;
+use pyo3::types::PyIterator;
+
+use crate::language::request_language;
+use crate::templates::PromptTemplates;
+use crate::{extract_request, ChatRequest};
+
+// Requests pulled one at a time from a Python iterator whenever the dispatcher has room for
+// another, so a dataset never has to be held in memory in full. Each is converted the way a
+// list's requests are; the index is its position in the iterator.
+pub struct RequestSource {
+    iterator: Py<PyIterator>,
+    pulled: usize,
+    stream_dir: Option<PathBuf>,
+    sanitize: bool,
+    templates: Option<PromptTemplates>,
+    detect_language: bool,
+    // Raised by the iterator or for a malformed request; nothing is pulled after it
+    error: Option<PyErr>,
+}
+
+impl RequestSource {
+    pub fn new(
+        iterator: &PyIterator,
+        stream_dir: Option<PathBuf>,
+        sanitize: bool,
+        templates: Option<PromptTemplates>,
+        detect_language: bool,
+    ) -> Self {
+        Self { iterator: iterator.into(), pulled: 0, stream_dir, sanitize, templates, detect_language, error: None }
+    }
+
+    // The next request, or None once the iterator is exhausted or has failed
+    pub fn next(&mut self) -> Option<ChatRequest> {
+        if self.error.is_some() {
+            return None;
+        }
+        Python::with_gil(|py| {
+            let mut iterator = self.iterator.as_ref(py);
+            let item = match iterator.next()? {
+                Ok(item) => item,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                }
+            };
+            let index = self.pulled;
+            match extract_request(item, index, self.stream_dir.as_deref(), self.sanitize, self.templates.as_ref()) {
+                Ok(mut request) => {
+                    self.pulled += 1;
+                    if self.detect_language {
+                        request.language = Some(request_language(&request));
+                    }
+                    Some(request)
+                }
+                Err(e) => {
+                    let message = format!("requests[{}]: {}", index, e.value(py));
+                    self.error = Some(PyErr::new::<pyo3::exceptions::PyValueError, _>(message));
+                    None
+                }
+            }
+        })
+    }
+
+    pub fn take_error(&mut self) -> Option<PyErr> {
+        self.error.take()
+    }
+}
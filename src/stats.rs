@@ -0,0 +1,76 @@
+use std::sync::{Arc, Mutex};
+use pyo3::prelude::*;
+
+use crate::LLMProvider;
+
+// One provider's queue at a moment during a run
+#[pyclass]
+#[derive(Clone)]
+pub struct ProviderStats {
+    #[pyo3(get)]
+    pub provider_name: String,
+    // "active", "unavailable" (circuit breaker tripped or health check failed) or "drained"
+    #[pyo3(get)]
+    pub state: String,
+    // Sent to the provider but held back by its rate or concurrency limits
+    #[pyo3(get)]
+    pub pending: usize,
+    // Past its limits and waiting on the response
+    #[pyo3(get)]
+    pub in_flight: usize,
+    #[pyo3(get)]
+    pub completed: usize,
+    // Failed fast because its queue was at max_queue_depth
+    #[pyo3(get)]
+    pub shed: usize,
+}
+
+#[pymethods]
+impl ProviderStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "ProviderStats(provider_name={:?}, state={:?}, pending={}, in_flight={}, completed={}, shed={})",
+            self.provider_name, self.state, self.pending, self.in_flight, self.completed, self.shed
+        )
+    }
+}
+
+// What the dispatcher knows about a provider; pending requests are read from its limiter
+pub struct ProviderStatus {
+    pub provider: Arc<dyn LLMProvider>,
+    pub state: &'static str,
+    // Sent and not finished yet, pending or in flight
+    pub assigned: usize,
+    pub completed: usize,
+    pub shed: usize,
+}
+
+impl ProviderStatus {
+    pub fn new(provider: Arc<dyn LLMProvider>) -> Self {
+        Self { provider, state: "active", assigned: 0, completed: 0, shed: 0 }
+    }
+
+    // Requests held back by the provider's own limits; one just handed to the runtime
+    // counts as in flight until it reaches them
+    pub fn pending(&self) -> usize {
+        self.provider.limits().map_or(0, |limits| limits.waiting()).min(self.assigned)
+    }
+
+    fn snapshot(&self) -> ProviderStats {
+        let pending = self.pending();
+        ProviderStats {
+            provider_name: self.provider.display_name(),
+            state: self.state.to_string(),
+            pending,
+            in_flight: self.assigned - pending,
+            completed: self.completed,
+            shed: self.shed,
+        }
+    }
+}
+
+pub type SharedStatus = Arc<Mutex<Vec<ProviderStatus>>>;
+
+pub fn snapshot(status: &SharedStatus) -> Vec<ProviderStats> {
+    status.lock().unwrap().iter().map(ProviderStatus::snapshot).collect()
+}
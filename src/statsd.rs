@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::fmt::Write;
+
+use crate::status::Status;
+use crate::RequestMetrics;
+
+// The StatsD agent's usual port, for an address given without one
+const DEFAULT_PORT: u16 = 8125;
+
+// Sends a few counters and timings per result to a StatsD agent over UDP, in DogStatsD's
+// format so each carries provider, model and status tags along with the caller's. Every
+// result goes out as one datagram the moment it is final; a send that fails is dropped,
+// as StatsD clients do, rather than holding up the run.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    // The caller's tags, already formatted, sent with every metric
+    tags: String,
+}
+
+impl StatsdSink {
+    // `address` is "host" or "host:port"; an IPv6 host needs its brackets
+    pub fn connect(address: &str, prefix: &str, tags: &BTreeMap<String, String>) -> io::Result<Self> {
+        let target = if address.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+            address.to_socket_addrs()?.next()
+        } else {
+            (address.trim_start_matches('[').trim_end_matches(']'), DEFAULT_PORT).to_socket_addrs()?.next()
+        };
+        let target = target.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} didn't resolve", address)))?;
+        let local = match target {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        socket.set_nonblocking(true)?;
+        let prefix = if prefix.is_empty() || prefix.ends_with('.') { prefix.to_string() } else { format!("{}.", prefix) };
+        let tags = tags.iter().map(|(key, value)| tag(key, value)).collect::<Vec<_>>().join(",");
+        Ok(Self { socket, prefix, tags })
+    }
+
+    // A final result; `model` is the one it was sent to
+    pub fn record(&self, metrics: &RequestMetrics, model: Option<&str>) {
+        let mut tags = vec![tag("status", metrics.status.as_str())];
+        if !metrics.provider_name.is_empty() {
+            tags.push(tag("provider", &metrics.provider_name));
+        }
+        if let Some(model) = metrics.model.as_deref().or(model) {
+            tags.push(tag("model", model));
+        }
+        if let Some(category) = &metrics.error_category {
+            tags.push(tag("error_category", category));
+        }
+        if !self.tags.is_empty() {
+            tags.push(self.tags.clone());
+        }
+        let tags = tags.join(",");
+
+        let mut packet = String::new();
+        let mut metric = |name: &str, value: f64, kind: &str| {
+            let _ = writeln!(packet, "{}{}:{}|{}|#{}", self.prefix, name, value, kind, tags);
+        };
+        metric("requests", 1.0, "c");
+        // Skipped and cancelled requests never got an answer to time
+        if !matches!(metrics.status, Status::Skipped | Status::Cancelled) {
+            metric("latency", metrics.latency_ms, "ms");
+            metric("queue_time", metrics.queue_ms, "ms");
+        }
+        if let Some(ttft_ms) = metrics.ttft_ms {
+            metric("ttft", ttft_ms, "ms");
+        }
+        if metrics.prompt_tokens > 0 {
+            metric("prompt_tokens", metrics.prompt_tokens as f64, "c");
+        }
+        if metrics.completion_tokens > 0 {
+            metric("completion_tokens", metrics.completion_tokens as f64, "c");
+        }
+        if metrics.retries > 0 {
+            metric("retries", metrics.retries as f64, "c");
+        }
+        if let Some(cost_usd) = metrics.cost_usd {
+            metric("cost_usd", cost_usd, "c");
+        }
+        packet.pop();
+        let _ = self.socket.send(packet.as_bytes());
+    }
+}
+
+// "key:value", with the characters DogStatsD uses as separators replaced
+fn tag(key: &str, value: &str) -> String {
+    let clean = |text: &str| text.replace([',', '|', '#', '\n'], "_");
+    format!("{}:{}", clean(key), clean(value))
+}
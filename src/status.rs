@@ -0,0 +1,49 @@
+use std::fmt;
+
+// How a request ended, as its result reports it. Every request gets exactly one result,
+// whatever its outcome; Python sees the lowercase name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    Ok,
+    // Failed for any reason but the two below; error_category narrows it down
+    Error,
+    Timeout,
+    // Still rate limited once the retries ran out
+    RateLimited,
+    // Aborted in flight or before it was sent, by the caller or an interrupted run
+    Cancelled,
+    // Never sent: excluded by the caller, or left over once a run budget was reached
+    Skipped,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Error => "error",
+            Self::Timeout => "timeout",
+            Self::RateLimited => "rate_limited",
+            Self::Cancelled => "cancelled",
+            Self::Skipped => "skipped",
+        }
+    }
+
+    // A failed request's status, from its error category
+    pub fn of_failure(category: &str) -> Self {
+        match category {
+            "timeout" => Self::Timeout,
+            "rate_limit" => Self::RateLimited,
+            _ => Self::Error,
+        }
+    }
+
+    pub fn is_failure(self) -> bool {
+        matches!(self, Self::Error | Self::Timeout | Self::RateLimited)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+
+type StorageResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+// Key-value blob store behind the persistent subsystems (currently the artifact store).
+// Keys are '/'-separated paths such as "ab/abcdef.txt". Calls block, so async code should
+// go through spawn_blocking.
+pub trait Storage: Send + Sync {
+    fn get(&self, key: &str) -> StorageResult<Option<Vec<u8>>>;
+    fn put(&self, key: &str, value: &[u8]) -> StorageResult<()>;
+    // Keys starting with `prefix`, sorted
+    fn list(&self, prefix: &str) -> StorageResult<Vec<String>>;
+
+    fn contains(&self, key: &str) -> StorageResult<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    // Local file holding `key`, for backends that have one
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+// Pick a backend from a location: "sqlite:///path/to/db.sqlite", "redis://host:port/db"
+// (with the redis feature) or a plain directory
+pub fn open_storage(location: &str) -> StorageResult<Box<dyn Storage>> {
+    if let Some(path) = location.strip_prefix("sqlite://") {
+        return Ok(Box::new(SqliteStorage::open(Path::new(path))?));
+    }
+    if location.starts_with("redis://") || location.starts_with("rediss://") {
+        #[cfg(feature = "redis")]
+        return Ok(Box::new(RedisStorage::open(location)?));
+        #[cfg(not(feature = "redis"))]
+        return Err("Redis storage needs axicontraves built with the redis feature".into());
+    }
+    Ok(Box::new(FsStorage::new(PathBuf::from(location.strip_prefix("file://").unwrap_or(location)))))
+}
+
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn collect(&self, directory: &Path, keys: &mut Vec<String>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.collect(&path, keys)?;
+            } else if path.extension().and_then(|extension| extension.to_str()) != Some("tmp") {
+                let relative = path.strip_prefix(&self.root).expect("walk stays under root");
+                keys.push(relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Storage for FsStorage {
+    fn get(&self, key: &str) -> StorageResult<Option<Vec<u8>>> {
+        match std::fs::read(self.root.join(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> StorageResult<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write-then-rename so concurrent writers of the same key never expose a partial file
+        let staging = path.with_extension(format!("{}.tmp", rand::random::<u32>()));
+        std::fs::write(&staging, value)?;
+        std::fs::rename(&staging, &path)?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> StorageResult<Vec<String>> {
+        let mut keys = Vec::new();
+        if self.root.is_dir() {
+            self.collect(&self.root, &mut keys)?;
+        }
+        keys.retain(|key| key.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn contains(&self, key: &str) -> StorageResult<bool> {
+        Ok(self.root.join(key).is_file())
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        Some(self.root.join(key))
+    }
+}
+
+// Single-table SQLite database; one connection shared behind a mutex
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> StorageResult<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS entries (key TEXT PRIMARY KEY, value BLOB NOT NULL);",
+        )?;
+        Ok(Self { connection: Mutex::new(connection) })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn get(&self, key: &str) -> StorageResult<Option<Vec<u8>>> {
+        let connection = self.connection.lock().unwrap();
+        Ok(connection
+            .query_row("SELECT value FROM entries WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> StorageResult<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute("INSERT OR REPLACE INTO entries (key, value) VALUES (?1, ?2)", params![key, value])?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> StorageResult<Vec<String>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT key FROM entries WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key")?;
+        let keys = statement.query_map(params![prefix], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(keys)
+    }
+}
+
+#[cfg(feature = "redis")]
+pub struct RedisStorage {
+    client: redis::Client,
+}
+
+#[cfg(feature = "redis")]
+impl RedisStorage {
+    pub fn open(url: &str) -> StorageResult<Self> {
+        Ok(Self { client: redis::Client::open(url)? })
+    }
+}
+
+#[cfg(feature = "redis")]
+impl Storage for RedisStorage {
+    fn get(&self, key: &str) -> StorageResult<Option<Vec<u8>>> {
+        let mut connection = self.client.get_connection()?;
+        Ok(redis::cmd("GET").arg(key).query(&mut connection)?)
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> StorageResult<()> {
+        let mut connection = self.client.get_connection()?;
+        redis::cmd("SET").arg(key).arg(value).query::<()>(&mut connection)?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> StorageResult<Vec<String>> {
+        let mut connection = self.client.get_connection()?;
+        let pattern = format!("{}*", prefix.replace('\\', "\\\\").replace('*', "\\*").replace('?', "\\?").replace('[', "\\["));
+        let mut command = redis::cmd("SCAN");
+        command.cursor_arg(0).arg("MATCH").arg(pattern);
+        let mut keys: Vec<String> = command.iter::<String>(&mut connection)?.collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
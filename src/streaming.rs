@@ -0,0 +1,280 @@
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use futures::StreamExt;
+use regex::Regex;
+use serde_json::json;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::time::timeout;
+
+use crate::summary::percentile;
+use crate::RequestMetrics;
+
+// Incremental server-sent-events parser yielding the payload of each `data:` line
+#[derive(Default)]
+pub struct SseParser {
+    // Raw bytes, since network chunks may split a multi-byte character
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(newline) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if let Some(data) = line.strip_prefix("data:") {
+                events.push(data.trim_start().to_string());
+            }
+        }
+        events
+    }
+}
+
+// finish_reason of a stream cut short by a request's stop_regex. The provider never sends
+// its final usage, so token counts for these are estimated.
+pub const STOPPED_BY_PATTERN: &str = "stop_regex";
+
+// Folds chat.completion.chunk deltas (or Anthropic message events) back into the shape of
+// a non-streaming chat completion, so the regular response handling applies unchanged.
+// Usage objects from successive events are merged, since Anthropic splits input and
+// output counts across message_start and message_delta.
+#[derive(Default)]
+pub struct StreamAccumulator {
+    contents: Vec<String>,
+    finish_reasons: Vec<Option<String>>,
+    // Tool calls arrive as fragments keyed by their position; arguments are streamed text
+    tool_calls: Vec<Vec<serde_json::Value>>,
+    // Per-token logprob entries, when requested
+    logprobs: Vec<Vec<serde_json::Value>>,
+    usage: serde_json::Map<String, serde_json::Value>,
+    model: Option<String>,
+    system_fingerprint: Option<String>,
+}
+
+impl StreamAccumulator {
+    // Returns the text appended to the first choice by this chunk
+    pub fn push(&mut self, chunk: &serde_json::Value) -> Option<String> {
+        match chunk["type"].as_str() {
+            Some("message_start") => {
+                self.model = chunk["message"]["model"].as_str().map(str::to_string);
+                self.merge_usage(&chunk["message"]["usage"]);
+                return None;
+            }
+            Some("content_block_delta") => {
+                // A forced schema tool streams its input as JSON fragments; that input is
+                // the answer, so it reads as text like any other delta
+                let delta = &chunk["delta"];
+                let text = delta["text"].as_str().or_else(|| delta["partial_json"].as_str())?;
+                self.ensure_choice(0);
+                self.contents[0].push_str(text);
+                return Some(text.to_string());
+            }
+            Some("message_delta") => {
+                self.ensure_choice(0);
+                self.finish_reasons[0] = chunk["delta"]["stop_reason"].as_str().map(str::to_string);
+                self.merge_usage(&chunk["usage"]);
+                return None;
+            }
+            Some(_) => return None,
+            None => {}
+        }
+        if let Some(model) = chunk["model"].as_str() {
+            self.model = Some(model.to_string());
+        }
+        if let Some(fingerprint) = chunk["system_fingerprint"].as_str() {
+            self.system_fingerprint = Some(fingerprint.to_string());
+        }
+        self.merge_usage(&chunk["usage"]);
+        let mut first_delta = None;
+        for choice in chunk["choices"].as_array().into_iter().flatten() {
+            let index = choice["index"].as_u64().unwrap_or(0) as usize;
+            self.ensure_choice(index);
+            if let Some(delta) = choice["delta"]["content"].as_str() {
+                self.contents[index].push_str(delta);
+                if index == 0 {
+                    first_delta = Some(delta.to_string());
+                }
+            }
+            if let Some(tokens) = choice["logprobs"]["content"].as_array() {
+                self.logprobs[index].extend(tokens.iter().cloned());
+            }
+            for fragment in choice["delta"]["tool_calls"].as_array().into_iter().flatten() {
+                self.push_tool_call(index, fragment);
+            }
+            if let Some(reason) = choice["finish_reason"].as_str() {
+                self.finish_reasons[index] = Some(reason.to_string());
+            }
+        }
+        first_delta
+    }
+
+    // End the first choice early because its text matched a request's stop_regex
+    fn stop_on_pattern(&mut self, pattern: &Regex) -> bool {
+        if !self.contents.first().is_some_and(|text| pattern.is_match(text)) {
+            return false;
+        }
+        self.finish_reasons[0] = Some(STOPPED_BY_PATTERN.to_string());
+        true
+    }
+
+    fn ensure_choice(&mut self, index: usize) {
+        if self.contents.len() <= index {
+            self.contents.resize(index + 1, String::new());
+            self.finish_reasons.resize(index + 1, None);
+            self.tool_calls.resize(index + 1, Vec::new());
+            self.logprobs.resize(index + 1, Vec::new());
+        }
+    }
+
+    fn push_tool_call(&mut self, choice: usize, fragment: &serde_json::Value) {
+        let calls = &mut self.tool_calls[choice];
+        let position = fragment["index"].as_u64().unwrap_or(0) as usize;
+        while calls.len() <= position {
+            calls.push(json!({"id": "", "type": "function", "function": {"name": "", "arguments": ""}}));
+        }
+        let call = &mut calls[position];
+        if let Some(id) = fragment["id"].as_str() {
+            call["id"] = json!(id);
+        }
+        for field in ["name", "arguments"] {
+            if let Some(part) = fragment["function"][field].as_str() {
+                let joined = format!("{}{}", call["function"][field].as_str().unwrap_or_default(), part);
+                call["function"][field] = json!(joined);
+            }
+        }
+    }
+
+    fn merge_usage(&mut self, usage: &serde_json::Value) {
+        if let Some(usage) = usage.as_object() {
+            self.usage.extend(usage.clone());
+        }
+    }
+
+    pub fn into_response(self) -> serde_json::Value {
+        let choices: Vec<serde_json::Value> = self
+            .contents
+            .into_iter()
+            .zip(self.finish_reasons)
+            .zip(self.tool_calls)
+            .zip(self.logprobs)
+            .enumerate()
+            .map(|(index, (((content, finish_reason), tool_calls), logprobs))| {
+                let mut message = json!({"role": "assistant", "content": content});
+                if !tool_calls.is_empty() {
+                    message["tool_calls"] = json!(tool_calls);
+                }
+                let mut choice = json!({"index": index, "message": message, "finish_reason": finish_reason});
+                if !logprobs.is_empty() {
+                    choice["logprobs"] = json!({"content": logprobs});
+                }
+                choice
+            })
+            .collect();
+        json!({
+            "choices": choices,
+            "usage": self.usage,
+            "model": self.model,
+            "system_fingerprint": self.system_fingerprint,
+        })
+    }
+}
+
+// When each piece of the first choice's text arrived, counted from when the request was
+// sent. Each content delta is taken as one token, which is how OpenAI-compatible servers
+// and Anthropic stream them.
+pub struct StreamTiming {
+    sent: Instant,
+    arrivals: Vec<Duration>,
+}
+
+impl StreamTiming {
+    pub fn new(sent: Instant) -> Self {
+        Self { sent, arrivals: Vec::new() }
+    }
+
+    fn record(&mut self) {
+        self.arrivals.push(self.sent.elapsed());
+    }
+
+    // Time to first token, inter-token latency and decode speed; left unset for a stream
+    // that produced no text
+    pub fn apply(&self, metrics: &mut RequestMetrics) {
+        let (Some(&first), Some(&last)) = (self.arrivals.first(), self.arrivals.last()) else { return };
+        metrics.ttft_ms = Some(millis(first));
+        let mut gaps: Vec<f64> = self.arrivals.windows(2).map(|pair| millis(pair[1] - pair[0])).collect();
+        if !gaps.is_empty() {
+            metrics.itl_mean_ms = Some(gaps.iter().sum::<f64>() / gaps.len() as f64);
+            gaps.sort_by(f64::total_cmp);
+            metrics.itl_p50_ms = Some(percentile(&gaps, 0.5));
+            metrics.itl_p95_ms = Some(percentile(&gaps, 0.95));
+        }
+        let decoding = (last - first).as_secs_f64();
+        if decoding > 0.0 && metrics.completion_tokens > 1 {
+            metrics.output_tokens_per_second = Some((metrics.completion_tokens - 1) as f64 / decoding);
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Read a streaming response to completion, appending first-choice tokens to `sink` as
+// they arrive (flushed per chunk so partial output survives a crash). With a `stop`
+// pattern the stream is dropped, cancelling the request, as soon as the first choice's
+// text matches. Returns the reassembled response, the number of body bytes received and
+// when its text arrived relative to `sent`.
+pub async fn consume_stream(
+    response: reqwest::Response,
+    sink: &Path,
+    stop: Option<&Regex>,
+    // Give up when the server sends nothing for this long
+    read_timeout: Option<Duration>,
+    sent: Instant,
+) -> Result<(serde_json::Value, usize, StreamTiming), Box<dyn Error + Send + Sync>> {
+    if let Some(parent) = sink.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut file = File::create(sink).await?;
+    let mut parser = SseParser::default();
+    let mut accumulator = StreamAccumulator::default();
+    let mut received = 0;
+    let mut timing = StreamTiming::new(sent);
+    let mut body = response.bytes_stream();
+    'stream: loop {
+        let next = match read_timeout {
+            Some(limit) => timeout(limit, body.next())
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, format!("Stream stalled: no data for {:.1}s", limit.as_secs_f64()))
+                })?,
+            None => body.next().await,
+        };
+        let Some(chunk) = next else { break };
+        let chunk = chunk?;
+        received += chunk.len();
+        for data in parser.feed(&chunk) {
+            if data == "[DONE]" {
+                break 'stream;
+            }
+            let event: serde_json::Value = serde_json::from_str(&data)?;
+            if let Some(error) = event.get("error") {
+                return Err(format!("Stream error: {}", error).into());
+            }
+            if let Some(delta) = accumulator.push(&event) {
+                timing.record();
+                file.write_all(delta.as_bytes()).await?;
+                file.flush().await?;
+                if stop.is_some_and(|pattern| accumulator.stop_on_pattern(pattern)) {
+                    break 'stream;
+                }
+            }
+        }
+    }
+    Ok((accumulator.into_response(), received, timing))
+}
@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, HashMap};
+use pyo3::prelude::*;
+
+use crate::histogram::LatencyHistogram;
+use crate::status::Status;
+use crate::throughput::ThroughputSample;
+use crate::RequestMetrics;
+
+// Run-level figures computed from the results, so every run reports the same percentiles
+// and rates without the caller re-deriving them. Latency percentiles cover successful
+// requests only; a run without any has them at 0.
+#[pyclass]
+#[derive(Clone)]
+pub struct RunSummary {
+    #[pyo3(get)]
+    pub requests: usize,
+    #[pyo3(get)]
+    pub succeeded: usize,
+    // Every failure: status "error", "timeout" or "rate_limited"; the last two are also
+    // counted on their own
+    #[pyo3(get)]
+    pub failed: usize,
+    #[pyo3(get)]
+    pub timed_out: usize,
+    #[pyo3(get)]
+    pub rate_limited: usize,
+    #[pyo3(get)]
+    pub cancelled: usize,
+    #[pyo3(get)]
+    pub skipped: usize,
+    #[pyo3(get)]
+    pub prompt_tokens: usize,
+    #[pyo3(get)]
+    pub completion_tokens: usize,
+    #[pyo3(get)]
+    pub duration_seconds: f64,
+    #[pyo3(get)]
+    pub requests_per_second: f64,
+    // Prompt and completion tokens together
+    #[pyo3(get)]
+    pub tokens_per_second: f64,
+    #[pyo3(get)]
+    pub mean_latency_ms: f64,
+    #[pyo3(get)]
+    pub p50_latency_ms: f64,
+    #[pyo3(get)]
+    pub p90_latency_ms: f64,
+    #[pyo3(get)]
+    pub p95_latency_ms: f64,
+    #[pyo3(get)]
+    pub p99_latency_ms: f64,
+    // Every successful request's latency_ms, and ttft_ms for the streamed ones, for tail
+    // percentiles past p99
+    #[pyo3(get)]
+    pub latency_histogram: LatencyHistogram,
+    #[pyo3(get)]
+    pub ttft_histogram: LatencyHistogram,
+    // Sum of the priced requests' cost_usd; None when none could be priced
+    #[pyo3(get)]
+    pub cost_usd: Option<f64>,
+    // Failed requests per error category ("timeout", "rate_limit", "server_error", ...,
+    // see RequestMetrics.error_category); categories without failures are left out
+    #[pyo3(get)]
+    pub errors: HashMap<String, usize>,
+    // Breakdown by provider_name ("name:base_url"), for the requests a provider answered
+    // or failed; requests never sent to one aren't counted
+    #[pyo3(get)]
+    pub providers: HashMap<String, ProviderSummary>,
+    // Throughput over time, with a sample_interval set on the run; oldest first
+    #[pyo3(get)]
+    pub samples: Vec<ThroughputSample>,
+}
+
+// One provider's share of a run
+#[pyclass]
+#[derive(Clone)]
+pub struct ProviderSummary {
+    #[pyo3(get)]
+    pub provider_name: String,
+    #[pyo3(get)]
+    pub requests: usize,
+    #[pyo3(get)]
+    pub succeeded: usize,
+    #[pyo3(get)]
+    pub failed: usize,
+    // Failed out of those that finished, ok or failed
+    #[pyo3(get)]
+    pub error_rate: f64,
+    #[pyo3(get)]
+    pub errors: HashMap<String, usize>,
+    #[pyo3(get)]
+    pub prompt_tokens: usize,
+    #[pyo3(get)]
+    pub completion_tokens: usize,
+    #[pyo3(get)]
+    pub request_bytes: usize,
+    #[pyo3(get)]
+    pub response_bytes: usize,
+    #[pyo3(get)]
+    pub mean_latency_ms: f64,
+    #[pyo3(get)]
+    pub p50_latency_ms: f64,
+    #[pyo3(get)]
+    pub p90_latency_ms: f64,
+    #[pyo3(get)]
+    pub p95_latency_ms: f64,
+    #[pyo3(get)]
+    pub p99_latency_ms: f64,
+    #[pyo3(get)]
+    pub cost_usd: Option<f64>,
+}
+
+// Sorted latencies of the successful requests in a group
+struct Latencies(Vec<f64>);
+
+impl Latencies {
+    fn of<'a>(results: impl Iterator<Item = &'a RequestMetrics>) -> Self {
+        let mut latencies: Vec<f64> = results.filter(|metrics| metrics.status == Status::Ok).map(|metrics| metrics.latency_ms).collect();
+        latencies.sort_by(f64::total_cmp);
+        Self(latencies)
+    }
+
+    fn mean(&self) -> f64 {
+        if self.0.is_empty() { 0.0 } else { self.0.iter().sum::<f64>() / self.0.len() as f64 }
+    }
+
+    fn at(&self, fraction: f64) -> f64 {
+        if self.0.is_empty() { 0.0 } else { percentile(&self.0, fraction) }
+    }
+}
+
+fn error_counts<'a>(results: impl Iterator<Item = &'a RequestMetrics>) -> HashMap<String, usize> {
+    let mut errors = HashMap::new();
+    for metrics in results.filter(|metrics| metrics.status.is_failure()) {
+        let category = metrics.error_category.as_deref().unwrap_or("other");
+        *errors.entry(category.to_string()).or_default() += 1;
+    }
+    errors
+}
+
+fn total_cost<'a>(results: impl Iterator<Item = &'a RequestMetrics>) -> Option<f64> {
+    results.filter_map(|metrics| metrics.cost_usd).reduce(|total, cost| total + cost)
+}
+
+pub fn summarize(results: &[RequestMetrics], duration_seconds: f64, samples: Vec<ThroughputSample>) -> RunSummary {
+    let count = |status: Status| results.iter().filter(|metrics| metrics.status == status).count();
+    let latencies = Latencies::of(results.iter());
+    let prompt_tokens = results.iter().map(|metrics| metrics.prompt_tokens).sum();
+    let completion_tokens = results.iter().map(|metrics| metrics.completion_tokens).sum();
+    let rate = |amount: usize| if duration_seconds > 0.0 { amount as f64 / duration_seconds } else { 0.0 };
+    let mut by_provider: HashMap<&str, Vec<&RequestMetrics>> = HashMap::new();
+    for metrics in results.iter().filter(|metrics| !metrics.provider_name.is_empty()) {
+        by_provider.entry(&metrics.provider_name).or_default().push(metrics);
+    }
+    RunSummary {
+        requests: results.len(),
+        succeeded: latencies.0.len(),
+        failed: results.iter().filter(|metrics| metrics.status.is_failure()).count(),
+        timed_out: count(Status::Timeout),
+        rate_limited: count(Status::RateLimited),
+        cancelled: count(Status::Cancelled),
+        skipped: count(Status::Skipped),
+        prompt_tokens,
+        completion_tokens,
+        duration_seconds,
+        requests_per_second: rate(results.len()),
+        tokens_per_second: rate(prompt_tokens + completion_tokens),
+        mean_latency_ms: latencies.mean(),
+        p50_latency_ms: latencies.at(0.5),
+        p90_latency_ms: latencies.at(0.9),
+        p95_latency_ms: latencies.at(0.95),
+        p99_latency_ms: latencies.at(0.99),
+        latency_histogram: LatencyHistogram::of(latencies.0.iter().copied()),
+        ttft_histogram: LatencyHistogram::of(
+            results.iter().filter(|metrics| metrics.status == Status::Ok).filter_map(|metrics| metrics.ttft_ms),
+        ),
+        cost_usd: total_cost(results.iter()),
+        errors: error_counts(results.iter()),
+        providers: by_provider
+            .into_iter()
+            .map(|(name, results)| (name.to_string(), summarize_provider(name, &results)))
+            .collect(),
+        samples,
+    }
+}
+
+fn summarize_provider(provider_name: &str, results: &[&RequestMetrics]) -> ProviderSummary {
+    let latencies = Latencies::of(results.iter().copied());
+    let failed = results.iter().filter(|metrics| metrics.status.is_failure()).count();
+    let finished = latencies.0.len() + failed;
+    let sum = |field: fn(&RequestMetrics) -> usize| results.iter().map(|metrics| field(metrics)).sum();
+    ProviderSummary {
+        provider_name: provider_name.to_string(),
+        requests: results.len(),
+        succeeded: latencies.0.len(),
+        failed,
+        error_rate: if finished > 0 { failed as f64 / finished as f64 } else { 0.0 },
+        errors: error_counts(results.iter().copied()),
+        prompt_tokens: sum(|metrics| metrics.prompt_tokens),
+        completion_tokens: sum(|metrics| metrics.completion_tokens),
+        request_bytes: sum(|metrics| metrics.request_bytes),
+        response_bytes: sum(|metrics| metrics.response_bytes),
+        mean_latency_ms: latencies.mean(),
+        p50_latency_ms: latencies.at(0.5),
+        p90_latency_ms: latencies.at(0.9),
+        p95_latency_ms: latencies.at(0.95),
+        p99_latency_ms: latencies.at(0.99),
+        cost_usd: total_cost(results.iter().copied()),
+    }
+}
+
+// Nearest-rank percentile of sorted, non-empty values
+pub fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    let rank = ((sorted.len() as f64 * fraction).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+#[pymethods]
+impl RunSummary {
+    fn __repr__(&self) -> String {
+        format!(
+            "RunSummary(requests={}, succeeded={}, failed={}, errors={:?}, duration_seconds={:.2}, requests_per_second={:.2}, \
+             tokens_per_second={:.1}, p50_latency_ms={:.0}, p95_latency_ms={:.0}, p99_latency_ms={:.0})",
+            self.requests,
+            self.succeeded,
+            self.failed,
+            sorted(&self.errors),
+            self.duration_seconds,
+            self.requests_per_second,
+            self.tokens_per_second,
+            self.p50_latency_ms,
+            self.p95_latency_ms,
+            self.p99_latency_ms
+        )
+    }
+}
+
+#[pymethods]
+impl ProviderSummary {
+    fn __repr__(&self) -> String {
+        format!(
+            "ProviderSummary(provider_name={:?}, requests={}, failed={}, error_rate={:.3}, p50_latency_ms={:.0}, \
+             p95_latency_ms={:.0})",
+            self.provider_name, self.requests, self.failed, self.error_rate, self.p50_latency_ms, self.p95_latency_ms
+        )
+    }
+}
+
+fn sorted(errors: &HashMap<String, usize>) -> BTreeMap<&str, usize> {
+    errors.iter().map(|(category, &count)| (category.as_str(), count)).collect()
+}
+
+// Summarize a result list from a run that took `duration_seconds`, with the throughput
+// samples taken during it
+#[pyfunction]
+#[pyo3(signature = (results, duration_seconds, samples=None))]
+pub fn summarize_results(results: Vec<RequestMetrics>, duration_seconds: f64, samples: Option<Vec<ThroughputSample>>) -> RunSummary {
+    summarize(&results, duration_seconds, samples.unwrap_or_default())
+}
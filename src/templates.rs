@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use minijinja::{Environment, UndefinedBehavior, Value};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+
+use crate::message::Message;
+use crate::sanitize::SanitizeReport;
+use crate::get_required_value;
+
+// Named prompt templates rendered in Rust, so a dataset can be submitted as
+// (template_name, variables) pairs instead of fully rendered prompts. A template is either
+// a string, rendered into a single user message, or a list of {"role", "content"} messages
+// whose contents are templates. Variables missing from a request are an error rather than
+// rendering as empty text.
+pub struct PromptTemplates {
+    env: Environment<'static>,
+    // Roles of a message-list template's messages (registered as "{name}#{position}"),
+    // None for a single-string template
+    shapes: HashMap<String, Option<Vec<String>>>,
+}
+
+impl PromptTemplates {
+    pub fn extract(templates: &PyDict) -> PyResult<Self> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        let mut shapes = HashMap::new();
+        for (name, template) in templates {
+            let name: String = name.extract()?;
+            let invalid = |e: minijinja::Error| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Template '{}': {}", name, e))
+            };
+            if let Ok(source) = template.downcast::<PyString>() {
+                env.add_template_owned(name.clone(), source.to_str()?.to_string()).map_err(invalid)?;
+                shapes.insert(name, None);
+                continue;
+            }
+            let mut roles = Vec::new();
+            for (position, message) in template.extract::<Vec<&PyDict>>()?.into_iter().enumerate() {
+                roles.push(get_required_value::<String>(message, "role")?);
+                let source: String = get_required_value(message, "content")?;
+                env.add_template_owned(format!("{}#{}", name, position), source).map_err(invalid)?;
+            }
+            shapes.insert(name, Some(roles));
+        }
+        Ok(Self { env, shapes })
+    }
+
+    pub fn render(
+        &self,
+        name: &str,
+        variables: &serde_json::Value,
+        mut sanitize: Option<&mut SanitizeReport>,
+    ) -> Result<Vec<Message>, String> {
+        let shape = self.shapes.get(name).ok_or_else(|| format!("Unknown template '{}'", name))?;
+        let context = Value::from_serialize(variables);
+        let mut render = |template: &str| {
+            let text = self
+                .env
+                .get_template(template)
+                .and_then(|template| template.render(&context))
+                .map_err(|e| format!("Template '{}': {}", name, e))?;
+            Ok::<_, String>(match sanitize.as_deref_mut() {
+                Some(report) => report.clean(text),
+                None => text,
+            })
+        };
+        match shape {
+            None => Ok(vec![Message::user(render(name)?)]),
+            Some(roles) => roles
+                .iter()
+                .enumerate()
+                .map(|(position, role)| Ok(Message::new(role, render(&format!("{}#{}", name, position))?)))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use pyo3::prelude::*;
+
+use crate::RequestMetrics;
+
+// Where the run stood at one point in time, for plotting throughput over the run. The rates
+// cover the interval since the previous sample.
+#[pyclass]
+#[derive(Clone)]
+pub struct ThroughputSample {
+    // Since the first request was sent
+    #[pyo3(get)]
+    pub elapsed_seconds: f64,
+    #[pyo3(get)]
+    pub in_flight: usize,
+    // Results in so far
+    #[pyo3(get)]
+    pub completed: usize,
+    #[pyo3(get)]
+    pub requests_per_second: f64,
+    // Prompt and completion tokens together
+    #[pyo3(get)]
+    pub tokens_per_second: f64,
+}
+
+#[pymethods]
+impl ThroughputSample {
+    fn __repr__(&self) -> String {
+        format!(
+            "ThroughputSample(elapsed_seconds={:.1}, in_flight={}, completed={}, requests_per_second={:.2}, \
+             tokens_per_second={:.1})",
+            self.elapsed_seconds, self.in_flight, self.completed, self.requests_per_second, self.tokens_per_second
+        )
+    }
+}
+
+pub type SharedSamples = Arc<Mutex<Vec<ThroughputSample>>>;
+
+// Takes a sample every `interval` once the run has started
+pub struct Sampler {
+    interval: Duration,
+    started: Option<Instant>,
+    completed: usize,
+    tokens: usize,
+    // Counts and time of the previous sample
+    last: (usize, usize, Instant),
+    samples: SharedSamples,
+}
+
+impl Sampler {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, started: None, completed: 0, tokens: 0, last: (0, 0, Instant::now()), samples: SharedSamples::default() }
+    }
+
+    pub fn start(&mut self) {
+        let now = Instant::now();
+        self.started.get_or_insert(now);
+        self.last.2 = now;
+    }
+
+    // Results that just came in
+    pub fn count(&mut self, results: &[RequestMetrics]) {
+        self.completed += results.len();
+        self.tokens += results.iter().map(|metrics| metrics.prompt_tokens + metrics.completion_tokens).sum::<usize>();
+    }
+
+    // When the next sample is due; None before the run has started
+    pub fn due(&self) -> Option<Instant> {
+        self.started.map(|_| self.last.2 + self.interval)
+    }
+
+    pub fn sample(&mut self, in_flight: usize) {
+        let Some(started) = self.started else { return };
+        let now = Instant::now();
+        let (completed, tokens, at) = self.last;
+        let seconds = now.duration_since(at).as_secs_f64();
+        let rate = |amount: usize| if seconds > 0.0 { amount as f64 / seconds } else { 0.0 };
+        self.samples.lock().unwrap().push(ThroughputSample {
+            elapsed_seconds: now.duration_since(started).as_secs_f64(),
+            in_flight,
+            completed: self.completed,
+            requests_per_second: rate(self.completed - completed),
+            tokens_per_second: rate(self.tokens - tokens),
+        });
+        self.last = (self.completed, self.tokens, now);
+    }
+
+    // Whether results came in since the last sample
+    pub fn has_unsampled(&self) -> bool {
+        self.completed > self.last.0
+    }
+
+    pub fn samples(&self) -> SharedSamples {
+        Arc::clone(&self.samples)
+    }
+}
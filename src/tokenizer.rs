@@ -0,0 +1,32 @@
+use std::sync::OnceLock;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+use crate::message::Message;
+
+// Token counting for responses that come back without usage (proxies often strip it).
+// Models tiktoken knows use their own encoding; anything else falls back to cl100k_base,
+// which is close enough for other BPE vocabularies to keep totals meaningful.
+fn encoding(model: &str) -> &'static CoreBPE {
+    static CL100K: OnceLock<CoreBPE> = OnceLock::new();
+    static O200K: OnceLock<CoreBPE> = OnceLock::new();
+    match get_tokenizer(model) {
+        Some(Tokenizer::O200kBase) => O200K.get_or_init(|| tiktoken_rs::o200k_base().expect("bundled o200k_base encoding")),
+        _ => CL100K.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k_base encoding")),
+    }
+}
+
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    encoding(model).encode_ordinary(text).len()
+}
+
+// Prompt tokens for a chat request, counting the per-message framing the way OpenAI's
+// chat format does: 3 tokens per message plus 3 priming the assistant's reply
+pub fn count_prompt_tokens(model: &str, messages: &[Message]) -> usize {
+    let bpe = encoding(model);
+    let per_message: usize = messages
+        .iter()
+        .map(|message| 3 + bpe.encode_ordinary(&message.role).len() + bpe.encode_ordinary(&message.text()).len())
+        .sum();
+    per_message + 3
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+use serde_json::json;
+
+use crate::message::Message;
+use crate::{add_counts, json_to_py, ChatRequest, LLMProvider, RequestMetrics};
+
+// Python callables the model may invoke, by tool name. A request with `tools` runs as a
+// bounded agent loop: each response's tool calls are executed, their results appended to
+// the conversation and the request re-sent until the model answers without calling a tool.
+pub struct ToolRunner {
+    callbacks: HashMap<String, PyObject>,
+    max_rounds: usize,
+}
+
+impl ToolRunner {
+    pub fn new(callbacks: HashMap<String, PyObject>, max_rounds: usize) -> Self {
+        Self { callbacks, max_rounds }
+    }
+
+    // Runs on a blocking thread since it needs the GIL. Failures are reported back to the
+    // model as the tool's result rather than failing the request.
+    fn invoke(&self, name: &str, arguments: &str) -> String {
+        let Some(callback) = self.callbacks.get(name) else {
+            return format!("Error: unknown tool '{}'", name);
+        };
+        let arguments: serde_json::Value = match serde_json::from_str(if arguments.is_empty() { "{}" } else { arguments }) {
+            Ok(arguments) => arguments,
+            Err(e) => return format!("Error: invalid arguments: {}", e),
+        };
+        Python::with_gil(|py| {
+            let result = json_to_py(py, &arguments).and_then(|arguments| {
+                let kwargs: &PyDict = arguments.as_ref(py).downcast()?;
+                let result = callback.call(py, (), Some(kwargs))?;
+                let result = result.as_ref(py);
+                if let Ok(text) = result.downcast::<PyString>() {
+                    return Ok(text.to_str()?.to_string());
+                }
+                py.import("json")?.call_method1("dumps", (result,))?.extract::<String>()
+            });
+            result.unwrap_or_else(|e| format!("Error: {}", e.value(py)))
+        })
+    }
+}
+
+pub async fn run_tool_loop(
+    provider: &Arc<dyn LLMProvider>,
+    request: &ChatRequest,
+    runner: &Arc<ToolRunner>,
+) -> Result<RequestMetrics, Box<dyn Error + Send + Sync>> {
+    let mut conversation = request.clone();
+    let mut metrics = provider.send_chat_request(&conversation).await?;
+    let mut trace = Vec::new();
+    let mut rounds = 0;
+    while rounds < runner.max_rounds {
+        let Some(calls) = metrics.tool_calls.clone().filter(|calls| calls.as_array().is_some_and(|c| !c.is_empty())) else {
+            break;
+        };
+        rounds += 1;
+        let content = metrics.first_content().map(|content| content.text()).transpose()?;
+        conversation.messages.push(Message::tool_request(content, calls.clone()));
+        for call in calls.as_array().into_iter().flatten() {
+            let id = call["id"].as_str().unwrap_or_default().to_string();
+            let name = call["function"]["name"].as_str().unwrap_or_default().to_string();
+            let arguments = call["function"]["arguments"].as_str().unwrap_or_default().to_string();
+            let result = {
+                let runner = Arc::clone(runner);
+                let (name, arguments) = (name.clone(), arguments.clone());
+                tokio::task::spawn_blocking(move || runner.invoke(&name, &arguments)).await?
+            };
+            trace.push(json!({"id": id, "name": name, "arguments": arguments, "result": result}));
+            conversation.messages.push(Message::tool_result(id, result));
+        }
+        let mut next = provider.send_chat_request(&conversation).await?;
+        next.prompt_tokens += metrics.prompt_tokens;
+        next.completion_tokens += metrics.completion_tokens;
+        next.total_tokens += metrics.total_tokens;
+        next.request_bytes += metrics.request_bytes;
+        next.response_bytes += metrics.response_bytes;
+        next.cached_tokens = add_counts(next.cached_tokens, metrics.cached_tokens);
+        next.reasoning_tokens = add_counts(next.reasoning_tokens, metrics.reasoning_tokens);
+        next.audio_tokens = add_counts(next.audio_tokens, metrics.audio_tokens);
+        metrics = next;
+    }
+    metrics.tool_trace = trace;
+    metrics.tool_rounds = rounds;
+    Ok(metrics)
+}
@@ -0,0 +1,107 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::message::{ContentPart, ImageSource, Message, MessageContent, MessageFormat};
+
+// Size assumed for images whose dimensions can't be read locally (URLs, unknown formats):
+// a typical 1024px upload for OpenAI and Anthropic's recommended maximum for its formula
+const OPENAI_DEFAULT_SIZE: (u32, u32) = (1024, 1024);
+const ANTHROPIC_DEFAULT_SIZE: (u32, u32) = (1092, 1092);
+
+// Prompt tokens the provider charges for the image inputs of a conversation, following
+// each provider's published formula
+pub fn image_tokens(format: MessageFormat, messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .filter_map(|message| match &message.content {
+            MessageContent::Parts(parts) => Some(parts),
+            MessageContent::Text(_) => None,
+        })
+        .flatten()
+        .filter_map(|part| match part {
+            ContentPart::Image { source, detail } => Some(part_tokens(format, source, detail.as_deref())),
+            _ => None,
+        })
+        .sum()
+}
+
+fn part_tokens(format: MessageFormat, source: &ImageSource, detail: Option<&str>) -> usize {
+    let size = match source {
+        ImageSource::Base64 { data, .. } => STANDARD.decode(data).ok().and_then(|bytes| image_size(&bytes)),
+        ImageSource::Url(_) => None,
+    };
+    match format {
+        MessageFormat::OpenAI => openai_tokens(size.unwrap_or(OPENAI_DEFAULT_SIZE), detail),
+        MessageFormat::Anthropic => anthropic_tokens(size.unwrap_or(ANTHROPIC_DEFAULT_SIZE)),
+        MessageFormat::Gemini => gemini_tokens(size.unwrap_or(OPENAI_DEFAULT_SIZE)),
+    }
+}
+
+// 85 base tokens plus 170 per 512px tile, after fitting into 2048x2048 and scaling the
+// shortest side down to 768px; "low" detail is a flat 85
+fn openai_tokens((width, height): (u32, u32), detail: Option<&str>) -> usize {
+    if detail == Some("low") {
+        return 85;
+    }
+    let (mut width, mut height) = (width as f64, height as f64);
+    let fit = (2048.0 / width.max(height)).min(1.0);
+    width *= fit;
+    height *= fit;
+    let shrink = (768.0 / width.min(height)).min(1.0);
+    width *= shrink;
+    height *= shrink;
+    let tiles = (width / 512.0).ceil() * (height / 512.0).ceil();
+    85 + 170 * tiles as usize
+}
+
+// width * height / 750, after downscaling to a long edge of 1568px and about 1.15 megapixels
+fn anthropic_tokens((width, height): (u32, u32)) -> usize {
+    let (width, height) = (width as f64, height as f64);
+    let scale = (1568.0 / width.max(height)).min((1_150_000.0 / (width * height)).sqrt()).min(1.0);
+    ((width * scale) * (height * scale) / 750.0).ceil() as usize
+}
+
+// 258 tokens for images up to 384px, otherwise 258 per 768px tile
+fn gemini_tokens((width, height): (u32, u32)) -> usize {
+    if width <= 384 && height <= 384 {
+        return 258;
+    }
+    258 * (width.div_ceil(768) * height.div_ceil(768)) as usize
+}
+
+// Width and height from a PNG, GIF, WebP or JPEG header
+fn image_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    let u16_be = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32);
+    let u16_le = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32);
+    let u24_le = |at: usize| Some(u16_le(at)? | (*bytes.get(at + 2)? as u32) << 16);
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let u32_be = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+        return Some((u32_be(16)?, u32_be(20)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        return Some((u16_le(6)?, u16_le(8)?));
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return match bytes.get(12..16)? {
+            b"VP8 " => Some((u16_le(26)? & 0x3fff, u16_le(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((u24_le(24)? + 1, u24_le(27)? + 1)),
+            _ => None,
+        };
+    }
+    if bytes.starts_with(b"\xff\xd8") {
+        // Walk the segments up to the start-of-frame marker carrying the dimensions
+        let mut at = 2;
+        while *bytes.get(at)? == 0xff {
+            let marker = *bytes.get(at + 1)?;
+            if matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                return Some((u16_be(at + 7)?, u16_be(at + 5)?));
+            }
+            at += 2 + u16_be(at + 2)? as usize;
+        }
+    }
+    None
+}
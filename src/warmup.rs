@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+use crate::ratelimit::RateLimiter;
+use crate::{BatchProcessor, ChatRequest, LLMProvider, ResultOptions};
+
+// Throwaway traffic sent before the run proper so TLS handshakes, connection pool fill and
+// server-side cold starts don't show up in its results
+#[derive(Debug, Clone, Copy)]
+pub enum Warmup {
+    // This many requests to each provider
+    Requests(usize),
+    // Requests to every provider in turn until this much time has passed
+    Duration(Duration),
+}
+
+impl Warmup {
+    pub fn new(requests: Option<usize>, seconds: Option<f64>) -> Result<Option<Self>, String> {
+        match (requests, seconds) {
+            (Some(_), Some(_)) => Err("warmup_requests and warmup_seconds can't be combined".to_string()),
+            (Some(0), None) | (None, None) => Ok(None),
+            (Some(requests), None) => Ok(Some(Self::Requests(requests))),
+            (None, Some(seconds)) => match Duration::try_from_secs_f64(seconds) {
+                Ok(duration) if !duration.is_zero() => Ok(Some(Self::Duration(duration))),
+                _ => Err("warmup_seconds must be a positive number of seconds".to_string()),
+            },
+        }
+    }
+
+    // Send copies of `request` to `providers`, at most `concurrency` at a time, and discard
+    // the results. With a duration, requests still in flight when it runs out are waited
+    // for rather than aborted, so their connections stay in the pool.
+    pub async fn run(
+        self,
+        request: &ChatRequest,
+        providers: &[Arc<dyn LLMProvider>],
+        concurrency: usize,
+        rate_limiter: &Arc<RateLimiter>,
+        options: &ResultOptions,
+    ) {
+        if providers.is_empty() {
+            return;
+        }
+        // Nothing a throwaway request does may outlive it
+        let mut request = request.clone();
+        request.stream_to = None;
+        let options = ResultOptions { artifacts: None, tools: None, capture_raw_response: false, retry_budget: None, ..options.clone() };
+        let deadline = match self {
+            Self::Duration(duration) => Some(Instant::now() + duration),
+            Self::Requests(_) => None,
+        };
+        let mut remaining = match self {
+            Self::Requests(requests) => Some(requests * providers.len()),
+            Self::Duration(_) => None,
+        };
+        // Aborted if the warm-up is cut short
+        let mut in_flight = JoinSet::new();
+        let mut sent = 0;
+        loop {
+            let more = remaining != Some(0) && deadline.is_none_or(|deadline| Instant::now() < deadline);
+            if more && in_flight.len() < concurrency {
+                let provider = Arc::clone(&providers[sent % providers.len()]);
+                in_flight.spawn(BatchProcessor::process_request(
+                    provider,
+                    request.clone(),
+                    Arc::clone(rate_limiter),
+                    options.clone(),
+                ));
+                sent += 1;
+                remaining = remaining.map(|remaining| remaining - 1);
+                continue;
+            }
+            if in_flight.join_next().await.is_none() {
+                return;
+            }
+        }
+    }
+}
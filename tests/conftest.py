@@ -0,0 +1,194 @@
+import json
+import select
+import socket
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+
+def answer(body, path):
+    """Answers in OpenAI's format, or Anthropic's on /v1/messages."""
+    if path.endswith("/v1/messages"):
+        return {
+            "content": [{"type": "text", "text": "Hello."}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 1, "output_tokens": 2},
+        }
+    return {
+        "choices": [{"message": {"content": "Hello."}, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 1, "completion_tokens": 2},
+    }
+
+
+class MockServer:
+    """A completion endpoint on localhost that records every request it gets.
+
+    `response` is the body sent back: a dict as JSON, a string as is, or a list streamed
+    one server-sent event per item. It may also be a function of the request's body and
+    path returning one of those, or a `(body, status)` or `(body, status, headers)` tuple
+    to answer that request differently. Tests may change any attribute between runs.
+    """
+
+    def __init__(self, response=answer, **attributes):
+        self.response = response
+        # Status and extra headers sent with every response
+        self.status = 200
+        self.headers = {}
+        # Seconds to wait before answering: a number, or a function of the request's body.
+        # The wait ends early if the client hangs up.
+        self.delay = 0
+        # Extra seconds the first request waits, like a cold start
+        self.cold_start = 0
+        # Requests still to be answered with a 500 before the server answers properly
+        self.failures = 0
+        # Status of the GET /v1/models health probe, and how many probes came in
+        self.health = 200
+        self.probes = 0
+        # Path, Content-Length, parsed body and client port of each request, in arrival order
+        self.paths = []
+        self.lengths = []
+        self.bodies = []
+        self.clients = []
+        # Requests being answered at once, now and at most
+        self.in_flight = 0
+        self.peak = 0
+        self.lock = threading.Lock()
+        self.url = None
+        for name, value in attributes.items():
+            setattr(self, name, value)
+
+    @property
+    def body(self):
+        return self.bodies[-1]
+
+    @property
+    def path(self):
+        return self.paths[-1]
+
+    @property
+    def calls(self):
+        return len(self.bodies)
+
+    def provider(self, name="openai", config=None, **options):
+        """A provider pointed at this server, with model "m" unless `config` says otherwise."""
+        return ProviderConfig(name=name, api_key="k", base_url=self.url, config={"model": "m", **(config or {})}, **options)
+
+    def process(self, requests, provider=None, **options):
+        """Runs `requests` through a BatchProcessor with `options`, against `provider` or a default one."""
+        processor = BatchProcessor(provider or self.provider(), **options)
+        return processor.process_batch(requests, show_progress=False)
+
+    def run(self, request, name="openai", **config):
+        """Sends one request with `config` on top of the default and returns its metrics."""
+        return self.process([request], self.provider(name, config)).metrics[0]
+
+
+class Server(ThreadingHTTPServer):
+    daemon_threads = True
+    # The default listen backlog of 5 stalls bursts of connections for a second
+    request_queue_size = 128
+
+
+class Handler(BaseHTTPRequestHandler):
+    # Keep-alive, so a client that holds on to its connections comes back on them
+    protocol_version = "HTTP/1.1"
+
+    def do_GET(self):
+        mock = self.server.mock
+        mock.probes += 1
+        assert self.path == "/v1/models"
+        self.reply({"data": [{"id": "m"}]}, mock.health, {})
+
+    def do_POST(self):
+        mock = self.server.mock
+        length = int(self.headers["Content-Length"])
+        body = json.loads(self.rfile.read(length))
+        with mock.lock:
+            mock.paths.append(self.path)
+            mock.lengths.append(length)
+            mock.bodies.append(body)
+            mock.clients.append(self.client_address[1])
+            first = len(mock.bodies) == 1
+            mock.in_flight += 1
+            mock.peak = max(mock.peak, mock.in_flight)
+            failing = mock.failures > 0
+            mock.failures -= failing
+        if failing:
+            response = ({"error": {"message": "server error"}}, 500)
+        else:
+            response = mock.response(body, self.path) if callable(mock.response) else mock.response
+        if not isinstance(response, tuple):
+            response = (response, mock.status)
+        response, status, headers = (*response, {}) if len(response) == 2 else response
+        delay = mock.delay(body) if callable(mock.delay) else mock.delay
+        self.wait(delay + (mock.cold_start if first else 0))
+        with mock.lock:
+            mock.in_flight -= 1
+        try:
+            self.reply(response, status, {**mock.headers, **headers})
+        except OSError:
+            # The client gave up on this request, as when another copy of it won
+            pass
+
+    def reply(self, response, status, headers):
+        self.send_response(status)
+        for name, value in headers.items():
+            self.send_header(name, value)
+        if isinstance(response, list):
+            self.send_header("Content-Type", "text/event-stream")
+            self.send_header("Connection", "close")
+            self.close_connection = True
+            self.end_headers()
+            for event in response:
+                self.wfile.write(f"data: {json.dumps(event)}\n\n".encode())
+            return
+        payload = (response if isinstance(response, str) else json.dumps(response)).encode()
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def wait(self, seconds):
+        """Sleeps, cut short when the client hangs up."""
+        deadline = time.monotonic() + seconds
+        while (left := deadline - time.monotonic()) > 0:
+            if select.select([self.connection], [], [], left)[0] and not self.connection.recv(1, socket.MSG_PEEK):
+                return
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def make_server():
+    """Starts a MockServer per call, answering with `response` and with any attributes
+    given; they all stop with the test."""
+    started = []
+
+    def make(response=answer, **attributes):
+        mock = MockServer(response, **attributes)
+        httpd = Server(("127.0.0.1", 0), Handler)
+        httpd.mock = mock
+        mock.url = f"http://127.0.0.1:{httpd.server_port}"
+        threading.Thread(target=httpd.serve_forever, daemon=True).start()
+        started.append(httpd)
+        return mock
+
+    yield make
+    for httpd in started:
+        httpd.shutdown()
+
+
+@pytest.fixture
+def response():
+    """What `server` answers with; override it in a module or parametrize it per test."""
+    return answer
+
+
+@pytest.fixture
+def server(make_server, response):
+    return make_server(response)
@@ -0,0 +1,50 @@
+import pytest
+
+from axicontraves import BatchProcessor
+
+OK = {
+    "model": "m",
+    "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+    "usage": {"prompt_tokens": 3, "completion_tokens": 1},
+}
+
+
+def capacity(server, limit):
+    """Answers 429 beyond `limit` requests in flight."""
+
+    def respond(body, path):
+        if limit is not None and server.in_flight > limit:
+            return {"error": {"message": "Too many requests"}}, 429, {"retry-after-ms": "100"}
+        return OK
+
+    return respond
+
+
+@pytest.fixture
+def server(make_server):
+    return make_server(delay=0.05)
+
+
+def run(server, count, limit, **kwargs):
+    server.response = capacity(server, limit)
+    requests = [[{"role": "user", "content": f"Hi {i}"}] for i in range(count)]
+    metrics = BatchProcessor(server.provider(), **kwargs).process_batch(requests, show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    return [m.concurrency for m in metrics]
+
+
+def test_grows_to_ceiling_while_healthy(server):
+    levels = run(server, 60, None, max_concurrency=16, adaptive_concurrency=True)
+    assert max(levels[:4]) <= 4
+    assert max(levels) == 16
+
+
+def test_backs_off_on_rate_limits(server):
+    levels = run(server, 120, 6, max_concurrency=64, adaptive_concurrency=True, rate_limit_retries=20)
+    assert max(levels) > 6
+    # Overshooting the capacity halves the limit, so it saws around it instead of climbing
+    assert max(levels[-40:]) < 16
+
+
+def test_fixed_concurrency_without_adaptive(server):
+    assert max(run(server, 20, None, max_concurrency=5)) == 5
@@ -0,0 +1,44 @@
+import pytest
+
+from axicontraves import list_artifacts, read_artifact
+
+LONG = "lorem ipsum " * 100
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        prompt = body["messages"][-1]["content"]
+        return {
+            "choices": [{"message": {"content": LONG if prompt.startswith("long") else "short"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 5},
+        }
+
+    return answer
+
+
+def test_large_outputs_are_stored_once(server, tmp_path):
+    requests = [[{"role": "user", "content": p}] for p in ("long a", "long b", "hi")]
+    result = server.process(requests, artifact_dir=str(tmp_path), artifact_min_bytes=100)
+    metrics = sorted(result.metrics, key=lambda m: m.index)
+
+    first, second, small = metrics
+    assert first.artifact_path is not None
+    assert first.artifact_path == second.artifact_path
+    assert first.artifact_path.endswith(f"{first.artifact_hash}.txt")
+    assert first.content == LONG
+    assert small.artifact_path is None and small.content == "short"
+    assert len(list(tmp_path.rglob("*.txt"))) == 1
+
+
+def test_sqlite_store(server, tmp_path):
+    location = f"sqlite://{tmp_path / 'artifacts.db'}"
+    request = [{"role": "user", "content": "long"}]
+    metrics = server.process([request], artifact_dir=location, artifact_min_bytes=100).metrics[0]
+
+    assert metrics.artifact_path is None
+    assert metrics.content == LONG
+    assert list_artifacts(location) == [metrics.artifact_key]
+    assert read_artifact(location, metrics.artifact_key) == LONG
+    with pytest.raises(KeyError):
+        read_artifact(location, "00/missing.txt")
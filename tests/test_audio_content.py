@@ -0,0 +1,39 @@
+import base64
+import os
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+# A megabyte of audio, as large request bodies go
+AUDIO = base64.b64encode(os.urandom(768 * 1024)).decode()
+
+
+def listen(part):
+    return [{"role": "user", "content": [{"type": "text", "text": "Transcribe this."}, part]}]
+
+
+@pytest.mark.parametrize("part", [
+    {"type": "input_audio", "input_audio": {"data": AUDIO, "format": "wav"}},
+    {"type": "audio", "data": AUDIO, "format": "wav"},
+])
+def test_audio_is_sent_as_input_audio(server, part):
+    metrics = server.run(listen(part), model="gpt-4o-audio-preview")
+    assert metrics.status == "ok"
+    assert server.body["messages"][0]["content"][1] == {
+        "type": "input_audio", "input_audio": {"data": AUDIO, "format": "wav"},
+    }
+    # The whole body is counted, plus no more than the request line and headers
+    assert server.lengths[-1] <= metrics.request_bytes < server.lengths[-1] + 1024
+
+
+def test_test_mode_counts_the_audio_bytes():
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+    request = listen({"type": "audio", "data": AUDIO, "format": "mp3"})
+    metrics = BatchProcessor(provider).process_batch([request], show_progress=False).metrics[0]
+    assert metrics.request_bytes > len(AUDIO)
+
+
+def test_audio_without_a_format_is_rejected(server):
+    with pytest.raises(ValueError, match="format"):
+        server.run(listen({"type": "input_audio", "input_audio": {"data": AUDIO}}))
@@ -0,0 +1,34 @@
+import pytest
+
+from axicontraves import start_requests_multi
+
+# Slow simulated server: one request at a time, 200 ms each
+SLOW = ("openai", "test", None, {"model": "m"},
+        {"simulator": {"max_concurrency": 1, "service_time": {"distribution": "constant", "ms": 200}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(6)]
+
+
+def test_wait_returns_every_result():
+    handle = start_requests_multi([SLOW], REQUESTS)
+    results = handle.wait()
+    assert handle.done()
+    assert sorted(m.index for m in results) == list(range(len(REQUESTS)))
+    assert {m.status for m in results} == {"ok"}
+
+
+def test_cancelled_requests_are_reported():
+    handle = start_requests_multi([SLOW], REQUESTS)
+    assert handle.cancel_request(5)
+    results = {m.index: m for m in handle.wait()}
+    assert len(results) == len(REQUESTS)
+    assert results[5].status == "cancelled"
+    assert results[5].completion_tokens == 0
+    assert all(results[i].status == "ok" for i in range(5))
+    assert not handle.cancel_request(0)
+
+
+def test_cancel_out_of_range():
+    handle = start_requests_multi([SLOW], REQUESTS[:1])
+    with pytest.raises(IndexError):
+        handle.cancel_request(10)
+    handle.wait()
@@ -0,0 +1,124 @@
+import json
+import socket
+import threading
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+COMPLETION = {
+    "model": "m",
+    "choices": [{"message": {"content": "hello there"}, "finish_reason": "stop"}],
+    "usage": {"prompt_tokens": 3, "completion_tokens": 2},
+}
+MESSAGES = {
+    "model": "claude",
+    "content": [{"type": "text", "text": "hello there"}],
+    "stop_reason": "end_turn",
+    "usage": {"input_tokens": 3, "output_tokens": 2},
+}
+
+
+class RawServer:
+    """Answers one request per connection without a Content-Length and records the bytes
+    of each request it read and each response body it sent."""
+
+    def __init__(self, body):
+        self.body = body
+        self.received = []
+        self.sent = []
+        self.sock = socket.socket()
+        self.sock.bind(("127.0.0.1", 0))
+        self.sock.listen()
+        threading.Thread(target=self.serve, daemon=True).start()
+
+    @property
+    def url(self):
+        return "http://127.0.0.1:%d" % self.sock.getsockname()[1]
+
+    def serve(self):
+        while True:
+            try:
+                conn, _ = self.sock.accept()
+            except OSError:
+                return
+            with conn:
+                data = b""
+                while b"\r\n\r\n" not in data:
+                    data += conn.recv(65536)
+                head, body = data.split(b"\r\n\r\n", 1)
+                length = next(int(line.split(b":")[1]) for line in head.split(b"\r\n") if line.lower().startswith(b"content-length:"))
+                while len(body) < length:
+                    body += conn.recv(65536)
+                self.received.append(len(head) + 4 + len(body))
+                payload = self.body(json.loads(body))
+                self.sent.append(len(payload))
+                conn.sendall(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n")
+                for start in range(0, len(payload), 7):
+                    chunk = payload[start:start + 7]
+                    conn.sendall(b"%x\r\n%s\r\n" % (len(chunk), chunk))
+                conn.sendall(b"0\r\n\r\n")
+
+    def close(self):
+        self.sock.close()
+
+
+def completion(request):
+    if not request.get("stream"):
+        return json.dumps(COMPLETION).encode()
+    chunk = {"model": "m", "choices": [{"index": 0, "delta": {"content": "hello there"}, "finish_reason": "stop"}]}
+    usage = {"choices": [], "usage": COMPLETION["usage"]}
+    return f"data: {json.dumps(chunk)}\n\ndata: {json.dumps(usage)}\n\ndata: [DONE]\n\n".encode()
+
+
+@pytest.fixture
+def server():
+    server = RawServer(completion)
+    yield server
+    server.close()
+
+
+def run(provider, **options):
+    requests = [[{"role": "user", "content": "héllo " * 50}]]
+    return BatchProcessor(provider, **options).process_batch(requests, show_progress=False).metrics[0]
+
+
+def test_request_and_chunked_response_bytes_match_the_wire(server):
+    provider = ProviderConfig(
+        name="openai", api_key="sk-test", base_url=server.url, config={"model": "m"}, headers={"X-Team": "research"}
+    )
+    metrics = run(provider)
+    assert metrics.status == "ok"
+    assert metrics.request_bytes == server.received[0]
+    assert metrics.response_bytes == server.sent[0] > 0
+
+
+def test_streamed_response_bytes_are_counted(server, tmp_path):
+    provider = ProviderConfig(name="openai", api_key="sk-test", base_url=server.url, config={"model": "m"})
+    metrics = run(provider, stream_dir=str(tmp_path))
+    assert metrics.status == "ok"
+    assert metrics.request_bytes == server.received[0]
+    assert metrics.response_bytes == server.sent[0]
+
+
+def test_anthropic_bytes_match_the_wire():
+    server = RawServer(lambda request: json.dumps(MESSAGES).encode())
+    try:
+        provider = ProviderConfig(name="anthropic", api_key="sk-ant", base_url=server.url, config={"model": "claude"})
+        metrics = run(provider)
+    finally:
+        server.close()
+    assert metrics.status == "ok"
+    assert (metrics.request_bytes, metrics.response_bytes) == (server.received[0], server.sent[0])
+
+
+@pytest.mark.parametrize("name", ["openai", "anthropic"])
+def test_test_mode_counts_request_bytes_like_a_real_run(name):
+    server = RawServer(lambda request: json.dumps(MESSAGES if name == "anthropic" else COMPLETION).encode())
+    config = dict(name=name, api_key="sk-test", base_url=server.url, config={"model": "m"}, headers={"X-Team": "research"})
+    try:
+        sent = run(ProviderConfig(**config))
+    finally:
+        server.close()
+    simulated = run(ProviderConfig(**config, test_mode=True))
+    assert simulated.request_bytes == sent.request_bytes == server.received[0]
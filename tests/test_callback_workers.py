@@ -0,0 +1,89 @@
+import threading
+import time
+
+import pytest
+
+import axicontraves
+from axicontraves import BatchProcessor, ProviderConfig
+
+# One request at a time, 100 ms each
+PROVIDER = ProviderConfig(
+    name="openai",
+    api_key="k",
+    config={"model": "m"},
+    simulator={"max_concurrency": 1, "service_time": {"distribution": "constant", "ms": 100}, "per_token_ms": 0},
+)
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(4)]
+
+
+def test_slow_callbacks_overlap_with_in_flight_requests():
+    calls = []
+
+    def on_result(metrics):
+        calls.append((time.monotonic(), threading.current_thread()))
+        time.sleep(0.15)
+
+    processor = BatchProcessor(PROVIDER, max_concurrency=1, result_callback=on_result, callback_workers=4)
+    started = time.monotonic()
+    processor.process_batch(REQUESTS, show_progress=False)
+    elapsed = time.monotonic() - started
+    # The first callback runs while the other three requests are still to come, and
+    # the run takes the requests' 0.4s plus one callback, not all four
+    assert calls[0][0] - started < 0.3
+    assert elapsed < 0.85
+    assert all(thread is not threading.main_thread() for _, thread in calls)
+
+
+def test_every_result_is_delivered_before_returning():
+    seen = []
+
+    def on_result(metrics):
+        time.sleep(0.05)
+        seen.append(metrics.index)
+
+    processor = BatchProcessor(PROVIDER, result_callback=on_result, callback_workers=2)
+    result = processor.process_batch(REQUESTS, show_progress=False)
+    assert sorted(seen) == sorted(m.index for m in result.metrics) == list(range(len(REQUESTS)))
+
+
+def test_callback_exception_is_raised():
+    def on_result(metrics):
+        raise KeyError("no such row")
+
+    processor = BatchProcessor(PROVIDER, result_callback=on_result, callback_workers=2)
+    with pytest.raises(KeyError, match="no such row"):
+        processor.process_batch(REQUESTS, show_progress=False)
+
+
+def test_executor_shuts_down_when_the_run_raises(monkeypatch):
+    executors = []
+
+    class Recording(axicontraves.ThreadPoolExecutor):
+        def __init__(self, *args, **kwargs):
+            super().__init__(*args, **kwargs)
+            self.waited = None
+            executors.append(self)
+
+        def shutdown(self, wait=True, **kwargs):
+            self.waited = wait
+            super().shutdown(wait=wait, **kwargs)
+
+    monkeypatch.setattr(axicontraves, "ThreadPoolExecutor", Recording)
+    seen = []
+
+    def requests():
+        yield REQUESTS[0]
+        yield REQUESTS[1]
+        raise RuntimeError("dataset is corrupt")
+
+    def on_result(metrics):
+        time.sleep(0.05)
+        seen.append(metrics.index)
+
+    processor = BatchProcessor(PROVIDER, result_callback=on_result, callback_workers=2)
+    with pytest.raises(RuntimeError, match="dataset is corrupt"):
+        processor.process_batch(requests(), show_progress=False)
+    [executor] = executors
+    assert executor.waited is True
+    # Callbacks already handed to the pool ran to completion
+    assert sorted(seen) == [0, 1]
@@ -0,0 +1,83 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+CHATML = (
+    "{{ bos_token }}{% for message in messages %}"
+    "<|im_start|>{{ message['role'] }}\n{{ message['content'].strip() }}<|im_end|>\n"
+    "{% endfor %}{% if add_generation_prompt %}<|im_start|>assistant\n{% endif %}"
+)
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        if path == "/completion":
+            return {"content": "Paris.", "stop": True, "stop_type": "limit", "tokens_evaluated": 21, "tokens_predicted": 3}
+        response = {
+            "model": "local",
+            "choices": [{"index": 0, "text": "Paris.", "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 20, "completion_tokens": 2},
+        }
+        if body.get("n") == 2:
+            response["choices"].append({"index": 1, "text": "Paris, the capital", "finish_reason": "length"})
+        return response
+
+    return answer
+
+
+def run(server, request, choice_policy=None, **options):
+    provider = server.provider(
+        config={"model": "local", "max_tokens": 16}, chat_template=CHATML, special_tokens={"bos_token": "<s>"}, **options,
+    )
+    return server.process([request], provider, choice_policy=choice_policy).metrics[0]
+
+
+MESSAGES = [{"role": "system", "content": " Be brief. "}, {"role": "user", "content": "Capital of France?"}]
+PROMPT = "<s><|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nCapital of France?<|im_end|>\n<|im_start|>assistant\n"
+
+
+def test_openai_completions(server):
+    metrics = run(server, MESSAGES)
+    path, body = server.path, server.body
+    assert path == "/v1/completions"
+    assert body == {"prompt": PROMPT, "model": "local", "max_tokens": 16}
+    assert metrics.status == "ok"
+    assert metrics.content == "Paris."
+    assert metrics.finish_reason == "stop"
+    assert (metrics.prompt_tokens, metrics.completion_tokens) == (20, 2)
+
+
+def test_llamacpp_completion(server):
+    metrics = run(server, MESSAGES, backend="llamacpp")
+    path, body = server.path, server.body
+    assert path == "/completion"
+    assert body == {"prompt": PROMPT, "model": "local", "n_predict": 16}
+    assert metrics.content == "Paris."
+    assert metrics.finish_reason == "length"
+    assert (metrics.prompt_tokens, metrics.completion_tokens) == (21, 3)
+
+
+def test_selected_choice_keeps_its_finish_reason(server):
+    metrics = run(server, {"messages": MESSAGES, "n": 2}, choice_policy="longest")
+    assert metrics.content == "Paris, the capital"
+    assert metrics.finish_reason == "length"
+
+
+def test_prefill_is_appended(server):
+    run(server, MESSAGES + [{"role": "assistant", "content": "The capital is"}])
+    body = server.body
+    assert body["prompt"] == PROMPT + "The capital is"
+
+
+def test_raise_exception(server):
+    provider = server.provider(config={"model": "local"}, chat_template="{{ raise_exception('Roles must alternate') }}")
+    metrics = server.process([MESSAGES], provider).metrics[0]
+    assert metrics.status == "error"
+    assert "Roles must alternate" in metrics.error
+
+
+def test_invalid_template():
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "local"}, chat_template="{% for %}")
+    with pytest.raises(ValueError, match="chat_template"):
+        BatchProcessor(provider).process_batch([MESSAGES], show_progress=False)
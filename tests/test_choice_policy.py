@@ -0,0 +1,74 @@
+import pytest
+
+# (text, finish_reason, per-token logprobs)
+CHOICES = [
+    ("short", "stop", [-0.5, -0.7]),
+    ("the longest answer", "length", [-2.0, -3.0, -1.0]),
+    ("medium one", "stop", [-0.1, -0.2]),
+]
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        choices = []
+        for index, (text, reason, logprobs) in enumerate(CHOICES[: body.get("n", 1)]):
+            choice = {"index": index, "message": {"content": text}, "finish_reason": reason}
+            if body.get("logprobs"):
+                choice["logprobs"] = {"content": [{"token": "t", "logprob": lp} for lp in logprobs]}
+            choices.append(choice)
+        return {"choices": choices, "usage": {"prompt_tokens": 3, "completion_tokens": 30}}
+
+    return answer
+
+
+def run(server, policy):
+    provider = server.provider(config={"n": 3})
+    return server.process([[{"role": "user", "content": "hi"}]], provider, choice_policy=policy).metrics[0]
+
+
+def test_default_keeps_provider_order(server):
+    metrics = run(server, None)
+    assert metrics.content == "short"
+    assert metrics.selected_choice is None
+
+
+def test_longest(server):
+    metrics = run(server, "longest")
+    assert metrics.content == "the longest answer"
+    assert metrics.selected_choice == 1
+    assert metrics.finish_reason == "length"
+    assert metrics.choices == ["the longest answer", "short", "medium one"]
+    assert metrics.completion_tokens == 30
+    assert "logprobs" not in server.body
+
+
+def test_mean_logprob(server):
+    metrics = run(server, "logprob")
+    assert server.body["logprobs"] is True
+    assert metrics.content == "medium one"
+    assert metrics.selected_choice == 2
+    assert metrics.choice_logprobs[0] == pytest.approx(-0.15)
+
+
+def test_judge(server):
+    seen = []
+
+    def judge(choices):
+        seen.append(choices)
+        return choices.index("short")
+
+    metrics = run(server, judge)
+    assert seen == [[text for text, _, _ in CHOICES]]
+    assert metrics.content == "short" and metrics.selected_choice == 0
+
+
+def test_failing_judge_fails_the_request(server):
+    metrics = run(server, lambda choices: 7)
+    assert metrics.status == "error"
+    assert "Choice judge returned 7" in metrics.error
+
+
+def test_unknown_policy(server):
+    with pytest.raises(ValueError):
+        run(server, "shortest")
@@ -0,0 +1,32 @@
+from axicontraves import process_requests_multi
+
+# Nothing listens on the discard port, so every request to it fails fast
+DEAD = ("openai", "test", "http://127.0.0.1:9", {"model": "m"}, {"test_mode": False})
+HEALTHY = ("openai", "test", None, {"model": "m"}, {"test_mode": True})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(8)]
+
+
+def run(providers, **kwargs):
+    # One request at a time, so the breaker trips before the rest are sent
+    return process_requests_multi(providers, REQUESTS, lambda *args: None, False, None, max_concurrency=1, **kwargs)
+
+
+def test_without_breaker_failures_are_reported():
+    results = run([DEAD, HEALTHY])
+    assert len(results) == len(REQUESTS)
+    failed = [m for m in results if m.status == "error"]
+    assert len(failed) == len(REQUESTS) // 2
+    assert all(m.error and m.provider_name == "openai:http://127.0.0.1:9" for m in failed)
+
+
+def test_tripped_provider_requests_move_to_healthy_provider():
+    results = run([DEAD, HEALTHY], circuit_breaker=1)
+    assert sorted(m.index for m in results) == list(range(len(REQUESTS)))
+    assert {m.provider_name for m in results} == {"openai:https://api.openai.com"}
+
+
+def test_all_providers_tripped_fails_remaining_requests():
+    results = run([DEAD], circuit_breaker=1)
+    assert sorted(m.index for m in results) == list(range(len(REQUESTS)))
+    assert {m.status for m in results} == {"error"}
+    assert sum("circuit breaker" in m.error for m in results) == len(REQUESTS) - 1
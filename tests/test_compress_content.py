@@ -0,0 +1,32 @@
+import pytest
+
+TEXT = "The quick brown fox jumps over the lazy dog. " * 200
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        choices = [
+            {"index": i, "message": {"content": f"{i}: {TEXT}"}, "finish_reason": "stop"} for i in range(body.get("n", 1))
+        ]
+        return {"choices": choices, "usage": {"prompt_tokens": 3, "completion_tokens": 900}}
+
+    return answer
+
+
+def run(server, compress_content, **config):
+    provider = server.provider(config=config)
+    return server.process([[{"role": "user", "content": "hi"}]], provider, compress_content=compress_content).metrics[0]
+
+
+def test_compressed_content_reads_back_unchanged(server):
+    plain, compressed = run(server, False), run(server, True)
+    assert not plain.is_compressed
+    assert compressed.is_compressed
+    assert compressed.content == plain.content == f"0: {TEXT}"
+
+
+def test_every_choice_is_compressed(server):
+    metrics = run(server, True, n=2)
+    assert metrics.is_compressed
+    assert metrics.choices == [f"0: {TEXT}", f"1: {TEXT}"]
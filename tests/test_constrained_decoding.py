@@ -0,0 +1,43 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Is the sky blue?"}]
+SCHEMA = {"type": "object", "properties": {"answer": {"type": "string"}}}
+
+
+def run(server, backend, kind, value):
+    request = {"messages": QUESTION, "constraint": {"type": kind, "value": value}}
+    return server.process([request], server.provider(backend=backend), rate_limit_retries=0).metrics[0]
+
+
+@pytest.mark.parametrize("backend, kind, value, field", [
+    ("vllm", "gbnf", 'root ::= "yes" | "no"', "guided_grammar"),
+    ("vllm", "regex", "yes|no", "guided_regex"),
+    ("vllm", "json_schema", SCHEMA, "guided_json"),
+    ("vllm", "choice", ["yes", "no"], "guided_choice"),
+    ("llamacpp", "gbnf", 'root ::= "yes" | "no"', "grammar"),
+    ("llamacpp", "json_schema", SCHEMA, "json_schema"),
+])
+def test_each_backend_gets_its_own_field(server, backend, kind, value, field):
+    metrics = run(server, backend, kind, value)
+    assert metrics.status == "ok"
+    assert server.body[field] == value
+
+
+def test_openai_gets_a_strict_response_format(server):
+    run(server, "openai", "json_schema", SCHEMA)
+    assert server.body["response_format"] == {
+        "type": "json_schema",
+        "json_schema": {"name": "response", "schema": SCHEMA, "strict": True},
+    }
+
+
+def test_unsupported_constraint_fails_without_sending(server):
+    metrics = run(server, "openai", "regex", "yes|no")
+    assert metrics.status == "error"
+    assert "does not support regex" in metrics.error
+    assert server.bodies == []
+
+
+def test_unknown_backend_is_rejected(server):
+    with pytest.raises(ValueError, match="backend"):
+        run(server, "tgi", "regex", "yes|no")
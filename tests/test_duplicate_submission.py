@@ -0,0 +1,36 @@
+import warnings
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+PROVIDER = ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+REQUESTS = [[{"role": "user", "content": f"row {i}"}] for i in range(3)]
+
+
+def statuses(result):
+    return [m.status for m in sorted(result.metrics, key=lambda m: m.index)]
+
+
+def test_resubmitted_requests_are_skipped():
+    processor = BatchProcessor(PROVIDER, dedupe_ttl=60)
+    assert statuses(processor.process_batch(REQUESTS, show_progress=False)) == ["ok"] * 3
+
+    with pytest.warns(UserWarning, match="2 request"):
+        second = processor.process_batch(REQUESTS[:2] + [[{"role": "user", "content": "new"}]], show_progress=False)
+    assert statuses(second) == ["skipped", "skipped", "ok"]
+
+
+def test_warn_mode_still_sends():
+    processor = BatchProcessor(PROVIDER, dedupe_ttl=60, on_duplicate="warn")
+    processor.process_batch(REQUESTS, show_progress=False)
+    with pytest.warns(UserWarning, match="sending anyway"):
+        assert statuses(processor.process_batch(REQUESTS, show_progress=False)) == ["ok"] * 3
+
+
+def test_expired_entries_are_forgotten():
+    processor = BatchProcessor(PROVIDER, dedupe_ttl=0)
+    processor.process_batch(REQUESTS, show_progress=False)
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        assert statuses(processor.process_batch(REQUESTS, show_progress=False)) == ["ok"] * 3
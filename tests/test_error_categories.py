@@ -0,0 +1,48 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, process_requests_multi, summarize_results
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(4)]
+
+
+def run(base_url, **options):
+    provider = ProviderConfig(name="openai", api_key="k", base_url=base_url, config={"model": "m"})
+    return BatchProcessor(provider, **options).process_batch(REQUESTS, show_progress=False)
+
+
+@pytest.mark.parametrize(
+    "status, category, outcome",
+    [(500, "server_error", "error"), (404, "client_error", "error"), (429, "rate_limit", "rate_limited")],
+)
+def test_http_failures_are_categorized_by_status(make_server, status, category, outcome):
+    result = run(make_server('{"error": "nope"}', status=status).url, rate_limit_retries=0)
+    assert all(m.status == outcome and m.error_category == category for m in result.metrics)
+    assert result.summary.errors == {category: len(REQUESTS)}
+
+
+def test_unparseable_responses(make_server):
+    result = run(make_server("not json").url)
+    assert result.summary.errors == {"parse": len(REQUESTS)}
+
+
+def test_refused_connections():
+    # Nothing listens on the discard port
+    result = run("http://127.0.0.1:9")
+    assert result.summary.errors == {"connection": len(REQUESTS)}
+
+
+def test_provider_breakdown_counts_its_own_errors(make_server):
+    failing = ("openai", "k", make_server("{}", status=503).url, {"model": "m"}, {"test_mode": False})
+    healthy = ("openai", "k", None, {"model": "m"}, {"test_mode": True})
+    results = process_requests_multi([failing, healthy], REQUESTS, lambda *args: None, False, None, rate_limit_retries=0)
+    summary = summarize_results(results, 1.0)
+    assert summary.errors == {"server_error": len(REQUESTS) // 2}
+    assert summary.providers[f"openai:{failing[2]}"].errors == {"server_error": len(REQUESTS) // 2}
+    assert summary.providers["openai:https://api.openai.com"].errors == {}
+
+
+def test_successful_runs_have_no_errors():
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+    result = BatchProcessor(provider).process_batch(REQUESTS, show_progress=False)
+    assert result.summary.errors == {}
+    assert all(m.error_category is None for m in result.metrics)
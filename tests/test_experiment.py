@@ -0,0 +1,53 @@
+import pytest
+
+from axicontraves import Experiment, ProviderConfig, Variant
+
+
+def provider(model: str) -> ProviderConfig:
+    return ProviderConfig(name="openai", api_key="test", config={"model": model}, test_mode=True)
+
+
+REQUESTS = [[{"role": "user", "content": f"question {i} " * 20}] for i in range(6)]
+PRICING = {"small": {"input": 0.15, "output": 0.6}, "large": {"input": 2.5, "output": 10.0}}
+
+
+@pytest.mark.parametrize("mode", ["sequential", "interleaved"])
+def test_every_variant_sees_every_request(mode):
+    experiment = Experiment(
+        [Variant("small", provider("small")), Variant("large", provider("large"))],
+        pricing=PRICING,
+        mode=mode,
+        chunk_size=4,
+    )
+    result = experiment.run(REQUESTS, show_progress=False)
+    assert [v.name for v in result.variants] == ["small", "large"]
+    for summary in result.variants:
+        assert summary.succeeded == summary.requests == len(REQUESTS)
+        assert [index for index, _ in summary.results] == list(range(len(REQUESTS)))
+        assert summary.mean_latency_ms > 0
+        assert summary.cost_usd > 0
+    small, large = result.variants
+    assert large.cost_usd > small.cost_usd
+
+
+def test_overrides_and_quality_hook():
+    seen = []
+
+    def quality(request, metrics):
+        seen.append(request is REQUESTS[0] or request is REQUESTS[1])
+        return 1.0 if metrics.model == "large" else 0.0
+
+    experiment = Experiment(
+        [Variant("upgraded", provider("small"), {"model": "large"})],
+        pricing={"large": PRICING["large"]},
+        quality=quality,
+    )
+    summary = experiment.run(REQUESTS[:2], show_progress=False).variants[0]
+    assert summary.mean_quality == 1.0
+    assert summary.cost_usd > 0
+    assert seen == [True, True]
+
+
+def test_rejects_duplicate_variant_names():
+    with pytest.raises(ValueError, match="unique"):
+        Experiment([Variant("a", provider("small")), Variant("a", provider("large"))])
@@ -0,0 +1,31 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Hi"}]
+
+
+def test_fields_are_merged_verbatim(server):
+    extra = {"top_k": 20, "repetition_penalty": 1.1, "chat_template_kwargs": {"enable_thinking": False}}
+    server.run(QUESTION, extra_body=extra)
+    assert {key: server.body[key] for key in extra} == extra
+    assert server.body["model"] == "m"
+
+
+def test_request_fields_are_merged_over_the_config(server):
+    request = {"messages": QUESTION, "extra_body": {"top_k": 5, "min_p": 0.1}}
+    server.run(request, extra_body={"top_k": 20, "repetition_penalty": 1.1})
+    assert (server.body["top_k"], server.body["min_p"], server.body["repetition_penalty"]) == (5, 0.1, 1.1)
+
+
+def test_extra_body_replaces_built_in_fields(server):
+    server.run(QUESTION, temperature=0.2, extra_body={"temperature": 0.9})
+    assert server.body["temperature"] == 0.9
+
+
+def test_anthropic_gets_extra_body_too(server):
+    server.run(QUESTION, name="anthropic", extra_body={"thinking": {"type": "enabled", "budget_tokens": 1024}})
+    assert server.body["thinking"] == {"type": "enabled", "budget_tokens": 1024}
+
+
+def test_extra_body_must_be_a_dict(server):
+    with pytest.raises(ValueError, match="extra_body"):
+        server.run(QUESTION, extra_body=["top_k", 20])
@@ -0,0 +1,88 @@
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+
+class Failing(BaseHTTPRequestHandler):
+    def do_POST(self):
+        self.rfile.read(int(self.headers["Content-Length"]))
+        payload = b'{"error": {"message": "upstream exploded"}}'
+        self.send_response(500)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def log_message(self, *args):
+        pass
+
+
+class Working(BaseHTTPRequestHandler):
+    def do_POST(self):
+        self.rfile.read(int(self.headers["Content-Length"]))
+        response = {
+            "model": "m",
+            "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1},
+        }
+        payload = json.dumps(response).encode()
+        self.send_response(200)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def log_message(self, *args):
+        pass
+
+
+def serve(handler):
+    httpd = HTTPServer(("127.0.0.1", 0), handler)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    return httpd, f"http://127.0.0.1:{httpd.server_port}"
+
+
+@pytest.fixture
+def providers():
+    failing, failing_url = serve(Failing)
+    working, working_url = serve(Working)
+    yield [
+        ProviderConfig(name="openai", api_key="k", base_url=url, config={"model": "m"})
+        for url in (failing_url, working_url)
+    ]
+    failing.shutdown()
+    working.shutdown()
+
+
+def requests(count):
+    return [[{"role": "user", "content": f"Hi {i}"}] for i in range(count)]
+
+
+def test_failed_request_moves_to_next_provider(providers):
+    failing_url, working_url = (provider.base_url for provider in providers)
+    metrics = BatchProcessor(providers, failover=[0, 1]).process_batch(requests(4), show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    assert all(m.provider_name == f"openai:{working_url}" for m in metrics)
+    assert sorted(len(m.failovers) for m in metrics) == [0, 0, 1, 1]
+    assert {name for m in metrics for name in m.failovers} == {f"openai:{failing_url}"}
+
+
+def test_without_failover_requests_fail(providers):
+    metrics = BatchProcessor(providers).process_batch(requests(4), show_progress=False).metrics
+    assert sorted(m.status for m in metrics) == ["error", "error", "ok", "ok"]
+    assert all(m.failovers == [] for m in metrics)
+
+
+def test_failover_only_moves_forward(providers):
+    # The failing provider is last in the order, so its requests have nowhere to go
+    metrics = BatchProcessor(providers, failover=[1, 0]).process_batch(requests(4), show_progress=False).metrics
+    assert sorted(m.status for m in metrics) == ["error", "error", "ok", "ok"]
+
+
+def test_unknown_provider_is_rejected(providers):
+    with pytest.raises(ValueError, match="failover"):
+        BatchProcessor(providers, failover=[0, 5]).process_batch(requests(1), show_progress=False)
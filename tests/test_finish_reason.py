@@ -0,0 +1,54 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Hi"}]
+
+
+def ending_with(reason):
+    """Ends each response with `reason`, in OpenAI's format or Anthropic's on /v1/messages."""
+
+    def answer(body, path):
+        if path.endswith("/v1/messages"):
+            return {
+                "content": [{"type": "text", "text": "Hello."}],
+                "stop_reason": reason,
+                "usage": {"input_tokens": 1, "output_tokens": 2},
+            }
+        # With n = 2 the second choice is longer and was cut off
+        choices = [{"message": {"content": "Hello."}, "finish_reason": reason}]
+        if body.get("n") == 2:
+            choices.append({"message": {"content": "Hello there, how"}, "finish_reason": "length"})
+        return {"choices": choices, "usage": {"prompt_tokens": 1, "completion_tokens": 6}}
+
+    return answer
+
+
+def run(server, reason, name="openai", choice_policy=None, **config):
+    server.response = ending_with(reason)
+    return server.process([QUESTION], server.provider(name, config), choice_policy=choice_policy).metrics[0]
+
+
+@pytest.mark.parametrize("reason", ["stop", "length", "tool_calls", "content_filter"])
+def test_openai_reason_is_recorded(server, reason):
+    assert run(server, reason).finish_reason == reason
+
+
+@pytest.mark.parametrize("stop_reason, reason", [
+    ("end_turn", "stop"),
+    ("stop_sequence", "stop"),
+    ("max_tokens", "length"),
+    ("tool_use", "tool_calls"),
+    ("refusal", "content_filter"),
+])
+def test_anthropic_reason_is_normalized(server, stop_reason, reason):
+    assert run(server, stop_reason, name="anthropic").finish_reason == reason
+
+
+def test_missing_reason_is_none(server):
+    assert run(server, None).finish_reason is None
+
+
+def test_reason_follows_the_selected_choice(server):
+    assert run(server, "stop", n=2).finish_reason == "stop"
+    metrics = run(server, "stop", choice_policy="longest", n=2)
+    assert metrics.content == "Hello there, how"
+    assert metrics.finish_reason == "length"
@@ -0,0 +1,34 @@
+import threading
+import time
+
+from axicontraves import process_requests_multi
+
+# Slow simulated server: one request at a time, 200 ms each
+SLOW = ("openai", "test", None, {"model": "m"},
+        {"simulator": {"max_concurrency": 1, "service_time": {"distribution": "constant", "ms": 200}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(5)]
+
+
+def test_other_threads_run_during_a_batch():
+    ticks = []
+    stop = threading.Event()
+
+    def ticker():
+        while not stop.is_set():
+            ticks.append(time.monotonic())
+            time.sleep(0.01)
+
+    thread = threading.Thread(target=ticker)
+    thread.start()
+    try:
+        started = time.monotonic()
+        results = process_requests_multi([SLOW], REQUESTS, lambda *args: None, False, None)
+        finished = time.monotonic()
+    finally:
+        stop.set()
+        thread.join()
+    assert {m.status for m in results} == {"ok"}
+    # The ticker kept going for the whole second the run took, not just between batches
+    during = [tick for tick in ticks if started <= tick <= finished]
+    assert len(during) > 0.5 * (finished - started) / 0.01
+    assert max(b - a for a, b in zip(during, during[1:])) < 0.1
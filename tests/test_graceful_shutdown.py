@@ -0,0 +1,82 @@
+import subprocess
+import sys
+import textwrap
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, start_requests_multi
+
+# Four requests at a time, 500 ms each
+SLOW = ("openai", "test", None, {"model": "m"},
+        {"simulator": {"max_concurrency": 4, "service_time": {"distribution": "constant", "ms": 500}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(12)]
+
+
+def test_in_flight_requests_drain_after_cancel():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=4, drain_timeout=5.0)
+    time.sleep(0.2)
+    handle.cancel()
+    results = handle.wait()
+    statuses = [m.status for m in sorted(results, key=lambda m: m.index)]
+    assert statuses == ["ok"] * 4 + ["cancelled"] * 8
+
+
+def test_without_a_drain_timeout_in_flight_requests_are_aborted():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=4)
+    time.sleep(0.2)
+    handle.cancel()
+    assert all(m.status == "cancelled" for m in handle.wait())
+
+
+def test_requests_still_running_past_the_drain_timeout_are_aborted():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=4, drain_timeout=0.1)
+    time.sleep(0.2)
+    started = time.monotonic()
+    handle.cancel()
+    results = handle.wait()
+    assert time.monotonic() - started < 0.3
+    assert all(m.status == "cancelled" for m in results)
+
+
+def test_cancelling_again_cuts_the_drain_short():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=4, drain_timeout=5.0)
+    time.sleep(0.2)
+    handle.cancel()
+    handle.cancel()
+    assert all(m.status == "cancelled" for m in handle.wait())
+
+
+def test_negative_drain_timeout_is_rejected():
+    with pytest.raises(ValueError):
+        start_requests_multi([SLOW], REQUESTS, drain_timeout=-1.0)
+
+
+def test_interpreter_exit_drains_running_batches():
+    script = textwrap.dedent("""
+        import atexit
+        import time
+
+        handles = []
+        # Registered first, so it runs after axicontraves has drained the batch
+        atexit.register(lambda: print(sorted(m.status for m in handles[0].results())))
+
+        from axicontraves import BatchProcessor, ProviderConfig
+
+        provider = ProviderConfig(
+            name="openai", api_key="test", config={"model": "m"},
+            simulator={"max_concurrency": 4, "service_time": {"distribution": "constant", "ms": 500}},
+        )
+        processor = BatchProcessor(provider, max_concurrency=4, drain_timeout=5.0)
+        handles.append(processor.start_batch([[{"role": "user", "content": str(i)}] for i in range(12)]))
+        time.sleep(0.2)
+    """)
+    output = subprocess.run([sys.executable, "-c", script], capture_output=True, text=True, check=True).stdout
+    assert output.strip() == str(sorted(["ok"] * 4 + ["cancelled"] * 8))
+
+
+def test_interrupted_runs_are_marked_on_the_result():
+    provider = ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+    result = BatchProcessor(provider).process_batch(REQUESTS, show_progress=False)
+    assert not result.interrupted
+    assert result.cancelled_requests == 0
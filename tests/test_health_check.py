@@ -0,0 +1,52 @@
+import threading
+import warnings
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(30)]
+
+
+@pytest.fixture
+def servers(make_server):
+    return [make_server(delay=0.05) for _ in range(2)]
+
+
+def providers(servers):
+    return [server.provider() for server in servers]
+
+
+def test_unhealthy_provider_is_left_out(servers):
+    servers[1].health = 503
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        metrics = BatchProcessor(providers(servers), health_check=True).process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    assert {m.provider_name for m in metrics} == {f"openai:{servers[0].url}"}
+    assert any("failed its health check" in str(w.message) and "503" in str(w.message) for w in caught)
+    assert [server.probes for server in servers] == [1, 1]
+
+
+def test_without_health_check_nothing_is_probed(servers):
+    BatchProcessor(providers(servers)).process_batch(REQUESTS[:4], show_progress=False)
+    assert [server.probes for server in servers] == [0, 0]
+
+
+def test_recovered_provider_is_readmitted(servers):
+    servers[1].health = 503
+    threading.Timer(0.3, lambda: setattr(servers[1], "health", 200)).start()
+    processor = BatchProcessor(providers(servers), max_concurrency=2, health_check_interval=0.1)
+    with warnings.catch_warnings():
+        warnings.simplefilter("ignore")
+        metrics = processor.process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    recovered = f"openai:{servers[1].url}"
+    assert metrics[0].provider_name != recovered
+    assert sum(m.provider_name == recovered for m in metrics) >= 5
+    assert servers[1].probes >= 3
+
+
+def test_interval_must_be_positive(servers):
+    with pytest.raises(ValueError, match="health_check_interval"):
+        BatchProcessor(providers(servers), health_check_interval=0).process_batch(REQUESTS[:1], show_progress=False)
@@ -0,0 +1,85 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+# Enough quick requests to establish the p95 latency, then some the slow server stalls on
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(30)] + [
+    [{"role": "user", "content": f"Slow question {i}"}] for i in range(6)
+]
+
+
+def delay(stalls):
+    """Quick answers, but slow ones stall on a stalling server and busy ones take a while."""
+
+    def wait(body):
+        content = body["messages"][0]["content"]
+        return 3 if stalls and "Slow" in content else 0.3 if "Busy" in content else 0.01
+
+    return wait
+
+
+@pytest.fixture
+def servers(make_server):
+    return [make_server(delay=delay(stalls)) for stalls in (True, False)]
+
+
+def provider(server):
+    return server.provider()
+
+
+def test_stalled_requests_are_hedged_to_the_other_provider(servers):
+    stalling, quick = servers
+    started = time.monotonic()
+    processor = BatchProcessor([provider(stalling), provider(quick)], max_concurrency=1, hedge_requests=True)
+    result = processor.process_batch(REQUESTS, show_progress=False)
+    assert time.monotonic() - started < 3
+    assert all(m.status == "ok" for m in result.metrics)
+    stalled = [m for m in result.metrics[30:] if m.hedged_to is not None]
+    assert stalled
+    quick_name = f"openai:{quick.url}"
+    for m in stalled:
+        assert m.hedge_won
+        assert m.hedged_to == quick_name
+        assert m.provider_name == quick_name
+        assert m.latency_ms < 3000
+    assert result.hedged_requests >= len(stalled)
+    assert result.hedges_won >= len(stalled)
+
+
+def test_without_hedging_nothing_is_duplicated(servers):
+    stalling, quick = servers
+    processor = BatchProcessor([provider(stalling), provider(quick)], max_concurrency=6)
+    result = processor.process_batch(REQUESTS, show_progress=False)
+    assert all(m.hedged_to is None and not m.hedge_won for m in result.metrics)
+    assert result.hedged_requests == 0
+    assert stalling.calls + quick.calls == len(REQUESTS)
+
+
+def test_a_single_provider_has_nowhere_to_hedge(servers):
+    stalling, _ = servers
+    processor = BatchProcessor([provider(stalling)], max_concurrency=6, hedge_requests=True)
+    result = processor.process_batch(REQUESTS, show_progress=False)
+    assert all(m.status == "ok" and m.hedged_to is None for m in result.metrics)
+    assert stalling.calls == len(REQUESTS)
+
+
+def test_hedges_respect_the_target_cap(servers):
+    stalling, quick = servers
+
+    def requests():
+        yield from REQUESTS[:30]
+        for i in range(6):
+            # Both providers are idle when a stalled request goes out, so it can be hedged,
+            # and the busy one right after it would fit on the quick one but for the hedge
+            time.sleep(0.2)
+            yield [{"role": "user", "content": f"Slow question {i}"}]
+            yield [{"role": "user", "content": f"Busy question {i}"}]
+
+    processor = BatchProcessor(
+        [provider(stalling), provider(quick)], max_concurrency=3, max_concurrency_per_host=1, hedge_requests=True
+    )
+    result = processor.process_batch(requests(), show_progress=False)
+    assert all(m.status == "ok" for m in result.metrics)
+    assert quick.peak == 1
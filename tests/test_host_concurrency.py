@@ -0,0 +1,39 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(20)]
+
+
+@pytest.fixture
+def servers(make_server):
+    return [make_server(delay=0.2), make_server(delay=0.01)]
+
+
+def provider(server, key="k", **options):
+    return ProviderConfig(name="openai", api_key=key, base_url=server.url, config={"model": "m"}, **options)
+
+
+def test_providers_on_one_host_share_its_cap(servers):
+    slow, fast = servers
+    providers = [provider(slow, "a"), provider(slow, "b"), provider(fast)]
+    processor = BatchProcessor(providers, max_concurrency=8, max_concurrency_per_host=2)
+    metrics = processor.process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    assert slow.peak <= 2
+    # The slots the slow host can't take go to the fast one
+    assert fast.calls > slow.calls
+
+
+def test_provider_at_its_cap_is_passed_over(servers):
+    slow, fast = servers
+    processor = BatchProcessor([provider(slow, max_concurrency=1), provider(fast)], max_concurrency=4)
+    metrics = processor.process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    assert slow.peak == 1
+    assert fast.calls >= 15
+
+
+def test_cap_must_be_positive(servers):
+    with pytest.raises(ValueError, match="max_concurrency_per_host"):
+        BatchProcessor(provider(servers[0]), max_concurrency_per_host=0).process_batch(REQUESTS, show_progress=False)
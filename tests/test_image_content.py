@@ -0,0 +1,41 @@
+import pytest
+
+from axicontraves import normalize_messages
+
+
+def ask(*images):
+    return [{"role": "user", "content": [{"type": "text", "text": "What is this?"}, *images]}]
+
+
+def test_openai_receives_image_url_parts(server):
+    metrics = server.run(ask(
+        {"type": "image_url", "image_url": {"url": "https://example.com/cat.png", "detail": "low"}},
+        {"type": "image", "data": "aGVsbG8="},
+    ))
+    assert metrics.status == "ok"
+    assert server.body["messages"][0]["content"] == [
+        {"type": "text", "text": "What is this?"},
+        {"type": "image_url", "image_url": {"url": "https://example.com/cat.png", "detail": "low"}},
+        {"type": "image_url", "image_url": {"url": "data:image/png;base64,aGVsbG8="}},
+    ]
+
+
+def test_anthropic_receives_image_sources(server):
+    metrics = server.run(ask(
+        {"type": "image_url", "image_url": "https://example.com/cat.png"},
+        {"type": "image", "source": {"type": "base64", "media_type": "image/webp", "data": "aGVsbG8="}},
+    ), name="anthropic")
+    assert metrics.status == "ok"
+    assert [block["source"] for block in server.body["messages"][0]["content"][1:]] == [
+        {"type": "url", "url": "https://example.com/cat.png"},
+        {"type": "base64", "media_type": "image/webp", "data": "aGVsbG8="},
+    ]
+
+
+def test_plain_string_content_is_unchanged():
+    assert normalize_messages("openai", [{"role": "user", "content": "Hi"}])["messages"][0]["content"] == "Hi"
+
+
+def test_unknown_part_type_is_rejected():
+    with pytest.raises(ValueError, match="video"):
+        normalize_messages("openai", ask({"type": "video", "url": "https://example.com/cat.mp4"}))
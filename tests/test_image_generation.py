@@ -0,0 +1,48 @@
+import base64
+
+import pytest
+
+PNG = b"\x89PNG\r\n\x1a\n" + bytes(range(200))
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        if body.get("response_format") == "b64_json":
+            data = [{"b64_json": base64.b64encode(PNG).decode()} for _ in range(body.get("n", 1))]
+            return {"created": 0, "data": data, "usage": {"input_tokens": 12, "output_tokens": 272}}
+        return {"created": 0, "data": [{"url": "https://images.example/1.png"}]}
+
+    return answer
+
+
+def run(server, request, **options):
+    return server.process([request], server.provider(config={"model": "gpt-image-1"}), **options).metrics[0]
+
+
+def test_url_response(server):
+    metrics = run(server, {"type": "image", "prompt": "a red fox", "size": "1024x1024"})
+    path, body = server.path, server.body
+    assert path == "/v1/images/generations"
+    assert body == {"prompt": "a red fox", "model": "gpt-image-1", "size": "1024x1024"}
+    assert metrics.status == "ok"
+    assert metrics.image_urls == ["https://images.example/1.png"]
+    assert metrics.image_bytes == 0
+    assert metrics.usage_estimated
+    assert metrics.latency_ms > 0
+
+
+def test_b64_response(server, tmp_path):
+    request = {"type": "image", "prompt": "a red fox", "n": 2, "response_format": "b64_json"}
+    metrics = run(server, request, stream_dir=str(tmp_path))
+    assert metrics.image_bytes == 2 * len(PNG)
+    assert base64.b64decode(metrics.content) == PNG
+    assert len(metrics.choices) == 2
+    assert (metrics.prompt_tokens, metrics.completion_tokens) == (12, 272)
+    assert not metrics.usage_estimated
+    assert not list(tmp_path.iterdir())
+
+
+def test_unknown_request_type(server):
+    with pytest.raises(ValueError):
+        run(server, {"type": "video", "prompt": "a red fox"})
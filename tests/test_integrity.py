@@ -0,0 +1,35 @@
+from axicontraves import BatchProcessor, ProviderConfig, start_requests_multi, verify_results
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(4)]
+HEALTHY = ("openai", "test", None, {"model": "m"}, {"test_mode": True})
+
+
+def test_complete_run():
+    processor = BatchProcessor(ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True))
+    result = processor.process_batch(REQUESTS, show_progress=False)
+    assert result.integrity.complete
+    assert result.integrity.statuses == {"ok": len(REQUESTS)}
+
+
+def test_failed_requests_count_as_terminal():
+    dead = ProviderConfig(name="openai", api_key="test", base_url="http://127.0.0.1:9", config={"model": "m"})
+    result = BatchProcessor(dead).process_batch(REQUESTS, show_progress=False)
+    assert result.integrity.complete
+    assert result.integrity.statuses == {"error": len(REQUESTS)}
+
+
+def test_gaps_are_reported():
+    results = start_requests_multi([HEALTHY], REQUESTS).wait()
+    by_index = sorted(results, key=lambda m: m.index)
+
+    report = verify_results(by_index[1:] + by_index[2:3], len(REQUESTS))
+    assert not report.complete
+    assert report.missing == [0]
+    assert report.duplicated == [2]
+    assert verify_results(by_index, len(REQUESTS) - 1).unexpected == [len(REQUESTS) - 1]
+
+
+def test_handle_reports_integrity():
+    handle = start_requests_multi([HEALTHY], REQUESTS)
+    handle.wait()
+    assert handle.integrity().complete
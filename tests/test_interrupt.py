@@ -0,0 +1,48 @@
+import _thread
+import threading
+import time
+
+import pytest
+
+from axicontraves import process_requests_multi, start_requests_multi
+
+# Slow simulated server: one request at a time, 200 ms each
+SLOW = ("openai", "test", None, {"model": "m"},
+        {"simulator": {"max_concurrency": 1, "service_time": {"distribution": "constant", "ms": 200}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(20)]
+
+
+def interrupt_after(seconds):
+    threading.Timer(seconds, _thread.interrupt_main).start()
+
+
+def test_ctrl_c_returns_partial_results():
+    interrupt_after(0.5)
+    started = time.monotonic()
+    with pytest.warns(RuntimeWarning, match="interrupted"):
+        results = process_requests_multi([SLOW], REQUESTS, lambda *args: None, False, None, max_concurrency=1)
+    assert time.monotonic() - started < 2
+    assert sorted(m.index for m in results) == list(range(len(REQUESTS)))
+    statuses = [m.status for m in results]
+    assert 0 < statuses.count("ok") < len(REQUESTS)
+    assert statuses.count("ok") + statuses.count("cancelled") == len(REQUESTS)
+
+
+def test_ctrl_c_during_wait_cancels_the_run():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=1)
+    interrupt_after(0.5)
+    results = handle.wait()
+    assert handle.done()
+    assert len(results) == len(REQUESTS)
+    assert "cancelled" in {m.status for m in results}
+
+
+def test_cancel_stops_the_whole_run():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=1)
+    time.sleep(0.5)
+    handle.cancel()
+    results = handle.wait()
+    assert sorted(m.index for m in results) == list(range(len(REQUESTS)))
+    statuses = [m.status for m in results]
+    assert 0 < statuses.count("ok") < len(REQUESTS)
+    assert statuses.count("ok") + statuses.count("cancelled") == len(REQUESTS)
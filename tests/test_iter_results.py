@@ -0,0 +1,31 @@
+import time
+
+from axicontraves import BatchProcessor, ProviderConfig, start_requests_multi
+
+# Slow simulated server: one request at a time, 200 ms each
+SLOW = ("openai", "test", None, {"model": "m"},
+        {"simulator": {"max_concurrency": 1, "service_time": {"distribution": "constant", "ms": 200}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(5)]
+
+
+def test_results_are_yielded_as_they_finish():
+    started = time.monotonic()
+    arrivals = [(m, time.monotonic() - started) for m in start_requests_multi([SLOW], REQUESTS, max_concurrency=5)]
+    assert sorted(m.index for m, _ in arrivals) == list(range(len(REQUESTS)))
+    # All five share one batch, yet the first result arrives long before the last
+    assert arrivals[0][1] < 0.6
+    assert arrivals[-1][1] > 0.9
+
+
+def test_iteration_matches_results():
+    handle = start_requests_multi([SLOW], REQUESTS)
+    yielded = list(handle)
+    assert handle.done()
+    assert [m.index for m in yielded] == [m.index for m in handle.results()]
+
+
+def test_iter_batch():
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+    metrics = list(BatchProcessor(provider).iter_batch(REQUESTS))
+    assert sorted(m.index for m in metrics) == list(range(len(REQUESTS)))
+    assert {m.status for m in metrics} == {"ok"}
@@ -0,0 +1,60 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, detect_language
+
+
+@pytest.mark.parametrize("text,language", [
+    ("What is the capital of France?", "en"),
+    ("¿Cuál es la capital de Francia y por qué?", "es"),
+    ("Quelle est la capitale de la France ?", "fr"),
+    ("Was ist die Hauptstadt von Frankreich und wo liegt das?", "de"),
+    ("Какая столица Франции?", "ru"),
+    ("法国的首都是哪里？", "zh"),
+    ("フランスの首都はどこですか？", "ja"),
+    ("프랑스의 수도는 어디입니까?", "ko"),
+    ("12345", "unknown"),
+])
+def test_detect_language(text, language):
+    assert detect_language(text) == language
+
+
+def providers():
+    return [
+        ProviderConfig(name="openai", api_key="k", base_url=url, config={"model": "m"}, test_mode=True)
+        for url in ("http://a", "http://b")
+    ]
+
+
+def request(text):
+    return [{"role": "system", "content": "You are a helpful assistant."}, {"role": "user", "content": text}]
+
+
+def test_cjk_requests_are_routed():
+    processor = BatchProcessor(providers(), language_routing={"cjk": 1})
+    texts = ["法国的首都是哪里？", "フランスの首都はどこですか？", "프랑스의 수도는?", "What is this?"] * 2
+    result = processor.process_batch([request(t) for t in texts], show_progress=False)
+    for m in result.metrics:
+        if m.language in ("zh", "ja", "ko"):
+            assert m.provider_name == "openai:http://b"
+    assert set(result.language_metrics) == {"en", "ja", "ko", "zh"}
+    assert result.language_metrics["zh"].total_requests == 2
+
+
+def test_detection_without_routing():
+    result = BatchProcessor(providers(), detect_language=True).process_batch(
+        [request("Какая столица Франции?")], show_progress=False
+    )
+    assert result.metrics[0].language == "ru"
+    assert BatchProcessor(providers()).process_batch([request("hi")], show_progress=False).metrics[0].language is None
+
+
+def test_route_to_unknown_provider():
+    with pytest.raises(ValueError, match="provider 5"):
+        BatchProcessor(providers(), language_routing={"ru": 5}).process_batch([request("hi")], show_progress=False)
+
+
+def test_providers_on_their_default_endpoint_are_broken_down():
+    default = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+    result = BatchProcessor([default, providers()[0]]).process_batch([request("hi")] * 4, show_progress=False)
+    assert set(result.provider_metrics) == {"openai:https://api.openai.com", "openai:http://a"}
+    assert sum(p.total_requests for p in result.provider_metrics.values()) == 4
@@ -0,0 +1,52 @@
+import pytest
+
+from axicontraves import BatchProcessor, LatencyHistogram, ProviderConfig
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(200)]
+
+
+@pytest.fixture(scope="module")
+def summary():
+    provider = ProviderConfig(
+        name="openai", api_key="test", config={"model": "m"},
+        simulator={"service_time": {"distribution": "lognormal", "median_ms": 5, "sigma": 0.8}},
+    )
+    return BatchProcessor(provider, max_concurrency=50).process_batch(REQUESTS, show_progress=False).summary
+
+
+def test_histogram_holds_every_successful_latency(summary):
+    histogram = summary.latency_histogram
+    assert histogram.count == summary.succeeded == len(REQUESTS)
+    assert histogram.mean_ms == pytest.approx(summary.mean_latency_ms, rel=0.01)
+    assert histogram.min_ms <= summary.p50_latency_ms <= histogram.max_ms
+
+
+def test_percentiles_match_the_summary(summary):
+    histogram = summary.latency_histogram
+    p50, p99 = histogram.percentiles([0.5, 0.99])
+    # Three significant digits, and whole microseconds
+    assert p50 == pytest.approx(summary.p50_latency_ms, rel=0.002, abs=0.002)
+    assert p99 == pytest.approx(summary.p99_latency_ms, rel=0.002, abs=0.002)
+    defaults = histogram.percentiles()
+    assert len(defaults) == 7
+    assert defaults == sorted(defaults)
+    assert defaults[-1] == pytest.approx(histogram.max_ms, rel=0.002)
+    with pytest.raises(ValueError, match="quantile"):
+        histogram.value_at(1.5)
+
+
+def test_encoding_round_trips(summary):
+    histogram = summary.latency_histogram
+    decoded = LatencyHistogram.decode(histogram.encode())
+    assert decoded.count == histogram.count
+    assert decoded.percentiles() == histogram.percentiles()
+    merged = decoded.merge(histogram)
+    assert merged.count == 2 * histogram.count
+    assert merged.value_at(0.5) == histogram.value_at(0.5)
+    with pytest.raises(ValueError):
+        LatencyHistogram.decode("not a histogram")
+
+
+def test_ttft_histogram_is_empty_without_streaming(summary):
+    assert summary.ttft_histogram.count == 0
+    assert summary.ttft_histogram.value_at(0.99) == 0
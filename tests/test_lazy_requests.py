@@ -0,0 +1,66 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+PROVIDER = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+
+
+def request(i):
+    return [{"role": "user", "content": f"Question {i}"}]
+
+
+def test_generator_is_pulled_as_slots_free_up():
+    pulled = 0
+    finished = 0
+    lead = 0
+
+    def requests():
+        nonlocal pulled, lead
+        for i in range(60):
+            pulled += 1
+            lead = max(lead, pulled - finished)
+            yield request(i)
+
+    def on_result(metrics):
+        nonlocal finished
+        finished += 1
+
+    processor = BatchProcessor(PROVIDER, max_concurrency=4, result_callback=on_result)
+    result = processor.process_batch(requests(), show_progress=False)
+    assert [m.index for m in result.metrics] == list(range(60))
+    assert all(m.status == "ok" for m in result.metrics)
+    assert result.integrity.complete
+    # Never more than a few requests ahead of the results instead of all 60 up front
+    assert lead <= 8
+
+
+def test_handle_over_an_iterator():
+    handle = BatchProcessor(PROVIDER, max_concurrency=2).start_batch(request(i) for i in range(10))
+    assert sorted(m.index for m in handle) == list(range(10))
+    assert handle.total == 10
+
+
+def test_iterator_exception_is_raised_after_in_flight_requests():
+    seen = []
+
+    def requests():
+        yield request(0)
+        yield request(1)
+        raise RuntimeError("dataset is corrupt")
+
+    processor = BatchProcessor(PROVIDER, result_callback=seen.append)
+    with pytest.raises(RuntimeError, match="dataset is corrupt"):
+        processor.process_batch(requests(), show_progress=False)
+    assert sorted(m.index for m in seen) == [0, 1]
+
+
+def test_malformed_request_names_its_position():
+    requests = iter([request(0), request(1), 42])
+    with pytest.raises(ValueError, match=r"requests\[2\]"):
+        BatchProcessor(PROVIDER).process_batch(requests, show_progress=False)
+
+
+@pytest.mark.parametrize("option", [{"reorder_by_prefix": True}, {"dedupe_ttl": 60}])
+def test_options_that_need_a_list_are_rejected(option):
+    with pytest.raises(ValueError, match="as a list"):
+        BatchProcessor(PROVIDER, **option).process_batch(iter([request(0)]), show_progress=False)
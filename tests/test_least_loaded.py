@@ -0,0 +1,34 @@
+from collections import Counter
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(40)]
+
+
+@pytest.fixture
+def providers(make_server):
+    return [make_server(delay=delay).provider() for delay in (0.005, 0.15)]
+
+
+def split(providers, routing):
+    metrics = BatchProcessor(providers, max_concurrency=4, routing=routing).process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    counts = Counter(m.provider_name for m in metrics)
+    return [counts[f"openai:{p.base_url}"] for p in providers]
+
+
+def test_round_robin_ignores_latency(providers):
+    assert split(providers, "round_robin") == [20, 20]
+
+
+def test_least_loaded_favours_the_fast_provider(providers):
+    fast, slow = split(providers, "least_loaded")
+    assert fast >= 30
+    assert slow >= 1
+
+
+def test_unknown_routing_is_rejected(providers):
+    with pytest.raises(ValueError, match="routing"):
+        BatchProcessor(providers, routing="random").process_batch(REQUESTS[:1], show_progress=False)
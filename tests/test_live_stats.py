@@ -0,0 +1,47 @@
+import threading
+import time
+
+from axicontraves import RunStats, start_requests_multi
+
+# Simulated server taking 50 ms per request, two at a time
+SLOW = ("openai", "test", None, {"model": "m"},
+        {"simulator": {"max_concurrency": 2, "service_time": {"distribution": "constant", "ms": 50}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(20)]
+
+
+def test_stats_track_the_run_as_it_goes():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=2)
+    polled = []
+    while not handle.done():
+        polled.append(handle.get_stats())
+        time.sleep(0.02)
+    handle.wait()
+    assert all(isinstance(stats, RunStats) for stats in polled)
+    assert [stats.completed for stats in polled] == sorted(stats.completed for stats in polled)
+    assert any(0 < stats.completed < len(REQUESTS) and stats.in_flight > 0 for stats in polled)
+    final = handle.get_stats()
+    assert (final.completed, final.total, final.errors, final.in_flight) == (len(REQUESTS), len(REQUESTS), 0, 0)
+    assert final.p50_latency_ms >= 50
+    assert final.p50_latency_ms <= final.p90_latency_ms <= final.p99_latency_ms
+    assert final.tokens_per_second > 0
+
+
+def test_stats_can_be_polled_from_another_thread():
+    handle = start_requests_multi([SLOW], REQUESTS, max_concurrency=2)
+    seen = []
+
+    def poll():
+        while not handle.done():
+            seen.append(handle.get_stats().completed)
+            time.sleep(0.01)
+
+    threads = [threading.Thread(target=poll) for _ in range(3)]
+    for thread in threads:
+        thread.start()
+    results = handle.wait()
+    for thread in threads:
+        thread.join()
+    assert len(results) == len(REQUESTS)
+    assert seen and max(seen) <= len(REQUESTS)
+    assert handle.get_stats().completed == len(REQUESTS)
+
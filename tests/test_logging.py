@@ -0,0 +1,46 @@
+import logging
+
+from axicontraves import BatchProcessor, ProviderConfig, process_requests_multi
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(3)]
+# Nothing listens on the discard port, so every request to it fails fast
+DEAD = ("openai", "test", "http://127.0.0.1:9", {"model": "m"}, {"test_mode": False})
+
+
+def run(**options):
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+    return BatchProcessor(provider, **options).process_batch(REQUESTS, show_progress=False)
+
+
+def messages(caplog, logger):
+    return [record.getMessage() for record in caplog.records if record.name == logger]
+
+
+def test_dispatch_is_logged_at_debug(caplog):
+    caplog.set_level(logging.DEBUG, logger="axicontraves")
+    run()
+    logged = messages(caplog, "axicontraves.dispatch")
+    assert any(message.startswith("run started") for message in logged)
+    assert sum(message.startswith("sending request") for message in logged) == len(REQUESTS)
+    assert sum(message.startswith("request finished") for message in logged) == len(REQUESTS)
+    assert any(message.startswith("run finished") for message in logged)
+
+
+def test_failures_are_warnings(caplog):
+    caplog.set_level(logging.WARNING, logger="axicontraves")
+    process_requests_multi([DEAD], REQUESTS, lambda *args: None, False, None)
+    failures = [r for r in caplog.records if r.name == "axicontraves.dispatch" and r.levelno == logging.WARNING]
+    assert len(failures) == len(REQUESTS)
+    assert all("category=connection" in record.getMessage() for record in failures)
+    # Nothing below the level set on the logger is emitted
+    assert not messages(caplog, "axicontraves.dispatch") or all(r.levelno >= logging.WARNING for r in caplog.records)
+
+
+def test_log_level_option_sets_the_logger_level(caplog):
+    try:
+        run(log_level="INFO")
+        assert logging.getLogger("axicontraves").level == logging.INFO
+        assert any(message.startswith("run started") for message in messages(caplog, "axicontraves.dispatch"))
+        assert not any(message.startswith("sending request") for message in messages(caplog, "axicontraves.dispatch"))
+    finally:
+        logging.getLogger("axicontraves").setLevel(logging.NOTSET)
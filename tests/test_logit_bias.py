@@ -0,0 +1,23 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Yes or no?"}]
+
+
+def test_token_ids_are_sent_as_string_keys(server):
+    server.run(QUESTION, logit_bias={9642: 100, "2822": -100})
+    assert server.body["logit_bias"] == {"9642": 100, "2822": -100}
+
+
+def test_request_bias_replaces_the_config(server):
+    server.run({"messages": QUESTION, "logit_bias": {1: 5.5}}, logit_bias={9642: 100})
+    assert server.body["logit_bias"] == {"1": 5.5}
+
+
+def test_no_bias_by_default(server):
+    server.run(QUESTION)
+    assert "logit_bias" not in server.body
+
+
+def test_a_bias_that_is_not_a_dict_is_rejected(server):
+    with pytest.raises(ValueError, match="logit_bias"):
+        server.run(QUESTION, logit_bias=[1, 2])
@@ -0,0 +1,72 @@
+import json
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+
+class Slow(BaseHTTPRequestHandler):
+    """Answers after 200ms, tracking the most requests seen in flight at once."""
+
+    lock = threading.Lock()
+    in_flight = 0
+    peak = 0
+
+    def do_POST(self):
+        self.rfile.read(int(self.headers["Content-Length"]))
+        with Slow.lock:
+            Slow.in_flight += 1
+            Slow.peak = max(Slow.peak, Slow.in_flight)
+        time.sleep(0.2)
+        with Slow.lock:
+            Slow.in_flight -= 1
+        response = {
+            "model": "m",
+            "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1},
+        }
+        payload = json.dumps(response).encode()
+        self.send_response(200)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def provider():
+    httpd = ThreadingHTTPServer(("127.0.0.1", 0), Slow)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    Slow.peak = 0
+    yield ProviderConfig(name="openai", api_key="k", base_url=f"http://127.0.0.1:{httpd.server_port}", config={"model": "m"})
+    httpd.shutdown()
+
+
+REQUESTS = [[{"role": "user", "content": f"Hi {i}"}] for i in range(16)]
+
+
+def test_default_allows_more_than_four_in_flight(provider):
+    metrics = BatchProcessor(provider).process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    assert Slow.peak > 4
+
+
+def test_max_concurrency_caps_requests_in_flight(provider):
+    BatchProcessor(provider, max_concurrency=2).process_batch(REQUESTS, show_progress=False)
+    assert Slow.peak == 2
+
+
+def test_plan_uses_max_concurrency(provider):
+    estimate = BatchProcessor(provider, max_concurrency=3).plan(REQUESTS)
+    assert estimate.providers[0].concurrency == 3
+
+
+def test_zero_is_rejected(provider):
+    with pytest.raises(ValueError, match="max_concurrency"):
+        BatchProcessor(provider, max_concurrency=0).process_batch(REQUESTS, show_progress=False)
@@ -0,0 +1,159 @@
+import base64
+
+import pytest
+
+from axicontraves import normalize_messages
+
+MESSAGES = [
+    {"role": "system", "content": "You are terse."},
+    {"role": "user", "content": "Hi"},
+    {"role": "assistant", "content": "Hello."},
+    {"role": "user", "content": "Bye"},
+]
+
+
+def test_openai_keeps_system_inline():
+    fields = normalize_messages("openai", MESSAGES)
+    assert "system" not in fields
+    assert fields["messages"][0] == {"role": "system", "content": "You are terse."}
+    assert len(fields["messages"]) == 4
+
+
+def test_anthropic_moves_system_to_top_level():
+    fields = normalize_messages("anthropic", MESSAGES)
+    assert fields["system"] == "You are terse."
+    assert [m["role"] for m in fields["messages"]] == ["user", "assistant", "user"]
+    assert fields["messages"][0]["content"] == [{"type": "text", "text": "Hi"}]
+
+
+def test_anthropic_joins_multiple_system_messages():
+    messages = [
+        {"role": "system", "content": "First."},
+        {"role": "user", "content": "Hi"},
+        {"role": "system", "content": "Second."},
+    ]
+    assert normalize_messages("anthropic", messages)["system"] == "First.\n\nSecond."
+
+
+def test_anthropic_merges_consecutive_user_turns():
+    messages = [{"role": "user", "content": "a"}, {"role": "user", "content": "b"}]
+    fields = normalize_messages("anthropic", messages)
+    assert len(fields["messages"]) == 1
+    assert [block["text"] for block in fields["messages"][0]["content"]] == ["a", "b"]
+
+
+def test_anthropic_places_cache_control_on_last_block():
+    messages = [
+        {"role": "system", "content": "Long shared context.", "cache_control": {"type": "ephemeral"}},
+        {"role": "user", "content": [
+            {"type": "text", "text": "doc"},
+            {"type": "text", "text": "question"},
+        ], "cache_control": {"type": "ephemeral"}},
+    ]
+    fields = normalize_messages("anthropic", messages)
+    assert fields["system"] == [
+        {"type": "text", "text": "Long shared context.", "cache_control": {"type": "ephemeral"}}
+    ]
+    blocks = fields["messages"][0]["content"]
+    assert "cache_control" not in blocks[0]
+    assert blocks[1]["cache_control"] == {"type": "ephemeral"}
+
+
+def test_openai_ignores_cache_control():
+    messages = [{"role": "user", "content": "Hi", "cache_control": {"type": "ephemeral"}}]
+    assert normalize_messages("openai", messages)["messages"] == [{"role": "user", "content": "Hi"}]
+
+
+def test_gemini_uses_system_instruction_and_model_role():
+    fields = normalize_messages("gemini", MESSAGES)
+    assert fields["systemInstruction"] == {"parts": [{"text": "You are terse."}]}
+    assert [c["role"] for c in fields["contents"]] == ["user", "model", "user"]
+
+
+def test_developer_role_is_treated_as_system():
+    messages = [{"role": "developer", "content": "Answer in French."}, {"role": "user", "content": "Hi"}]
+    assert normalize_messages("openai", messages)["messages"][0]["role"] == "developer"
+    anthropic = normalize_messages("anthropic", messages)
+    assert anthropic["system"] == "Answer in French."
+    assert [m["role"] for m in anthropic["messages"]] == ["user"]
+    assert normalize_messages("gemini", messages)["systemInstruction"] == {"parts": [{"text": "Answer in French."}]}
+
+
+def test_no_system_message_omits_field():
+    messages = MESSAGES[1:]
+    assert "system" not in normalize_messages("anthropic", messages)
+    assert "systemInstruction" not in normalize_messages("gemini", messages)
+
+
+def test_image_parts_translate_per_provider():
+    messages = [{"role": "user", "content": [
+        {"type": "text", "text": "What is this?"},
+        {"type": "image", "data": "aGVsbG8=", "media_type": "image/jpeg"},
+    ]}]
+    openai = normalize_messages("openai", messages)["messages"][0]["content"][1]
+    assert openai["image_url"]["url"] == "data:image/jpeg;base64,aGVsbG8="
+    anthropic = normalize_messages("anthropic", messages)["messages"][0]["content"][1]
+    assert anthropic["source"] == {"type": "base64", "media_type": "image/jpeg", "data": "aGVsbG8="}
+    gemini = normalize_messages("gemini", messages)["contents"][0]["parts"][1]
+    assert gemini == {"inline_data": {"mime_type": "image/jpeg", "data": "aGVsbG8="}}
+
+
+def test_audio_is_rejected_for_anthropic():
+    messages = [{"role": "user", "content": [{"type": "audio", "data": "AAAA", "format": "wav"}]}]
+    with pytest.raises(ValueError, match="audio"):
+        normalize_messages("anthropic", messages)
+
+
+def test_name_and_extra_keys_pass_through_for_openai():
+    messages = [{"role": "user", "content": "Hi", "name": "alice", "weight": 0}]
+    assert normalize_messages("openai", messages)["messages"] == [
+        {"role": "user", "content": "Hi", "name": "alice", "weight": 0}
+    ]
+
+
+def test_name_prefixes_text_for_anthropic_and_gemini():
+    messages = [
+        {"role": "user", "content": "Hi", "name": "alice"},
+        {"role": "user", "content": [{"type": "text", "text": "Hello"}], "name": "bob"},
+    ]
+    anthropic = normalize_messages("anthropic", messages)["messages"]
+    assert [block["text"] for block in anthropic[0]["content"]] == ["alice: Hi", "bob: Hello"]
+    gemini = normalize_messages("gemini", messages)["contents"]
+    assert [c["parts"][0]["text"] for c in gemini] == ["alice: Hi", "bob: Hello"]
+
+
+def test_file_path_is_encoded_per_provider(tmp_path):
+    pdf = tmp_path / "report.pdf"
+    pdf.write_bytes(b"%PDF-1.4 test")
+    encoded = base64.b64encode(pdf.read_bytes()).decode()
+    messages = [{"role": "user", "content": [
+        {"type": "text", "text": "Summarize"},
+        {"type": "file", "path": str(pdf)},
+    ]}]
+    openai = normalize_messages("openai", messages)["messages"][0]["content"][1]
+    assert openai == {"type": "file", "file": {"filename": "report.pdf", "file_data": f"data:application/pdf;base64,{encoded}"}}
+    anthropic = normalize_messages("anthropic", messages)["messages"][0]["content"][1]
+    assert anthropic == {"type": "document", "source": {"type": "base64", "media_type": "application/pdf", "data": encoded}}
+    gemini = normalize_messages("gemini", messages)["contents"][0]["parts"][1]
+    assert gemini == {"inline_data": {"mime_type": "application/pdf", "data": encoded}}
+
+
+def test_file_parts_accept_data_urls_and_ids():
+    messages = [{"role": "user", "content": [
+        {"type": "input_file", "file_data": "data:application/pdf;base64,JVBERg==", "filename": "a.pdf"},
+        {"type": "file", "file": {"file_id": "file-123"}},
+    ]}]
+    anthropic = normalize_messages("anthropic", messages)["messages"][0]["content"]
+    assert anthropic[0] == {
+        "type": "document", "title": "a.pdf",
+        "source": {"type": "base64", "media_type": "application/pdf", "data": "JVBERg=="},
+    }
+    assert anthropic[1]["source"] == {"type": "file", "file_id": "file-123"}
+    openai = normalize_messages("openai", messages)["messages"][0]["content"]
+    assert openai[1] == {"type": "file", "file": {"file_id": "file-123"}}
+
+
+def test_missing_file_is_reported(tmp_path):
+    messages = [{"role": "user", "content": [{"type": "file", "path": str(tmp_path / "missing.pdf")}]}]
+    with pytest.raises(ValueError, match="missing.pdf"):
+        normalize_messages("openai", messages)
@@ -0,0 +1,91 @@
+import json
+import subprocess
+import sys
+import textwrap
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(5)]
+
+
+def provider():
+    return ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+
+
+def read(path):
+    return [json.loads(line) for line in path.read_text().splitlines()]
+
+
+def test_one_line_per_result(tmp_path):
+    log = tmp_path / "metrics.jsonl"
+    result = BatchProcessor(provider(), metrics_log=str(log)).process_batch(REQUESTS, show_progress=False)
+    lines = read(log)
+    assert sorted(line["index"] for line in lines) == list(range(len(REQUESTS)))
+    by_index = {m.index: m for m in result.metrics}
+    for line in lines:
+        metrics = by_index[line["index"]]
+        assert line["status"] == "ok"
+        assert line["provider"] == metrics.provider_name
+        assert line["prompt_tokens"] == metrics.prompt_tokens
+        assert line["completion_tokens"] == metrics.completion_tokens
+        assert line["latency_ms"] == pytest.approx(metrics.latency_ms)
+        assert line["timestamp"] > 1e9
+
+
+def test_runs_append_to_the_same_file(tmp_path):
+    log = tmp_path / "metrics.jsonl"
+    processor = BatchProcessor(provider(), metrics_log=str(log))
+    processor.process_batch(REQUESTS, show_progress=False)
+    processor.start_batch(REQUESTS).wait()
+    assert len(read(log)) == 2 * len(REQUESTS)
+
+
+def test_failures_are_logged_with_their_category(tmp_path):
+    log = tmp_path / "metrics.jsonl"
+    dead = ProviderConfig(name="openai", api_key="test", base_url="http://127.0.0.1:9", config={"model": "m"})
+    BatchProcessor(dead, metrics_log=str(log), rate_limit_retries=0).process_batch(REQUESTS[:2], show_progress=False)
+    assert [(line["status"], line["error_category"]) for line in read(log)] == [("error", "connection")] * 2
+
+
+def test_providers_sharing_an_endpoint_log_their_own_model(tmp_path):
+    log = tmp_path / "metrics.jsonl"
+    # Failures, so the model logged is the one asked for rather than one from a response
+    providers = [
+        ProviderConfig(name="openai", api_key="test", base_url="http://127.0.0.1:9", config={"model": model})
+        for model in "ab"
+    ]
+    BatchProcessor(providers, metrics_log=str(log), rate_limit_retries=0).process_batch(REQUESTS[:4], show_progress=False)
+    assert sorted(line["model"] for line in read(log)) == ["a", "a", "b", "b"]
+
+
+def test_unwritable_path_is_rejected(tmp_path):
+    with pytest.raises(ValueError, match="metrics_log"):
+        BatchProcessor(provider(), metrics_log=str(tmp_path / "missing" / "metrics.jsonl")).process_batch(
+            REQUESTS, show_progress=False
+        )
+
+
+def test_lines_survive_the_process_dying(tmp_path):
+    log = tmp_path / "metrics.jsonl"
+    script = textwrap.dedent(f"""
+        import os
+        import time
+
+        from axicontraves import BatchProcessor, ProviderConfig
+
+        provider = ProviderConfig(
+            name="openai", api_key="test", config={{"model": "m"}},
+            simulator={{"max_concurrency": 2, "service_time": {{"distribution": "constant", "ms": 100}}}},
+        )
+        processor = BatchProcessor(provider, max_concurrency=2, metrics_log={str(log)!r})
+        handle = processor.start_batch([[{{"role": "user", "content": str(i)}}] for i in range(100)])
+        while handle.completed < 4:
+            time.sleep(0.01)
+        os._exit(1)
+    """)
+    subprocess.run([sys.executable, "-c", script], check=False)
+    lines = read(log)
+    assert 4 <= len(lines) < 100
+    assert all(line["status"] == "ok" for line in lines)
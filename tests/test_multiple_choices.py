@@ -0,0 +1,60 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Name a colour."}]
+TOOL = {"type": "function", "function": {"name": "pick", "parameters": {"type": "object"}}}
+CALL = {"id": "call_0", "type": "function", "function": {"name": "pick", "arguments": "{}"}}
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        n = body.get("n", 1)
+        choices = [{"index": i, "message": {"content": f"colour {i}"}, "finish_reason": "stop"} for i in range(n)]
+        if "tools" in body:
+            # The first choice only calls a tool, so its content is null
+            choices[0] = {"index": 0, "message": {"content": None, "tool_calls": [CALL]}, "finish_reason": "tool_calls"}
+        # Usage covers every choice
+        return {"choices": choices, "usage": {"prompt_tokens": 4, "completion_tokens": 2 * n}}
+
+    return answer
+
+
+def run(server, request, choice_policy=None, **config):
+    return server.process([request], server.provider(config=config), choice_policy=choice_policy).metrics[0]
+
+
+def test_every_choice_is_returned(server):
+    metrics = run(server, QUESTION, n=3)
+    assert server.body["n"] == 3
+    assert metrics.choices == ["colour 0", "colour 1", "colour 2"]
+    assert metrics.content == "colour 0"
+    assert (metrics.prompt_tokens, metrics.completion_tokens) == (4, 6)
+
+
+def test_request_overrides_n(server):
+    metrics = run(server, {"messages": QUESTION, "n": 2}, n=3)
+    assert server.body["n"] == 2
+    assert len(metrics.choices) == 2
+    assert metrics.completion_tokens == 4
+
+
+def test_n_is_left_out_by_default(server):
+    metrics = run(server, QUESTION)
+    assert "n" not in server.body
+    assert metrics.choices == ["colour 0"]
+
+
+def test_tool_call_choices_keep_their_place(server):
+    metrics = run(server, {"messages": QUESTION, "tools": [TOOL]}, n=2)
+    assert metrics.choices == [None, "colour 1"]
+    assert metrics.content is None
+    assert metrics.tool_calls == [CALL]
+    assert metrics.finish_reason == "tool_calls"
+
+
+def test_selected_choice_carries_its_own_tool_calls(server):
+    metrics = run(server, {"messages": QUESTION, "tools": [TOOL]}, choice_policy="longest", n=2)
+    assert metrics.selected_choice == 1
+    assert metrics.choices == ["colour 1", None]
+    assert metrics.tool_calls is None
+    assert metrics.finish_reason == "stop"
@@ -0,0 +1,56 @@
+import json
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+
+class Mixed(BaseHTTPRequestHandler):
+    """Answers prompts containing "slow" after a second, everything else after 50ms."""
+
+    def do_POST(self):
+        body = self.rfile.read(int(self.headers["Content-Length"]))
+        time.sleep(1.0 if b"slow" in body else 0.05)
+        response = {
+            "model": "m",
+            "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1},
+        }
+        payload = json.dumps(response).encode()
+        self.send_response(200)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def provider():
+    httpd = ThreadingHTTPServer(("127.0.0.1", 0), Mixed)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield ProviderConfig(name="openai", api_key="k", base_url=f"http://127.0.0.1:{httpd.server_port}", config={"model": "m"})
+    httpd.shutdown()
+
+
+REQUESTS = [[{"role": "user", "content": "slow"}]] + [[{"role": "user", "content": f"fast {i}"}] for i in range(16)]
+
+
+def test_slow_request_does_not_hold_up_the_rest(provider):
+    processor = BatchProcessor(provider, max_concurrency=2)
+    started = time.monotonic()
+    arrivals = list(processor.iter_batch(REQUESTS))
+    elapsed = time.monotonic() - started
+    # The other slot works through the fast requests while the slow one is running
+    assert [m.index for m in arrivals][-1] == 0
+    assert elapsed < 1.4
+
+
+def test_process_batch_keeps_dispatch_order(provider):
+    metrics = BatchProcessor(provider, max_concurrency=2).process_batch(REQUESTS, show_progress=False).metrics
+    assert [m.index for m in metrics] == list(range(len(REQUESTS)))
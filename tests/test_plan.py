@@ -0,0 +1,52 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, RunPlan, plan
+
+REQUESTS = [[{"role": "user", "content": f"Summarize chapter {i} of the book."}] for i in range(8)]
+
+
+def simulated(name="m", **simulator):
+    simulator = {"service_time": {"distribution": "constant", "ms": 100}, "per_token_ms": 0, **simulator}
+    return ProviderConfig(name="openai", api_key="test", config={"model": name}, simulator=simulator)
+
+
+def test_load_splits_across_providers_by_capacity():
+    estimate = plan(REQUESTS, [simulated(max_concurrency=2), simulated(max_concurrency=1)], concurrency=4)
+    assert isinstance(estimate, RunPlan)
+    assert estimate.total_requests == 8
+    assert [(p.requests, p.concurrency) for p in estimate.providers] == [(4, 2), (4, 1)]
+    assert [p.estimated_seconds for p in estimate.providers] == pytest.approx([0.2, 0.4])
+    # The run lasts as long as its slowest provider
+    assert estimate.estimated_seconds == pytest.approx(0.4)
+    assert estimate.prompt_tokens == sum(p.prompt_tokens for p in estimate.providers) > 0
+
+
+def test_cost_follows_the_pricing():
+    pricing = {"priced": {"input": 1.0, "output": 2.0}}
+    estimate = plan(REQUESTS, simulated("priced"), pricing=pricing)
+    expected = (estimate.prompt_tokens * 1.0 + estimate.completion_tokens * 2.0) / 1_000_000
+    assert estimate.estimated_cost_usd == pytest.approx(expected)
+    # A model without a price leaves the whole run unpriced
+    mixed = plan(REQUESTS, [simulated("priced"), simulated("unknown-model")], pricing=pricing)
+    assert mixed.providers[0].estimated_cost_usd is not None
+    assert mixed.providers[1].estimated_cost_usd is None
+    assert mixed.estimated_cost_usd is None
+
+
+def test_nothing_is_sent():
+    unreachable = ProviderConfig(name="openai", api_key="k", base_url="http://127.0.0.1:9", config={"model": "m"})
+    estimate = plan(REQUESTS, unreachable)
+    assert estimate.total_requests == len(REQUESTS)
+    assert estimate.estimated_seconds > 0
+
+
+def test_estimate_is_close_to_a_simulated_run():
+    processor = BatchProcessor(simulated(max_concurrency=2), max_concurrency=2)
+    estimate = processor.plan(REQUESTS)
+    started = time.monotonic()
+    processor.process_batch(REQUESTS, show_progress=False)
+    elapsed = time.monotonic() - started
+    assert estimate.estimated_seconds == pytest.approx(0.4)
+    assert 0.4 <= elapsed < 1.0
@@ -0,0 +1,42 @@
+import pytest
+
+from axicontraves import normalize_messages
+
+PREFILL = [{"role": "user", "content": "Name a colour."}, {"role": "assistant", "content": "The colour is "}]
+
+
+@pytest.fixture
+def response():
+    return {
+        "choices": [{"message": {"role": "assistant", "content": " blue."}, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 8, "completion_tokens": 2},
+    }
+
+
+def run(server, request, backend="openai"):
+    return server.process([request], server.provider(backend=backend)).metrics[0]
+
+
+def test_anthropic_prefill_is_trimmed():
+    fields = normalize_messages("anthropic", PREFILL)
+    assert fields["messages"][-1] == {"role": "assistant", "content": [{"type": "text", "text": "The colour is"}]}
+
+
+def test_vllm_continues_final_message(server):
+    run(server, PREFILL, backend="vllm")
+    assert server.body["continue_final_message"] is True
+    assert server.body["add_generation_prompt"] is False
+    assert server.body["messages"][-1]["content"] == "The colour is "
+
+
+def test_continue_mode_returns_full_text(server):
+    metrics = run(server, {"messages": PREFILL, "continue": True})
+    assert server.body["messages"][-1]["content"] == "The colour is"
+    assert "continue_final_message" not in server.body
+    assert metrics.content == "The colour is blue."
+
+
+def test_plain_requests_are_unchanged(server):
+    metrics = run(server, PREFILL[:1], backend="vllm")
+    assert "continue_final_message" not in server.body
+    assert metrics.content == " blue."
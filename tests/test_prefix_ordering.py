@@ -0,0 +1,39 @@
+import pytest
+
+from axicontraves import ProviderConfig, plan
+
+SYSTEMS = ["You review Rust code. " * 40, "You translate into French. " * 40]
+# Two long shared system prompts, interleaved
+REQUESTS = [
+    [{"role": "system", "content": SYSTEMS[i % 2]}, {"role": "user", "content": f"Item {i}"}] for i in range(8)
+]
+
+
+def run(server, reorder_by_prefix):
+    return server.process(REQUESTS, max_concurrency=1, reorder_by_prefix=reorder_by_prefix)
+
+
+def received(server):
+    return [SYSTEMS.index(body["messages"][0]["content"]) for body in server.bodies]
+
+
+def test_requests_sharing_a_prefix_are_sent_back_to_back(server):
+    result = run(server, True)
+    assert received(server) == [0, 0, 0, 0, 1, 1, 1, 1]
+    # Results come back in the reordered dispatch order, each with its own index
+    assert [m.index % 2 for m in result.metrics] == [0, 0, 0, 0, 1, 1, 1, 1]
+    assert sorted(m.index for m in result.metrics) == list(range(len(REQUESTS)))
+
+
+def test_submission_order_is_kept_by_default(server):
+    run(server, False)
+    assert received(server) == [0, 1] * 4
+
+
+def test_plan_estimates_the_prefix_reuse():
+    provider = ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+    interleaved = plan(REQUESTS, provider)
+    clustered = plan(REQUESTS, provider, reorder_by_prefix=True)
+    assert interleaved.prefix_reuse < 0.1
+    assert clustered.prefix_reuse > 0.5
+    assert clustered.providers[0].prefix_reuse == pytest.approx(clustered.prefix_reuse)
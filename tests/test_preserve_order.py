@@ -0,0 +1,50 @@
+import pytest
+
+from axicontraves import BatchProcessor
+
+COUNT = 6
+
+
+def index(body):
+    return int(body["messages"][0]["content"].split()[-1])
+
+
+@pytest.fixture
+def provider(make_server):
+    # Request i takes (COUNT - i) * 30ms, so later requests finish first
+    server = make_server(
+        lambda body, path: {
+            "model": "m",
+            "choices": [{"message": {"content": str(index(body))}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1},
+        },
+        delay=lambda body: (COUNT - index(body)) * 0.03,
+    )
+    return server.provider()
+
+
+def requests(priorities=()):
+    return [
+        {"messages": [{"role": "user", "content": f"Request {i}"}], **({"priority": p} if p else {})}
+        for i, p in zip(range(COUNT), list(priorities) + [None] * COUNT)
+    ]
+
+
+def test_iter_batch_follows_completion_order_by_default(provider):
+    indices = [m.index for m in BatchProcessor(provider).iter_batch(requests())]
+    assert sorted(indices) == list(range(COUNT))
+    assert indices != list(range(COUNT))
+
+
+def test_iter_batch_with_preserve_order(provider):
+    results = list(BatchProcessor(provider, preserve_order=True).iter_batch(requests()))
+    assert [m.index for m in results] == list(range(COUNT))
+    assert [m.choices[0] for m in results] == [str(i) for i in range(COUNT)]
+
+
+def test_process_batch_with_preserve_order_undoes_priorities(provider):
+    prioritized = requests(["low", None, "high"])
+    dispatched = BatchProcessor(provider).process_batch(prioritized, show_progress=False).metrics
+    assert [m.index for m in dispatched][0] == 2
+    ordered = BatchProcessor(provider, preserve_order=True).process_batch(prioritized, show_progress=False).metrics
+    assert [m.index for m in ordered] == list(range(COUNT))
@@ -0,0 +1,62 @@
+import pytest
+
+from axicontraves import builtin_pricing
+
+# 800 of the prompt tokens were served from cache, 250 of the completion tokens were reasoning
+USAGE = {
+    "prompt_tokens": 1000,
+    "completion_tokens": 300,
+    "prompt_tokens_details": {"cached_tokens": 800},
+    "completion_tokens_details": {"reasoning_tokens": 250},
+}
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(3)]
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        return {"model": body["model"], "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}], "usage": USAGE}
+
+    return answer
+
+
+def run(server, model, **options):
+    return server.process(REQUESTS, server.provider(config={"model": model}), **options)
+
+
+def test_cached_and_reasoning_tokens_have_their_own_prices(server):
+    pricing = {"m": {"input": 10.0, "output": 20.0, "cached_input": 1.0, "reasoning": 40.0}}
+    result = run(server, "m", pricing=pricing)
+    expected = (200 * 10.0 + 800 * 1.0 + 50 * 20.0 + 250 * 40.0) / 1_000_000
+    assert all(m.cost_usd == pytest.approx(expected) for m in result.metrics)
+    assert result.cost_usd == pytest.approx(3 * expected)
+    assert result.summary.cost_usd == pytest.approx(3 * expected)
+
+
+def test_without_separate_prices_everything_bills_at_input_and_output(server):
+    result = run(server, "m", pricing={"m": {"input": 10.0, "output": 20.0}})
+    assert result.metrics[0].cost_usd == pytest.approx((1000 * 10.0 + 300 * 20.0) / 1_000_000)
+
+
+def test_builtin_prices_cover_dated_snapshots(server):
+    price = builtin_pricing()["gpt-4o-mini"]
+    result = run(server, "gpt-4o-mini-2024-07-18")
+    expected = (200 * price["input"] + 800 * price["cached_input"] + 300 * price["output"]) / 1_000_000
+    assert result.metrics[0].cost_usd == pytest.approx(expected)
+
+
+def test_user_pricing_overrides_the_builtin_table(server):
+    result = run(server, "gpt-4o", pricing={"gpt-4o": {"input": 0.0, "output": 0.0}})
+    assert result.metrics[0].cost_usd == 0.0
+
+
+def test_unknown_models_are_not_priced(server):
+    result = run(server, "mystery-model")
+    assert result.metrics[0].cost_usd is None
+    assert result.cost_usd is None
+    assert result.summary.cost_usd is None
+
+
+def test_cost_budget_works_with_builtin_prices(server):
+    result = run(server, "gpt-4o", max_cost_usd=1.0)
+    assert all(m.status == "ok" for m in result.metrics)
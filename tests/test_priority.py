@@ -0,0 +1,29 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+PROVIDER = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+
+
+def request(i, priority=None):
+    request = {"messages": [{"role": "user", "content": f"request {i}"}]}
+    if priority:
+        request["priority"] = priority
+    return request
+
+
+def test_higher_priorities_are_dispatched_first():
+    requests = [request(0, "low"), request(1), request(2, "high"), request(3, "low"), request(4, "high"), request(5, "normal")]
+    metrics = BatchProcessor(PROVIDER, max_concurrency=2).process_batch(requests, show_progress=False).metrics
+    assert [m.index for m in metrics] == [2, 4, 1, 5, 0, 3]
+    assert all(m.status == "ok" for m in metrics)
+
+
+def test_without_priorities_submission_order_is_kept():
+    metrics = BatchProcessor(PROVIDER, max_concurrency=2).process_batch([request(i) for i in range(5)], show_progress=False).metrics
+    assert [m.index for m in metrics] == list(range(5))
+
+
+def test_unknown_priority_is_rejected():
+    with pytest.raises(ValueError, match="priority"):
+        BatchProcessor(PROVIDER).process_batch([request(0, "urgent")], show_progress=False)
@@ -0,0 +1,93 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, RunProgress, process_requests_multi
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(12)]
+SLOW = ProviderConfig(
+    name="openai", api_key="test", config={"model": "m"},
+    simulator={"max_concurrency": 3, "service_time": {"distribution": "constant", "ms": 50}},
+)
+DEAD = ProviderConfig(name="openai", api_key="test", base_url="http://127.0.0.1:9", config={"model": "m"})
+
+
+def test_callback_gets_structured_progress():
+    updates = []
+    BatchProcessor(SLOW, progress_callback=updates.append, max_concurrency=3).process_batch(REQUESTS, show_progress=False)
+    assert all(isinstance(update, RunProgress) for update in updates)
+    last = updates[-1]
+    assert (last.completed, last.total, last.errors) == (len(REQUESTS), len(REQUESTS), 0)
+    assert [update.completed for update in updates] == sorted(update.completed for update in updates)
+    assert [update.elapsed_seconds for update in updates] == sorted(update.elapsed_seconds for update in updates)
+    assert last.elapsed_seconds >= 0.15
+    assert last.requests_per_second > 0 and last.tokens_per_second > 0
+    assert last.prompt_tokens > 0 and last.completion_tokens > 0
+    # Before the end there is still work left at a known rate
+    assert any(update.eta_seconds and update.eta_seconds > 0 for update in updates[:-1])
+    assert last.eta_seconds == 0
+    [provider] = last.providers.values()
+    assert (provider.state, provider.completed, provider.errors, provider.in_flight) == ("active", len(REQUESTS), 0, 0)
+
+
+def test_errors_are_counted_per_provider():
+    updates = []
+    processor = BatchProcessor([SLOW, DEAD], progress_callback=updates.append, rate_limit_retries=0)
+    processor.process_batch(REQUESTS, show_progress=False)
+    last = updates[-1]
+    assert last.errors == len(REQUESTS) // 2
+    dead = last.providers["openai:http://127.0.0.1:9"]
+    assert dead.errors == dead.completed == len(REQUESTS) // 2
+
+
+def test_eta_is_unknown_for_an_iterator():
+    updates = []
+    BatchProcessor(SLOW, progress_callback=updates.append).process_batch(iter(REQUESTS), show_progress=False)
+    assert all(update.eta_seconds is None for update in updates)
+    assert updates[-1].completed == len(REQUESTS)
+
+
+def test_legacy_progress_passes_completed_and_total():
+    calls = []
+    processor = BatchProcessor(SLOW, progress_callback=lambda *args: calls.append(args), legacy_progress=True)
+    processor.process_batch(REQUESTS, show_progress=False)
+    assert calls[-1] == (len(REQUESTS), len(REQUESTS))
+
+
+def test_legacy_progress_keeps_the_positional_tuple():
+    calls = []
+    process_requests_multi(
+        [SLOW.as_tuple()], REQUESTS, lambda *args: calls.append(args), False, None, legacy_progress=True
+    )
+    assert all(len(call) == 11 and all(isinstance(value, int) for value in call) for call in calls)
+    assert calls[-1][:2] == (len(REQUESTS), len(REQUESTS))
+    # The batch prompt tokens add up to the running total
+    assert sum(call[2] for call in calls) == calls[-1][7]
+
+
+# Past what an i32 holds, per response and more so in total
+HUGE_PROMPT = 2**31 + 5
+HUGE_COMPLETION = 2**32 + 7
+HUGE = {
+    "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+    "usage": {"prompt_tokens": HUGE_PROMPT, "completion_tokens": HUGE_COMPLETION},
+}
+
+
+@pytest.mark.parametrize("response", [HUGE])
+def test_counts_past_i32_reach_the_callback_intact(server):
+    updates = []
+    result = server.process(REQUESTS[:3], progress_callback=updates.append)
+    last = updates[-1]
+    assert (last.prompt_tokens, last.completion_tokens) == (3 * HUGE_PROMPT, 3 * HUGE_COMPLETION)
+    assert (result.prompt_tokens, result.completion_tokens) == (3 * HUGE_PROMPT, 3 * HUGE_COMPLETION)
+    assert result.total_tokens == 3 * (HUGE_PROMPT + HUGE_COMPLETION)
+
+
+@pytest.mark.parametrize("response", [HUGE])
+def test_legacy_tuple_carries_counts_past_i32(server):
+    calls = []
+    process_requests_multi(
+        [server.provider().as_tuple()], REQUESTS[:3], lambda *args: calls.append(args), False, None, max_concurrency=1, legacy_progress=True
+    )
+    # One result per call: the batch counts are a single response's, the totals add up
+    assert all(call[2:4] == (HUGE_PROMPT, HUGE_COMPLETION) for call in calls)
+    assert calls[-1][7:9] == (3 * HUGE_PROMPT, 3 * HUGE_COMPLETION)
@@ -0,0 +1,63 @@
+import json
+
+import pytest
+
+from axicontraves import ProviderConfig, plan
+
+TEMPLATES = {
+    "translate": "Translate to {{ language }}: {{ text }}",
+    "review": [
+        {"role": "system", "content": "You review {{ kind }} code."},
+        {"role": "user", "content": "{% for line in lines %}{{ loop.index }}: {{ line }}\n{% endfor %}"},
+    ],
+}
+
+
+@pytest.fixture
+def response():
+    """Answers with the messages it was sent."""
+
+    def answer(body, path):
+        return {
+            "choices": [{"message": {"content": json.dumps(body["messages"])}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 1, "completion_tokens": 1},
+        }
+
+    return answer
+
+
+def sent(server, requests):
+    metrics = server.process(requests, templates=TEMPLATES).metrics
+    return [json.loads(m.content) for m in sorted(metrics, key=lambda m: m.index)]
+
+
+def test_tuple_and_dict_forms(server):
+    requests = [
+        ("translate", {"language": "French", "text": "cheese"}),
+        {"template": "review", "variables": {"kind": "Rust", "lines": ["fn main() {", "}"]}, "temperature": 0},
+        [{"role": "user", "content": "plain"}],
+    ]
+    translate, review, plain = sent(server, requests)
+    assert translate == [{"role": "user", "content": "Translate to French: cheese"}]
+    assert review == [
+        {"role": "system", "content": "You review Rust code."},
+        {"role": "user", "content": "1: fn main() {\n2: }\n"},
+    ]
+    assert plain == [{"role": "user", "content": "plain"}]
+
+
+def test_missing_variable_is_an_error(server):
+    with pytest.raises(ValueError, match="requests\\[0\\]"):
+        sent(server, [("translate", {"language": "French"})])
+
+
+def test_unknown_template(server):
+    with pytest.raises(ValueError, match="Unknown template 'nope'"):
+        sent(server, [("nope", {})])
+
+
+def test_plan_renders_templates():
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"})
+    estimate = plan([("translate", {"language": "German", "text": "x" * 400})], provider, templates=TEMPLATES)
+    assert estimate.total_requests == 1
+    assert estimate.prompt_tokens > 100
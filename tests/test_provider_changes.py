@@ -0,0 +1,78 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(40)]
+
+
+@pytest.fixture
+def servers(make_server):
+    return [make_server(delay=0.02) for _ in range(2)]
+
+
+def provider(server, **options):
+    return server.provider(**options)
+
+
+def name(server):
+    return f"openai:{server.url}"
+
+
+def test_rotate_to_a_new_provider_mid_run(servers):
+    old, new = servers
+    handle = BatchProcessor(provider(old), max_concurrency=2).start_batch(REQUESTS)
+    results = iter(handle)
+    first = [next(results) for _ in range(5)]
+    assert handle.add_provider(provider(new)) == 1
+    handle.drain_provider(0)
+    sent_before = old.calls
+    metrics = first + list(results)
+    assert sorted(m.index for m in metrics) == list(range(len(REQUESTS)))
+    assert all(m.status == "ok" for m in metrics)
+    # Besides what was in flight at the switch, everything after it went to the new provider
+    assert old.calls <= sent_before + 2
+    assert new.calls == len(REQUESTS) - old.calls > 0
+    assert sum(m.provider_name == name(new) for m in metrics) == new.calls
+    assert handle.integrity().complete
+
+
+def test_draining_hands_back_what_its_limits_hold(servers):
+    old, new = servers
+    # Five requests a second on the old provider, so the others sent to it wait on its limit
+    handle = BatchProcessor(provider(old, rpm=300), max_concurrency=4).start_batch(REQUESTS[:10])
+    results = iter(handle)
+    first = next(results)
+    while handle.provider_stats()[0].pending < 3:
+        time.sleep(0.01)
+    handle.add_provider(provider(new))
+    handle.drain_provider(0)
+    sent_before = old.calls
+    metrics = [first] + list(results)
+    assert sorted(m.index for m in metrics) == list(range(10))
+    assert all(m.status == "ok" for m in metrics)
+    # Whatever was on the wire finished there; the held ones went to the new provider
+    assert old.calls == sent_before
+    assert new.calls == 10 - old.calls
+    [drained, _] = handle.provider_stats()
+    assert (drained.state, drained.pending, drained.in_flight, drained.completed) == ("drained", 0, 0, old.calls)
+
+
+def test_draining_every_provider_fails_the_rest(servers):
+    handle = BatchProcessor(provider(servers[0]), max_concurrency=1).start_batch(REQUESTS[:10])
+    next(iter(handle))
+    handle.drain_provider(0)
+    metrics = handle.wait()
+    assert len(metrics) == 10
+    failed = [m for m in metrics if m.status == "error"]
+    assert failed and all("drained" in m.error for m in failed)
+
+
+def test_invalid_changes(servers):
+    handle = BatchProcessor(provider(servers[0])).start_batch(REQUESTS[:2])
+    with pytest.raises(IndexError):
+        handle.drain_provider(3)
+    handle.wait()
+    with pytest.raises(RuntimeError, match="finished"):
+        handle.add_provider(provider(servers[1]))
@@ -0,0 +1,61 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(8)]
+
+
+@pytest.fixture
+def servers(make_server):
+    return [make_server(delay=delay) for delay in (1.0, 0.01, 0.5)]
+
+
+def name(server):
+    return f"openai:{server.url}"
+
+
+def providers(servers):
+    return [server.provider() for server in servers]
+
+
+def test_the_fastest_provider_wins_every_race(servers):
+    started = time.monotonic()
+    processor = BatchProcessor(providers(servers), max_concurrency=8, race_providers=3)
+    metrics = processor.process_batch(REQUESTS, show_progress=False).metrics
+    assert time.monotonic() - started < 1.0
+    assert all(m.status == "ok" for m in metrics)
+    assert all(m.provider_name == name(servers[1]) for m in metrics)
+    assert all(sorted(m.raced) == sorted(name(server) for server in servers) for m in metrics)
+    assert servers[1].calls == len(REQUESTS)
+
+
+def test_a_failing_racer_loses_to_a_slower_success(servers):
+    servers[1].status = 400
+    servers[1].response = {"error": {"message": "bad request"}}
+    metrics = BatchProcessor(providers(servers), max_concurrency=8, race_providers=3).process_batch(
+        REQUESTS, show_progress=False
+    ).metrics
+    assert all(m.status == "ok" for m in metrics)
+    assert all(m.provider_name == name(servers[2]) for m in metrics)
+
+
+def test_races_are_capped_at_the_race_size(servers):
+    metrics = BatchProcessor(providers(servers), max_concurrency=8, race_providers=2).process_batch(
+        REQUESTS, show_progress=False
+    ).metrics
+    assert all(m.status == "ok" and len(m.raced) == 2 for m in metrics)
+    assert sum(server.calls for server in servers) == 2 * len(REQUESTS)
+
+
+def test_without_racing_each_request_goes_out_once(servers):
+    metrics = BatchProcessor(providers(servers), max_concurrency=8).process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.raced == [] for m in metrics)
+    assert sum(server.calls for server in servers) == len(REQUESTS)
+
+
+@pytest.mark.parametrize("options", [{"race_providers": 1}, {"race_providers": 2, "hedge_requests": True}])
+def test_invalid_race_options_are_rejected(servers, options):
+    with pytest.raises(ValueError):
+        BatchProcessor(providers(servers), **options).process_batch(REQUESTS, show_progress=False)
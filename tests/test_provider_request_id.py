@@ -0,0 +1,26 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Hi"}]
+
+
+def run(server, headers, name="openai"):
+    server.headers = headers
+    return server.run(QUESTION, name)
+
+
+@pytest.mark.parametrize("header", ["x-request-id", "request-id", "cf-ray"])
+def test_id_is_read_from_each_header(server, header):
+    assert run(server, {header: "req_abc123"}).provider_request_id == "req_abc123"
+
+
+def test_x_request_id_comes_first(server):
+    headers = {"cf-ray": "8f1e2d-SJC", "request-id": "req_2", "x-request-id": "req_1"}
+    assert run(server, headers).provider_request_id == "req_1"
+
+
+def test_anthropic_request_id_is_captured(server):
+    assert run(server, {"request-id": "req_011abc"}, name="anthropic").provider_request_id == "req_011abc"
+
+
+def test_missing_headers_leave_it_unset(server):
+    assert run(server, {}).provider_request_id is None
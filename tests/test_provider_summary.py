@@ -0,0 +1,33 @@
+from axicontraves import ProviderSummary, process_requests_multi, summarize_results
+
+# Nothing listens on the discard port, so every request to it fails fast
+DEAD = ("openai", "test", "http://127.0.0.1:9", {"model": "m"}, {"test_mode": False})
+HEALTHY = ("openai", "test", None, {"model": "m"}, {"test_mode": True})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(8)]
+
+
+def run(providers, **kwargs):
+    return process_requests_multi(providers, REQUESTS, lambda *args: None, False, None, max_concurrency=1, **kwargs)
+
+
+def test_summary_is_broken_down_per_provider():
+    results = run([DEAD, HEALTHY])
+    providers = summarize_results(results, 1.0).providers
+    assert set(providers) == {"openai:http://127.0.0.1:9", "openai:https://api.openai.com"}
+    dead, healthy = providers["openai:http://127.0.0.1:9"], providers["openai:https://api.openai.com"]
+    assert isinstance(healthy, ProviderSummary)
+    assert dead.requests == dead.failed == len(REQUESTS) // 2
+    assert dead.error_rate == 1.0
+    assert dead.p50_latency_ms == 0.0
+    assert healthy.requests == healthy.succeeded == len(REQUESTS) // 2
+    assert healthy.error_rate == 0.0
+    assert healthy.prompt_tokens == sum(m.prompt_tokens for m in results if m.provider_name == healthy.provider_name)
+    assert healthy.response_bytes == sum(m.response_bytes for m in results if m.provider_name == healthy.provider_name)
+    assert healthy.p50_latency_ms <= healthy.p99_latency_ms
+
+
+def test_unsent_requests_belong_to_no_provider():
+    results = run([HEALTHY], skip=[0, 1])
+    summary = summarize_results(results, 1.0)
+    assert summary.skipped == 2
+    assert summary.providers["openai:https://api.openai.com"].requests == len(REQUESTS) - 2
@@ -0,0 +1,93 @@
+import pytest
+from hypothesis import given, strategies as st
+
+from axicontraves import BatchProcessor, ProviderConfig, process_requests_multi
+
+VALID_CONFIG = {"model": "gpt-3.5-turbo", "temperature": 0.7}
+REQUESTS = [[{"role": "user", "content": "Hello"}]]
+
+
+def noop(*args):
+    pass
+
+
+def run(providers, requests=REQUESTS):
+    return process_requests_multi(providers, requests, noop, True, None)
+
+
+def test_empty_provider_list_raises():
+    with pytest.raises(ValueError, match="At least one provider"):
+        run([])
+
+
+def test_batch_processor_rejects_empty_providers():
+    with pytest.raises(ValueError, match="At least one provider"):
+        BatchProcessor([])
+
+
+def test_batch_processor_rejects_non_provider_config():
+    with pytest.raises(TypeError, match=r"providers\[0\]"):
+        BatchProcessor([("openai", "key", None, VALID_CONFIG)])
+
+
+def test_valid_provider_tuple_runs():
+    assert len(run([("openai", "key", None, VALID_CONFIG)])) == 1
+
+
+def test_unsupported_provider_names_index():
+    with pytest.raises(ValueError, match=r"providers\[1\]: unsupported provider 'nope'"):
+        run([("openai", "key", None, VALID_CONFIG), ("nope", "key", None, VALID_CONFIG)])
+
+
+def test_missing_config_key_is_reported():
+    with pytest.raises(ValueError, match=r"providers\[0\]: Missing required key: model"):
+        run([("openai", "key", None, {"temperature": 0.7})])
+
+
+def test_wrongly_typed_config_value_is_reported():
+    with pytest.raises(ValueError, match=r"providers\[0\]: Invalid value for 'max_tokens'"):
+        run([("openai", "key", None, {**VALID_CONFIG, "max_tokens": "many"})])
+
+
+def test_malformed_request_names_index():
+    with pytest.raises(ValueError, match=r"requests\[1\]"):
+        run([("openai", "key", None, VALID_CONFIG)], REQUESTS + [[{"content": "no role"}]])
+
+
+@given(st.lists(st.one_of(st.none(), st.integers(), st.text()), max_size=6).map(tuple))
+def test_malformed_tuples_raise_value_error(provider):
+    # Anything that isn't a well-formed 4-tuple must surface as a ValueError, never a panic
+    with pytest.raises(ValueError, match=r"providers\[0\]"):
+        run([provider])
+
+
+@given(st.one_of(st.integers(), st.text(), st.none(), st.lists(st.integers())))
+def test_non_tuple_providers_raise_value_error(provider):
+    with pytest.raises(ValueError, match=r"providers\[0\]"):
+        run([provider])
+
+
+@given(
+    name=st.one_of(st.integers(), st.none(), st.binary()),
+    api_key=st.one_of(st.integers(), st.none(), st.floats()),
+)
+def test_wrongly_typed_tuple_fields_raise_value_error(name, api_key):
+    with pytest.raises(ValueError, match=r"providers\[0\]"):
+        run([(name, api_key, None, VALID_CONFIG)])
+
+
+def test_options_element_must_be_a_dict():
+    with pytest.raises(ValueError, match=r"providers\[0\]: options must be a dict"):
+        run([("openai", "key", None, VALID_CONFIG, "test")])
+
+
+def test_per_provider_test_mode_overrides_global():
+    metrics = process_requests_multi(
+        [("openai", "key", None, VALID_CONFIG, {"test_mode": True})], REQUESTS, noop, False, None
+    )
+    assert len(metrics) == 1
+
+
+def test_invalid_header_names_are_reported():
+    with pytest.raises(ValueError, match=r"providers\[0\]: Invalid header: bad header"):
+        run([("openai", "key", None, VALID_CONFIG, {"test_mode": True, "headers": {"bad header": "x"}})])
@@ -0,0 +1,43 @@
+import time
+
+from axicontraves import BatchProcessor, ProviderStats
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(12)]
+
+
+def provider(server, **options):
+    # Ten requests a second, spaced evenly
+    return server.provider(rpm=600, **options)
+
+
+def test_stats_show_requests_held_back_by_the_provider(server):
+    handle = BatchProcessor(provider(server), max_concurrency=8).start_batch(REQUESTS)
+    time.sleep(0.25)
+    [stats] = handle.provider_stats()
+    assert isinstance(stats, ProviderStats)
+    assert stats.state == "active"
+    assert stats.pending >= 3
+    assert stats.pending + stats.in_flight <= 8
+    handle.wait()
+    [stats] = handle.provider_stats()
+    assert (stats.pending, stats.in_flight, stats.completed, stats.shed) == (0, 0, len(REQUESTS), 0)
+
+
+def test_requests_beyond_the_queue_depth_are_shed(server):
+    started = time.monotonic()
+    metrics = BatchProcessor(provider(server), max_concurrency=8, max_queue_depth=2).process_batch(REQUESTS, show_progress=False).metrics
+    shed = [m for m in metrics if m.status == "error"]
+    assert shed and all(m.error.startswith("Shed:") for m in shed)
+    assert any(m.status == "ok" for m in metrics)
+    # Shedding keeps the run from waiting out the whole queue
+    assert time.monotonic() - started < 1.0
+
+
+def test_stats_across_providers_with_shedding(server):
+    providers = [provider(server), provider(server)]
+    handle = BatchProcessor(providers, max_concurrency=8, max_queue_depth=2).start_batch(REQUESTS)
+    metrics = handle.wait()
+    stats = handle.provider_stats()
+    assert sum(s.completed for s in stats) == sum(m.status == "ok" for m in metrics)
+    assert sum(s.shed for s in stats) == sum(m.status == "error" for m in metrics)
+    assert all(s.completed > 0 for s in stats)
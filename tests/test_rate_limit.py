@@ -0,0 +1,63 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, plan
+
+
+@pytest.fixture
+def response():
+    """Reports 10 prompt and 20 completion tokens for every request."""
+    return {
+        "model": "m",
+        "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 10, "completion_tokens": 20},
+    }
+
+
+# "x" * 40 is estimated at 10 prompt tokens, so each request costs 30 tokens in total
+REQUESTS = [[{"role": "user", "content": "x" * 40}] for _ in range(22)]
+
+
+def elapsed(server, tokens_per_minute=None, rpm=None):
+    started = time.monotonic()
+    result = server.process(REQUESTS, server.provider(tokens_per_minute=tokens_per_minute, rpm=rpm))
+    assert [m.status for m in result.metrics] == ["ok"] * len(REQUESTS)
+    return time.monotonic() - started
+
+
+def test_unlimited_runs_at_full_speed(server):
+    assert elapsed(server, None) < 2
+
+
+def test_budget_throttles_dispatch(server):
+    # 660 tokens against 600 per minute (10 per second): the last requests wait for refill
+    assert 2 < elapsed(server, 600) < 20
+
+
+def test_rpm_spaces_out_starts(server):
+    # One start every 100ms: 22 requests take at least 2.1s
+    assert 2 < elapsed(server, rpm=600) < 10
+
+
+def test_rpm_beyond_u32_does_not_wrap(server):
+    # 2**32 + 1 used to truncate to one start per minute, and 2**32 to a zero divisor
+    assert elapsed(server, rpm=2**32 + 1) < 2
+    assert elapsed(server, rpm=2**32) < 2
+
+
+def test_limits_are_per_provider(server):
+    providers = [
+        ProviderConfig(name="openai", api_key=key, base_url=server.url, config={"model": "m"}, rpm=600)
+        for key in ("a", "b")
+    ]
+    started = time.monotonic()
+    BatchProcessor(providers).process_batch(REQUESTS, show_progress=False)
+    # Each key starts 11 requests 100ms apart; one shared limit would need 2.1s
+    assert 0.9 < time.monotonic() - started < 1.9
+
+
+def test_plan_respects_max_concurrency():
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, max_concurrency=2)
+    estimate = plan(REQUESTS, provider, concurrency=8)
+    assert estimate.providers[0].concurrency == 2
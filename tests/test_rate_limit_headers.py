@@ -0,0 +1,62 @@
+import threading
+import time
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+# The server admits LIMIT requests per WINDOW seconds and rejects the rest with 429
+LIMIT = 5
+WINDOW = 0.5
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(12)]
+OK = {
+    "model": "m",
+    "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+    "usage": {"prompt_tokens": 3, "completion_tokens": 1},
+}
+
+
+def openai_headers(remaining, reset):
+    return {"x-ratelimit-remaining-requests": str(remaining), "x-ratelimit-reset-requests": f"{int(reset * 1000)}ms"}
+
+
+def anthropic_headers(remaining, reset):
+    resets_at = datetime.now(timezone.utc) + timedelta(seconds=reset)
+    return {
+        "anthropic-ratelimit-requests-remaining": str(remaining),
+        "anthropic-ratelimit-requests-reset": resets_at.isoformat().replace("+00:00", "Z"),
+    }
+
+
+def quota(quota_headers):
+    """Admits LIMIT requests per WINDOW and rejects the rest, reporting what's left in `quota_headers`."""
+    lock = threading.Lock()
+    window_start, count = time.monotonic(), 0
+
+    def respond(body, path):
+        nonlocal window_start, count
+        with lock:
+            now = time.monotonic()
+            if now - window_start >= WINDOW:
+                window_start, count = now, 0
+            count += 1
+            allowed = count <= LIMIT
+            headers = quota_headers(max(LIMIT - count, 0), window_start + WINDOW - now)
+        if allowed:
+            return OK, 200, headers
+        return {"error": {"message": "rate limited"}}, 429, headers
+
+    return respond
+
+
+@pytest.mark.parametrize("quota_headers", [openai_headers, anthropic_headers])
+def test_dispatch_waits_for_reported_reset(server, quota_headers):
+    server.response = quota(quota_headers)
+    started = time.monotonic()
+    metrics = server.process(REQUESTS, max_concurrency=2, rate_limit_retries=0).metrics
+    elapsed = time.monotonic() - started
+    assert all(m.status == "ok" for m in metrics), [m.error for m in metrics if m.error]
+    assert all(m.retries == 0 for m in metrics)
+    # Nothing was rejected
+    assert server.calls == len(REQUESTS)
+    # 12 requests at 5 per window need three windows
+    assert elapsed >= 2 * WINDOW * 0.9
@@ -0,0 +1,32 @@
+import json
+
+import pytest
+
+QUESTION = [{"role": "user", "content": "Hi"}]
+# Fields the crate doesn't model ride along in the raw response
+OPENAI = {
+    "id": "chatcmpl-123",
+    "system_fingerprint": "fp_abc",
+    "choices": [{"message": {"content": "Hello."}, "finish_reason": "stop"}],
+    "usage": {"prompt_tokens": 1, "completion_tokens": 2},
+}
+ANTHROPIC = {
+    "id": "msg_123",
+    "content": [{"type": "text", "text": "Hello."}],
+    "stop_reason": "end_turn",
+    "usage": {"input_tokens": 1, "output_tokens": 2, "service_tier": "standard"},
+}
+
+
+@pytest.mark.parametrize("name, response", [("openai", OPENAI), ("anthropic", ANTHROPIC)])
+def test_full_response_is_kept(server, name, response):
+    metrics = server.process([QUESTION], server.provider(name), capture_raw_response=True).metrics[0]
+    assert metrics.raw_response == response
+    assert json.loads(metrics.raw_response_json) == response
+    assert metrics.content == "Hello."
+
+
+def test_nothing_is_kept_by_default(server):
+    metrics = server.run(QUESTION)
+    assert metrics.raw_response is None
+    assert metrics.raw_response_json is None
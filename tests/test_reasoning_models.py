@@ -0,0 +1,41 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Prove it."}]
+SAMPLING = {"temperature": 0.7, "top_p": 0.9, "max_tokens": 256, "reasoning_effort": "high"}
+
+
+@pytest.mark.parametrize("model", ["o3", "o4-mini", "openrouter/o1-preview"])
+def test_reasoning_models_get_no_sampling_parameters(server, model):
+    metrics = server.run(QUESTION, model=model, **SAMPLING)
+    assert metrics.status == "ok"
+    assert "temperature" not in server.body
+    assert "top_p" not in server.body
+    assert "max_tokens" not in server.body
+    # max_tokens carries over as the completion cap
+    assert server.body["max_completion_tokens"] == 256
+    assert server.body["reasoning_effort"] == "high"
+
+
+def test_other_models_keep_sampling_parameters(server):
+    server.run(QUESTION, model="gpt-4o", **SAMPLING)
+    assert (server.body["temperature"], server.body["max_tokens"]) == (pytest.approx(0.7), 256)
+    assert "reasoning_effort" not in server.body
+
+
+def test_max_completion_tokens_wins_over_max_tokens(server):
+    server.run(QUESTION, model="o3", max_tokens=256, max_completion_tokens=4096)
+    assert server.body["max_completion_tokens"] == 4096
+
+
+def test_request_overrides_pick_the_reasoning_rules(server):
+    request = {"messages": QUESTION, "model": "o3", "reasoning_effort": "low"}
+    server.run(request, model="gpt-4o", temperature=0.7)
+    assert server.body["model"] == "o3"
+    assert "temperature" not in server.body
+    assert server.body["reasoning_effort"] == "low"
+
+
+def test_reasoning_flag_covers_unrecognized_models(server):
+    server.run(QUESTION, model="deepseek-reasoner", reasoning=True, **SAMPLING)
+    assert "temperature" not in server.body
+    assert server.body["max_completion_tokens"] == 256
@@ -0,0 +1,63 @@
+import pytest
+
+from axicontraves import BatchProcessor
+
+
+@pytest.fixture
+def response():
+    return lambda body, path: {
+        "model": "m",
+        "choices": [{"message": {"content": "re: " + body["messages"][0]["content"]}, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 5, "completion_tokens": 2},
+    }
+
+
+def provider(server):
+    return server.provider()
+
+
+def request(text, **params):
+    return {"messages": [{"role": "user", "content": text}], **params}
+
+
+REQUESTS = [
+    request("a"),
+    request("b"),
+    request("a"),
+    request("a", temperature=0.5),
+    request("b"),
+    request("a", request_id="row-5"),
+]
+
+
+@pytest.mark.parametrize("max_concurrency", [1, 8])
+def test_identical_requests_are_sent_once(server, max_concurrency):
+    processor = BatchProcessor(provider(server), dedupe_requests=True, max_concurrency=max_concurrency)
+    result = processor.process_batch(REQUESTS, show_progress=False)
+    assert server.calls == 3
+    metrics = sorted(result.metrics, key=lambda m: m.index)
+    assert [m.duplicate_of for m in metrics] == [None, None, 0, None, 1, 0]
+    assert [m.content for m in metrics] == ["re: a", "re: b", "re: a", "re: a", "re: b", "re: a"]
+    assert metrics[5].request_id == "row-5"
+    assert metrics[2].completion_tokens == 0 and metrics[0].completion_tokens == 2
+    assert result.total_tokens == 3 * 7
+    assert result.integrity.complete
+
+
+def test_off_by_default(server):
+    metrics = BatchProcessor(provider(server)).process_batch(REQUESTS, show_progress=False).metrics
+    assert server.calls == len(REQUESTS)
+    assert all(m.duplicate_of is None for m in metrics)
+
+
+@pytest.mark.parametrize("max_concurrency", [1, 8])
+def test_a_failure_is_not_shared(server, max_concurrency):
+    server.failures = 1
+    processor = BatchProcessor(provider(server), dedupe_requests=True, max_concurrency=max_concurrency, rate_limit_retries=0)
+    metrics = processor.process_batch([request("a")] * 3, show_progress=False).metrics
+    assert server.calls == 2
+    [failed] = [m for m in metrics if m.status == "error"]
+    [sent] = [m for m in metrics if m.status == "ok" and m.duplicate_of is None]
+    [copy] = [m for m in metrics if m.duplicate_of is not None]
+    assert failed.duplicate_of is None
+    assert copy.duplicate_of == sent.index and copy.content == "re: a"
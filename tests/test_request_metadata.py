@@ -0,0 +1,33 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Hi"}]
+
+
+def test_user_and_metadata_are_forwarded(server):
+    metadata = {"experiment": "ablation-3", "shard": "7"}
+    server.run({"messages": QUESTION, "user": "user-42", "metadata": metadata})
+    assert (server.body["user"], server.body["metadata"]) == ("user-42", metadata)
+
+
+def test_plain_requests_carry_neither(server):
+    server.run(QUESTION)
+    assert "user" not in server.body
+    assert "metadata" not in server.body
+
+
+def test_anthropic_gets_the_user_as_metadata(server):
+    server.run({"messages": QUESTION, "user": "user-42"}, name="anthropic")
+    assert server.body["metadata"] == {"user_id": "user-42"}
+    assert "user" not in server.body
+
+
+def test_request_id_is_echoed_on_the_result(server):
+    metrics = server.run({"messages": QUESTION, "request_id": "row-0017"})
+    assert metrics.request_id == "row-0017"
+    assert "request_id" not in server.body
+    assert server.run(QUESTION).request_id is None
+
+
+def test_metadata_must_be_a_dict(server):
+    with pytest.raises(ValueError, match=r"requests\[0\]: metadata must be a dict"):
+        server.run({"messages": QUESTION, "metadata": ["experiment"]})
@@ -0,0 +1,54 @@
+import pytest
+
+CONFIG = {"model": "base-model", "temperature": 0.2, "max_tokens": 100, "top_p": 0.9}
+
+
+@pytest.fixture
+def response():
+    """Answers as the model it was asked for."""
+
+    def answer(body, path):
+        return {
+            "model": body["model"],
+            "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 2, "completion_tokens": 1},
+        }
+
+    return answer
+
+
+def run(server, requests):
+    return server.process(requests, server.provider(config=CONFIG)).metrics
+
+
+def bodies(server):
+    return {body["messages"][0]["content"]: body for body in server.bodies}
+
+
+def test_mixed_models_in_one_batch(server):
+    metrics = run(server, [
+        [{"role": "user", "content": "plain"}],
+        {"messages": [{"role": "user", "content": "small"}], "model": "small-model", "temperature": 0.0},
+        {"messages": [{"role": "user", "content": "large"}], "model": "large-model", "max_tokens": 500},
+    ])
+    models = {name: body["model"] for name, body in bodies(server).items()}
+    assert models == {"plain": "base-model", "small": "small-model", "large": "large-model"}
+    assert {m.index: m.model for m in metrics} == {0: "base-model", 1: "small-model", 2: "large-model"}
+
+
+def test_overrides_replace_only_what_they_set(server):
+    run(server, [{
+        "messages": [{"role": "user", "content": "tuned"}],
+        "temperature": 1.0,
+        "frequency_penalty": 0.5,
+        "presence_penalty": -0.5,
+    }])
+    body = bodies(server)["tuned"]
+    assert (body["temperature"], body["frequency_penalty"], body["presence_penalty"]) == (1.0, 0.5, -0.5)
+    # The rest still comes from the provider config
+    assert (body["model"], body["max_tokens"], body["top_p"]) == ("base-model", 100, pytest.approx(0.9))
+
+
+def test_invalid_override_is_rejected(server):
+    with pytest.raises(ValueError, match=r"requests\[0\].*temperature"):
+        run(server, [{"messages": [{"role": "user", "content": "bad"}], "temperature": "hot"}])
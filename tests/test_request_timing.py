@@ -0,0 +1,31 @@
+from axicontraves import process_requests_multi
+
+# One request at a time, 200 ms each
+SLOW = ("openai", "test", None, {"model": "m"},
+        {"simulator": {"max_concurrency": 1, "service_time": {"distribution": "constant", "ms": 200}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(3)]
+
+
+def run(requests, **options):
+    results = process_requests_multi([SLOW], requests, lambda *args: None, False, None, **options)
+    return sorted(results, key=lambda m: m.index)
+
+
+def test_queued_requests_report_their_wait():
+    results = run(REQUESTS, max_concurrency=1)
+    for position, metrics in enumerate(results):
+        assert 180 < metrics.latency_ms < 400
+        assert position * 180 < metrics.queue_ms < position * 200 + 150
+        assert metrics.total_ms >= metrics.queue_ms + metrics.latency_ms - 1
+
+
+def test_requests_pulled_from_an_iterator_are_timed_from_the_pull():
+    results = run(iter(REQUESTS), max_concurrency=1)
+    assert all(m.queue_ms < 50 for m in results)
+    assert all(m.total_ms >= m.latency_ms for m in results)
+
+
+def test_skipped_requests_have_no_timing():
+    results = run(REQUESTS, skip=[1])
+    assert results[1].status == "skipped"
+    assert results[1].queue_ms == results[1].total_ms == 0.0
@@ -0,0 +1,37 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "List three colours."}]
+JSON_OBJECT = {"type": "json_object"}
+
+
+def test_config_response_format_is_sent(server):
+    server.run(QUESTION, response_format=JSON_OBJECT)
+    assert server.body["response_format"] == JSON_OBJECT
+
+
+def test_request_response_format_replaces_the_config(server):
+    text = {"type": "text"}
+    server.run({"messages": QUESTION, "response_format": text}, response_format=JSON_OBJECT)
+    assert server.body["response_format"] == text
+
+
+def test_request_response_format_without_a_config(server):
+    server.run({"messages": QUESTION, "response_format": JSON_OBJECT})
+    assert server.body["response_format"] == JSON_OBJECT
+
+
+def test_json_schema_takes_precedence(server):
+    schema = {"type": "object"}
+    server.run({"messages": QUESTION, "json_schema": schema, "response_format": {"type": "text"}}, response_format=JSON_OBJECT)
+    assert server.body["response_format"]["type"] == "json_schema"
+    assert server.body["response_format"]["json_schema"]["schema"] == schema
+
+
+def test_no_response_format_by_default(server):
+    server.run(QUESTION)
+    assert "response_format" not in server.body
+
+
+def test_response_format_must_be_json(server):
+    with pytest.raises(ValueError, match=r"providers\[0\]: Cannot convert"):
+        server.run(QUESTION, response_format={"type": object()})
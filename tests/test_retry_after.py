@@ -0,0 +1,114 @@
+import json
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+
+class Limited(BaseHTTPRequestHandler):
+    """Rejects the first `failures` requests with a 429 carrying `hint` headers."""
+
+    failures = 0
+    hint = {}
+
+    def do_POST(self):
+        self.rfile.read(int(self.headers["Content-Length"]))
+        if Limited.failures > 0:
+            Limited.failures -= 1
+            payload = b'{"error": {"message": "Rate limit reached"}}'
+            self.send_response(429)
+            for name, value in Limited.hint.items():
+                self.send_header(name, value)
+        else:
+            response = {
+                "model": "m",
+                "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 3, "completion_tokens": 1},
+            }
+            payload = json.dumps(response).encode()
+            self.send_response(200)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def server():
+    httpd = HTTPServer(("127.0.0.1", 0), Limited)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{httpd.server_port}"
+    httpd.shutdown()
+
+
+def run(server, failures, hint, **kwargs):
+    Limited.failures, Limited.hint = failures, hint
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"})
+    started = time.monotonic()
+    metrics = BatchProcessor(provider, **kwargs).process_batch([[{"role": "user", "content": "Hi"}]], show_progress=False).metrics[0]
+    return metrics, time.monotonic() - started
+
+
+def test_retry_after_ms_is_honored(server):
+    metrics, elapsed = run(server, 2, {"retry-after-ms": "300"})
+    assert metrics.status == "ok"
+    assert metrics.retries == 2
+    assert 0.6 <= elapsed < 1.0
+
+
+def test_openai_reset_header(server):
+    hint = {"x-ratelimit-remaining-tokens": "0", "x-ratelimit-reset-tokens": "400ms", "x-ratelimit-reset-requests": "1m0s"}
+    metrics, elapsed = run(server, 1, hint)
+    assert metrics.status == "ok"
+    assert 0.4 <= elapsed < 1.0
+
+
+def test_retries_are_bounded(server):
+    metrics, _ = run(server, 5, {"retry-after": "0"}, rate_limit_retries=2)
+    assert metrics.status == "rate_limited"
+    assert "429" in metrics.error
+
+
+def test_backoff_without_hint(server):
+    metrics, elapsed = run(server, 1, {})
+    assert metrics.retries == 1
+    assert elapsed >= 1.0
+
+
+@pytest.mark.parametrize("hint", [
+    {"retry-after": "inf"},
+    {"retry-after": "NaN"},
+    {"retry-after": "1e300"},
+    {"retry-after": "-5"},
+    {"retry-after-ms": "inf"},
+    {"retry-after-ms": "1e300"},
+    {"x-ratelimit-remaining-requests": "0", "x-ratelimit-reset-requests": "9" * 400 + "s"},
+])
+def test_unusable_hints_fall_back_to_backoff(server, hint):
+    metrics, elapsed = run(server, 1, hint)
+    assert metrics.status == "ok"
+    assert metrics.retries == 1
+    assert 1.0 <= elapsed < 2.0
+
+
+def test_unusable_retry_after_ms_falls_back_to_retry_after(server):
+    metrics, elapsed = run(server, 1, {"retry-after-ms": "NaN", "retry-after": "0.2"})
+    assert metrics.status == "ok"
+    assert 0.2 <= elapsed < 1.0
+
+
+def test_retries_wait_their_turn_under_rpm(server):
+    # One request a second: the retry is a second request, so it waits out the first's second
+    Limited.failures, Limited.hint = 1, {"retry-after-ms": "10"}
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"}, rpm=60)
+    started = time.monotonic()
+    metrics = BatchProcessor(provider).process_batch([[{"role": "user", "content": "Hi"}]], show_progress=False).metrics[0]
+    assert metrics.status == "ok"
+    assert metrics.retries == 1
+    assert time.monotonic() - started >= 0.9
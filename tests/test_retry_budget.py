@@ -0,0 +1,39 @@
+import warnings
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(60)]
+
+
+@pytest.fixture
+def server(make_server):
+    # An outage: every request is rate limited, with a short Retry-After
+    return make_server({"error": {"message": "overloaded"}}, status=503, headers={"Retry-After": "0.05"})
+
+
+def provider(server):
+    return server.provider()
+
+
+def test_retries_stop_once_the_budget_is_spent(server):
+    processor = BatchProcessor(provider(server), max_concurrency=4, rate_limit_retries=3, retry_budget=0.2)
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        metrics = processor.process_batch(REQUESTS, show_progress=False).metrics
+    assert all(m.status == "error" for m in metrics)
+    # Three retries each would be 4 calls per request
+    assert server.calls <= len(REQUESTS) + 10 + 0.2 * len(REQUESTS)
+    assert any("Retry budget spent" in str(w.message) for w in caught)
+
+
+def test_without_a_budget_every_request_retries(server):
+    metrics = BatchProcessor(provider(server), max_concurrency=8, rate_limit_retries=2).process_batch(REQUESTS[:10], show_progress=False).metrics
+    assert all(m.status == "error" for m in metrics)
+    assert server.calls == 30
+
+
+def test_budget_must_be_non_negative(server):
+    with pytest.raises(ValueError, match="retry_budget"):
+        BatchProcessor(provider(server), retry_budget=-0.1).process_batch(REQUESTS[:1], show_progress=False)
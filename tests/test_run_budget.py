@@ -0,0 +1,59 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+FAST = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+# One request at a time, 200 ms each
+SLOW = ProviderConfig(
+    name="openai", api_key="k", config={"model": "m"},
+    simulator={"max_concurrency": 1, "service_time": {"distribution": "constant", "ms": 200}},
+)
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(10)]
+
+
+def run(provider, **kwargs):
+    processor = BatchProcessor(provider, max_concurrency=1, **kwargs)
+    with pytest.warns(RuntimeWarning, match="Run stopped"):
+        metrics = processor.process_batch(REQUESTS, show_progress=False).metrics
+    assert [m.index for m in metrics] == list(range(len(REQUESTS)))
+    sent = [m for m in metrics if m.status == "ok"]
+    skipped = [m for m in metrics if m.status == "skipped"]
+    assert len(sent) + len(skipped) == len(REQUESTS)
+    return sent, skipped
+
+
+def test_token_budget_stops_dispatch():
+    sent, skipped = run(FAST, max_total_tokens=1)
+    assert len(sent) == 1
+    assert all(m.error == "Not sent: max_total_tokens reached" for m in skipped)
+
+
+def test_cost_budget_uses_pricing():
+    # A dollar per token: the first request alone exceeds the budget
+    sent, skipped = run(FAST, max_cost_usd=0.5, pricing={"m": {"input": 1e6, "output": 1e6}})
+    assert len(sent) == 1
+    assert "max_cost_usd" in skipped[0].error
+
+
+def test_deadline_drains_in_flight_requests():
+    sent, skipped = run(SLOW, max_duration_seconds=0.5)
+    assert 2 <= len(sent) <= 4
+    assert "max_duration_seconds" in skipped[0].error
+
+
+def test_unreached_budget_sends_everything():
+    metrics = BatchProcessor(FAST, max_total_tokens=10**9).process_batch(REQUESTS, show_progress=False).metrics
+    assert {m.status for m in metrics} == {"ok"}
+
+
+def test_cost_budget_needs_pricing():
+    with pytest.raises(ValueError, match="pricing"):
+        BatchProcessor(FAST, max_cost_usd=1.0).process_batch(REQUESTS, show_progress=False)
+
+
+def test_requests_are_priced_by_the_model_they_ask_for():
+    requests = [REQUESTS[0], {"messages": REQUESTS[1], "model": "big"}]
+    pricing = {"m": {"input": 1, "output": 1}, "big": {"input": 1e6, "output": 1e6}}
+    plain, big = BatchProcessor(FAST, max_cost_usd=1e9, pricing=pricing).process_batch(requests, show_progress=False).metrics
+    assert plain.cost_usd == pytest.approx((plain.prompt_tokens + plain.completion_tokens) / 1e6)
+    assert big.cost_usd == pytest.approx(big.prompt_tokens + big.completion_tokens)
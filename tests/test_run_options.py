@@ -0,0 +1,37 @@
+import pytest
+
+from axicontraves import process_requests_multi, start_requests_multi
+
+PROVIDER = ("openai", "test", None, {"model": "m"}, {"test_mode": True})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(3)]
+
+
+def process(**options):
+    return process_requests_multi([PROVIDER], REQUESTS, lambda *args: None, False, None, **options)
+
+
+def start(**options):
+    return start_requests_multi([PROVIDER], REQUESTS, **options).wait()
+
+
+@pytest.mark.parametrize("run", [process, start])
+def test_both_entry_points_take_the_same_options(run):
+    results = run(max_concurrency=1, rate_limit_retries=0, routing="least_loaded", statsd_tags=None)
+    assert sorted(m.index for m in results) == [0, 1, 2]
+
+
+@pytest.mark.parametrize("run", [process, start])
+def test_none_means_the_default(run):
+    assert len(run(artifact_min_bytes=None, routing=None, skip=None)) == len(REQUESTS)
+
+
+@pytest.mark.parametrize("run", [process, start])
+def test_unknown_option_is_rejected(run):
+    with pytest.raises(TypeError, match="max_concurency"):
+        run(max_concurency=4)
+
+
+@pytest.mark.parametrize("run", [process, start])
+def test_options_are_checked_the_same_way(run):
+    with pytest.raises(ValueError, match="routing"):
+        run(routing="fastest")
@@ -0,0 +1,40 @@
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, RunRegistry, list_runs, load_summary
+
+REQUESTS = [[{"role": "user", "content": "hello there"}] for _ in range(3)]
+
+
+def run(tmp_path, name):
+    provider = ProviderConfig(name="openai", api_key="sk-secret", config={"model": "m"}, test_mode=True)
+    processor = BatchProcessor(provider, run_name=name, registry=str(tmp_path))
+    return processor.process_batch(REQUESTS, show_progress=False)
+
+
+def test_runs_are_recorded_and_queryable(tmp_path):
+    first = run(tmp_path, "baseline")
+    second = run(tmp_path, "candidate")
+
+    assert [r.run_id for r in list_runs(root=tmp_path)] == [first.run_id, second.run_id]
+    assert [r.name for r in list_runs("candidate", root=tmp_path)] == ["candidate"]
+
+    summary = load_summary(first.run_id, root=tmp_path)
+    assert summary["total_requests"] == 3
+    assert summary["completion_tokens"] == first.completion_tokens
+    assert summary["finish_reasons"] == {"stop": 3}
+
+
+def test_date_filters_and_no_api_keys(tmp_path):
+    result = run(tmp_path, "nightly")
+    registry = RunRegistry(tmp_path)
+    now = datetime.now(timezone.utc)
+    assert registry.list_runs(since=now - timedelta(minutes=1))[0].run_id == result.run_id
+    assert registry.list_runs(until=now - timedelta(days=1)) == []
+    assert "sk-secret" not in (tmp_path / result.run_id / "manifest.json").read_text()
+
+
+def test_unknown_run(tmp_path):
+    with pytest.raises(KeyError):
+        load_summary("missing", root=tmp_path)
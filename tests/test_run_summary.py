@@ -0,0 +1,51 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, RunSummary, start_requests_multi, summarize_results
+
+# 100 ms per request, ten at a time
+SIMULATED = ("openai", "test", None, {"model": "m"},
+             {"simulator": {"max_concurrency": 10, "service_time": {"distribution": "constant", "ms": 100}}})
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(20)]
+
+
+def test_process_batch_returns_a_summary():
+    provider = ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+    result = BatchProcessor(provider).process_batch(REQUESTS, show_progress=False)
+    summary = result.summary
+    assert isinstance(summary, RunSummary)
+    assert summary.requests == summary.succeeded == len(REQUESTS)
+    assert summary.failed == summary.cancelled == summary.skipped == 0
+    assert summary.prompt_tokens == result.prompt_tokens
+    assert summary.completion_tokens == result.completion_tokens
+    assert summary.duration_seconds == pytest.approx(result.total_time, rel=0.05)
+    assert summary.p50_latency_ms <= summary.p90_latency_ms <= summary.p95_latency_ms <= summary.p99_latency_ms
+
+
+def test_percentiles_and_rates():
+    handle = start_requests_multi([SIMULATED], REQUESTS, max_concurrency=10)
+    results = handle.wait()
+    summary = summarize_results(results, 2.0)
+    assert 90 < summary.p50_latency_ms < 300
+    assert summary.p99_latency_ms == max(m.latency_ms for m in results)
+    assert summary.requests_per_second == pytest.approx(len(REQUESTS) / 2.0)
+    total_tokens = sum(m.prompt_tokens + m.completion_tokens for m in results)
+    assert summary.tokens_per_second == pytest.approx(total_tokens / 2.0)
+
+
+def test_handle_summary_covers_the_whole_run():
+    handle = start_requests_multi([SIMULATED], REQUESTS, max_concurrency=10, skip=[0, 1])
+    handle.wait()
+    summary = handle.summary()
+    assert summary.requests == len(REQUESTS)
+    assert summary.skipped == 2
+    assert summary.succeeded == len(REQUESTS) - 2
+    # Two rounds of ten, less the skipped ones
+    assert 0.15 < summary.duration_seconds < 1.0
+    assert handle.summary().duration_seconds == summary.duration_seconds
+
+
+def test_empty_run_summary():
+    summary = summarize_results([], 0.0)
+    assert summary.requests == 0
+    assert summary.requests_per_second == summary.tokens_per_second == 0.0
+    assert summary.p99_latency_ms == 0.0
@@ -0,0 +1,32 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+PROVIDER = ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+
+
+def run(content, **options):
+    processor = BatchProcessor(PROVIDER, **options)
+    return processor.process_batch([[{"role": "user", "content": content}]], show_progress=False).metrics[0]
+
+
+def test_report_counts_each_fix():
+    # "e" + combining acute is NFC "é"; \ud800 is a lone surrogate from broken scraping
+    metrics = run([
+        {"type": "text", "text": "nul\x00l\x00"},
+        {"type": "text", "text": "cafe\u0301 \ud800"},
+    ], sanitize_inputs=True)
+    assert metrics.status == "ok"
+    assert metrics.sanitization == {"null_bytes": 2, "lone_surrogates": 1, "normalized": 1}
+
+
+def test_clean_text_reports_nothing():
+    assert run("plain text", sanitize_inputs=True).sanitization == {
+        "null_bytes": 0, "lone_surrogates": 0, "normalized": 0,
+    }
+
+
+def test_off_by_default():
+    assert run("plain text").sanitization is None
+    with pytest.raises(ValueError, match="surrogates not allowed"):
+        run("broken \ud800")
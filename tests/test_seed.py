@@ -0,0 +1,37 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Pick a number."}]
+
+
+@pytest.fixture
+def response():
+    """Reports a system_fingerprint only for seeded requests."""
+
+    def answer(body, path):
+        response = {
+            "choices": [{"message": {"content": "7"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 4, "completion_tokens": 1},
+        }
+        if "seed" in body:
+            response["system_fingerprint"] = f"fp_{body['seed']}"
+        return response
+
+    return answer
+
+
+def test_seed_is_sent_and_fingerprint_recorded(server):
+    metrics = server.run(QUESTION, seed=42)
+    assert server.body["seed"] == 42
+    assert metrics.system_fingerprint == "fp_42"
+
+
+def test_request_seed_replaces_the_config(server):
+    metrics = server.run({"messages": QUESTION, "seed": 7}, seed=42)
+    assert server.body["seed"] == 7
+    assert metrics.system_fingerprint == "fp_7"
+
+
+def test_unseeded_requests_have_no_fingerprint(server):
+    metrics = server.run(QUESTION)
+    assert "seed" not in server.body
+    assert metrics.system_fingerprint is None
@@ -0,0 +1,26 @@
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(8)]
+
+
+def provider(server, **options):
+    return server.provider(**options)
+
+
+@pytest.mark.parametrize("options", [{}, {"timeout": 30}])
+def test_later_runs_reuse_connections(server, options):
+    processor = BatchProcessor(provider(server, **options), max_concurrency=2)
+    processor.process_batch(REQUESTS, show_progress=False)
+    first = set(server.clients)
+    server.clients.clear()
+    BatchProcessor(provider(server, **options), max_concurrency=2).process_batch(REQUESTS, show_progress=False)
+    assert set(server.clients) <= first
+
+
+def test_concurrent_runs_share_the_runtime(server):
+    handles = [BatchProcessor(provider(server), max_concurrency=2).start_batch(REQUESTS) for _ in range(3)]
+    for handle in handles:
+        assert sorted(m.index for m in handle.wait()) == list(range(len(REQUESTS)))
+    assert len(server.clients) == 3 * len(REQUESTS)
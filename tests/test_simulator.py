@@ -0,0 +1,49 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(6)]
+
+
+def simulated(**simulator):
+    simulator.setdefault("per_token_ms", 0)
+    return ProviderConfig(name="openai", api_key="test", config={"model": "m"}, simulator=simulator)
+
+
+def run(provider, requests=REQUESTS, **options):
+    return BatchProcessor(provider, rate_limit_retries=0, **options).process_batch(requests, show_progress=False)
+
+
+def test_requests_queue_for_the_servers_capacity():
+    provider = simulated(max_concurrency=2, service_time={"distribution": "constant", "ms": 100})
+    started = time.monotonic()
+    result = run(provider, max_concurrency=6)
+    # Six requests two at a time take three rounds, however many the client sends at once
+    assert time.monotonic() - started >= 0.3
+    assert all(m.status == "ok" for m in result.metrics)
+    assert max(m.latency_ms for m in result.metrics) >= 250
+
+
+def test_a_deep_queue_answers_429():
+    provider = simulated(
+        max_concurrency=1, rate_limit_threshold=2, service_time={"distribution": "constant", "ms": 100}
+    )
+    statuses = [m.status for m in run(provider, max_concurrency=6).metrics]
+    assert statuses.count("rate_limited") >= 1
+    assert statuses.count("ok") >= 3
+
+
+def test_warmup_slows_the_first_requests():
+    provider = simulated(
+        warmup_requests=2, warmup_factor=5.0, service_time={"distribution": "constant", "ms": 40}
+    )
+    latencies = [m.latency_ms for m in run(provider, REQUESTS[:4], max_concurrency=1).metrics]
+    assert latencies[0] >= 180
+    assert latencies[3] < 120
+
+
+def test_unknown_distribution_is_rejected():
+    with pytest.raises(ValueError, match="distribution"):
+        run(simulated(service_time={"distribution": "pareto"}))
@@ -0,0 +1,44 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(10)]
+
+
+@pytest.fixture
+def servers(make_server):
+    return [make_server(delay=0.02) for _ in range(2)]
+
+
+def providers(servers):
+    # The first allows one request a second, the second is unlimited
+    limited, open_ = servers
+    return [limited.provider(rpm=60), open_.provider()]
+
+
+def test_requests_spill_over_to_the_provider_with_headroom(servers):
+    started = time.monotonic()
+    processor = BatchProcessor(providers(servers), max_concurrency=2, spillover=True)
+    metrics = processor.process_batch(REQUESTS, show_progress=False).metrics
+    elapsed = time.monotonic() - started
+    assert all(m.status == "ok" for m in metrics)
+    assert servers[0].calls <= 2
+    assert servers[1].calls >= len(REQUESTS) - 2
+    assert elapsed < 1.5
+
+
+def test_without_spillover_requests_wait_their_turn(servers):
+    started = time.monotonic()
+    BatchProcessor(providers(servers), max_concurrency=2).process_batch(REQUESTS, show_progress=False)
+    assert servers[0].calls == len(REQUESTS) // 2
+    assert time.monotonic() - started >= 3.5
+
+
+def test_saturated_providers_still_wait(servers):
+    limited = providers(servers)[:1]
+    started = time.monotonic()
+    metrics = BatchProcessor(limited, spillover=True).process_batch(REQUESTS[:3], show_progress=False).metrics
+    assert all(m.status == "ok" for m in metrics)
+    assert time.monotonic() - started >= 1.8
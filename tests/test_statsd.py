@@ -0,0 +1,59 @@
+import socket
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(4)]
+
+
+@pytest.fixture
+def agent():
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    sock.bind(("127.0.0.1", 0))
+    sock.settimeout(2)
+    yield sock
+    sock.close()
+
+
+def received(sock, count):
+    """The metrics in the first `count` datagrams, as (name, value, kind, tags)."""
+    metrics = []
+    for _ in range(count):
+        for line in sock.recv(65536).decode().splitlines():
+            name_value, kind, tags = line.split("|")
+            name, value = name_value.split(":")
+            metrics.append((name, float(value), kind, dict(tag.split(":", 1) for tag in tags[1:].split(","))))
+    return metrics
+
+
+def test_counters_and_timings_per_result(agent):
+    provider = ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+    address = "127.0.0.1:%d" % agent.getsockname()[1]
+    processor = BatchProcessor(provider, statsd=address, statsd_prefix="batch", statsd_tags={"env": "ci"})
+    result = processor.process_batch(REQUESTS, show_progress=False)
+    metrics = received(agent, len(REQUESTS))
+    requests = [m for m in metrics if m[0] == "batch.requests"]
+    assert len(requests) == len(REQUESTS)
+    assert all(m[1] == 1 and m[2] == "c" for m in requests)
+    assert all(m[3]["status"] == "ok" and m[3]["env"] == "ci" and m[3]["model"] == "m" for m in metrics)
+    assert {m[3]["provider"] for m in metrics} == {result.metrics[0].provider_name}
+    latencies = sorted(m[1] for m in metrics if m[0] == "batch.latency")
+    assert latencies == pytest.approx(sorted(m.latency_ms for m in result.metrics))
+    assert all(m[2] == "ms" for m in metrics if m[0] == "batch.latency")
+    prompt_tokens = sum(m[1] for m in metrics if m[0] == "batch.prompt_tokens")
+    assert prompt_tokens == sum(m.prompt_tokens for m in result.metrics)
+
+
+def test_failures_are_tagged_with_their_category(agent):
+    dead = ProviderConfig(name="openai", api_key="test", base_url="http://127.0.0.1:9", config={"model": "m"})
+    address = "127.0.0.1:%d" % agent.getsockname()[1]
+    BatchProcessor(dead, statsd=address, rate_limit_retries=0).process_batch(REQUESTS[:1], show_progress=False)
+    [requests] = [m for m in received(agent, 1) if m[0] == "axicontraves.requests"]
+    assert (requests[3]["status"], requests[3]["error_category"]) == ("error", "connection")
+
+
+def test_unresolvable_address_is_rejected():
+    provider = ProviderConfig(name="openai", api_key="test", config={"model": "m"}, test_mode=True)
+    with pytest.raises(ValueError, match="statsd"):
+        BatchProcessor(provider, statsd="no-such-host.invalid").process_batch(REQUESTS, show_progress=False)
@@ -0,0 +1,78 @@
+import json
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import pytest
+
+from axicontraves import ProviderConfig, process_requests_multi, summarize_results
+
+
+class Outcomes(BaseHTTPRequestHandler):
+    """Answers according to the prompt: "slow" stalls, "limit" is rate limited, "boom" errors."""
+
+    def do_POST(self):
+        body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        prompt = body["messages"][0]["content"]
+        status, payload = 200, {
+            "choices": [{"message": {"content": "hi"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 1, "completion_tokens": 1},
+        }
+        if prompt == "slow":
+            time.sleep(1)
+        elif prompt == "limit":
+            status, payload = 429, {"error": {"message": "slow down"}}
+        elif prompt == "boom":
+            status, payload = 500, {"error": {"message": "broken"}}
+        data = json.dumps(payload).encode()
+        try:
+            self.send_response(status)
+            self.send_header("Content-Type", "application/json")
+            self.send_header("Content-Length", str(len(data)))
+            self.end_headers()
+            self.wfile.write(data)
+        except (BrokenPipeError, ConnectionResetError):
+            pass
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def server():
+    httpd = ThreadingHTTPServer(("127.0.0.1", 0), Outcomes)
+    httpd.daemon_threads = True
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{httpd.server_port}"
+    httpd.shutdown()
+
+
+def test_every_request_gets_one_result_with_its_outcome(server):
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"}, timeout=0.3)
+    prompts = ["fine", "slow", "limit", "boom", "skipped"]
+    requests = [[{"role": "user", "content": prompt}] for prompt in prompts]
+    results = process_requests_multi(
+        [provider.as_tuple()], requests, lambda *args: None, False, None, skip=[4], rate_limit_retries=0
+    )
+    by_prompt = {prompts[m.index]: m for m in results}
+    assert len(results) == len(prompts) == len(by_prompt)
+    assert {prompt: m.status for prompt, m in by_prompt.items()} == {
+        "fine": "ok",
+        "slow": "timeout",
+        "limit": "rate_limited",
+        "boom": "error",
+        "skipped": "skipped",
+    }
+    assert by_prompt["fine"].error is None
+    assert all(by_prompt[prompt].error for prompt in ["slow", "limit", "boom"])
+    assert by_prompt["boom"].error_category == "server_error"
+
+
+def test_summary_counts_failures_by_status(server):
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"}, timeout=0.3)
+    requests = [[{"role": "user", "content": prompt}] for prompt in ["fine", "slow", "limit", "boom"]]
+    results = process_requests_multi(
+        [provider.as_tuple()], requests, lambda *args: None, False, None, rate_limit_retries=0
+    )
+    summary = summarize_results(results, 1.0)
+    assert (summary.succeeded, summary.failed, summary.timed_out, summary.rate_limited) == (1, 3, 1, 1)
@@ -0,0 +1,69 @@
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, count_tokens
+
+TOKENS = ["Here:\n", "```py\n", "x = 1\n", "```", "\nand some ", "more prose"]
+
+
+class Stream(BaseHTTPRequestHandler):
+    def do_POST(self):
+        self.rfile.read(int(self.headers["Content-Length"]))
+        self.send_response(200)
+        self.send_header("Content-Type", "text/event-stream")
+        self.end_headers()
+        for token in TOKENS:
+            chunk = {"model": "m", "choices": [{"index": 0, "delta": {"content": token}, "finish_reason": None}]}
+            self.wfile.write(f"data: {json.dumps(chunk)}\n\n".encode())
+        usage = {"choices": [], "usage": {"prompt_tokens": 11, "completion_tokens": 7}}
+        self.wfile.write(f"data: {json.dumps(usage)}\n\n".encode())
+        self.wfile.write(b"data: [DONE]\n\n")
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def server():
+    httpd = HTTPServer(("127.0.0.1", 0), Stream)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{httpd.server_port}"
+    httpd.shutdown()
+
+
+def processor(server, tmp_path):
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"})
+    return BatchProcessor(provider, stream_dir=str(tmp_path))
+
+
+def test_stops_once_pattern_matches(server, tmp_path):
+    request = {"messages": [{"role": "user", "content": "code please"}], "stop_regex": r"```\w*\n[\s\S]*?```"}
+    metrics = processor(server, tmp_path).process_batch([request], show_progress=False).metrics[0]
+    assert metrics.content == "".join(TOKENS[:4])
+    assert metrics.finish_reason == "stop_regex"
+    assert metrics.usage_estimated
+    assert metrics.completion_tokens == count_tokens("".join(TOKENS[:4]), "m")
+    assert (tmp_path / "0.txt").read_text() == metrics.content
+
+
+def test_unmatched_pattern_reads_whole_stream(server, tmp_path):
+    request = {"messages": [{"role": "user", "content": "code please"}], "stop_regex": "never"}
+    metrics = processor(server, tmp_path).process_batch([request], show_progress=False).metrics[0]
+    assert metrics.content == "".join(TOKENS)
+    assert not metrics.usage_estimated
+
+
+def test_requires_streaming(server):
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"})
+    request = {"messages": [{"role": "user", "content": "hi"}], "stop_regex": "x"}
+    with pytest.raises(ValueError):
+        BatchProcessor(provider).process_batch([request], show_progress=False)
+
+
+def test_invalid_pattern(server, tmp_path):
+    request = {"messages": [{"role": "user", "content": "hi"}], "stop_regex": "("}
+    with pytest.raises(ValueError):
+        processor(server, tmp_path).process_batch([request], show_progress=False)
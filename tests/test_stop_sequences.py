@@ -0,0 +1,30 @@
+import pytest
+
+QUESTION = [{"role": "user", "content": "Count to ten."}]
+
+
+def test_a_single_stop_string_is_sent_as_a_list(server):
+    server.run(QUESTION, stop="4")
+    assert server.body["stop"] == ["4"]
+
+
+def test_request_stop_replaces_the_config(server):
+    server.run({"messages": QUESTION, "stop": ["5", "six"]}, stop="4")
+    assert server.body["stop"] == ["5", "six"]
+
+
+def test_no_stop_by_default(server):
+    server.run(QUESTION)
+    assert "stop" not in server.body
+
+
+def test_anthropic_gets_stop_sequences(server):
+    metrics = server.run(QUESTION, name="anthropic", stop=["4"])
+    assert server.body["stop_sequences"] == ["4"]
+    assert "stop" not in server.body
+    assert metrics.finish_reason == "stop"
+
+
+def test_invalid_stop_is_rejected(server):
+    with pytest.raises(ValueError, match="stop"):
+        server.run(QUESTION, stop=4)
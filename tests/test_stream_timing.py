@@ -0,0 +1,77 @@
+import json
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+TOKENS = ["one", " two", " three", " four", " five"]
+FIRST_TOKEN_DELAY = 0.3
+TOKEN_GAP = 0.05
+
+
+class Stream(BaseHTTPRequestHandler):
+    def do_POST(self):
+        body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        if not body.get("stream"):
+            payload = json.dumps({
+                "model": "m",
+                "choices": [{"message": {"content": "".join(TOKENS)}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 3, "completion_tokens": len(TOKENS)},
+            }).encode()
+            self.send_response(200)
+            self.send_header("Content-Type", "application/json")
+            self.send_header("Content-Length", str(len(payload)))
+            self.end_headers()
+            self.wfile.write(payload)
+            return
+        self.send_response(200)
+        self.send_header("Content-Type", "text/event-stream")
+        self.end_headers()
+        time.sleep(FIRST_TOKEN_DELAY)
+        for position, token in enumerate(TOKENS):
+            if position:
+                time.sleep(TOKEN_GAP)
+            chunk = {"model": "m", "choices": [{"index": 0, "delta": {"content": token}, "finish_reason": None}]}
+            self.wfile.write(f"data: {json.dumps(chunk)}\n\n".encode())
+            self.wfile.flush()
+        usage = {"choices": [], "usage": {"prompt_tokens": 3, "completion_tokens": len(TOKENS)}}
+        self.wfile.write(f"data: {json.dumps(usage)}\n\n".encode())
+        self.wfile.write(b"data: [DONE]\n\n")
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def server():
+    httpd = HTTPServer(("127.0.0.1", 0), Stream)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{httpd.server_port}"
+    httpd.shutdown()
+
+
+def provider(server):
+    return ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"})
+
+
+def test_streamed_requests_report_token_timing(server, tmp_path):
+    processor = BatchProcessor(provider(server), stream_dir=str(tmp_path))
+    metrics = processor.process_batch([[{"role": "user", "content": "count"}]], show_progress=False).metrics[0]
+    assert FIRST_TOKEN_DELAY * 1000 <= metrics.ttft_ms < metrics.latency_ms
+    assert TOKEN_GAP * 1000 * 0.8 < metrics.itl_mean_ms < TOKEN_GAP * 1000 * 3
+    assert metrics.itl_p50_ms <= metrics.itl_p95_ms
+    # Four tokens after the first over four gaps
+    assert metrics.output_tokens_per_second == pytest.approx(1000 / metrics.itl_mean_ms, rel=0.01)
+
+
+def test_unstreamed_requests_have_no_token_timing(server):
+    metrics = BatchProcessor(provider(server)).process_batch(
+        [[{"role": "user", "content": "count"}]], show_progress=False
+    ).metrics[0]
+    assert metrics.status == "ok"
+    assert metrics.ttft_ms is None
+    assert metrics.itl_mean_ms is None
+    assert metrics.output_tokens_per_second is None
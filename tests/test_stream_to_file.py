@@ -0,0 +1,79 @@
+import json
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+TOKENS = ["Once", " upon", " a", " time"]
+
+
+class Stream(BaseHTTPRequestHandler):
+    """Streams the first token, then holds the rest until `release` is set."""
+
+    def do_POST(self):
+        Stream.body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        self.send_response(200)
+        self.send_header("Content-Type", "text/event-stream")
+        self.end_headers()
+        for position, token in enumerate(TOKENS):
+            if position == 1:
+                Stream.release.wait(5)
+            chunk = {"model": "m", "choices": [{"index": 0, "delta": {"content": token}, "finish_reason": None}]}
+            self.wfile.write(f"data: {json.dumps(chunk)}\n\n".encode())
+            self.wfile.flush()
+        done = {"model": "m", "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}
+        usage = {"choices": [], "usage": {"prompt_tokens": 3, "completion_tokens": len(TOKENS)}}
+        self.wfile.write(f"data: {json.dumps(done)}\n\ndata: {json.dumps(usage)}\n\ndata: [DONE]\n\n".encode())
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def server():
+    Stream.release = threading.Event()
+    Stream.release.set()
+    httpd = HTTPServer(("127.0.0.1", 0), Stream)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{httpd.server_port}"
+    Stream.release.set()
+    httpd.shutdown()
+
+
+def processor(server, **options):
+    return BatchProcessor(ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"}), **options)
+
+
+def test_each_request_streams_into_its_own_file(server, tmp_path):
+    requests = [[{"role": "user", "content": "Tell a story."}]] * 2
+    result = processor(server, stream_dir=str(tmp_path)).process_batch(requests, show_progress=False)
+    assert Stream.body["stream"] is True
+    assert sorted(path.name for path in tmp_path.iterdir()) == ["0.txt", "1.txt"]
+    for metrics in result.metrics:
+        assert (tmp_path / f"{metrics.index}.txt").read_text() == metrics.content == "Once upon a time"
+        assert metrics.finish_reason == "stop"
+
+
+def test_partial_output_is_on_disk_before_the_response_ends(server, tmp_path):
+    Stream.release.clear()
+    handle = processor(server, stream_dir=str(tmp_path)).start_batch([[{"role": "user", "content": "Tell a story."}]])
+    path = tmp_path / "0.txt"
+    deadline = time.monotonic() + 5
+    while not (path.exists() and path.read_text()) and time.monotonic() < deadline:
+        time.sleep(0.01)
+    assert path.read_text() == "Once"
+    assert not handle.done()
+    Stream.release.set()
+    [metrics] = handle.wait()
+    assert path.read_text() == metrics.content == "Once upon a time"
+
+
+def test_stream_to_names_the_file(server, tmp_path):
+    target = tmp_path / "story.txt"
+    request = {"messages": [{"role": "user", "content": "Tell a story."}], "stream_to": str(target)}
+    processor(server, stream_dir=str(tmp_path / "default")).process_batch([request], show_progress=False)
+    assert target.read_text() == "Once upon a time"
+    assert not (tmp_path / "default" / "0.txt").exists()
@@ -0,0 +1,61 @@
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, count_tokens
+
+TOKENS = ["The answer ", "is forty-two."]
+
+
+class Stream(BaseHTTPRequestHandler):
+    """Streams TOKENS and, like OpenAI, sends a usage chunk only when asked to."""
+
+    def do_POST(self):
+        body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        Stream.last = body
+        self.send_response(200)
+        self.send_header("Content-Type", "text/event-stream")
+        self.end_headers()
+        for token in TOKENS:
+            chunk = {"model": "m", "choices": [{"index": 0, "delta": {"content": token}, "finish_reason": None}]}
+            self.wfile.write(f"data: {json.dumps(chunk)}\n\n".encode())
+        if body.get("stream_options", {}).get("include_usage"):
+            usage = {"choices": [], "usage": {"prompt_tokens": 11, "completion_tokens": 7}}
+            self.wfile.write(f"data: {json.dumps(usage)}\n\n".encode())
+        self.wfile.write(b"data: [DONE]\n\n")
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def server():
+    httpd = HTTPServer(("127.0.0.1", 0), Stream)
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{httpd.server_port}"
+    httpd.shutdown()
+
+
+def run(server, tmp_path, config):
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m", **config})
+    processor = BatchProcessor(provider, stream_dir=str(tmp_path))
+    return processor.process_batch([[{"role": "user", "content": "What is the answer?"}]], show_progress=False).metrics[0]
+
+
+def test_usage_chunk_is_used(server, tmp_path):
+    metrics = run(server, tmp_path, {})
+    assert Stream.last["stream_options"] == {"include_usage": True}
+    assert (metrics.prompt_tokens, metrics.completion_tokens) == (11, 7)
+    assert not metrics.usage_estimated
+
+
+def test_estimated_when_stream_has_no_usage(server, tmp_path):
+    metrics = run(server, tmp_path, {"stream_usage": False})
+    assert "stream_options" not in Stream.last
+    assert metrics.usage_estimated
+    assert metrics.content == "".join(TOKENS)
+    assert metrics.completion_tokens == count_tokens("".join(TOKENS), "m")
+    # Message framing: 3 per message, the role and 3 priming the reply
+    assert metrics.prompt_tokens == 3 + count_tokens("user") + count_tokens("What is the answer?") + 3
@@ -0,0 +1,116 @@
+import json
+
+QUESTION = [{"role": "user", "content": "Who wrote Dune?"}]
+SCHEMA = {
+    "type": "object",
+    "properties": {"author": {"type": "string"}, "year": {"type": "integer"}},
+    "required": ["author", "year"],
+}
+
+
+def answering(output):
+    """Answers with `output`: as text in OpenAI's format, or as the forced tool call's
+    input in Anthropic's on /v1/messages."""
+
+    def answer(body, path):
+        if path.endswith("/v1/messages"):
+            return {
+                "content": [{"type": "tool_use", "id": "toolu_1", "name": "book", "input": json.loads(output)}],
+                "stop_reason": "tool_use",
+                "usage": {"input_tokens": 12, "output_tokens": 9},
+            }
+        return {
+            "choices": [{"message": {"content": output}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 9},
+        }
+
+    return answer
+
+
+def run(server, output, json_schema=SCHEMA, name="openai", validate_schema=True):
+    server.response = answering(output)
+    request = {"messages": QUESTION, "json_schema": json_schema}
+    return server.process([request], server.provider(name), validate_schema=validate_schema).metrics[0]
+
+
+def test_openai_gets_a_json_schema_response_format(server):
+    run(server, '{"author": "Frank Herbert", "year": 1965}')
+    assert server.body["response_format"] == {
+        "type": "json_schema",
+        "json_schema": {"name": "response", "schema": SCHEMA, "strict": True},
+    }
+
+
+def test_named_schema_is_sent_as_given(server):
+    named = {"name": "book", "schema": SCHEMA, "strict": False}
+    run(server, '{"author": "Frank Herbert", "year": 1965}', json_schema=named)
+    assert server.body["response_format"]["json_schema"] == named
+
+
+def test_valid_output(server):
+    metrics = run(server, '{"author": "Frank Herbert", "year": 1965}')
+    assert metrics.schema_valid is True
+    assert metrics.schema_error is None
+
+
+def test_invalid_output_is_flagged(server):
+    metrics = run(server, '{"author": "Frank Herbert", "year": "1965"}')
+    assert metrics.status == "ok"
+    assert metrics.schema_valid is False
+    assert "/year" in metrics.schema_error
+
+
+def test_non_json_output_is_flagged(server):
+    metrics = run(server, "Frank Herbert, in 1965.")
+    assert metrics.schema_valid is False
+    assert "not valid JSON" in metrics.schema_error
+
+
+def test_nothing_is_validated_unless_asked(server):
+    metrics = run(server, "Frank Herbert, in 1965.", validate_schema=False)
+    assert metrics.schema_valid is None
+    assert metrics.schema_error is None
+
+
+def test_anthropic_is_forced_to_call_a_schema_tool(server):
+    named = {"name": "book", "schema": SCHEMA}
+    metrics = run(server, '{"author": "Frank Herbert", "year": 1965}', json_schema=named, name="anthropic")
+    assert server.body["tools"] == [{
+        "name": "book",
+        "description": "Respond with a JSON object matching this schema",
+        "input_schema": SCHEMA,
+    }]
+    assert server.body["tool_choice"] == {"type": "tool", "name": "book"}
+    assert "response_format" not in server.body
+    # The tool call's input is the output, and answering through it is a normal stop
+    assert json.loads(metrics.content) == {"author": "Frank Herbert", "year": 1965}
+    assert metrics.finish_reason == "stop"
+    assert metrics.schema_valid is True
+
+
+def test_anthropic_tool_input_is_validated(server):
+    metrics = run(server, '{"author": "Frank Herbert"}', name="anthropic")
+    assert server.body["tool_choice"] == {"type": "tool", "name": "response"}
+    assert metrics.schema_valid is False
+    assert "year" in metrics.schema_error
+
+
+def test_streamed_anthropic_tool_input_is_the_output(server, tmp_path):
+    output = '{"author": "Frank Herbert", "year": 1965}'
+    server.response = [
+        {"type": "message_start", "message": {"model": "m", "usage": {"input_tokens": 12, "output_tokens": 1}}},
+        {"type": "content_block_start", "index": 0,
+         "content_block": {"type": "tool_use", "id": "toolu_1", "name": "response", "input": {}}},
+        {"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": output[:20]}},
+        {"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": output[20:]}},
+        {"type": "content_block_stop", "index": 0},
+        {"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 9}},
+        {"type": "message_stop"},
+    ]
+    target = tmp_path / "book.json"
+    request = {"messages": QUESTION, "json_schema": SCHEMA, "stream_to": str(target)}
+    [metrics] = server.process([request], server.provider("anthropic"), validate_schema=True).metrics
+    assert server.body["stream"] is True
+    assert metrics.content == target.read_text() == output
+    assert metrics.finish_reason == "stop"
+    assert metrics.schema_valid is True
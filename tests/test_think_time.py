@@ -0,0 +1,40 @@
+import time
+
+import pytest
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(4)]
+THINK = {"distribution": "constant", "ms": 300}
+
+
+def run(server, think_time):
+    arrivals = []
+
+    def answer(body, path):
+        arrivals.append(time.monotonic())
+        return {
+            "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 2, "completion_tokens": 1},
+        }
+
+    server.response = answer
+    start = time.monotonic()
+    result = server.process(REQUESTS, max_concurrency=2, think_time=think_time)
+    return result, [arrival - start for arrival in arrivals]
+
+
+def test_each_user_thinks_between_requests(server):
+    result, arrivals = run(server, THINK)
+    assert all(m.status == "ok" for m in result.metrics)
+    # Both users open with a request right away, then pause before the next one
+    assert all(arrival < 0.2 for arrival in arrivals[:2])
+    assert all(arrival >= 0.3 for arrival in arrivals[2:])
+
+
+def test_requests_follow_back_to_back_without_think_time(server):
+    _, arrivals = run(server, None)
+    assert max(arrivals) < 0.2
+
+
+def test_unknown_distribution_is_rejected(server):
+    with pytest.raises(ValueError, match="distribution"):
+        run(server, {"distribution": "pareto", "ms": 300})
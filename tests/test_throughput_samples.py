@@ -0,0 +1,50 @@
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(20)]
+
+
+def provider():
+    # Two at a time, 100ms each: about 20 requests per second
+    return ProviderConfig(
+        name="openai", api_key="test", config={"model": "m"},
+        simulator={"max_concurrency": 2, "service_time": {"distribution": "constant", "ms": 100}},
+    )
+
+
+def test_samples_cover_the_run():
+    processor = BatchProcessor(provider(), max_concurrency=2, sample_interval=0.2)
+    summary = processor.process_batch(REQUESTS, show_progress=False).summary
+    samples = summary.samples
+    assert len(samples) >= 4
+    elapsed = [sample.elapsed_seconds for sample in samples]
+    assert elapsed == sorted(elapsed)
+    completed = [sample.completed for sample in samples]
+    assert completed == sorted(completed)
+    assert completed[-1] == len(REQUESTS)
+    assert all(sample.in_flight <= 2 for sample in samples)
+    assert samples[-1].in_flight == 0
+    # Full intervals in the middle of the run see the steady rate
+    steady = samples[1:-1]
+    assert all(sample.requests_per_second == pytest.approx(20, rel=0.5) for sample in steady)
+    assert all(sample.tokens_per_second > 0 for sample in steady)
+
+
+def test_handle_summary_has_the_samples_so_far():
+    handle = BatchProcessor(provider(), max_concurrency=2, sample_interval=0.2).start_batch(REQUESTS)
+    handle.wait()
+    samples = handle.summary().samples
+    assert len(samples) >= 4
+    assert samples[-1].completed == len(REQUESTS)
+
+
+def test_no_samples_without_an_interval():
+    summary = BatchProcessor(provider(), max_concurrency=2).process_batch(REQUESTS[:4], show_progress=False).summary
+    assert summary.samples == []
+
+
+@pytest.mark.parametrize("interval", [0, -1.0])
+def test_interval_must_be_positive(interval):
+    with pytest.raises(ValueError, match="sample_interval"):
+        BatchProcessor(provider(), sample_interval=interval).process_batch(REQUESTS, show_progress=False)
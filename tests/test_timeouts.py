@@ -0,0 +1,79 @@
+import json
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+
+class Slow(BaseHTTPRequestHandler):
+    """Stalls for a second: before answering, or after the first chunk when streaming."""
+
+    def do_POST(self):
+        body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        chunk = {"model": "m", "choices": [{"index": 0, "delta": {"content": "partial"}, "finish_reason": None}]}
+        try:
+            if body.get("stream"):
+                self.send_response(200)
+                self.send_header("Content-Type", "text/event-stream")
+                self.end_headers()
+                self.wfile.write(f"data: {json.dumps(chunk)}\n\n".encode())
+                self.wfile.flush()
+                time.sleep(1)
+                self.wfile.write(b"data: [DONE]\n\n")
+            else:
+                time.sleep(1)
+                payload = json.dumps({"choices": [{"message": {"content": "late"}, "finish_reason": "stop"}],
+                                      "usage": {"prompt_tokens": 1, "completion_tokens": 1}}).encode()
+                self.send_response(200)
+                self.send_header("Content-Type", "application/json")
+                self.send_header("Content-Length", str(len(payload)))
+                self.end_headers()
+                self.wfile.write(payload)
+        except (BrokenPipeError, ConnectionResetError):
+            pass
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def server():
+    httpd = ThreadingHTTPServer(("127.0.0.1", 0), Slow)
+    httpd.daemon_threads = True
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    yield f"http://127.0.0.1:{httpd.server_port}"
+    httpd.shutdown()
+
+
+def run(server, stream_dir=None, **timeouts):
+    provider = ProviderConfig(name="openai", api_key="k", base_url=server, config={"model": "m"}, **timeouts)
+    processor = BatchProcessor(provider, stream_dir=stream_dir)
+    started = time.monotonic()
+    metrics = processor.process_batch([[{"role": "user", "content": "Hi"}]], show_progress=False).metrics[0]
+    return metrics, time.monotonic() - started
+
+
+def test_request_timeout(server):
+    metrics, elapsed = run(server, timeout=0.3)
+    assert metrics.status == "timeout"
+    assert elapsed < 0.9
+
+
+def test_without_timeout_the_slow_answer_arrives(server):
+    metrics, _ = run(server)
+    assert metrics.content == "late"
+
+
+def test_stream_read_timeout(server, tmp_path):
+    metrics, elapsed = run(server, stream_dir=str(tmp_path), read_timeout=0.3)
+    assert metrics.status == "timeout"
+    assert "Stream stalled" in metrics.error
+    assert elapsed < 0.9
+
+
+def test_negative_timeout_is_rejected():
+    with pytest.raises(ValueError, match="connect_timeout"):
+        run("http://127.0.0.1:9", connect_timeout=-1)
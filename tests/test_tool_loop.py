@@ -0,0 +1,77 @@
+import json
+
+import pytest
+
+WEATHER_TOOL = {"type": "function", "function": {
+    "name": "get_weather",
+    "parameters": {"type": "object", "properties": {"city": {"type": "string"}}},
+}}
+
+
+def agent(always_call=False):
+    """Calls get_weather until a tool result is in the conversation, then answers with it."""
+
+    def answer(body, path):
+        results = [m["content"] for m in body["messages"] if m["role"] == "tool"]
+        if results and not always_call:
+            message = {"role": "assistant", "content": f"It is {results[-1]}."}
+            finish_reason = "stop"
+        else:
+            call = {"id": f"call_{len(results)}", "type": "function",
+                    "function": {"name": "get_weather", "arguments": json.dumps({"city": "Oslo"})}}
+            message = {"role": "assistant", "content": None, "tool_calls": [call]}
+            finish_reason = "tool_calls"
+        return {
+            "choices": [{"message": message, "finish_reason": finish_reason}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 2},
+        }
+
+    return answer
+
+
+@pytest.fixture
+def response():
+    return agent()
+
+
+def run(server, tools, **options):
+    request = {"messages": [{"role": "user", "content": "Weather in Oslo?"}], "tools": [WEATHER_TOOL]}
+    return server.process([request], tools=tools, **options).metrics[0]
+
+
+def test_tool_results_are_fed_back(server):
+    metrics = run(server, {"get_weather": lambda city: f"sunny in {city}"})
+    assert metrics.content == "It is sunny in Oslo."
+    assert metrics.tool_rounds == 1
+    assert metrics.tool_trace == [
+        {"id": "call_0", "name": "get_weather", "arguments": '{"city": "Oslo"}', "result": "sunny in Oslo"}
+    ]
+    assert (metrics.prompt_tokens, metrics.completion_tokens) == (20, 4)
+    replayed = server.bodies[-1]["messages"]
+    assert replayed[1]["tool_calls"][0]["id"] == "call_0"
+    assert replayed[2] == {"role": "tool", "content": "sunny in Oslo", "tool_call_id": "call_0"}
+
+
+def test_tool_errors_are_reported_to_the_model(server):
+    def broken(city):
+        raise RuntimeError("service down")
+
+    metrics = run(server, {"get_weather": broken})
+    assert metrics.tool_trace[0]["result"] == "Error: service down"
+    assert metrics.content == "It is Error: service down."
+
+
+def test_rounds_are_bounded(server):
+    server.response = agent(always_call=True)
+    metrics = run(server, {"get_weather": lambda city: {"temp": 3}}, max_tool_rounds=2)
+    assert metrics.tool_rounds == 2
+    assert len(server.bodies) == 3
+    assert metrics.finish_reason == "tool_calls"
+    assert metrics.tool_calls[0]["function"]["name"] == "get_weather"
+    assert metrics.tool_trace[0]["result"] == '{"temp": 3}'
+
+
+def test_without_callbacks_tool_calls_are_returned(server):
+    metrics = run(server, None)
+    assert metrics.tool_rounds == 0
+    assert metrics.tool_calls[0]["id"] == "call_0"
@@ -0,0 +1,126 @@
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig, process_requests_multi
+
+REQUESTS = [[{"role": "user", "content": f"request {i}"}] for i in range(5)]
+PARENT_TRACE = "4bf92f3577b34da6a3ce929d0e0e4736"
+PARENT_SPAN = "00f067aa0ba902b7"
+
+
+class Collector(BaseHTTPRequestHandler):
+    def do_POST(self):
+        body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        self.server.exports.append((self.path, dict(self.headers), body))
+        self.send_response(200)
+        self.send_header("Content-Length", "0")
+        self.end_headers()
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def collector(monkeypatch):
+    httpd = HTTPServer(("127.0.0.1", 0), Collector)
+    httpd.exports = []
+    threading.Thread(target=httpd.serve_forever, daemon=True).start()
+    monkeypatch.setenv("OTEL_EXPORTER_OTLP_ENDPOINT", f"http://127.0.0.1:{httpd.server_port}")
+    monkeypatch.setenv("OTEL_SERVICE_NAME", "pipeline")
+    monkeypatch.setenv("OTEL_EXPORTER_OTLP_HEADERS", "x-team=evals,authorization=Bearer%20abc")
+    yield httpd
+    httpd.shutdown()
+
+
+def spans(exports):
+    return [
+        span
+        for _, _, body in exports
+        for resource in body["resourceSpans"]
+        for scope in resource["scopeSpans"]
+        for span in scope["spans"]
+    ]
+
+
+def attributes(span):
+    return {a["key"]: next(iter(a["value"].values())) for a in span["attributes"]}
+
+
+def run(**options):
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+    return BatchProcessor(provider, **options).process_batch(REQUESTS, show_progress=False)
+
+
+def test_a_span_per_request_under_a_batch_span(collector):
+    run()
+    path, headers, body = collector.exports[0]
+    assert path == "/v1/traces"
+    assert headers["x-team"] == "evals"
+    assert headers["authorization"] == "Bearer abc"
+    resource = {a["key"]: a["value"]["stringValue"] for a in body["resourceSpans"][0]["resource"]["attributes"]}
+    assert resource["service.name"] == "pipeline"
+
+    exported = spans(collector.exports)
+    [batch] = [span for span in exported if "parentSpanId" not in span]
+    requests = [span for span in exported if span.get("parentSpanId") == batch["spanId"]]
+    assert len(requests) == len(REQUESTS)
+    assert {span["traceId"] for span in exported} == {batch["traceId"]}
+    assert attributes(batch)["axicontraves.requests"] == str(len(REQUESTS))
+    for span in requests:
+        found = attributes(span)
+        assert span["name"] == "chat m"
+        assert found["gen_ai.request.model"] == "m"
+        assert found["gen_ai.system"] == "openai"
+        assert found["axicontraves.status"] == "ok"
+        assert int(found["gen_ai.usage.input_tokens"]) > 0
+        assert int(span["startTimeUnixNano"]) <= int(span["endTimeUnixNano"])
+        assert "status" not in span
+
+
+def test_failed_requests_carry_an_error_status(collector):
+    dead = ("openai", "k", "http://127.0.0.1:9", {"model": "m"}, {"test_mode": False})
+    process_requests_multi([dead], REQUESTS[:1], lambda *args: None, False, None)
+    [request] = [span for span in spans(collector.exports) if "parentSpanId" in span]
+    assert request["status"]["code"] == 2
+    assert attributes(request)["error.type"] == "connection"
+
+
+
+def test_spans_name_the_model_the_request_asked_for(collector):
+    dead = ("openai", "k", "http://127.0.0.1:9", {"model": "m"}, {"test_mode": False})
+    requests = [{"messages": REQUESTS[0], "model": "big"}]
+    process_requests_multi([dead], requests, lambda *args: None, False, None, rate_limit_retries=0)
+    [request] = [span for span in spans(collector.exports) if "parentSpanId" in span]
+    assert request["name"] == "chat big"
+    assert attributes(request)["gen_ai.request.model"] == "big"
+
+def test_batch_span_continues_the_callers_trace(collector):
+    run(traceparent=f"00-{PARENT_TRACE}-{PARENT_SPAN}-01")
+    exported = spans(collector.exports)
+    assert {span["traceId"] for span in exported} == {PARENT_TRACE}
+    assert PARENT_SPAN in {span.get("parentSpanId") for span in exported}
+
+
+def test_unsampled_parent_is_not_exported(collector):
+    run(traceparent=f"00-{PARENT_TRACE}-{PARENT_SPAN}-00")
+    assert collector.exports == []
+
+
+def test_invalid_traceparent():
+    with pytest.raises(ValueError, match="traceparent"):
+        run(traceparent="not-a-traceparent")
+
+
+def test_nothing_is_exported_without_an_endpoint(collector, monkeypatch):
+    monkeypatch.delenv("OTEL_EXPORTER_OTLP_ENDPOINT")
+    run()
+    assert collector.exports == []
+
+
+def test_unreachable_collector_warns(monkeypatch):
+    monkeypatch.setenv("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "http://127.0.0.1:9/v1/traces")
+    with pytest.warns(RuntimeWarning, match="Exporting traces failed"):
+        run()
@@ -0,0 +1,40 @@
+import pytest
+
+from axicontraves.experiment import Experiment, Variant
+
+QUESTION = [{"role": "user", "content": "hi"}]
+USAGE = {
+    "prompt_tokens": 1000,
+    "completion_tokens": 300,
+    "prompt_tokens_details": {"cached_tokens": 800, "audio_tokens": 20},
+    "completion_tokens_details": {"reasoning_tokens": 250, "audio_tokens": 5},
+}
+
+
+@pytest.fixture
+def response():
+    def answer(body, path):
+        usage = USAGE if body["model"] == "detailed" else {"prompt_tokens": 10, "completion_tokens": 2}
+        return {"model": body["model"], "choices": [{"message": {"content": "ok"}, "finish_reason": "stop"}], "usage": usage}
+
+    return answer
+
+
+def test_details_are_reported(server):
+    metrics = server.run(QUESTION, model="detailed")
+    assert (metrics.prompt_tokens, metrics.completion_tokens) == (1000, 300)
+    assert metrics.cached_tokens == 800
+    assert metrics.reasoning_tokens == 250
+    assert metrics.audio_tokens == 25
+
+
+def test_missing_details_are_none(server):
+    metrics = server.run(QUESTION, model="plain")
+    assert (metrics.cached_tokens, metrics.reasoning_tokens, metrics.audio_tokens) == (None, None, None)
+
+
+def test_cached_input_price(server):
+    pricing = {"detailed": {"input": 10.0, "cached_input": 1.0, "output": 20.0}}
+    experiment = Experiment([Variant("v", server.provider(config={"model": "detailed"}))], pricing=pricing)
+    summary = experiment.run([QUESTION], show_progress=False).variants[0]
+    assert summary.cost_usd == pytest.approx((200 * 10.0 + 800 * 1.0 + 300 * 20.0) / 1_000_000)
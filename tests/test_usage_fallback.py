@@ -0,0 +1,40 @@
+import pytest
+
+from axicontraves import count_tokens
+
+QUESTION = [{"role": "user", "content": "Hello there"}]
+ANSWER = "Usage was stripped by the proxy in front of this server."
+
+
+@pytest.fixture
+def response():
+    """Answers like an OpenAI-compatible proxy that drops the usage object."""
+
+    def answer(body, path):
+        response = {"model": body["model"], "choices": [{"message": {"content": ANSWER}, "finish_reason": "stop"}]}
+        if body["model"] == "partial":
+            response["usage"] = {"prompt_tokens": 40}
+        return response
+
+    return answer
+
+
+def test_missing_usage_is_tokenized(server):
+    metrics = server.run(QUESTION, model="gpt-4o-mini")
+    assert metrics.status == "ok"
+    assert metrics.usage_estimated
+    assert metrics.completion_tokens == count_tokens(ANSWER, "gpt-4o-mini")
+    assert metrics.prompt_tokens == 3 + count_tokens("user", "gpt-4o-mini") + count_tokens("Hello there", "gpt-4o-mini") + 3
+
+
+def test_partial_usage_keeps_reported_counts(server):
+    metrics = server.run(QUESTION, model="partial")
+    assert metrics.usage_estimated
+    assert metrics.prompt_tokens == 40
+    assert metrics.completion_tokens == count_tokens(ANSWER)
+
+
+def test_encoding_follows_model():
+    text = "naïve café — 東京"
+    assert count_tokens(text) > 0
+    assert count_tokens(text, "gpt-4o") != count_tokens(text, "gpt-4")
@@ -0,0 +1,45 @@
+import base64
+import struct
+
+from axicontraves import BatchProcessor, ProviderConfig, plan
+
+
+def png(width, height):
+    header = b"\x89PNG\r\n\x1a\n" + struct.pack(">I", 13) + b"IHDR" + struct.pack(">II", width, height)
+    return base64.b64encode(header + b"\x08\x02\x00\x00\x00").decode()
+
+
+def request(width, height, detail=None):
+    image = {"type": "image", "data": png(width, height)}
+    if detail:
+        image["detail"] = detail
+    return [{"role": "user", "content": [image]}]
+
+
+def prompt_tokens(name, requests):
+    provider = ProviderConfig(name=name, api_key="k", config={"model": "m"})
+    return plan(requests, provider).prompt_tokens
+
+
+def test_openai_tile_formula():
+    # Fit into 2048x4096 -> 1024x2048 -> 768x1536: 2 x 3 tiles
+    assert prompt_tokens("openai", [request(2048, 4096)]) == 85 + 170 * 6
+    assert prompt_tokens("openai", [request(2048, 4096, detail="low")]) == 85
+    assert prompt_tokens("openai", [request(512, 512)]) == 85 + 170
+
+
+def test_anthropic_pixel_formula():
+    assert prompt_tokens("anthropic", [request(1000, 1000)]) == 1334
+    # Downscaled to about 1.15 megapixels
+    assert prompt_tokens("anthropic", [request(4000, 4000)]) == 1534
+
+
+def test_url_images_use_default_size():
+    url = [{"role": "user", "content": [{"type": "image_url", "image_url": {"url": "https://example.com/a.png"}}]}]
+    assert prompt_tokens("openai", [url]) == 765
+
+
+def test_test_mode_counts_images():
+    provider = ProviderConfig(name="openai", api_key="k", config={"model": "m"}, test_mode=True)
+    metrics = BatchProcessor(provider).process_batch([request(512, 512)], show_progress=False).metrics[0]
+    assert metrics.prompt_tokens == 255
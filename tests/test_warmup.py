@@ -0,0 +1,58 @@
+import time
+
+import pytest
+
+from axicontraves import BatchProcessor
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(6)]
+COLD_START = 0.3
+
+
+@pytest.fixture
+def servers(make_server):
+    # The first request a server sees is slow, like a cold start
+    return [make_server(cold_start=COLD_START) for _ in range(2)]
+
+
+def providers(servers):
+    return [server.provider() for server in servers]
+
+
+def test_warmup_requests_go_to_every_provider(servers):
+    result = BatchProcessor(providers(servers), warmup_requests=2).process_batch(REQUESTS, show_progress=False)
+    assert [server.calls for server in servers] == [2 + 3, 2 + 3]
+    assert sorted(m.index for m in result.metrics) == list(range(len(REQUESTS)))
+    assert result.integrity.complete
+    # The cold starts were absorbed by the warm-up
+    assert max(m.latency_ms for m in result.metrics) < COLD_START * 1000
+    assert result.total_time < COLD_START
+
+
+def test_without_warmup_the_cold_start_is_measured(servers):
+    result = BatchProcessor(providers(servers)).process_batch(REQUESTS, show_progress=False)
+    assert sum(server.calls for server in servers) == len(REQUESTS)
+    assert max(m.latency_ms for m in result.metrics) >= COLD_START * 1000
+
+
+def test_warmup_seconds(servers):
+    started = time.monotonic()
+    result = BatchProcessor(providers(servers), max_concurrency=2, warmup_seconds=0.5).process_batch(REQUESTS, show_progress=False)
+    assert time.monotonic() - started >= 0.5
+    assert all(server.calls > 3 for server in servers)
+    assert len(result.metrics) == len(REQUESTS)
+    assert result.total_time < 0.5
+
+
+def test_warmup_with_a_handle(servers):
+    handle = BatchProcessor(providers(servers), warmup_requests=1).start_batch(iter(REQUESTS))
+    assert sorted(m.index for m in handle) == list(range(len(REQUESTS)))
+    assert [server.calls for server in servers] == [1 + 3, 1 + 3]
+
+
+@pytest.mark.parametrize(
+    "options, message",
+    [({"warmup_requests": 1, "warmup_seconds": 1.0}, "combined"), ({"warmup_seconds": 0}, "warmup_seconds")],
+)
+def test_invalid_warmup(servers, options, message):
+    with pytest.raises(ValueError, match=message):
+        BatchProcessor(providers(servers), **options).process_batch(REQUESTS, show_progress=False)
@@ -0,0 +1,41 @@
+from collections import Counter
+
+import pytest
+
+from axicontraves import BatchProcessor, ProviderConfig
+
+REQUESTS = [[{"role": "user", "content": f"Question {i}"}] for i in range(100)]
+
+
+def providers(weights):
+    return [
+        ProviderConfig(name="openai", api_key="k", base_url=url, config={"model": "m"}, test_mode=True, weight=weight)
+        for url, weight in zip(("http://a", "http://b"), weights)
+    ]
+
+
+def test_traffic_follows_weights():
+    metrics = BatchProcessor(providers([7, 3])).process_batch(REQUESTS, show_progress=False).metrics
+    assert Counter(m.provider_name for m in metrics) == {"openai:http://a": 70, "openai:http://b": 30}
+
+
+def test_weighted_picks_are_interleaved():
+    metrics = BatchProcessor(providers([2, 1]), max_concurrency=1).process_batch(REQUESTS[:6], show_progress=False).metrics
+    assert [m.provider_name[-1] for m in metrics] == ["a", "b", "a", "a", "b", "a"]
+
+
+def test_equal_weights_are_plain_round_robin():
+    metrics = BatchProcessor(providers([None, None])).process_batch(REQUESTS[:4], show_progress=False).metrics
+    assert [m.provider_name[-1] for m in metrics] == ["a", "b", "a", "b"]
+
+
+def test_plan_splits_by_weight():
+    estimate = BatchProcessor(providers([7, 3]), max_concurrency=10).plan(REQUESTS)
+    assert [p.requests for p in estimate.providers] == [70, 30]
+    assert [p.concurrency for p in estimate.providers] == [7, 3]
+
+
+@pytest.mark.parametrize("weight", [0, -1, float("inf")])
+def test_invalid_weight_is_rejected(weight):
+    with pytest.raises(ValueError, match="weight"):
+        BatchProcessor(providers([1, weight])).process_batch(REQUESTS[:1], show_progress=False)